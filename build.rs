@@ -50,7 +50,67 @@ pub fn main() {
     let payb_linenr = payloads_v(&fp_map);
     let map = port_payload_map(pb_linenr, payb_linenr);
 
-    generate_code(map);
+    generate_code(map, &service_names(), &port_groups());
+}
+
+/// Reads the named groups of common ports of the `port-groups` file
+///
+/// # Returns
+///
+/// The name and ports of every group, in the order of the file.
+fn port_groups() -> Vec<(String, Vec<u16>)> {
+    let mut file_path = env::current_dir().expect("cant find curr dir");
+    file_path.push("./port-groups");
+    let data = fs::read_to_string(&file_path).expect("File not found.");
+
+    let mut groups = Vec::new();
+    for line in data.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(ports)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let ports = ports
+            .split(',')
+            .map(|port| port.parse::<u16>().expect("Invalid port in port-groups."))
+            .collect();
+        groups.push((name.to_owned(), ports));
+    }
+    groups
+}
+
+/// Reads the well-known services of the `service-names` file, which is in
+/// the `/etc/services` format
+///
+/// # Returns
+///
+/// The port, protocol and name of every TCP and UDP service, sorted by port
+/// and protocol. The first name given for a port and protocol is kept.
+fn service_names() -> Vec<(u16, String, String)> {
+    let mut file_path = env::current_dir().expect("cant find curr dir");
+    file_path.push("./service-names");
+    let data = fs::read_to_string(&file_path).expect("File not found.");
+
+    let mut services = Vec::new();
+    for line in data.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(port)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some((port, protocol)) = port.split_once('/') else {
+            continue;
+        };
+        match port.parse::<u16>() {
+            Ok(port) if protocol == "tcp" || protocol == "udp" => {
+                services.push((port, protocol.to_owned(), name.to_owned()))
+            }
+            _ => println!("Skipping service: {line}"),
+        }
+    }
+    services.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+    services.dedup_by(|a, b| a.0 == b.0 && a.1 == b.1);
+    services
 }
 
 /// Generates a file called Generated.rs and calls cargo fmt from the command line
@@ -58,7 +118,13 @@ pub fn main() {
 /// # Arguments
 ///
 /// * `port_payload_map` - A BTreeMap mapping port numbers to payload data
-fn generate_code(port_payload_map: BTreeMap<Vec<u16>, Vec<u8>>) {
+/// * `services` - The port, protocol and name of the well-known services
+/// * `port_groups` - The name and ports of the groups of common ports
+fn generate_code(
+    port_payload_map: BTreeMap<Vec<u16>, Vec<u8>>,
+    services: &[(u16, String, String)],
+    port_groups: &[(String, Vec<u16>)],
+) {
     let dest_path = PathBuf::from("src/generated.rs");
 
     let mut generated_code = String::new();
@@ -96,6 +162,31 @@ fn generate_code(port_payload_map: BTreeMap<Vec<u16>, Vec<u8>>) {
     );
     generated_code.push_str("pub fn get_parsed_data() -> &'static BTreeMap<Vec<u16>, Vec<u8>> {\n");
     generated_code.push_str("    &PARSED_DATA\n");
+    generated_code.push_str("}\n\n");
+
+    generated_code.push_str("static SERVICE_NAMES: &[(u16, &str, &str)] = &[\n");
+    for (port, protocol, name) in services {
+        generated_code.push_str(&format!("    ({port}, \"{protocol}\", \"{name}\"),\n"));
+    }
+    generated_code.push_str("];\n");
+    generated_code
+        .push_str("pub fn get_service_names() -> &'static [(u16, &'static str, &'static str)] {\n");
+    generated_code.push_str("    SERVICE_NAMES\n");
+    generated_code.push_str("}\n\n");
+
+    generated_code.push_str("static PORT_GROUPS: &[(&str, &[u16])] = &[\n");
+    for (name, ports) in port_groups {
+        let ports = ports
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        generated_code.push_str(&format!("    (\"{name}\", &[{ports}]),\n"));
+    }
+    generated_code.push_str("];\n");
+    generated_code
+        .push_str("pub fn get_port_groups() -> &'static [(&'static str, &'static [u16])] {\n");
+    generated_code.push_str("    PORT_GROUPS\n");
     generated_code.push_str("}\n");
 
     fs::write(dest_path, generated_code).unwrap();
@@ -0,0 +1,1028 @@
+//! Provides functions to parse input IP addresses, CIDRs or files.
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{prelude::*, BufReader};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use cidr_utils::cidr::{IpCidr, IpInet};
+use hickory_resolver::{
+    config::{NameServerConfig, Protocol, ResolverConfig, ResolverOpts},
+    proto::rr::Name,
+    system_conf::read_system_conf,
+};
+use log::debug;
+use rand::RngExt;
+
+use crate::input::{Opts, WildcardDns};
+use crate::warning;
+
+mod overlap;
+mod resolver;
+mod sample;
+mod wildcard;
+mod zone;
+
+pub use overlap::Overlap;
+pub use resolver::{Lookup, ResolverPool};
+use sample::Segment;
+pub use sample::{SampleCoverage, MAX_SAMPLE};
+pub use zone::parse_zoned;
+
+/// How long each resolver given through `--resolver` gets to answer before
+/// the lookup fails over to the next one.
+const RESOLVER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Shortest prefix of the IPv6 networks expanded into every address, a
+/// /116 being 4096 of them. Larger ones can only be sampled with
+/// `--sample`.
+pub const MIN_IPV6_PREFIX: u8 = 116;
+
+/// Parses the string(s) into IP addresses.
+///
+/// Goes through all possible IP inputs (files or via argparsing).
+///
+/// ```rust
+/// # use rustscan::input::Opts;
+/// # use rustscan::address::parse_addresses;
+/// let mut opts = Opts::default();
+/// opts.addresses = vec!["192.168.0.0/30".to_owned()];
+///
+/// let ips = parse_addresses(&opts);
+/// ```
+///
+/// Finally, any duplicates are removed to avoid excessive scans.
+pub fn parse_addresses(input: &Opts) -> Vec<IpAddr> {
+    parse_targets(input).ips
+}
+
+/// The IPs to scan, along with the IPs each hostname resolved to, the
+/// tags each IP was annotated with, the targets covered by others and, with
+/// `--sample`, how much of the target space the IPs cover.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Targets {
+    pub ips: Vec<IpAddr>,
+    pub hosts: BTreeMap<String, Vec<IpAddr>>,
+    pub tags: BTreeMap<IpAddr, Vec<String>>,
+    pub overlaps: Vec<Overlap>,
+    pub coverage: Option<SampleCoverage>,
+    /// The scope IDs of the IPv6 addresses given with a zone, see
+    /// [`parse_zoned`].
+    pub scopes: BTreeMap<IpAddr, u32>,
+    /// The ports of the IPs of `--targets-file`, scanned instead of the
+    /// others.
+    pub ports: BTreeMap<IpAddr, Vec<u16>>,
+}
+
+impl Targets {
+    /// The IPs to scan, each with the hostname it was resolved from, if
+    /// any. An IP several hostnames resolved to keeps the first of them.
+    ///
+    /// ```rust
+    /// # use rustscan::address::{Target, Targets};
+    /// # use std::net::IpAddr;
+    /// let ip: IpAddr = "93.184.216.34".parse().unwrap();
+    /// let mut targets = Targets::default();
+    /// targets.ips.push(ip);
+    /// targets.hosts.insert("example.com".to_owned(), vec![ip]);
+    /// assert_eq!(targets.targets(), vec![Target::new(ip).with_host("example.com")]);
+    /// ```
+    pub fn targets(&self) -> Vec<Target> {
+        let mut names: HashMap<IpAddr, &str> = HashMap::new();
+        for (host, ips) in &self.hosts {
+            for ip in ips {
+                names.entry(*ip).or_insert(host);
+            }
+        }
+        self.ips
+            .iter()
+            .map(|ip| Target {
+                host: names.get(ip).map(|host| (*host).to_owned()),
+                ip: *ip,
+            })
+            .collect()
+    }
+}
+
+/// An IP to scan along with the hostname it was resolved from, so results
+/// and scripts can refer to the host by name. Virtual-hosted services often
+/// answer differently to their name than to their IP.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Target {
+    pub host: Option<String>,
+    pub ip: IpAddr,
+}
+
+impl Target {
+    pub fn new(ip: IpAddr) -> Self {
+        Self { host: None, ip }
+    }
+
+    #[must_use]
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+}
+
+/// The IP followed by the hostname in parentheses, like nmap prints hosts:
+/// `93.184.216.34 (example.com)`.
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.host {
+            Some(host) => write!(f, "{} ({host})", self.ip),
+            None => write!(f, "{}", self.ip),
+        }
+    }
+}
+
+/// Splits an annotated address into the address and its tags, which follow
+/// a `#` and are separated by commas or whitespace.
+///
+/// ```rust
+/// # use rustscan::address::split_tags;
+/// assert_eq!(split_tags("10.0.0.5 # db-prod, prod"), ("10.0.0.5", vec!["db-prod".to_owned(), "prod".to_owned()]));
+/// assert_eq!(split_tags("10.0.0.5"), ("10.0.0.5", vec![]));
+/// assert_eq!(split_tags("# just a comment"), ("", vec!["just".to_owned(), "a".to_owned(), "comment".to_owned()]));
+/// ```
+pub fn split_tags(address: &str) -> (&str, Vec<String>) {
+    match address.split_once('#') {
+        Some((address, tags)) => (
+            address.trim(),
+            tags.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|tag| !tag.is_empty())
+                .map(ToOwned::to_owned)
+                .collect(),
+        ),
+        None => (address.trim(), Vec::new()),
+    }
+}
+
+/// Parses the string(s) into IP addresses like [`parse_addresses`], but keeps
+/// track of which IPs each hostname resolved to so results for hostnames with
+/// several A/AAAA records can be reported together.
+///
+/// ```rust
+/// # use rustscan::input::Opts;
+/// # use rustscan::address::parse_targets;
+/// let mut opts = Opts::default();
+/// opts.addresses = vec!["127.0.0.1".to_owned(), "localhost".to_owned()];
+///
+/// let targets = parse_targets(&opts);
+/// assert!(targets.hosts.contains_key("localhost"));
+/// ```
+pub fn parse_targets(input: &Opts) -> Targets {
+    let backup_resolver = get_resolver(&input.resolver);
+    let mut address_tags = BTreeMap::new();
+    let mut networks = Vec::new();
+    let mut targets = resolve_targets(input, &backup_resolver, &mut address_tags, &mut networks);
+
+    if input.wildcard_dns != WildcardDns::Off {
+        collapse_wildcards(input, &mut targets, &backup_resolver);
+    }
+
+    let overlaps = overlap::find_overlaps(&targets, &mut networks);
+
+    let excluded_cidrs = parse_excluded_networks(&input.exclude_addresses, &backup_resolver);
+    let is_excluded = |ip: &IpAddr| excluded_cidrs.iter().any(|cidr| cidr.contains(ip));
+
+    let mut coverage = None;
+    if let Some(size) = input.sample {
+        // Addresses within the networks, or given twice, would be counted
+        // twice in the target space, and excluded ones not be scanned.
+        let mut seen = HashSet::new();
+        let hosts: Vec<(String, Segment)> = targets
+            .drain(..)
+            .map(|(address, mut ips)| {
+                ips.retain(|ip| {
+                    !networks.iter().any(|(_, cidr)| cidr.contains(ip))
+                        && !is_excluded(ip)
+                        && seen.insert(*ip)
+                });
+                (address, Segment::Ips(ips))
+            })
+            .collect();
+        let segments: Vec<(String, Segment)> = networks
+            .into_iter()
+            .map(|(address, cidr)| (address, Segment::network(cidr, &excluded_cidrs)))
+            .chain(hosts)
+            .collect();
+        let seed = input.seed.unwrap_or_else(|| rand::rng().random());
+        let (sampled, sample_coverage) = sample::sample(&segments, size, seed);
+        if sample_coverage.sampled == MAX_SAMPLE {
+            warning!(
+                format!("The sample is limited to {MAX_SAMPLE} addresses."),
+                input.greppable,
+                input.accessible
+            );
+        }
+        targets = sampled;
+        coverage = Some(sample_coverage);
+    }
+
+    let mut hosts: BTreeMap<String, Vec<IpAddr>> = BTreeMap::new();
+    let mut tags: BTreeMap<IpAddr, Vec<String>> = BTreeMap::new();
+    let mut scopes: BTreeMap<IpAddr, u32> = BTreeMap::new();
+    let mut ports: BTreeMap<IpAddr, Vec<u16>> = BTreeMap::new();
+    let mut ips: Vec<IpAddr> = Vec::new();
+    for (address, parsed_ips) in targets {
+        if let Some((ip, scope_id)) = parse_zoned(&address) {
+            scopes.insert(IpAddr::V6(ip), scope_id);
+        }
+        if let Some(address_ports) = input.target_ports.get(&address) {
+            for ip in &parsed_ips {
+                let ip_ports = ports.entry(*ip).or_default();
+                for port in address_ports {
+                    if !ip_ports.contains(port) {
+                        ip_ports.push(*port);
+                    }
+                }
+            }
+        }
+        if let Some(address_tags) = address_tags.get(&address) {
+            for ip in &parsed_ips {
+                let ip_tags = tags.entry(*ip).or_default();
+                for tag in address_tags {
+                    if !ip_tags.contains(tag) {
+                        ip_tags.push(tag.clone());
+                    }
+                }
+            }
+        }
+        if is_hostname(&address) {
+            let host_ips = hosts.entry(address).or_default();
+            for ip in &parsed_ips {
+                if !is_excluded(ip) && !host_ips.contains(ip) {
+                    host_ips.push(*ip);
+                }
+            }
+        }
+        ips.extend(parsed_ips);
+    }
+    hosts.retain(|_, ips| !ips.is_empty());
+
+    // Remove duplicated/excluded IPs.
+    let mut seen = BTreeSet::new();
+    ips.retain(|ip| seen.insert(*ip) && !is_excluded(ip));
+
+    tags.retain(|ip, _| seen.contains(ip) && !is_excluded(ip));
+    scopes.retain(|ip, _| seen.contains(ip) && !is_excluded(ip));
+    ports.retain(|ip, _| seen.contains(ip) && !is_excluded(ip));
+
+    Targets {
+        ips,
+        hosts,
+        tags,
+        overlaps,
+        coverage,
+        scopes,
+        ports,
+    }
+}
+
+/// The network of a CIDR address, `None` for IPs and hostnames.
+fn network(address: &str) -> Option<IpCidr> {
+    if IpAddr::from_str(address).is_ok() {
+        return None;
+    }
+    IpInet::from_str(address).ok().map(|inet| inet.network())
+}
+
+/// Whether an address is a hostname, as opposed to an IP or a CIDR.
+fn is_hostname(address: &str) -> bool {
+    IpAddr::from_str(address).is_err()
+        && IpInet::from_str(address).is_err()
+        && !zone::has_zone(address)
+}
+
+/// Whether `cidr` is an IPv6 network too large to expand, see
+/// [`MIN_IPV6_PREFIX`].
+fn is_too_large(cidr: &IpCidr) -> bool {
+    cidr.is_ipv6() && cidr.network_length() < MIN_IPV6_PREFIX
+}
+
+/// Expands every address given, or every line of the files given, into the
+/// IPs it stands for, keeping track of which address each IP came from.
+/// Tags are stripped from the addresses and collected in `tags`. With
+/// `--sample`, the networks given are collected in `networks` instead of
+/// being expanded.
+fn resolve_targets(
+    input: &Opts,
+    backup_resolver: &ResolverPool,
+    tags: &mut BTreeMap<String, Vec<String>>,
+    networks: &mut Vec<(String, IpCidr)>,
+) -> Vec<(String, Vec<IpAddr>)> {
+    let mut targets: Vec<(String, Vec<IpAddr>)> = Vec::new();
+    let mut unresolved_addresses: Vec<&str> = Vec::new();
+
+    let mut addresses = strip_tags(&input.addresses, tags);
+    if input.sample.is_some() {
+        addresses.retain(|address| match network(address) {
+            Some(cidr) => {
+                networks.push((address.clone(), cidr));
+                false
+            }
+            None => true,
+        });
+    } else {
+        addresses.retain(|address| match network(address) {
+            Some(cidr) if is_too_large(&cidr) => {
+                warning!(
+                    format!("{address} is larger than a /{MIN_IPV6_PREFIX}, scan a sample of it with --sample instead."),
+                    input.greppable,
+                    input.accessible
+                );
+                false
+            }
+            _ => true,
+        });
+    }
+    let resolved = resolve_all(&addresses, backup_resolver, input.resolve_concurrency);
+    for (address, parsed_ips) in addresses.iter().zip(resolved) {
+        if !parsed_ips.is_empty() {
+            targets.push((address.clone(), parsed_ips));
+        } else {
+            unresolved_addresses.push(address);
+        }
+    }
+
+    // If we got to this point this can only be a file path or the wrong input.
+    for file_path in unresolved_addresses {
+        let file_path = Path::new(file_path);
+
+        if !file_path.is_file() {
+            warning!(
+                format!("Host {file_path:?} could not be resolved."),
+                input.greppable,
+                input.accessible
+            );
+
+            continue;
+        }
+
+        if let Ok(x) =
+            read_ips_from_file(file_path, backup_resolver, input.resolve_concurrency, tags)
+        {
+            targets.extend(x);
+        } else {
+            warning!(
+                format!("Host {file_path:?} could not be resolved."),
+                input.greppable,
+                input.accessible
+            );
+        }
+    }
+
+    targets
+}
+
+/// Warns about wildcard domains and, when asked to, replaces the hostnames
+/// that only resolve to the wildcard with a single target for it.
+fn collapse_wildcards(
+    input: &Opts,
+    targets: &mut Vec<(String, Vec<IpAddr>)>,
+    resolver: &ResolverPool,
+) {
+    for wildcard in wildcard::detect(targets, resolver) {
+        warning!(
+            format!(
+                "Wildcard DNS detected for *.{}: {} hostnames only resolve to {:?}.",
+                wildcard.domain,
+                wildcard.hosts.len(),
+                wildcard.ips
+            ),
+            input.greppable,
+            input.accessible
+        );
+
+        if input.wildcard_dns == WildcardDns::Collapse {
+            for &index in &wildcard.hosts {
+                targets[index].1.clear();
+            }
+            targets.push((
+                format!("*.{}", wildcard.domain),
+                wildcard.ips.into_iter().collect(),
+            ));
+        }
+    }
+    targets.retain(|(_, ips)| !ips.is_empty());
+}
+
+/// Given a string, parse it as a host, IP address, or CIDR.
+///
+/// This allows us to pass files as hosts or cidr or IPs easily
+/// Call this every time you have a possible IP-or-host.
+///
+/// If the address is a domain, we can self-resolve the domain locally
+/// or resolve it with `resolver`, a single resolver or a [`ResolverPool`].
+///
+/// ```rust
+/// # use rustscan::address::parse_address;
+/// # use hickory_resolver::Resolver;
+/// let ips = parse_address("127.0.0.1", &Resolver::default().unwrap());
+/// ```
+pub fn parse_address(address: &str, resolver: &impl Lookup) -> Vec<IpAddr> {
+    if let Ok(addr) = IpAddr::from_str(address) {
+        // `address` is an IP string
+        vec![addr]
+    } else if let Ok(net_addr) = IpInet::from_str(address) {
+        // `address` is a CIDR string
+        let network = net_addr.network();
+        if is_too_large(&network) {
+            debug!("{address} is too large to expand");
+            return Vec::new();
+        }
+        network.into_iter().addresses().collect()
+    } else if zone::has_zone(address) {
+        // `address` is an IPv6 address with a zone, e.g. fe80::1%eth0
+        match parse_zoned(address) {
+            Some((ip, _)) => vec![IpAddr::V6(ip)],
+            None => {
+                debug!("{address} is not on an interface of this machine");
+                Vec::new()
+            }
+        }
+    } else {
+        // `address` is a hostname or DNS name
+        let Some(address) = normalize_hostname(address) else {
+            debug!("{address} is not a valid hostname");
+            return Vec::new();
+        };
+        // attempt default DNS lookup
+        match format!("{address}:80").to_socket_addrs() {
+            Ok(iter) => {
+                // keep every A/AAAA record, in the order the resolver gave them
+                let mut ips: Vec<IpAddr> = Vec::new();
+                for ip in iter.map(|socket| socket.ip()) {
+                    if !ips.contains(&ip) {
+                        ips.push(ip);
+                    }
+                }
+                ips
+            }
+            // default lookup didn't work, so try again with the dedicated resolver
+            Err(_) => resolve_ips_from_host(&address, resolver),
+        }
+    }
+}
+
+/// Converts a hostname to the form DNS lookups expect: internationalized
+/// labels are turned into punycode and the name is lowercased. Returns
+/// `None` for names IDNA rejects.
+///
+/// ```rust
+/// # use rustscan::address::normalize_hostname;
+/// assert_eq!(normalize_hostname("Bücher.Example").as_deref(), Some("xn--bcher-kva.example"));
+/// assert_eq!(normalize_hostname("WWW.Example.com").as_deref(), Some("www.example.com"));
+/// ```
+pub fn normalize_hostname(host: &str) -> Option<String> {
+    if host.is_ascii() {
+        return Some(host.to_ascii_lowercase());
+    }
+    Name::from_utf8(host)
+        .ok()
+        .map(|name| name.to_ascii().to_ascii_lowercase())
+}
+
+/// Uses DNS to get the IPS associated with host
+fn resolve_ips_from_host(source: &str, backup_resolver: &impl Lookup) -> Vec<IpAddr> {
+    let mut ips: Vec<IpAddr> = Vec::new();
+
+    if let Ok(addrs) = source.to_socket_addrs() {
+        for ip in addrs {
+            ips.push(ip.ip());
+        }
+    } else {
+        ips.extend(backup_resolver.lookup_ips(source));
+    }
+
+    ips
+}
+
+/// Parses excluded networks from a list of addresses.
+///
+/// This function handles three types of inputs:
+/// 1. CIDR notation (e.g. "192.168.0.0/24")
+/// 2. Single IP addresses (e.g. "192.168.0.1")
+/// 3. Hostnames that need to be resolved (e.g. "example.com")
+///
+/// ```rust
+/// # use rustscan::address::parse_excluded_networks;
+/// # use hickory_resolver::Resolver;
+/// let resolver = Resolver::default().unwrap();
+/// let excluded = parse_excluded_networks(&Some(vec!["192.168.0.0/24".to_owned()]), &resolver);
+/// ```
+pub fn parse_excluded_networks(
+    exclude_addresses: &Option<Vec<String>>,
+    resolver: &impl Lookup,
+) -> Vec<IpCidr> {
+    exclude_addresses
+        .iter()
+        .flatten()
+        .flat_map(|addr| parse_single_excluded_address(addr, resolver))
+        .collect()
+}
+
+/// Parses a single address into an IpCidr, handling CIDR notation, IP addresses, and hostnames.
+fn parse_single_excluded_address(addr: &str, resolver: &impl Lookup) -> Vec<IpCidr> {
+    if let Ok(cidr) = IpCidr::from_str(addr) {
+        return vec![cidr];
+    }
+
+    if let Ok(ip) = IpAddr::from_str(addr) {
+        return vec![IpCidr::new_host(ip)];
+    }
+
+    normalize_hostname(addr)
+        .map(|host| resolve_ips_from_host(&host, resolver))
+        .unwrap_or_default()
+        .into_iter()
+        .map(IpCidr::new_host)
+        .collect()
+}
+
+/// Derive a DNS resolver.
+///
+/// 1. if the `resolver` parameter has been set:
+///     1. assume the parameter is a path and attempt to read IPs.
+///     2. parse the input as a comma-separated list of IPs.
+/// 2. if `resolver` is not set:
+///    1. attempt to derive a resolver from the system config. (e.g.
+///       `/etc/resolv.conf` on *nix).
+///    2. finally, build a CloudFlare-based resolver (default
+///       behaviour).
+fn get_resolver(resolver: &Option<String>) -> ResolverPool {
+    match resolver {
+        Some(r) => {
+            let resolver_ips = match read_resolver_from_file(r) {
+                Ok(ips) => ips,
+                Err(_) => r
+                    .split(',')
+                    .filter_map(|r| IpAddr::from_str(r).ok())
+                    .collect::<Vec<_>>(),
+            };
+
+            // Each resolver gets its own config so the pool can fail over
+            // quickly instead of hickory waiting on every dead server in turn.
+            let mut opts = ResolverOpts::default();
+            opts.timeout = RESOLVER_TIMEOUT;
+            opts.attempts = 1;
+
+            let resolvers = resolver_ips
+                .into_iter()
+                .map(|ip| {
+                    let mut config = ResolverConfig::new();
+                    config.add_name_server(NameServerConfig::new(
+                        SocketAddr::new(ip, 53),
+                        Protocol::Udp,
+                    ));
+                    (ip.to_string(), config, opts.clone())
+                })
+                .collect();
+            ResolverPool::new(resolvers)
+        }
+        None => read_system_conf()
+            .unwrap_or_else(|_| (ResolverConfig::cloudflare_tls(), ResolverOpts::default()))
+            .into(),
+    }
+}
+
+/// Parses and input file of IPs for use in DNS resolution.
+fn read_resolver_from_file(path: &str) -> Result<Vec<IpAddr>, std::io::Error> {
+    let ips = fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| IpAddr::from_str(line.trim()).ok())
+        .collect();
+
+    Ok(ips)
+}
+
+#[cfg(not(tarpaulin_include))]
+/// Parses an input file of IPs and uses those
+fn read_ips_from_file(
+    ips: &std::path::Path,
+    backup_resolver: &ResolverPool,
+    concurrency: usize,
+    tags: &mut BTreeMap<String, Vec<String>>,
+) -> Result<Vec<(String, Vec<IpAddr>)>, std::io::Error> {
+    let file = File::open(ips)?;
+    let reader = BufReader::new(file);
+
+    let mut addresses: Vec<String> = Vec::new();
+
+    for address_line in reader.lines() {
+        if let Ok(address) = address_line {
+            addresses.push(address);
+        } else {
+            debug!("Line in file is not valid");
+        }
+    }
+
+    let addresses = strip_tags(&addresses, tags);
+    let resolved = resolve_all(&addresses, backup_resolver, concurrency);
+    Ok(addresses.into_iter().zip(resolved).collect())
+}
+
+/// Strips the tags off annotated addresses, adding them to `tags`. Lines
+/// that are only a comment are dropped.
+fn strip_tags(addresses: &[String], tags: &mut BTreeMap<String, Vec<String>>) -> Vec<String> {
+    let mut stripped = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        let (address, address_tags) = split_tags(address);
+        if address.is_empty() {
+            continue;
+        }
+        if !address_tags.is_empty() {
+            tags.entry(address.to_owned())
+                .or_default()
+                .extend(address_tags);
+        }
+        stripped.push(address.to_owned());
+    }
+    stripped
+}
+
+/// Parses every address with up to `concurrency` lookups in flight, since
+/// large host files are bound by resolver latency. Results keep the order of
+/// `addresses`.
+fn resolve_all(
+    addresses: &[String],
+    resolver: &ResolverPool,
+    concurrency: usize,
+) -> Vec<Vec<IpAddr>> {
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Vec<IpAddr>>> = addresses.iter().map(|_| Mutex::default()).collect();
+
+    thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, addresses.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(address) = addresses.get(index) else {
+                    break;
+                };
+                *results[index].lock().unwrap() = parse_address(address, resolver);
+            });
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|ips| ips.into_inner().unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_resolver, parse_addresses, parse_targets, resolve_all, Opts, Target};
+    use crate::input::SampleSize;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn samples_networks_deterministically() {
+        let opts = Opts {
+            addresses: vec!["10.0.0.0/16".to_owned(), "192.168.0.1".to_owned()],
+            sample: Some(SampleSize::Count(100)),
+            seed: Some(3),
+            ..Default::default()
+        };
+
+        let targets = parse_targets(&opts);
+        assert_eq!(targets.ips.len(), 100);
+        let coverage = targets.coverage.unwrap();
+        assert_eq!((coverage.sampled, coverage.population), (100, 65_537));
+        assert_eq!(parse_targets(&opts).ips, targets.ips);
+    }
+
+    #[test]
+    fn samples_after_exclusions() {
+        let opts = Opts {
+            addresses: vec!["10.0.0.0/24".to_owned(), "10.0.1.1".to_owned()],
+            exclude_addresses: Some(vec!["10.0.0.0/25".to_owned(), "10.0.1.1".to_owned()]),
+            sample: Some(SampleSize::Count(1000)),
+            seed: Some(3),
+            ..Default::default()
+        };
+
+        let targets = parse_targets(&opts);
+        let coverage = targets.coverage.unwrap();
+        assert_eq!((coverage.sampled, coverage.population), (128, 128));
+        assert_eq!(coverage.seed, 3);
+        assert!(targets.ips.iter().all(
+            |ip| matches!(ip, IpAddr::V4(ip) if ip.octets()[2] == 0 && ip.octets()[3] >= 128)
+        ));
+    }
+
+    #[test]
+    fn sampled_overlaps_are_counted_once() {
+        let opts = Opts {
+            addresses: vec![
+                "10.0.0.0/24".to_owned(),
+                "10.0.0.0/16".to_owned(),
+                "10.0.5.1".to_owned(),
+            ],
+            sample: Some(SampleSize::Count(10)),
+            seed: Some(3),
+            ..Default::default()
+        };
+
+        let targets = parse_targets(&opts);
+        assert_eq!(targets.coverage.unwrap().population, 65_536);
+        let covered: Vec<&str> = targets
+            .overlaps
+            .iter()
+            .map(|overlap| overlap.address.as_str())
+            .collect();
+        assert_eq!(covered, vec!["10.0.0.0/24", "10.0.5.1"]);
+    }
+
+    #[test]
+    fn parse_correct_addresses() {
+        let opts = Opts {
+            addresses: vec!["127.0.0.1".to_owned(), "192.168.0.0/30".to_owned()],
+            ..Default::default()
+        };
+
+        let ips = parse_addresses(&opts);
+
+        assert_eq!(
+            ips,
+            [
+                Ipv4Addr::new(127, 0, 0, 1),
+                Ipv4Addr::new(192, 168, 0, 0),
+                Ipv4Addr::new(192, 168, 0, 1),
+                Ipv4Addr::new(192, 168, 0, 2),
+                Ipv4Addr::new(192, 168, 0, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_addresses_with_address_exclusions() {
+        let opts = Opts {
+            addresses: vec!["192.168.0.0/30".to_owned()],
+            exclude_addresses: Some(vec!["192.168.0.1".to_owned()]),
+            ..Default::default()
+        };
+        let ips = parse_addresses(&opts);
+
+        assert_eq!(
+            ips,
+            [
+                Ipv4Addr::new(192, 168, 0, 0),
+                Ipv4Addr::new(192, 168, 0, 2),
+                Ipv4Addr::new(192, 168, 0, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_addresses_with_cidr_exclusions() {
+        let opts = Opts {
+            addresses: vec!["192.168.0.0/29".to_owned()],
+            exclude_addresses: Some(vec!["192.168.0.0/30".to_owned()]),
+            ..Default::default()
+        };
+        let ips = parse_addresses(&opts);
+
+        assert_eq!(
+            ips,
+            [
+                Ipv4Addr::new(192, 168, 0, 4),
+                Ipv4Addr::new(192, 168, 0, 5),
+                Ipv4Addr::new(192, 168, 0, 6),
+                Ipv4Addr::new(192, 168, 0, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_addresses_with_incorrect_address_exclusions() {
+        let opts = Opts {
+            addresses: vec!["192.168.0.0/30".to_owned()],
+            exclude_addresses: Some(vec!["192.168.0.1".to_owned()]),
+            ..Default::default()
+        };
+        let ips = parse_addresses(&opts);
+
+        assert_eq!(
+            ips,
+            [
+                Ipv4Addr::new(192, 168, 0, 0),
+                Ipv4Addr::new(192, 168, 0, 2),
+                Ipv4Addr::new(192, 168, 0, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_correct_host_addresses() {
+        let opts = Opts {
+            addresses: vec!["google.com".to_owned()],
+            ..Default::default()
+        };
+
+        let ips = parse_addresses(&opts);
+
+        // every A/AAAA record is kept, so this depends on the resolver
+        assert!(!ips.is_empty());
+    }
+
+    #[test]
+    fn parse_targets_keeps_hostnames() {
+        let opts = Opts {
+            addresses: vec!["127.0.0.1".to_owned(), "localhost".to_owned()],
+            ..Default::default()
+        };
+
+        let targets = parse_targets(&opts);
+
+        assert_eq!(targets.hosts.len(), 1);
+        assert!(targets.hosts["localhost"]
+            .iter()
+            .all(|ip| targets.ips.contains(ip)));
+        assert!(targets.ips.contains(&IpAddr::V4(Ipv4Addr::LOCALHOST)));
+    }
+
+    #[test]
+    fn parse_targets_keeps_tags() {
+        let opts = Opts {
+            addresses: vec![
+                "127.0.0.1 # db-prod prod".to_owned(),
+                "192.168.0.0/31 # lab".to_owned(),
+                "192.168.0.1 # prod".to_owned(),
+            ],
+            ..Default::default()
+        };
+
+        let targets = parse_targets(&opts);
+
+        assert_eq!(targets.ips.len(), 3);
+        assert_eq!(
+            targets.tags[&IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            vec!["db-prod", "prod"]
+        );
+        assert_eq!(
+            targets.tags[&IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1))],
+            vec!["lab", "prod"]
+        );
+    }
+
+    #[test]
+    fn parse_correct_and_incorrect_addresses() {
+        let opts = Opts {
+            addresses: vec!["127.0.0.1".to_owned(), "im_wrong".to_owned()],
+            ..Default::default()
+        };
+
+        let ips = parse_addresses(&opts);
+
+        assert_eq!(ips, [Ipv4Addr::new(127, 0, 0, 1),]);
+    }
+
+    #[test]
+    fn parse_incorrect_addresses() {
+        let opts = Opts {
+            addresses: vec!["im_wrong".to_owned(), "300.10.1.1".to_owned()],
+            ..Default::default()
+        };
+
+        let ips = parse_addresses(&opts);
+
+        assert!(ips.is_empty());
+    }
+
+    #[test]
+    fn parse_hosts_file_and_incorrect_hosts() {
+        // Host file contains IP, Hosts, incorrect IPs, incorrect hosts
+        let opts = Opts {
+            addresses: vec!["fixtures/hosts.txt".to_owned()],
+            ..Default::default()
+        };
+
+        let ips = parse_addresses(&opts);
+
+        // 127.0.0.1, plus every record of google.com and example.com
+        assert!(ips.len() >= 3);
+    }
+
+    #[test]
+    fn parse_empty_hosts_file() {
+        // Host file contains IP, Hosts, incorrect IPs, incorrect hosts
+        let opts = Opts {
+            addresses: vec!["fixtures/empty_hosts.txt".to_owned()],
+            ..Default::default()
+        };
+
+        let ips = parse_addresses(&opts);
+
+        assert_eq!(ips.len(), 0);
+    }
+
+    #[test]
+    fn parse_naughty_host_file() {
+        // Host file contains IP, Hosts, incorrect IPs, incorrect hosts
+        let opts = Opts {
+            addresses: vec!["fixtures/naughty_string.txt".to_owned()],
+            ..Default::default()
+        };
+
+        let ips = parse_addresses(&opts);
+
+        assert_eq!(ips.len(), 0);
+    }
+
+    #[test]
+    fn parse_duplicate_cidrs() {
+        let opts = Opts {
+            addresses: vec!["79.98.104.0/21".to_owned(), "79.98.104.0/24".to_owned()],
+            ..Default::default()
+        };
+
+        let ips = parse_addresses(&opts);
+
+        assert_eq!(ips.len(), 2_048);
+    }
+
+    #[test]
+    fn targets_keep_their_hostname() {
+        let opts = Opts {
+            addresses: vec!["localhost".to_owned(), "127.0.0.2".to_owned()],
+            ..Default::default()
+        };
+
+        let targets = parse_targets(&opts).targets();
+
+        let localhost = Target::new(Ipv4Addr::LOCALHOST.into()).with_host("localhost");
+        assert!(targets.contains(&localhost));
+        assert_eq!(localhost.to_string(), "127.0.0.1 (localhost)");
+        let other = Target::new(Ipv4Addr::new(127, 0, 0, 2).into());
+        assert!(targets.contains(&other));
+        assert_eq!(other.to_string(), "127.0.0.2");
+    }
+
+    #[test]
+    fn parse_ipv6_cidrs_and_zones() {
+        let opts = Opts {
+            addresses: vec![
+                "2001:db8::/120".to_owned(),
+                "2001:db8:1::/64".to_owned(),
+                "fe80::1%7".to_owned(),
+            ],
+            ..Default::default()
+        };
+
+        let targets = parse_targets(&opts);
+
+        assert_eq!(targets.ips.len(), 257);
+        let link_local: IpAddr = "fe80::1".parse().unwrap();
+        assert!(targets.ips.contains(&link_local));
+        assert_eq!(targets.scopes.get(&link_local), Some(&7));
+        assert!(targets.hosts.is_empty());
+    }
+
+    #[test]
+    fn parse_overspecific_cidr() {
+        // a canonical CIDR string has 0 in all host bits, but we want to treat any CIDR-like string as CIDR
+        let opts = Opts {
+            addresses: vec!["192.128.1.1/24".to_owned()],
+            ..Default::default()
+        };
+
+        let ips = parse_addresses(&opts);
+
+        assert_eq!(ips.len(), 256);
+    }
+
+    #[test]
+    fn resolve_all_keeps_order() {
+        let addresses: Vec<String> = (1..=20).map(|i| format!("10.0.0.{i}")).collect();
+        let resolver = get_resolver(&None);
+
+        let resolved = resolve_all(&addresses, &resolver, 4);
+
+        let expected: Vec<Vec<IpAddr>> = (1..=20)
+            .map(|i| vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, i))])
+            .collect();
+        assert_eq!(resolved, expected);
+    }
+
+    #[test]
+    fn resolver_args_google_dns() {
+        // https://developers.google.com/speed/public-dns
+        let opts = Opts {
+            resolver: Some("8.8.8.8,8.8.4.4".to_owned()),
+            ..Default::default()
+        };
+
+        let resolver = get_resolver(&opts.resolver);
+        let lookup = resolver.lookup_ip("www.example.com.");
+
+        assert!(!lookup.is_empty());
+    }
+}
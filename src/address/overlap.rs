@@ -0,0 +1,168 @@
+//! Finds the targets given more than once.
+//!
+//! Scope documents often list a host on its own and again within its
+//! network, or a hostname next to the IP it resolves to. Each IP is only
+//! scanned once either way; the targets that add nothing are reported so
+//! the scope can be cleaned up, and with `--sample` networks within another
+//! one are dropped so their addresses are not counted twice.
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+
+use cidr_utils::cidr::IpCidr;
+
+use super::network;
+
+/// A target whose addresses were all given by another one already.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    pub address: String,
+    pub covered_by: String,
+}
+
+impl fmt::Display for Overlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (covered by {})", self.address, self.covered_by)
+    }
+}
+
+/// Finds the targets covered by another one: networks within another
+/// network, and IPs or hostnames whose IPs are all within a network or were
+/// listed by an earlier target. `networks` holds the networks kept whole
+/// with `--sample`, the ones within another network are removed from it.
+pub(super) fn find_overlaps(
+    targets: &[(String, Vec<IpAddr>)],
+    networks: &mut Vec<(String, IpCidr)>,
+) -> Vec<Overlap> {
+    let mut overlaps = collapse_networks(networks);
+    let mut expanded: Vec<(String, IpCidr)> = targets
+        .iter()
+        .filter_map(|(address, _)| Some((address.clone(), network(address)?)))
+        .collect();
+    overlaps.extend(collapse_networks(&mut expanded));
+
+    let mut owners: HashMap<IpAddr, &str> = HashMap::new();
+    for (address, ips) in targets {
+        if ips.is_empty() || network(address).is_some() {
+            continue;
+        }
+        let covered_by = ips
+            .iter()
+            .map(|ip| {
+                networks
+                    .iter()
+                    .chain(&expanded)
+                    .find(|(_, cidr)| cidr.contains(ip))
+                    .map(|(network, _)| network.as_str())
+                    .or_else(|| owners.get(ip).copied())
+            })
+            .collect::<Option<Vec<&str>>>();
+        if let Some([first, ..]) = covered_by.as_deref() {
+            overlaps.push(Overlap {
+                address: address.clone(),
+                covered_by: (*first).to_owned(),
+            });
+        }
+        for ip in ips {
+            owners.entry(*ip).or_insert(address);
+        }
+    }
+    overlaps
+}
+
+/// Drops the networks within another one of `networks`, keeping the first
+/// of identical ones.
+fn collapse_networks(networks: &mut Vec<(String, IpCidr)>) -> Vec<Overlap> {
+    let mut overlaps = Vec::new();
+    let mut kept: Vec<(String, IpCidr)> = Vec::with_capacity(networks.len());
+    for (address, cidr) in networks.drain(..) {
+        if let Some((outer, _)) = kept.iter().find(|(_, outer)| within(&cidr, outer)) {
+            overlaps.push(Overlap {
+                address,
+                covered_by: outer.clone(),
+            });
+            continue;
+        }
+        let (covered, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut kept)
+            .into_iter()
+            .partition(|(_, inner)| within(inner, &cidr));
+        overlaps.extend(covered.into_iter().map(|(inner, _)| Overlap {
+            address: inner,
+            covered_by: address.clone(),
+        }));
+        kept = rest;
+        kept.push((address, cidr));
+    }
+    *networks = kept;
+    overlaps
+}
+
+/// Whether the network `inner` lies within `outer`.
+fn within(inner: &IpCidr, outer: &IpCidr) -> bool {
+    inner.is_ipv4() == outer.is_ipv4()
+        && outer.network_length() <= inner.network_length()
+        && outer.contains(&inner.first_address())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_overlaps, Overlap};
+    use cidr_utils::cidr::IpCidr;
+    use std::net::IpAddr;
+    use std::str::FromStr;
+
+    fn ips(ips: &[&str]) -> Vec<IpAddr> {
+        ips.iter().map(|ip| ip.parse().unwrap()).collect()
+    }
+
+    fn overlap(address: &str, covered_by: &str) -> Overlap {
+        Overlap {
+            address: address.to_owned(),
+            covered_by: covered_by.to_owned(),
+        }
+    }
+
+    #[test]
+    fn finds_covered_hosts_and_networks() {
+        let targets = vec![
+            ("10.0.0.5".to_owned(), ips(&["10.0.0.5"])),
+            ("10.0.0.4/31".to_owned(), ips(&["10.0.0.4", "10.0.0.5"])),
+            (
+                "10.0.0.0/30".to_owned(),
+                ips(&["10.0.0.0", "10.0.0.1", "10.0.0.2", "10.0.0.3"]),
+            ),
+            ("10.0.0.0/29".to_owned(), ips(&["10.0.0.0", "10.0.0.7"])),
+            ("192.168.1.1".to_owned(), ips(&["192.168.1.1"])),
+            ("db.example.com".to_owned(), ips(&["192.168.1.1"])),
+            (
+                "www.example.com".to_owned(),
+                ips(&["192.168.1.1", "192.168.1.2"]),
+            ),
+        ];
+        let mut networks = Vec::new();
+
+        assert_eq!(
+            find_overlaps(&targets, &mut networks),
+            vec![
+                overlap("10.0.0.4/31", "10.0.0.0/29"),
+                overlap("10.0.0.0/30", "10.0.0.0/29"),
+                overlap("10.0.0.5", "10.0.0.0/29"),
+                overlap("db.example.com", "192.168.1.1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_sampled_networks_within_another() {
+        let cidr = |cidr: &str| (cidr.to_owned(), IpCidr::from_str(cidr).unwrap());
+        let mut networks = vec![
+            cidr("10.0.0.0/24"),
+            cidr("10.0.0.0/16"),
+            cidr("10.0.0.0/16"),
+        ];
+
+        let overlaps = find_overlaps(&[], &mut networks);
+        assert_eq!(networks, vec![cidr("10.0.0.0/16")]);
+        assert_eq!(overlaps.len(), 2);
+    }
+}
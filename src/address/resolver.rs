@@ -0,0 +1,268 @@
+//! A pool of DNS resolvers with passive health checking.
+//!
+//! When several resolvers are given through `--resolver`, lookups rotate
+//! across them. Resolvers that keep failing or answer much slower than the
+//! rest are skipped, and a failed lookup is retried on the next resolver.
+//!
+//! hickory's blocking `Resolver` runs every lookup on one runtime behind a
+//! lock, so lookups made at the same time would queue on it. Each lookup
+//! takes a resolver of its own instead, created on demand and reused.
+use std::io;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::Resolver;
+use log::debug;
+
+/// Consecutive failures after which a resolver is only used as a last resort.
+const MAX_FAILURES: u32 = 3;
+
+/// A resolver is slow when its average latency is this many times the
+/// fastest healthy resolver's...
+const SLOW_FACTOR: u32 = 4;
+
+/// ...and above this, so a pool of fast resolvers isn't split by noise.
+const MIN_SLOW_LATENCY: Duration = Duration::from_millis(250);
+
+/// Rotates lookups across resolvers, failing over when one doesn't answer.
+///
+/// ```rust
+/// # use rustscan::address::ResolverPool;
+/// # use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+/// let pool = ResolverPool::from((ResolverConfig::default(), ResolverOpts::default()));
+/// let ips = pool.lookup_ip("localhost");
+/// ```
+pub struct ResolverPool {
+    members: Vec<Member>,
+    next: AtomicUsize,
+}
+
+struct Member {
+    name: String,
+    config: ResolverConfig,
+    options: ResolverOpts,
+    /// Resolvers of this member no lookup is using.
+    idle: Mutex<Vec<Resolver>>,
+    health: Mutex<Health>,
+}
+
+impl Member {
+    /// An idle resolver, or a new one when every resolver is in use.
+    fn take(&self) -> io::Result<Resolver> {
+        let idle = self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+        match idle {
+            Some(resolver) => Ok(resolver),
+            None => Resolver::new(self.config.clone(), self.options.clone()),
+        }
+    }
+
+    fn give_back(&self, resolver: Resolver) {
+        self.idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(resolver);
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Health {
+    failures: u32,
+    /// Moving average of the time taken by answered lookups.
+    latency: Option<Duration>,
+}
+
+impl Health {
+    fn is_dead(self) -> bool {
+        self.failures >= MAX_FAILURES
+    }
+
+    fn answered(&mut self, elapsed: Duration) {
+        self.failures = 0;
+        self.latency = Some(match self.latency {
+            Some(latency) => (latency * 3 + elapsed) / 4,
+            None => elapsed,
+        });
+    }
+}
+
+impl ResolverPool {
+    /// Creates a pool out of named resolver configurations, the name is only
+    /// used in logs.
+    pub fn new(resolvers: Vec<(String, ResolverConfig, ResolverOpts)>) -> Self {
+        Self {
+            members: resolvers
+                .into_iter()
+                .map(|(name, config, options)| Member {
+                    name,
+                    config,
+                    options,
+                    idle: Mutex::default(),
+                    health: Mutex::new(Health::default()),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Resolves a host to its IPs, trying each resolver in turn until one
+    /// answers. A negative answer is an answer and is not retried.
+    pub fn lookup_ip(&self, host: &str) -> Vec<IpAddr> {
+        for index in self.order() {
+            let member = &self.members[index];
+            let resolver = match member.take() {
+                Ok(resolver) => resolver,
+                Err(e) => {
+                    debug!("Could not create resolver {}: {e}", member.name);
+                    continue;
+                }
+            };
+            let start = Instant::now();
+            let result = resolver.lookup_ip(host);
+            member.give_back(resolver);
+            let mut health = member.health.lock().unwrap_or_else(PoisonError::into_inner);
+
+            match result {
+                Ok(lookup) => {
+                    health.answered(start.elapsed());
+                    return lookup.iter().collect();
+                }
+                Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {
+                    health.answered(start.elapsed());
+                    return Vec::new();
+                }
+                Err(e) => {
+                    health.failures += 1;
+                    debug!("Resolver {} failed to resolve {host}: {e}", member.name);
+                    if health.failures == MAX_FAILURES {
+                        debug!("Resolver {} looks dead, failing over", member.name);
+                    }
+                }
+            }
+        }
+
+        Vec::new()
+    }
+
+    /// The order to try resolvers in for the next lookup: healthy ones
+    /// first, starting from the next one in the rotation, then slow ones,
+    /// then dead ones as a last resort.
+    fn order(&self) -> Vec<usize> {
+        let len = self.members.len();
+        if len == 0 {
+            return Vec::new();
+        }
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % len;
+        let health: Vec<Health> = self
+            .members
+            .iter()
+            .map(|member| *member.health.lock().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+
+        let mut order: Vec<usize> = (0..len).map(|i| (start + i) % len).collect();
+        order.sort_by_key(|&i| rank(&health, i));
+        order
+    }
+}
+
+/// Something hostnames can be looked up with, a single resolver or a pool.
+pub trait Lookup {
+    fn lookup_ips(&self, host: &str) -> Vec<IpAddr>;
+}
+
+impl Lookup for Resolver {
+    fn lookup_ips(&self, host: &str) -> Vec<IpAddr> {
+        self.lookup_ip(host)
+            .map(|lookup| lookup.iter().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Lookup for ResolverPool {
+    fn lookup_ips(&self, host: &str) -> Vec<IpAddr> {
+        self.lookup_ip(host)
+    }
+}
+
+impl From<(ResolverConfig, ResolverOpts)> for ResolverPool {
+    fn from((config, options): (ResolverConfig, ResolverOpts)) -> Self {
+        Self::new(vec![("default".to_owned(), config, options)])
+    }
+}
+
+/// 0 for healthy, 1 for slow and 2 for dead resolvers. Sorting is stable,
+/// so the rotation is kept within each rank.
+fn rank(health: &[Health], index: usize) -> u8 {
+    let member = health[index];
+    if member.is_dead() {
+        return 2;
+    }
+
+    let fastest = health
+        .iter()
+        .filter(|h| !h.is_dead())
+        .filter_map(|h| h.latency)
+        .min();
+    match (member.latency, fastest) {
+        (Some(latency), Some(fastest))
+            if latency > MIN_SLOW_LATENCY && latency > fastest * SLOW_FACTOR =>
+        {
+            1
+        }
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rank, Health, MAX_FAILURES};
+    use std::time::Duration;
+
+    fn answered(ms: u64) -> Health {
+        Health {
+            failures: 0,
+            latency: Some(Duration::from_millis(ms)),
+        }
+    }
+
+    #[test]
+    fn ranks_healthy_slow_and_dead_resolvers() {
+        let dead = Health {
+            failures: MAX_FAILURES,
+            latency: Some(Duration::from_millis(1)),
+        };
+        let health = [answered(20), answered(2000), dead, Health::default()];
+
+        assert_eq!(rank(&health, 0), 0);
+        assert_eq!(rank(&health, 1), 1);
+        assert_eq!(rank(&health, 2), 2);
+        assert_eq!(rank(&health, 3), 0);
+    }
+
+    #[test]
+    fn fast_pools_are_not_split_by_noise() {
+        let health = [answered(1), answered(30)];
+
+        assert_eq!(rank(&health, 1), 0);
+    }
+
+    #[test]
+    fn answers_reset_failures() {
+        let mut health = Health {
+            failures: MAX_FAILURES,
+            latency: None,
+        };
+        health.answered(Duration::from_millis(100));
+
+        assert!(!health.is_dead());
+        assert_eq!(health.latency, Some(Duration::from_millis(100)));
+    }
+}
@@ -0,0 +1,305 @@
+//! Random sampling of target spaces too large to scan whole.
+//!
+//! Networks are never expanded: every address of the target space gets an
+//! index, a random set of distinct indexes is drawn and only those are
+//! turned into addresses. This keeps a sample of a `/64` as cheap as one of
+//! a `/24`. Excluded addresses are left out of the indexes, so they are
+//! neither drawn nor counted in the target space.
+use std::collections::HashSet;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use cidr_utils::cidr::IpCidr;
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::input::SampleSize;
+
+/// Largest sample drawn, to keep the target list in memory.
+pub const MAX_SAMPLE: u64 = 1 << 24;
+
+/// The addresses a target stands for: a network, or the IPs a host or a
+/// file resolved to.
+#[derive(Debug, Clone)]
+pub(super) enum Segment {
+    /// A network, but the excluded ranges of it, as offsets and lengths
+    /// in ascending order.
+    Network(IpCidr, Vec<(u128, u128)>),
+    Ips(Vec<IpAddr>),
+}
+
+impl Segment {
+    /// The addresses of `cidr` but those within `excluded`.
+    pub(super) fn network(cidr: IpCidr, excluded: &[IpCidr]) -> Self {
+        let base = index(cidr.first_address());
+        let mut holes: Vec<(u128, u128)> = excluded
+            .iter()
+            .filter(|hole| hole.is_ipv4() == cidr.is_ipv4())
+            .filter_map(|hole| {
+                // Networks either nest or don't overlap at all.
+                if hole.network_length() <= cidr.network_length() {
+                    hole.contains(&cidr.first_address())
+                        .then(|| (0, network_size(&cidr)))
+                } else {
+                    cidr.contains(&hole.first_address())
+                        .then(|| (index(hole.first_address()) - base, network_size(hole)))
+                }
+            })
+            .collect();
+        holes.sort_unstable();
+
+        let mut merged: Vec<(u128, u128)> = Vec::new();
+        for (start, len) in holes {
+            match merged.last_mut() {
+                Some((last, last_len)) if start < last.saturating_add(*last_len) => {
+                    *last_len = (*last_len).max(start.saturating_add(len) - *last);
+                }
+                _ => merged.push((start, len)),
+            }
+        }
+        Segment::Network(cidr, merged)
+    }
+
+    fn len(&self) -> u128 {
+        match self {
+            Segment::Network(cidr, holes) => {
+                holes.iter().fold(network_size(cidr), |len, (_, hole)| {
+                    len.saturating_sub(*hole)
+                })
+            }
+            Segment::Ips(ips) => ips.len() as u128,
+        }
+    }
+
+    fn get(&self, offset: u128) -> IpAddr {
+        match self {
+            Segment::Network(cidr, holes) => {
+                // Skips the holes up to the address, in order.
+                let mut offset = offset;
+                for (start, len) in holes {
+                    if offset < *start {
+                        break;
+                    }
+                    offset = offset.saturating_add(*len);
+                }
+                match cidr.first_address() {
+                    IpAddr::V4(base) => {
+                        IpAddr::V4(Ipv4Addr::from(u32::from(base).wrapping_add(offset as u32)))
+                    }
+                    IpAddr::V6(base) => {
+                        IpAddr::V6(Ipv6Addr::from(u128::from(base).wrapping_add(offset)))
+                    }
+                }
+            }
+            Segment::Ips(ips) => ips[offset as usize],
+        }
+    }
+}
+
+/// How many addresses `cidr` holds.
+fn network_size(cidr: &IpCidr) -> u128 {
+    let bits = if cidr.is_ipv4() { 32 } else { 128 };
+    1u128
+        .checked_shl(bits - u32::from(cidr.network_length()))
+        .unwrap_or(u128::MAX)
+}
+
+/// An address as a number, to count the offsets within networks.
+fn index(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(ip) => u128::from(u32::from(ip)),
+        IpAddr::V6(ip) => u128::from(ip),
+    }
+}
+
+/// How much of a target space a sample covers, for the scan summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SampleCoverage {
+    pub sampled: u64,
+    pub population: u128,
+    /// Draws the same sample again with `--seed`.
+    pub seed: u64,
+}
+
+impl SampleCoverage {
+    /// The share of the target space that was sampled, between 0 and 1.
+    pub fn fraction(&self) -> f64 {
+        if self.population == 0 {
+            return 0.0;
+        }
+        self.sampled as f64 / self.population as f64
+    }
+
+    /// Extrapolates `hits` out of the sample to the whole target space,
+    /// returning the estimate and its 95% margin of error.
+    pub fn extrapolate(&self, hits: usize) -> (f64, f64) {
+        if self.sampled == 0 {
+            return (0.0, 0.0);
+        }
+        let n = self.sampled as f64;
+        let p = hits as f64 / n;
+        let margin = 1.96 * (p * (1.0 - p) / n).sqrt();
+        let population = self.population as f64;
+        (p * population, margin * population)
+    }
+}
+
+impl fmt::Display for SampleCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} addresses ({:.4}%, --seed {})",
+            self.sampled,
+            self.population,
+            self.fraction() * 100.0,
+            self.seed
+        )
+    }
+}
+
+/// Draws a sample of `size` addresses out of `segments`, the same one for
+/// the same `seed`. Returns the sampled addresses of each segment, in the
+/// order of the segments, along with the coverage of the sample.
+pub(super) fn sample<K: Clone>(
+    segments: &[(K, Segment)],
+    size: SampleSize,
+    seed: u64,
+) -> (Vec<(K, Vec<IpAddr>)>, SampleCoverage) {
+    let population = segments.iter().fold(0u128, |total, (_, segment)| {
+        total.saturating_add(segment.len())
+    });
+    let wanted = match size {
+        SampleSize::Count(count) => u128::from(count),
+        SampleSize::Percent(percent) => (population as f64 * percent / 100.0).ceil() as u128,
+    };
+    let count = wanted.min(population).min(u128::from(MAX_SAMPLE));
+
+    let mut indexes: Vec<u128> = draw(population, count, &mut StdRng::seed_from_u64(seed))
+        .into_iter()
+        .collect();
+    indexes.sort_unstable();
+
+    let mut sampled = Vec::new();
+    let mut indexes = indexes.into_iter().peekable();
+    let mut start = 0u128;
+    for (key, segment) in segments {
+        let end = start.saturating_add(segment.len());
+        let mut ips = Vec::new();
+        while let Some(index) = indexes.next_if(|index| *index < end) {
+            ips.push(segment.get(index - start));
+        }
+        if !ips.is_empty() {
+            sampled.push((key.clone(), ips));
+        }
+        start = end;
+    }
+
+    let coverage = SampleCoverage {
+        sampled: count as u64,
+        population,
+        seed,
+    };
+    (sampled, coverage)
+}
+
+/// Draws `count` distinct indexes below `population` with Floyd's
+/// algorithm, which takes `count` steps however large the population is.
+fn draw(population: u128, count: u128, rng: &mut StdRng) -> HashSet<u128> {
+    let mut drawn = HashSet::with_capacity(count as usize);
+    for upper in population - count..population {
+        let index = rng.random_range(0..=upper);
+        if !drawn.insert(index) {
+            drawn.insert(upper);
+        }
+    }
+    drawn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sample, SampleCoverage, Segment};
+    use crate::input::SampleSize;
+    use cidr_utils::cidr::IpCidr;
+    use std::net::IpAddr;
+    use std::str::FromStr;
+
+    fn segments() -> Vec<(String, Segment)> {
+        vec![
+            (
+                "2001:db8::/32".to_owned(),
+                Segment::network(IpCidr::from_str("2001:db8::/32").unwrap(), &[]),
+            ),
+            (
+                "10.0.0.0/24".to_owned(),
+                Segment::network(IpCidr::from_str("10.0.0.0/24").unwrap(), &[]),
+            ),
+        ]
+    }
+
+    #[test]
+    fn samples_without_expanding_networks() {
+        let (sampled, coverage) = sample(&segments(), SampleSize::Count(1000), 7);
+        assert_eq!(coverage.sampled, 1000);
+        assert_eq!(coverage.population, (1u128 << 96) + 256);
+
+        let v6 = IpCidr::from_str("2001:db8::/32").unwrap();
+        let ips: Vec<IpAddr> = sampled.iter().flat_map(|(_, ips)| ips.clone()).collect();
+        assert_eq!(ips.len(), 1000);
+        assert!(ips.iter().all(|ip| v6.contains(ip)));
+    }
+
+    #[test]
+    fn same_seed_same_sample() {
+        let small = vec![(
+            "10.0.0.0/24".to_owned(),
+            Segment::network(IpCidr::from_str("10.0.0.0/24").unwrap(), &[]),
+        )];
+        let first = sample(&small, SampleSize::Percent(10.0), 42);
+        let second = sample(&small, SampleSize::Percent(10.0), 42);
+        assert_eq!(first.0, second.0);
+        assert_eq!(first.1.sampled, 26);
+
+        let mut ips = first.0[0].1.clone();
+        ips.dedup();
+        assert_eq!(ips.len(), 26);
+
+        let all = sample(&small, SampleSize::Count(1000), 1);
+        assert_eq!(all.0[0].1.len(), 256);
+    }
+
+    #[test]
+    fn excluded_addresses_are_never_drawn() {
+        let network = IpCidr::from_str("10.0.0.0/24").unwrap();
+        let excluded = [
+            IpCidr::from_str("10.0.0.0/25").unwrap(),
+            IpCidr::from_str("10.0.0.64/26").unwrap(),
+            IpCidr::from_str("10.0.0.200/32").unwrap(),
+            IpCidr::from_str("192.168.0.0/16").unwrap(),
+        ];
+        let segments = vec![((), Segment::network(network, &excluded))];
+
+        let (sampled, coverage) = sample(&segments, SampleSize::Count(1000), 5);
+        assert_eq!(coverage.population, 127);
+        let ips = &sampled[0].1;
+        assert_eq!(ips.len(), 127);
+        assert!(ips
+            .iter()
+            .all(|ip| network.contains(ip) && !excluded.iter().any(|cidr| cidr.contains(ip))));
+
+        let whole = [IpCidr::from_str("10.0.0.0/8").unwrap()];
+        assert_eq!(Segment::network(network, &whole).len(), 0);
+    }
+
+    #[test]
+    fn extrapolates_hits() {
+        let coverage = SampleCoverage {
+            sampled: 100,
+            population: 10_000,
+            seed: 0,
+        };
+        let (estimate, margin) = coverage.extrapolate(10);
+        assert!((estimate - 1000.0).abs() < 1e-6);
+        assert!(margin > 500.0 && margin < 650.0);
+    }
+}
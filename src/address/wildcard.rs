@@ -0,0 +1,131 @@
+//! Detects wildcard DNS while expanding hostnames.
+//!
+//! Subdomain lists often contain thousands of names under a domain where any
+//! label resolves, so every junk name turns into a target. Resolving a random
+//! label under each domain tells us which addresses the wildcard answers with,
+//! and names that only resolve to those are not real hosts.
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+
+use rand::RngExt;
+
+use super::{is_hostname, normalize_hostname, resolve_ips_from_host, ResolverPool};
+
+/// Domains with fewer hostnames than this are not worth probing.
+const MIN_HOSTS_PER_DOMAIN: usize = 10;
+
+/// Random labels resolved per domain, more than one catches wildcards that
+/// rotate through a pool of addresses.
+const PROBES_PER_DOMAIN: usize = 2;
+
+/// A domain answering for any label, and the targets it produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Wildcard {
+    pub domain: String,
+    pub ips: BTreeSet<IpAddr>,
+    /// Indices into the targets of the hostnames resolving only to `ips`.
+    pub hosts: Vec<usize>,
+}
+
+/// Looks for wildcard domains among the hostnames in `targets`, which pairs
+/// each address as given with the IPs it expanded to.
+pub(super) fn detect(targets: &[(String, Vec<IpAddr>)], resolver: &ResolverPool) -> Vec<Wildcard> {
+    group_by_domain(targets)
+        .into_iter()
+        .filter(|(_, hosts)| hosts.len() >= MIN_HOSTS_PER_DOMAIN)
+        .filter_map(|(domain, hosts)| {
+            let ips: BTreeSet<IpAddr> = (0..PROBES_PER_DOMAIN)
+                .filter_map(|_| normalize_hostname(&random_host(&domain)))
+                .flat_map(|host| resolve_ips_from_host(&host, resolver))
+                .collect();
+            if ips.is_empty() {
+                return None;
+            }
+
+            let hosts = wildcard_hosts(targets, &hosts, &ips);
+            Some(Wildcard { domain, ips, hosts })
+        })
+        .collect()
+}
+
+/// Groups the indices of hostname targets by their parent domain.
+fn group_by_domain(targets: &[(String, Vec<IpAddr>)]) -> BTreeMap<String, Vec<usize>> {
+    let mut domains: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (index, (address, ips)) in targets.iter().enumerate() {
+        if ips.is_empty() || !is_hostname(address) {
+            continue;
+        }
+        if let Some(domain) = parent_domain(address) {
+            domains
+                .entry(domain.to_ascii_lowercase())
+                .or_default()
+                .push(index);
+        }
+    }
+    domains
+}
+
+/// Strips the first label of a hostname, unless that would leave a bare TLD.
+fn parent_domain(host: &str) -> Option<&str> {
+    let (_, parent) = host.trim_end_matches('.').split_once('.')?;
+    parent.contains('.').then_some(parent)
+}
+
+fn random_host(domain: &str) -> String {
+    let mut rng = rand::rng();
+    let label: String = (0..20)
+        .map(|_| char::from(rng.random_range(b'a'..=b'z')))
+        .collect();
+    format!("{label}.{domain}")
+}
+
+/// Picks the hosts whose addresses are all answered by the wildcard.
+fn wildcard_hosts(
+    targets: &[(String, Vec<IpAddr>)],
+    hosts: &[usize],
+    wildcard_ips: &BTreeSet<IpAddr>,
+) -> Vec<usize> {
+    hosts
+        .iter()
+        .copied()
+        .filter(|&index| targets[index].1.iter().all(|ip| wildcard_ips.contains(ip)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{group_by_domain, parent_domain, wildcard_hosts};
+    use std::collections::BTreeSet;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn parent_domain_keeps_registrable_part() {
+        assert_eq!(parent_domain("www.example.com"), Some("example.com"));
+        assert_eq!(parent_domain("a.b.example.com."), Some("b.example.com"));
+        assert_eq!(parent_domain("example.com"), None);
+        assert_eq!(parent_domain("localhost"), None);
+    }
+
+    #[test]
+    fn only_hosts_answered_by_the_wildcard_are_junk() {
+        let wildcard = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let real = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let targets = vec![
+            ("junk.example.com".to_owned(), vec![wildcard]),
+            ("www.example.com".to_owned(), vec![real]),
+            ("mixed.example.com".to_owned(), vec![wildcard, real]),
+            ("10.0.0.1".to_owned(), vec![wildcard]),
+            ("gone.example.com".to_owned(), vec![]),
+        ];
+
+        let domains = group_by_domain(&targets);
+        assert_eq!(domains.len(), 1);
+        assert_eq!(domains["example.com"], vec![0, 1, 2]);
+
+        let wildcard_ips: BTreeSet<IpAddr> = std::iter::once(wildcard).collect();
+        assert_eq!(
+            wildcard_hosts(&targets, &domains["example.com"], &wildcard_ips),
+            vec![0]
+        );
+    }
+}
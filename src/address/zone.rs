@@ -0,0 +1,66 @@
+//! IPv6 addresses with a zone, like `fe80::1%eth0`.
+//!
+//! Link-local addresses are only meaningful on one link, so connecting to
+//! them needs the interface they are on. The zone can be given by the
+//! interface name or index, and is kept as the scope ID the sockets to the
+//! address are connected with.
+use std::net::Ipv6Addr;
+use std::str::FromStr;
+
+/// Parses an IPv6 address with a zone into the address and the index of
+/// the interface of the zone. `None` for anything else, and for zones that
+/// aren't an interface of this machine.
+///
+/// ```rust
+/// # use rustscan::address::parse_zoned;
+/// # use std::net::Ipv6Addr;
+/// assert_eq!(parse_zoned("fe80::1%2"), Some((Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1), 2)));
+/// assert_eq!(parse_zoned("fe80::1"), None);
+/// ```
+pub fn parse_zoned(address: &str) -> Option<(Ipv6Addr, u32)> {
+    let (ip, zone) = address.split_once('%')?;
+    let ip = Ipv6Addr::from_str(ip).ok()?;
+    let scope_id = match zone.parse::<u32>() {
+        Ok(index) => index,
+        Err(_) => interface_index(zone)?,
+    };
+    Some((ip, scope_id))
+}
+
+/// Whether `address` is an IPv6 address followed by a zone, known or not.
+pub(super) fn has_zone(address: &str) -> bool {
+    address
+        .split_once('%')
+        .is_some_and(|(ip, zone)| !zone.is_empty() && Ipv6Addr::from_str(ip).is_ok())
+}
+
+#[cfg(unix)]
+fn interface_index(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+    // SAFETY: `name` is a valid NUL terminated string for the whole call.
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    (index != 0).then_some(index)
+}
+
+#[cfg(not(unix))]
+fn interface_index(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_zone, parse_zoned};
+
+    #[test]
+    fn zones_by_index_and_name() {
+        assert_eq!(parse_zoned("fe80::1%3").map(|(_, scope)| scope), Some(3));
+        assert_eq!(parse_zoned("fe80::1%no-such-interface0"), None);
+        assert_eq!(parse_zoned("10.0.0.1%3"), None);
+        #[cfg(target_os = "linux")]
+        assert!(parse_zoned("fe80::1%lo").is_some());
+
+        assert!(has_zone("fe80::1%no-such-interface0"));
+        assert!(!has_zone("fe80::1%"));
+        assert!(!has_zone("example.com"));
+    }
+}
@@ -16,8 +16,25 @@
 //! // Print Benchmark Summary
 //! info!("{}", bm.summary());
 //! ```
+//!
+//! The results of a run can be saved with [`Benchmark::results`] and later
+//! compared against a newer run with [`BenchmarkResults::compare`], which is
+//! what `rustscan bench --compare old.json` does.
+//!
+//! [`ScanMetrics`] sums up the timing of a scan for library users, see
+//! [`Scanner::metrics`](crate::scanner::Scanner::metrics), and is part of
+//! the JSON report.
+use crate::scanner::{ErrorSummary, ScanSummary};
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
 use std::time::Instant;
 
+pub mod suite;
+
 /// A Benchmark struct to hold NamedTimers with name, start and end Instants,
 #[derive(Debug)]
 pub struct Benchmark {
@@ -51,6 +68,174 @@ impl Benchmark {
         }
         summary
     }
+
+    /// Collects the runtime in seconds of every finished timer, keyed by
+    /// the timer name, so it can be saved and compared with a later run.
+    pub fn results(&self) -> BenchmarkResults {
+        let mut phases = BTreeMap::new();
+
+        for timer in &self.named_timers {
+            if let (Some(start), Some(end)) = (timer.start, timer.end) {
+                let runtime_secs = end.saturating_duration_since(start).as_secs_f64();
+                phases.insert(timer.name.to_owned(), runtime_secs);
+            }
+        }
+
+        BenchmarkResults {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            phases,
+        }
+    }
+}
+
+/// How long a scan took and how fast it went, to tune the batch size and
+/// timeout without timing runs by hand.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScanMetrics {
+    /// How long the port scan took, in milliseconds.
+    pub duration_ms: u64,
+    /// The runtime of each phase in seconds, e.g. `Portscan` or `Scripts`,
+    /// once added with [`ScanMetrics::with_phases`].
+    pub phases: BTreeMap<String, f64>,
+    /// Sockets probed, retries not included.
+    pub probes: u64,
+    /// How many probes ran into each kind of error, e.g. `timed out`.
+    pub errors: BTreeMap<String, u64>,
+    /// Sockets probed per second.
+    pub pps: f64,
+}
+
+impl ScanMetrics {
+    /// The metrics of a scan summed up in `summary`, with its `errors`.
+    pub fn new(summary: &ScanSummary, errors: &ErrorSummary) -> Self {
+        Self {
+            duration_ms: u64::try_from(summary.duration.as_millis()).unwrap_or(u64::MAX),
+            phases: BTreeMap::new(),
+            probes: summary.probes,
+            errors: errors
+                .classes()
+                .into_iter()
+                .map(|(class, count)| (class.to_string(), count))
+                .collect(),
+            pps: summary.rate(),
+        }
+    }
+
+    /// Adds the runtime of the finished timers of `benchmark`.
+    #[must_use]
+    pub fn with_phases(mut self, benchmark: &Benchmark) -> Self {
+        self.phases = benchmark.results().phases;
+        self
+    }
+}
+
+/// Serializable runtimes of a benchmark run, one entry per phase.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkResults {
+    pub version: String,
+    pub phases: BTreeMap<String, f64>,
+}
+
+impl BenchmarkResults {
+    /// Reads previously saved results from a JSON file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Writes the results as JSON to the given file.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Compares these (newer) results against an older run, phase by phase.
+    ///
+    /// A phase only counts as a regression or improvement when its runtime
+    /// changed by more than `threshold_percent`, which keeps normal jitter
+    /// between runs from being reported.
+    pub fn compare(&self, old: &BenchmarkResults, threshold_percent: f64) -> Vec<PhaseComparison> {
+        let mut names: Vec<&String> = old.phases.keys().chain(self.phases.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let old_secs = old.phases.get(name).copied();
+                let new_secs = self.phases.get(name).copied();
+                let change = match (old_secs, new_secs) {
+                    (Some(old_secs), Some(new_secs)) => {
+                        let percent = if old_secs > 0.0 {
+                            (new_secs - old_secs) / old_secs * 100.0
+                        } else {
+                            0.0
+                        };
+
+                        if percent > threshold_percent {
+                            Change::Regression(percent)
+                        } else if percent < -threshold_percent {
+                            Change::Improvement(percent)
+                        } else {
+                            Change::Unchanged(percent)
+                        }
+                    }
+                    (None, Some(_)) => Change::Added,
+                    _ => Change::Removed,
+                };
+
+                PhaseComparison {
+                    name: name.clone(),
+                    old_secs,
+                    new_secs,
+                    change,
+                }
+            })
+            .collect()
+    }
+}
+
+/// How the runtime of a single phase changed between two runs.
+/// Percentages are relative to the old runtime, positive means slower.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Regression(f64),
+    Improvement(f64),
+    Unchanged(f64),
+    Added,
+    Removed,
+}
+
+/// The result of comparing a phase between an old and a new run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseComparison {
+    pub name: String,
+    pub old_secs: Option<f64>,
+    pub new_secs: Option<f64>,
+    pub change: Change,
+}
+
+impl std::fmt::Display for PhaseComparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs =
+            |secs: Option<f64>| secs.map_or_else(|| String::from("-"), |s| format!("{s:.3}s"));
+        let change = match self.change {
+            Change::Regression(p) => format!("{p:+.1}% regression"),
+            Change::Improvement(p) => format!("{p:+.1}% improvement"),
+            Change::Unchanged(p) => format!("{p:+.1}% unchanged"),
+            Change::Added => String::from("new phase"),
+            Change::Removed => String::from("missing from this run"),
+        };
+
+        write!(
+            f,
+            "{0: <20} | {1: >10} -> {2: <10} | {3}",
+            self.name,
+            secs(self.old_secs),
+            secs(self.new_secs),
+            change
+        )
+    }
 }
 
 /// The purpose of NamedTimer is to hold a name,
@@ -90,3 +275,66 @@ fn benchmark() {
         .contains("\nRustScan Benchmark Summary\ntest       | 0."));
     assert!(!benchmarks.summary().contains("only_start"));
 }
+
+#[test]
+fn benchmark_compare() {
+    let old = BenchmarkResults {
+        version: String::from("old"),
+        phases: BTreeMap::from([
+            (String::from("Portscan"), 2.0),
+            (String::from("Scripts"), 1.0),
+            (String::from("Removed"), 1.0),
+        ]),
+    };
+    let new = BenchmarkResults {
+        version: String::from("new"),
+        phases: BTreeMap::from([
+            (String::from("Portscan"), 1.0),
+            (String::from("Scripts"), 1.02),
+            (String::from("Added"), 1.0),
+        ]),
+    };
+
+    let comparison = new.compare(&old, 5.0);
+    let change_of = |name: &str| {
+        comparison
+            .iter()
+            .find(|phase| phase.name == name)
+            .map(|phase| phase.change)
+            .unwrap()
+    };
+
+    assert_eq!(change_of("Portscan"), Change::Improvement(-50.0));
+    assert!(matches!(change_of("Scripts"), Change::Unchanged(_)));
+    assert_eq!(change_of("Added"), Change::Added);
+    assert_eq!(change_of("Removed"), Change::Removed);
+    assert_eq!(old.compare(&new, 5.0)[1].change, Change::Regression(100.0));
+}
+
+#[test]
+fn scan_metrics() {
+    use crate::scanner::ProbeError;
+    use std::io;
+    use std::time::Duration;
+
+    let mut errors = ErrorSummary::default();
+    let ip = "10.0.0.1".parse().unwrap();
+    errors.record(ip, &io::Error::from(io::ErrorKind::TimedOut));
+    errors.record(ip, &io::Error::from(io::ErrorKind::TimedOut));
+    errors.record(ip, &io::Error::from(io::ErrorKind::ConnectionRefused));
+    let open: Vec<std::net::SocketAddr> = vec!["10.0.0.1:22".parse().unwrap()];
+    let summary = ScanSummary::new(1, &open, &errors, Duration::from_millis(2000));
+
+    let mut benchmarks = Benchmark::init();
+    let mut portscan = NamedTimer::start("Portscan");
+    portscan.end();
+    benchmarks.push(portscan);
+    let metrics = ScanMetrics::new(&summary, &errors).with_phases(&benchmarks);
+
+    assert_eq!(metrics.duration_ms, 2000);
+    assert_eq!(metrics.probes, 4);
+    assert!((metrics.pps - 2.0).abs() < f64::EPSILON);
+    assert_eq!(metrics.errors[&ProbeError::TimedOut.to_string()], 2);
+    assert_eq!(metrics.errors["connection refused"], 1);
+    assert!(metrics.phases.contains_key("Portscan"));
+}
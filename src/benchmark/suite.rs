@@ -0,0 +1,79 @@
+//! A small, fixed benchmark suite used by `rustscan bench`.
+//!
+//! Every phase is timed with a [`NamedTimer`] so that two runs of the suite
+//! can be compared with [`BenchmarkResults::compare`](super::BenchmarkResults::compare).
+//! The suite only targets localhost to keep runs reproducible.
+use super::{Benchmark, NamedTimer};
+use crate::address::parse_addresses;
+use crate::input::{Opts, PortRange, ScanOrder};
+use crate::port_strategy::PortStrategy;
+use crate::scanner::Scanner;
+
+use async_std::task::block_on;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Runs every phase of the benchmark suite and returns their timings.
+pub fn run() -> Benchmark {
+    let mut benchmarks = Benchmark::init();
+    let addrs = vec!["127.0.0.1".parse::<IpAddr>().unwrap()];
+    let range = PortRange {
+        start: 1,
+        end: 1_000,
+    };
+
+    let mut address_bench = NamedTimer::start("Address parsing");
+    let opts = Opts {
+        addresses: vec![
+            "127.0.0.1".to_owned(),
+            "10.2.0.1".to_owned(),
+            "192.168.0.0/24".to_owned(),
+        ],
+        exclude_addresses: Some(vec!["192.168.0.0/28".to_owned()]),
+        ..Default::default()
+    };
+    let _ips = parse_addresses(&opts);
+    address_bench.end();
+    benchmarks.push(address_bench);
+
+    let mut strategy_bench = NamedTimer::start("Port strategy");
+    for _ in 0..100 {
+        let _ports = PortStrategy::pick(&Some(range.clone()), None, ScanOrder::Random).order();
+    }
+    strategy_bench.end();
+    benchmarks.push(strategy_bench);
+
+    let mut tcp_bench = NamedTimer::start("Portscan TCP");
+    let scanner = Scanner::new(
+        &addrs,
+        500,
+        Duration::from_millis(100),
+        1,
+        true,
+        PortStrategy::pick(&Some(range.clone()), None, ScanOrder::Serial),
+        true,
+        vec![],
+        false,
+    );
+    block_on(scanner.run());
+    tcp_bench.end();
+    benchmarks.push(tcp_bench);
+
+    let mut udp_bench = NamedTimer::start("Portscan UDP");
+    let scanner = Scanner::new(
+        &addrs,
+        500,
+        Duration::from_millis(100),
+        1,
+        true,
+        PortStrategy::pick(&Some(range), None, ScanOrder::Serial),
+        true,
+        vec![],
+        true,
+    );
+    block_on(scanner.run());
+    udp_bench.end();
+    benchmarks.push(udp_bench);
+
+    benchmarks
+}
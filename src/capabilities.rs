@@ -0,0 +1,396 @@
+//! Detects what the process is allowed to do on this system.
+//!
+//! Some scan modes need more than a plain connect scan: raw sockets for
+//! crafting packets, ICMP for reading unreachable messages, or binding a
+//! source port below 1024. The capabilities are probed once at startup by
+//! trying to open the sockets involved, so the result reflects root, Linux
+//! capabilities, sysctls and Windows privileges alike. Modes whose needs
+//! aren't met can fall back to a connect scan instead of failing.
+//!
+//! ## Raw sockets without root on Linux
+//!
+//! Raw socket modes don't need root, only `CAP_NET_RAW`, which can be given
+//! to the binary once:
+//!
+//! ```text
+//! sudo setcap cap_net_raw+ep $(which rustscan)
+//! ```
+//!
+//! [`has_cap_net_raw`] tells whether raw sockets come from that capability,
+//! and [`drop_privileges`] gives it up, along with any other privilege, once
+//! the raw sockets are open, so the rest of the scan runs unprivileged.
+//!
+//! ```rust
+//! # use rustscan::capabilities::{Capabilities, Capability};
+//! let capabilities = Capabilities::detect();
+//! if let Err(reason) = capabilities.require("SYN scan", &[Capability::RawSockets]) {
+//!     println!("{reason}, falling back to a connect scan.");
+//! }
+//! ```
+use socket2::{Domain, Protocol, Socket, Type};
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+
+/// The source port tried when checking for low port binding.
+const LOW_SOURCE_PORT: u16 = 1;
+
+/// Capability numbers, from linux/capability.h.
+const CAP_NET_RAW: u32 = 13;
+
+/// Something a scan mode can need from the system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Opening raw IP sockets, to send hand-crafted TCP packets.
+    RawSockets,
+    /// Sending and receiving ICMP, through raw or unprivileged ping sockets.
+    Icmp,
+    /// Binding source ports below 1024.
+    LowSourcePorts,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Capability::RawSockets => "raw sockets",
+            Capability::Icmp => "ICMP",
+            Capability::LowSourcePorts => "low source ports",
+        })
+    }
+}
+
+/// What the process can do, as probed by [`Capabilities::detect`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub raw_sockets: bool,
+    pub icmp: bool,
+    pub low_source_ports: bool,
+}
+
+impl Capabilities {
+    /// Probes the system by opening the sockets each capability needs.
+    pub fn detect() -> Self {
+        Self {
+            raw_sockets: Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::TCP)).is_ok(),
+            icmp: Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)).is_ok()
+                || Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)).is_ok(),
+            low_source_ports: can_bind_low_port(),
+        }
+    }
+
+    pub fn has(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::RawSockets => self.raw_sockets,
+            Capability::Icmp => self.icmp,
+            Capability::LowSourcePorts => self.low_source_ports,
+        }
+    }
+
+    /// The capabilities the process has, in a fixed order.
+    pub fn available(&self) -> Vec<Capability> {
+        [
+            Capability::RawSockets,
+            Capability::Icmp,
+            Capability::LowSourcePorts,
+        ]
+        .iter()
+        .copied()
+        .filter(|&capability| self.has(capability))
+        .collect()
+    }
+
+    /// Checks that `mode` can run, or explains what it is missing so the
+    /// caller can warn and fall back to something that needs less.
+    pub fn require(&self, mode: &str, needed: &[Capability]) -> Result<(), String> {
+        let missing: Vec<String> = needed
+            .iter()
+            .filter(|&&capability| !self.has(capability))
+            .map(ToString::to_string)
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        Err(format!(
+            "{mode} needs {}, which this process is not allowed to use",
+            missing.join(" and ")
+        ))
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let available: Vec<String> = self.available().iter().map(ToString::to_string).collect();
+        if available.is_empty() {
+            return f.write_str("connect scans only");
+        }
+        f.write_str(&available.join(", "))
+    }
+}
+
+/// Whether the process holds `CAP_NET_RAW` without running as root, as it
+/// does when the capability was set on the binary with `setcap`.
+pub fn has_cap_net_raw() -> bool {
+    !running_as_root()
+        && effective_capabilities().is_some_and(|caps| caps & (1 << CAP_NET_RAW) != 0)
+}
+
+#[cfg(unix)]
+fn running_as_root() -> bool {
+    // SAFETY: geteuid cannot fail and has no side effects.
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+fn running_as_root() -> bool {
+    false
+}
+
+/// The effective capability set of the process, from /proc/self/status.
+fn effective_capabilities() -> Option<u64> {
+    parse_effective_capabilities(&std::fs::read_to_string("/proc/self/status").ok()?)
+}
+
+fn parse_effective_capabilities(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+}
+
+/// The user and group to drop privileges to: the real ones of the process,
+/// or the ones sudo ran it for (`SUDO_UID` and `SUDO_GID`) when it runs as
+/// root, which has no unprivileged user to go back to otherwise.
+#[cfg(unix)]
+fn unprivileged_ids(
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+    sudo_uid: Option<&str>,
+    sudo_gid: Option<&str>,
+) -> io::Result<(libc::uid_t, libc::gid_t)> {
+    if uid != 0 {
+        return Ok((uid, gid));
+    }
+    match (
+        sudo_uid.and_then(|id| id.parse().ok()),
+        sudo_gid.and_then(|id| id.parse().ok()),
+    ) {
+        (Some(uid), Some(gid)) if uid != 0 => Ok((uid, gid)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "running as root without SUDO_UID and SUDO_GID naming another user, there is no unprivileged user to drop to",
+        )),
+    }
+}
+
+/// Switches every id of the process (real, effective and saved) to `uid`
+/// and `gid`, giving up root's supplementary groups first while it still
+/// may, then checks that root is gone for good.
+#[cfg(unix)]
+fn switch_ids(uid: libc::uid_t, gid: libc::gid_t) -> io::Result<()> {
+    let check = |ret: libc::c_int| {
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    };
+
+    // SAFETY: the id calls only change the credentials of this process and
+    // setgroups reads a single gid that outlives the call.
+    unsafe {
+        if libc::geteuid() == 0 {
+            check(libc::setgroups(1, &gid))?;
+        }
+        #[cfg(target_os = "linux")]
+        {
+            check(libc::setresgid(gid, gid, gid))?;
+            check(libc::setresuid(uid, uid, uid))?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            check(libc::setgid(gid))?;
+            check(libc::setuid(uid))?;
+        }
+        if libc::geteuid() == 0 || libc::getuid() == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "still running as root after dropping privileges",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Gives up every privilege the process holds: a setuid binary goes back
+/// to the user that ran it, root run through sudo to the user that ran
+/// sudo, every Linux capability is cleared and new ones can't be gained
+/// through exec. Sockets that are already open keep working, so call this
+/// right after creating the raw sockets a scan needs.
+///
+/// Fails when the process runs as root and there is no user to go back to.
+#[cfg(target_os = "linux")]
+pub fn drop_privileges() -> io::Result<()> {
+    #[repr(C)]
+    struct CapUserHeader {
+        version: u32,
+        pid: libc::c_int,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct CapUserData {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+
+    const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+    let check = |ret: libc::c_long| {
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    };
+
+    // SAFETY: getuid and getgid can't fail.
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let (uid, gid) = unprivileged_ids(
+        uid,
+        gid,
+        std::env::var("SUDO_UID").ok().as_deref(),
+        std::env::var("SUDO_GID").ok().as_deref(),
+    )?;
+    switch_ids(uid, gid)?;
+
+    // SAFETY: capset reads a header and two data structs laid out as the
+    // kernel expects for version 3, both of which outlive the call.
+    unsafe {
+        let mut header = CapUserHeader {
+            version: LINUX_CAPABILITY_VERSION_3,
+            pid: 0,
+        };
+        let data = [CapUserData::default(); 2];
+        check(libc::syscall(
+            libc::SYS_capset,
+            &mut header as *mut CapUserHeader,
+            data.as_ptr(),
+        ))?;
+
+        check(
+            libc::prctl(
+                libc::PR_SET_NO_NEW_PRIVS,
+                1 as libc::c_ulong,
+                0 as libc::c_ulong,
+                0 as libc::c_ulong,
+                0 as libc::c_ulong,
+            )
+            .into(),
+        )
+    }
+}
+
+/// Gives up the privileges of a setuid binary, going back to the user that
+/// ran it, or those of root run through sudo, going back to the user that
+/// ran sudo.
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn drop_privileges() -> io::Result<()> {
+    // SAFETY: getuid and getgid can't fail.
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let (uid, gid) = unprivileged_ids(
+        uid,
+        gid,
+        std::env::var("SUDO_UID").ok().as_deref(),
+        std::env::var("SUDO_GID").ok().as_deref(),
+    )?;
+    switch_ids(uid, gid)
+}
+
+/// There is nothing to give up outside of unix.
+#[cfg(not(unix))]
+pub fn drop_privileges() -> io::Result<()> {
+    Ok(())
+}
+
+/// Binding a port that is already taken still proves the permission check
+/// passed, so only a permission error counts as missing the capability.
+fn can_bind_low_port() -> bool {
+    let Ok(socket) = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP)) else {
+        return false;
+    };
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, LOW_SOURCE_PORT));
+    match socket.bind(&address.into()) {
+        Ok(()) => true,
+        Err(e) => e.kind() != io::ErrorKind::PermissionDenied,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_effective_capabilities, Capabilities, Capability, CAP_NET_RAW};
+
+    #[test]
+    #[cfg(unix)]
+    fn root_drops_to_the_sudo_user() {
+        use super::unprivileged_ids;
+
+        assert_eq!(
+            unprivileged_ids(1000, 100, Some("0"), Some("0")).unwrap(),
+            (1000, 100)
+        );
+        assert_eq!(
+            unprivileged_ids(0, 0, Some("1000"), Some("100")).unwrap(),
+            (1000, 100)
+        );
+        assert!(unprivileged_ids(0, 0, None, None).is_err());
+        assert!(unprivileged_ids(0, 0, Some("1000"), None).is_err());
+        assert!(unprivileged_ids(0, 0, Some("0"), Some("0")).is_err());
+    }
+
+    #[test]
+    fn require_lists_missing_capabilities() {
+        let capabilities = Capabilities {
+            raw_sockets: false,
+            icmp: true,
+            low_source_ports: false,
+        };
+
+        assert_eq!(
+            capabilities.require("UDP scan", &[Capability::Icmp]),
+            Ok(())
+        );
+        assert_eq!(
+            capabilities.require(
+                "SYN scan",
+                &[Capability::RawSockets, Capability::LowSourcePorts]
+            ),
+            Err(
+                "SYN scan needs raw sockets and low source ports, which this process is not allowed to use"
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn parses_effective_capabilities() {
+        let status = "Name:\trustscan\nCapInh:\t0000000000000000\nCapPrm:\t0000000000002000\nCapEff:\t0000000000002000\n";
+        let caps = parse_effective_capabilities(status).unwrap();
+
+        assert_ne!(caps & (1 << CAP_NET_RAW), 0);
+        assert_eq!(parse_effective_capabilities("Name:\trustscan\n"), None);
+    }
+
+    #[test]
+    fn displays_available_capabilities() {
+        let capabilities = Capabilities {
+            raw_sockets: true,
+            icmp: true,
+            low_source_ports: false,
+        };
+
+        assert_eq!(capabilities.to_string(), "raw sockets, ICMP");
+        assert_eq!(Capabilities::default().to_string(), "connect scans only");
+    }
+}
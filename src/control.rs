@@ -0,0 +1,221 @@
+//! Control socket to steer a running scan from another terminal.
+//!
+//! The socket speaks a line based protocol: every line is a command and is
+//! answered with zero or more lines of output followed by `ok`, or by a
+//! single `error: ...` line.
+//!
+//! | command               | effect                                        |
+//! |-----------------------|-----------------------------------------------|
+//! | `status`              | progress, open ports found and the rate       |
+//! | `pause`               | stops starting new probes                     |
+//! | `resume`              | continues a paused scan                       |
+//! | `set-rate <n>`        | caps the scan to `n` probes per second        |
+//! | `set-rate unlimited`  | lifts the cap                                 |
+//! | `dump-partial`        | the open sockets found so far, one per line   |
+//!
+//! For example `echo status | nc -U /tmp/rustscan.sock`.
+use crate::scanner::ScanControl;
+
+use log::debug;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+/// Serves the control socket until dropped, which removes the socket file.
+#[derive(Debug)]
+pub struct ControlServer {
+    path: PathBuf,
+}
+
+impl ControlServer {
+    /// Listens on `path` and answers the commands of every client with
+    /// `control`. A stale socket file left by a previous scan is replaced,
+    /// anything else at `path` is left alone and fails the bind.
+    pub fn bind(path: &Path, control: Arc<ScanControl>) -> io::Result<Self> {
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if !metadata.file_type().is_socket() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ));
+            }
+            Ok(_) if UnixStream::connect(path).is_ok() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is in use by another scan", path.display()),
+                ));
+            }
+            Ok(_) => std::fs::remove_file(path)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let listener = UnixListener::bind(path)?;
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let control = Arc::clone(&control);
+                thread::spawn(move || {
+                    if let Err(e) = serve(stream, &control) {
+                        debug!("Control socket client failed: {e}");
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            path: path.to_owned(),
+        })
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn serve(stream: UnixStream, control: &ScanControl) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match execute(&line, control) {
+            Ok(output) => {
+                for line in output {
+                    writeln!(writer, "{line}")?;
+                }
+                writeln!(writer, "ok")?;
+            }
+            Err(e) => writeln!(writer, "error: {e}")?,
+        }
+    }
+    Ok(())
+}
+
+/// Runs a single command against `control` and returns its output lines.
+fn execute(line: &str, control: &ScanControl) -> Result<Vec<String>, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let argument = words.next();
+    if words.next().is_some() {
+        return Err(format!("too many arguments to {command}"));
+    }
+
+    match (command, argument) {
+        ("status", None) => Ok(vec![control.status().to_string()]),
+        ("pause", None) => {
+            control.pause();
+            Ok(Vec::new())
+        }
+        ("resume", None) => {
+            control.resume();
+            Ok(Vec::new())
+        }
+        ("set-rate", Some("unlimited")) => {
+            control.set_rate(None);
+            Ok(Vec::new())
+        }
+        ("set-rate", Some(rate)) => match rate.parse::<u32>() {
+            Ok(0) | Err(_) => Err(format!(
+                "invalid rate {rate}, expected probes per second or unlimited"
+            )),
+            Ok(rate) => {
+                control.set_rate(Some(rate));
+                Ok(Vec::new())
+            }
+        },
+        ("dump-partial", None) => Ok(control
+            .partial_results()
+            .iter()
+            .map(ToString::to_string)
+            .collect()),
+        ("status" | "pause" | "resume" | "dump-partial", Some(_)) => {
+            Err(format!("{command} takes no arguments"))
+        }
+        ("set-rate", None) => Err("set-rate needs a rate".to_owned()),
+        _ => Err(format!("unknown command {command}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{execute, ControlServer};
+    use crate::scanner::ScanControl;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::sync::Arc;
+
+    #[test]
+    fn commands_steer_the_scan() {
+        let control = ScanControl::default();
+
+        assert_eq!(execute("pause", &control), Ok(Vec::new()));
+        assert!(control.is_paused());
+        assert_eq!(execute("resume", &control), Ok(Vec::new()));
+        assert!(!control.is_paused());
+
+        assert_eq!(execute("set-rate 500", &control), Ok(Vec::new()));
+        assert_eq!(control.rate(), Some(500));
+        assert_eq!(execute("set-rate unlimited", &control), Ok(Vec::new()));
+        assert_eq!(control.rate(), None);
+
+        assert!(execute("set-rate fast", &control).is_err());
+        assert!(execute("set-rate 0", &control).is_err());
+        assert!(execute("pause now", &control).is_err());
+        assert!(execute("stop", &control).is_err());
+    }
+
+    #[test]
+    fn socket_answers_commands() {
+        let path =
+            std::env::temp_dir().join(format!("rustscan-control-test-{}.sock", std::process::id()));
+        let control = Arc::new(ScanControl::default());
+        let server = ControlServer::bind(&path, Arc::clone(&control)).unwrap();
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        writeln!(stream, "pause").unwrap();
+        writeln!(stream, "status").unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "ok");
+        assert!(lines.next().unwrap().unwrap().starts_with("paused"));
+        assert_eq!(lines.next().unwrap().unwrap(), "ok");
+        assert!(control.is_paused());
+
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn bind_keeps_files_that_are_not_sockets() {
+        let path =
+            std::env::temp_dir().join(format!("rustscan-control-file-{}.sock", std::process::id()));
+        std::fs::write(&path, "keep me").unwrap();
+
+        let result = ControlServer::bind(&path, Arc::new(ScanControl::default()));
+
+        assert_eq!(
+            result.unwrap_err().kind(),
+            std::io::ErrorKind::AlreadyExists
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bind_replaces_stale_sockets_only() {
+        let path = std::env::temp_dir().join(format!(
+            "rustscan-control-stale-{}.sock",
+            std::process::id()
+        ));
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let server = ControlServer::bind(&path, Arc::new(ScanControl::default())).unwrap();
+        let result = ControlServer::bind(&path, Arc::new(ScanControl::default()));
+
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::AddrInUse);
+        drop(server);
+    }
+}
@@ -0,0 +1,82 @@
+//! The kernel's ARP table, listing the hosts of the local networks that
+//! answered ARP requests. Linux only, the table is empty elsewhere.
+use std::fs;
+use std::net::Ipv4Addr;
+
+const ARP_PATH: &str = "/proc/net/arp";
+
+/// The entry is complete: the host answered with its hardware address.
+const ATF_COM: u32 = 0x2;
+
+/// A host of a local network that answered ARP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArpEntry {
+    pub ip: Ipv4Addr,
+    /// The hardware address, like `aa:bb:cc:dd:ee:ff`.
+    pub mac: String,
+    /// The interface the host is reached through.
+    pub device: String,
+}
+
+/// The complete entries of the ARP table.
+pub fn table() -> Vec<ArpEntry> {
+    fs::read_to_string(ARP_PATH)
+        .map(|table| parse(&table))
+        .unwrap_or_default()
+}
+
+/// The complete entries of `table`, in the format of `/proc/net/arp`.
+fn parse(table: &str) -> Vec<ArpEntry> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [ip, _, flags, mac, _, device] = fields[..] else {
+                return None;
+            };
+            let flags = u32::from_str_radix(flags.trim_start_matches("0x"), 16).ok()?;
+            if flags & ATF_COM == 0 {
+                return None;
+            }
+            Some(ArpEntry {
+                ip: ip.parse().ok()?,
+                mac: mac.to_owned(),
+                device: device.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, ArpEntry};
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn keeps_complete_entries() {
+        let table = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.1      0x1         0x2         aa:bb:cc:dd:ee:ff     *        eth0
+192.168.1.7      0x1         0x0         00:00:00:00:00:00     *        eth0
+192.168.1.9      0x1         0x6         11:22:33:44:55:66     *        wlan0
+garbage
+";
+
+        assert_eq!(
+            parse(table),
+            vec![
+                ArpEntry {
+                    ip: Ipv4Addr::new(192, 168, 1, 1),
+                    mac: "aa:bb:cc:dd:ee:ff".to_owned(),
+                    device: "eth0".to_owned(),
+                },
+                ArpEntry {
+                    ip: Ipv4Addr::new(192, 168, 1, 9),
+                    mac: "11:22:33:44:55:66".to_owned(),
+                    device: "wlan0".to_owned(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! ICMP echo requests, sent to every target at once before the replies are
+//! read until the timeout.
+use log::debug;
+use rand::RngExt;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::collections::HashSet;
+use std::io;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+const ECHO_REPLY: u8 = 0;
+const ECHO_REQUEST: u8 = 8;
+
+/// How often the receiving loop checks whether the timeout is over.
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A socket to send echo requests through.
+#[derive(Debug)]
+pub(super) struct IcmpSocket {
+    socket: Socket,
+    /// Raw sockets read the IP header too, and every ICMP packet the host
+    /// gets, not only the replies to our requests.
+    raw: bool,
+}
+
+impl IcmpSocket {
+    /// Uses `socket`, a raw IPv4 ICMP socket as
+    /// [`RawSockets`](crate::privsep::RawSockets) opens.
+    pub(super) fn raw(socket: Socket) -> Self {
+        Self { socket, raw: true }
+    }
+
+    /// Opens a ping socket, which Linux lets the groups in
+    /// `net.ipv4.ping_group_range` open unprivileged, or a raw socket.
+    /// `None` when neither is allowed.
+    pub(super) fn open() -> Option<Self> {
+        if let Ok(socket) = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)) {
+            return Some(Self { socket, raw: false });
+        }
+        Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))
+            .ok()
+            .map(Self::raw)
+    }
+
+    /// Sends an echo request to every target, then returns the ones that
+    /// replied within `timeout`.
+    pub(super) fn ping(&self, targets: &[Ipv4Addr], timeout: Duration) -> HashSet<Ipv4Addr> {
+        let identifier: u16 = rand::rng().random();
+        for (sequence, target) in targets.iter().enumerate() {
+            let packet = echo_request(identifier, sequence as u16);
+            let destination = SockAddr::from(SocketAddr::new(IpAddr::V4(*target), 0));
+            if let Err(e) = self.socket.send_to(&packet, &destination) {
+                debug!("Could not send an echo request to {target}: {e}");
+            }
+        }
+
+        let mut replied = HashSet::new();
+        if let Err(e) = self.socket.set_read_timeout(Some(RECEIVE_TIMEOUT)) {
+            debug!("Not waiting for echo replies: {e}");
+            return replied;
+        }
+        let targets: HashSet<&Ipv4Addr> = targets.iter().collect();
+        let deadline = Instant::now() + timeout;
+        let mut buffer = [MaybeUninit::<u8>::uninit(); 1500];
+        while Instant::now() < deadline && replied.len() < targets.len() {
+            let (size, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock
+                            | io::ErrorKind::TimedOut
+                            | io::ErrorKind::Interrupted
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => {
+                    debug!("Stopped reading echo replies: {e}");
+                    break;
+                }
+            };
+            // SAFETY: recv_from initialized the first `size` bytes.
+            let packet = unsafe { std::slice::from_raw_parts(buffer.as_ptr().cast::<u8>(), size) };
+            let Some(SocketAddr::V4(from)) = from.as_socket() else {
+                continue;
+            };
+            // Ping sockets replace the identifier with their own and only
+            // read the replies to their requests.
+            let identifier = self.raw.then_some(identifier);
+            if targets.contains(from.ip()) && is_reply(packet, self.raw, identifier) {
+                replied.insert(*from.ip());
+            }
+        }
+        replied
+    }
+}
+
+/// An echo request without payload, with its checksum.
+fn echo_request(identifier: u16, sequence: u16) -> [u8; 8] {
+    let mut packet = [0u8; 8];
+    packet[0] = ECHO_REQUEST;
+    packet[4..6].copy_from_slice(&identifier.to_be_bytes());
+    packet[6..8].copy_from_slice(&sequence.to_be_bytes());
+    let checksum = checksum(&packet);
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    packet
+}
+
+/// The internet checksum of `data`.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| {
+            u32::from(u16::from_be_bytes([
+                pair[0],
+                pair.get(1).copied().unwrap_or(0),
+            ]))
+        })
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Whether `packet` is an echo reply, with `identifier` when there is one
+/// to match. Packets of raw sockets start with the IPv4 header.
+fn is_reply(packet: &[u8], raw: bool, identifier: Option<u16>) -> bool {
+    let icmp = if raw {
+        packet
+            .first()
+            .and_then(|header| packet.get(usize::from(header & 0x0f) * 4..))
+    } else {
+        Some(packet)
+    };
+    let Some([kind, _, _, _, a, b, ..]) = icmp else {
+        return false;
+    };
+    *kind == ECHO_REPLY
+        && identifier.is_none_or(|identifier| identifier == u16::from_be_bytes([*a, *b]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checksum, echo_request, is_reply, ECHO_REPLY};
+
+    #[test]
+    fn builds_echo_requests_with_valid_checksums() {
+        let packet = echo_request(0x1234, 7);
+
+        assert_eq!(packet, [8, 0, 0xe5, 0xc4, 0x12, 0x34, 0, 7]);
+        assert_eq!(checksum(&packet), 0);
+    }
+
+    #[test]
+    fn matches_replies_to_our_requests() {
+        let mut reply = echo_request(0x1234, 7);
+        reply[0] = ECHO_REPLY;
+        let mut raw = vec![0x45];
+        raw.extend_from_slice(&[0; 19]);
+        raw.extend_from_slice(&reply);
+
+        assert!(is_reply(&raw, true, Some(0x1234)));
+        assert!(!is_reply(&raw, true, Some(0x4321)));
+        assert!(is_reply(&reply, false, None));
+        assert!(!is_reply(&echo_request(0x1234, 7), false, None));
+        assert!(!is_reply(&[], true, None));
+    }
+}
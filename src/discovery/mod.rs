@@ -0,0 +1,233 @@
+//! Host discovery: finding which targets are up before scanning all their
+//! ports, so a sparse network doesn't cost a full scan of every address.
+//!
+//! Three kinds of probes are used, each finding hosts the others miss:
+//!
+//! - ICMP echo requests to IPv4 targets, through a raw socket or, on Linux,
+//!   an unprivileged ping socket. Skipped when neither can be opened.
+//! - TCP connects to a few common ports. A refused connection proves the
+//!   host is up as much as an accepted one.
+//! - The kernel's ARP table, which the probes above fill in for targets on
+//!   the local networks, even those that drop everything else. Linux only.
+//!
+//! The ARP table also gives the MAC address of the targets on the local
+//! networks, and [`oui`] the vendor of their network card, see
+//! [`hardware_addresses`].
+use crate::scanner::ProbeError;
+
+use async_std::io;
+use async_std::net::TcpStream;
+use futures::stream::{FuturesUnordered, StreamExt};
+use socket2::Socket;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+pub mod arp;
+pub mod oui;
+
+mod icmp;
+use icmp::IcmpSocket;
+
+/// Ports probed over TCP when no others are given, open on most servers
+/// and desktops.
+pub const DEFAULT_TCP_PORTS: [u16; 4] = [80, 443, 22, 3389];
+
+/// How a host was found up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AliveReason {
+    EchoReply,
+    /// The port accepted or refused a connection.
+    Tcp(u16),
+    Arp,
+}
+
+impl fmt::Display for AliveReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AliveReason::EchoReply => f.write_str("echo reply"),
+            AliveReason::Tcp(port) => write!(f, "answered on TCP port {port}"),
+            AliveReason::Arp => f.write_str("ARP entry"),
+        }
+    }
+}
+
+/// The MAC address of a target on a local network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HardwareAddress {
+    /// Like `aa:bb:cc:dd:ee:ff`.
+    pub mac: String,
+    /// The vendor of the network card, when known.
+    pub vendor: Option<&'static str>,
+}
+
+impl fmt::Display for HardwareAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.mac)?;
+        if let Some(vendor) = self.vendor {
+            write!(f, " ({vendor})")?;
+        }
+        Ok(())
+    }
+}
+
+/// The MAC addresses of the targets among `ips` in the ARP table. Only
+/// targets on the local networks that were probed recently are there,
+/// e.g. after host discovery or a scan.
+pub fn hardware_addresses(ips: &[IpAddr]) -> BTreeMap<IpAddr, HardwareAddress> {
+    let ips: HashSet<&IpAddr> = ips.iter().collect();
+    arp::table()
+        .into_iter()
+        .filter(|entry| ips.contains(&IpAddr::V4(entry.ip)))
+        .map(|entry| {
+            let address = HardwareAddress {
+                vendor: oui::vendor(&entry.mac),
+                mac: entry.mac,
+            };
+            (IpAddr::V4(entry.ip), address)
+        })
+        .collect()
+}
+
+/// Probes targets to find the ones that are up.
+#[derive(Debug)]
+pub struct Discovery {
+    timeout: Duration,
+    tcp_ports: Vec<u16>,
+    batch_size: usize,
+    icmp: Option<Socket>,
+}
+
+impl Discovery {
+    /// Waits `timeout` for each probe.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            tcp_ports: DEFAULT_TCP_PORTS.to_vec(),
+            batch_size: 256,
+            icmp: None,
+        }
+    }
+
+    /// The ports connected to, [`DEFAULT_TCP_PORTS`] by default.
+    #[must_use]
+    pub fn with_tcp_ports(mut self, tcp_ports: Vec<u16>) -> Self {
+        self.tcp_ports = tcp_ports;
+        self
+    }
+
+    /// How many TCP connects are in flight at once.
+    #[must_use]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sends echo requests through `socket`, a raw IPv4 ICMP socket as
+    /// [`RawSockets`](crate::privsep::RawSockets) opens, instead of opening
+    /// one.
+    #[must_use]
+    pub fn with_icmp_socket(mut self, socket: Socket) -> Self {
+        self.icmp = Some(socket);
+        self
+    }
+
+    /// The targets among `ips` found up, with how each was first found.
+    pub async fn run(&self, ips: &[IpAddr]) -> BTreeMap<IpAddr, AliveReason> {
+        let icmp = match &self.icmp {
+            Some(socket) => socket.try_clone().ok().map(IcmpSocket::raw),
+            None => IcmpSocket::open(),
+        };
+        let targets: Vec<Ipv4Addr> = ips
+            .iter()
+            .filter_map(|ip| match ip {
+                IpAddr::V4(ip) => Some(*ip),
+                IpAddr::V6(_) => None,
+            })
+            .collect();
+        let timeout = self.timeout;
+        let echo = async move {
+            match icmp {
+                Some(icmp) if !targets.is_empty() => {
+                    async_std::task::spawn_blocking(move || icmp.ping(&targets, timeout)).await
+                }
+                _ => HashSet::new(),
+            }
+        };
+
+        let (replied, mut alive) = futures::join!(echo, self.tcp_sweep(ips));
+        for ip in replied {
+            alive.insert(IpAddr::V4(ip), AliveReason::EchoReply);
+        }
+        let arp: HashSet<IpAddr> = arp::table()
+            .into_iter()
+            .map(|entry| IpAddr::V4(entry.ip))
+            .collect();
+        for ip in ips {
+            if arp.contains(ip) {
+                alive.entry(*ip).or_insert(AliveReason::Arp);
+            }
+        }
+        alive
+    }
+
+    /// Connects to the TCP ports of every target, one port at a time, and
+    /// stops probing a host once one of its ports answered.
+    async fn tcp_sweep(&self, ips: &[IpAddr]) -> BTreeMap<IpAddr, AliveReason> {
+        let mut alive = BTreeMap::new();
+        let mut sockets = self
+            .tcp_ports
+            .iter()
+            .flat_map(|port| ips.iter().map(move |ip| SocketAddr::new(*ip, *port)));
+        let mut ftrs: FuturesUnordered<_> = sockets
+            .by_ref()
+            .take(self.batch_size)
+            .map(|socket| answers(socket, self.timeout))
+            .collect();
+
+        while let Some((socket, answered)) = ftrs.next().await {
+            if answered {
+                alive
+                    .entry(socket.ip())
+                    .or_insert(AliveReason::Tcp(socket.port()));
+            }
+            if let Some(socket) = sockets.find(|socket| !alive.contains_key(&socket.ip())) {
+                ftrs.push(answers(socket, self.timeout));
+            }
+        }
+        alive
+    }
+}
+
+/// Whether `socket` accepted or refused a connection within `timeout`.
+async fn answers(socket: SocketAddr, timeout: Duration) -> (SocketAddr, bool) {
+    let answered = match io::timeout(timeout, TcpStream::connect(socket)).await {
+        Ok(_) => true,
+        Err(e) => ProbeError::classify(&e) == ProbeError::ConnectionRefused,
+    };
+    (socket, answered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AliveReason, Discovery};
+    use async_std::task::block_on;
+    use std::net::{IpAddr, TcpListener};
+    use std::time::Duration;
+
+    #[test]
+    fn finds_hosts_answering_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let localhost: IpAddr = "127.0.0.1".parse().unwrap();
+
+        let discovery = Discovery::new(Duration::from_millis(500)).with_tcp_ports(vec![port]);
+        let alive = block_on(discovery.run(&[localhost]));
+
+        assert!(matches!(
+            alive.get(&localhost),
+            Some(AliveReason::Tcp(_) | AliveReason::EchoReply)
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+//! The vendors of network cards, from the Organizationally Unique
+//! Identifier in the first three bytes of their MAC address.
+//!
+//! Besides the built-in table of common vendors, nmap's database of MAC
+//! prefixes is used when nmap is installed, so most hardware on a local
+//! network can be named.
+use std::fs;
+use std::sync::OnceLock;
+
+/// Where nmap installs its database of MAC prefixes.
+const NMAP_MAC_PREFIXES: [&str; 2] = [
+    "/usr/share/nmap/nmap-mac-prefixes",
+    "/usr/local/share/nmap/nmap-mac-prefixes",
+];
+
+/// Common vendors by OUI, sorted by OUI.
+static VENDORS: &[(u32, &str)] = &[
+    (0x00_00_0c, "Cisco"),
+    (0x00_00_48, "Seiko Epson"),
+    (0x00_00_85, "Canon"),
+    (0x00_00_aa, "Xerox"),
+    (0x00_03_93, "Apple"),
+    (0x00_05_02, "Apple"),
+    (0x00_05_69, "VMware"),
+    (0x00_0a_95, "Apple"),
+    (0x00_0c_29, "VMware"),
+    (0x00_0c_42, "MikroTik"),
+    (0x00_0d_3a, "Microsoft"),
+    (0x00_11_32, "Synology"),
+    (0x00_14_22, "Dell"),
+    (0x00_15_5d, "Microsoft"),
+    (0x00_16_3e, "Xen"),
+    (0x00_17_88, "Philips Lighting"),
+    (0x00_1a_11, "Google"),
+    (0x00_1b_21, "Intel"),
+    (0x00_1c_14, "VMware"),
+    (0x00_1c_42, "Parallels"),
+    (0x00_21_5a, "Hewlett Packard"),
+    (0x00_50_56, "VMware"),
+    (0x00_80_77, "Brother"),
+    (0x00_e0_4c, "Realtek"),
+    (0x00_e0_fc, "Huawei"),
+    (0x08_00_27, "Oracle VirtualBox"),
+    (0x18_b4_30, "Nest Labs"),
+    (0x24_a4_3c, "Ubiquiti"),
+    (0x3c_d9_2b, "Hewlett Packard"),
+    (0x4c_5e_0c, "MikroTik"),
+    (0xb8_27_eb, "Raspberry Pi"),
+    (0xdc_a6_32, "Raspberry Pi"),
+    (0xe4_5f_01, "Raspberry Pi"),
+    (0xf0_1f_af, "Dell"),
+    (0xf4_f5_d8, "Google"),
+];
+
+/// The vendor of the network card with the MAC address `mac`, like
+/// `aa:bb:cc:dd:ee:ff`. `None` for unknown vendors and for locally
+/// administered addresses, which were made up rather than assigned.
+///
+/// ```rust
+/// # use rustscan::discovery::oui::vendor;
+/// assert_eq!(vendor("b8:27:eb:12:34:56"), Some("Raspberry Pi"));
+/// assert_eq!(vendor("02:42:ac:11:00:02"), None);
+/// ```
+pub fn vendor(mac: &str) -> Option<&'static str> {
+    let oui = oui(mac)?;
+    VENDORS
+        .binary_search_by_key(&oui, |(prefix, _)| *prefix)
+        .ok()
+        .map(|index| VENDORS[index].1)
+        .or_else(|| {
+            nmap_vendors()
+                .binary_search_by_key(&oui, |(prefix, _)| *prefix)
+                .ok()
+                .map(|index| nmap_vendors()[index].1.as_str())
+        })
+}
+
+/// The OUI of a globally unique MAC address.
+fn oui(mac: &str) -> Option<u32> {
+    let bytes: Vec<u8> = mac
+        .split([':', '-'])
+        .map(|byte| u8::from_str_radix(byte, 16))
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let [first, second, third, _, _, _] = bytes[..] else {
+        return None;
+    };
+    // Locally administered addresses, like those of containers and VMs
+    // without an assigned prefix, belong to no vendor.
+    if first & 0x02 != 0 {
+        return None;
+    }
+    Some(u32::from_be_bytes([0, first, second, third]))
+}
+
+fn nmap_vendors() -> &'static [(u32, String)] {
+    static NMAP: OnceLock<Vec<(u32, String)>> = OnceLock::new();
+    NMAP.get_or_init(|| {
+        NMAP_MAC_PREFIXES
+            .iter()
+            .find_map(|path| fs::read_to_string(path).ok())
+            .map(|content| parse_prefixes(&content))
+            .unwrap_or_default()
+    })
+}
+
+/// Parses a database in the format of nmap's `nmap-mac-prefixes`, e.g.
+/// `B827EB Raspberry Pi Foundation`, sorted by OUI.
+fn parse_prefixes(content: &str) -> Vec<(u32, String)> {
+    let mut vendors: Vec<(u32, String)> = content
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (prefix, vendor) = line.split_once(char::is_whitespace)?;
+            // Longer prefixes name blocks smaller than an OUI.
+            if prefix.len() != 6 {
+                return None;
+            }
+            let prefix = u32::from_str_radix(prefix, 16).ok()?;
+            Some((prefix, vendor.trim().to_owned()))
+        })
+        .collect();
+    vendors.sort_by_key(|(prefix, _)| *prefix);
+    vendors.dedup_by_key(|(prefix, _)| *prefix);
+    vendors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{oui, parse_prefixes, vendor, VENDORS};
+
+    #[test]
+    fn names_vendors_of_assigned_addresses() {
+        assert!(VENDORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(vendor("00:50:56:C0:00:08"), Some("VMware"));
+        assert_eq!(vendor("00-00-0c-07-ac-01"), Some("Cisco"));
+        assert_eq!(oui("52:54:00:12:34:56"), None);
+        assert_eq!(oui("00:50:56"), None);
+        assert_eq!(oui("not a mac"), None);
+    }
+
+    #[test]
+    fn parses_nmap_mac_prefixes() {
+        let content = "\
+# comment
+B827EB Raspberry Pi Foundation
+000000 Xerox
+0050C2123 Some IEEE Registered Block
+";
+        assert_eq!(
+            parse_prefixes(content),
+            vec![
+                (0x00_00_00, "Xerox".to_owned()),
+                (0xb8_27_eb, "Raspberry Pi Foundation".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use super::{HostsBuilder, ImportedHost};
+
+use serde_derive::Deserialize;
+use std::net::IpAddr;
+
+#[derive(Deserialize)]
+struct Record {
+    ip: IpAddr,
+    #[serde(default)]
+    ports: Vec<Port>,
+}
+
+#[derive(Deserialize)]
+struct Port {
+    port: u16,
+    #[serde(default)]
+    proto: Option<String>,
+    #[serde(default)]
+    status: Option<String>,
+}
+
+/// Reads the hosts of a masscan JSON file (`masscan -oJ`) along with their
+/// open TCP ports.
+///
+/// Older masscan versions leave a comma after the last record, which is
+/// accepted too.
+///
+/// ```rust
+/// # use rustscan::import::parse_masscan_json;
+/// let json = r#"[
+/// {"ip": "10.0.0.1", "ports": [{"port": 80, "proto": "tcp", "status": "open"}]},
+/// ]"#;
+/// let hosts = parse_masscan_json(json).unwrap();
+/// assert_eq!(hosts[0].ports, vec![80]);
+/// ```
+pub fn parse_masscan_json(content: &str) -> Result<Vec<ImportedHost>, String> {
+    let content = content.trim();
+    let records: Vec<Record> = match serde_json::from_str(content) {
+        Ok(records) => records,
+        Err(e) => {
+            let repaired = content
+                .strip_suffix(']')
+                .map(|records| format!("{}]", records.trim_end().trim_end_matches(',')));
+            repaired
+                .and_then(|repaired| serde_json::from_str(&repaired).ok())
+                .ok_or_else(|| format!("not a masscan JSON file: {e}"))?
+        }
+    };
+
+    let mut hosts = HostsBuilder::default();
+    for record in records {
+        hosts.add(record.ip, None);
+        for port in record.ports {
+            let tcp = port.proto.as_deref().unwrap_or("tcp") == "tcp";
+            let open = port.status.as_deref().unwrap_or("open") == "open";
+            if tcp && open {
+                hosts.add(record.ip, Some(port.port));
+            }
+        }
+    }
+    Ok(hosts.build())
+}
@@ -0,0 +1,111 @@
+//! Targets from the output of other scanners.
+//!
+//! Lets the hosts found by an earlier scan be scanned again, e.g. to verify
+//! them, without extracting them by hand: `--from-nmap results.xml` reads an
+//! nmap XML report or a masscan JSON file, and `--from-zmap results.csv` the
+//! hosts of a zmap sweep. The open ports of each host are kept too, so they
+//! can be rescanned on their own.
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+mod masscan;
+mod nmap;
+mod zmap;
+
+pub use masscan::parse_masscan_json;
+pub use nmap::parse_nmap_xml;
+pub use zmap::parse_zmap_csv;
+
+/// A host of a previous scan along with the TCP ports it had open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedHost {
+    pub ip: IpAddr,
+    pub ports: Vec<u16>,
+}
+
+/// Reads the hosts of an nmap XML report (`-oX`) or a masscan JSON file
+/// (`-oJ`), telling them apart by their content.
+pub fn read_scan_results(path: &Path) -> Result<Vec<ImportedHost>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("could not read {}: {e}", path.display()))?;
+    let hosts = match content.trim_start().chars().next() {
+        Some('<') => parse_nmap_xml(&content),
+        Some('[' | '{') => parse_masscan_json(&content),
+        _ => Err("expected an nmap XML report or a masscan JSON file".to_owned()),
+    };
+    hosts.map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Reads the hosts of a zmap result file, see [`parse_zmap_csv`].
+pub fn read_zmap_results(path: &Path) -> Result<Vec<ImportedHost>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("could not read {}: {e}", path.display()))?;
+    parse_zmap_csv(&content).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// The ports open on any of `hosts`, in ascending order.
+pub fn open_ports(hosts: &[ImportedHost]) -> Vec<u16> {
+    hosts
+        .iter()
+        .flat_map(|host| host.ports.iter().copied())
+        .collect::<BTreeSet<u16>>()
+        .into_iter()
+        .collect()
+}
+
+/// Collects the open ports of each host, keeping the hosts in the order
+/// they were first seen.
+#[derive(Debug, Default)]
+struct HostsBuilder {
+    hosts: Vec<ImportedHost>,
+    index: HashMap<IpAddr, usize>,
+}
+
+impl HostsBuilder {
+    /// Adds the host `ip`, with `port` open if given.
+    fn add(&mut self, ip: IpAddr, port: Option<u16>) {
+        let hosts = &mut self.hosts;
+        let index = *self.index.entry(ip).or_insert_with(|| {
+            hosts.push(ImportedHost {
+                ip,
+                ports: Vec::new(),
+            });
+            hosts.len() - 1
+        });
+        if let Some(port) = port {
+            if !self.hosts[index].ports.contains(&port) {
+                self.hosts[index].ports.push(port);
+            }
+        }
+    }
+
+    fn build(self) -> Vec<ImportedHost> {
+        self.hosts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open_ports, read_scan_results, read_zmap_results};
+    use std::path::Path;
+
+    #[test]
+    fn reads_nmap_and_masscan_files() {
+        let nmap = read_scan_results(Path::new("fixtures/import/nmap.xml")).unwrap();
+        let masscan = read_scan_results(Path::new("fixtures/import/masscan.json")).unwrap();
+        assert_eq!(open_ports(&nmap), vec![22, 80, 443]);
+        assert_eq!(open_ports(&masscan), vec![22, 80, 443]);
+
+        assert!(read_scan_results(Path::new("fixtures/hosts.txt")).is_err());
+    }
+
+    #[test]
+    fn reads_zmap_files() {
+        let zmap = read_zmap_results(Path::new("fixtures/import/zmap.csv")).unwrap();
+        let ips: Vec<String> = zmap.iter().map(|host| host.ip.to_string()).collect();
+        assert_eq!(ips, vec!["10.0.0.1", "10.0.0.3"]);
+        assert_eq!(open_ports(&zmap), vec![22]);
+    }
+}
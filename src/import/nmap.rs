@@ -0,0 +1,66 @@
+use super::{HostsBuilder, ImportedHost};
+use crate::nmap;
+use crate::output::Protocol;
+
+/// Reads the hosts that were up in an nmap XML report (`nmap -oX`) along
+/// with their open TCP ports.
+///
+/// The report is read with [`nmap::parse`], the parser of the output of
+/// [`nmap::run_nmap`].
+///
+/// ```rust
+/// # use rustscan::import::parse_nmap_xml;
+/// let xml = r#"<nmaprun><host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/>
+/// <ports><port protocol="tcp" portid="22"><state state="open"/></port></ports></host></nmaprun>"#;
+/// let hosts = parse_nmap_xml(xml).unwrap();
+/// assert_eq!(hosts[0].ports, vec![22]);
+/// ```
+pub fn parse_nmap_xml(content: &str) -> Result<Vec<ImportedHost>, String> {
+    if !content.contains("<nmaprun") {
+        return Err("not an nmap XML report".to_owned());
+    }
+
+    let run = nmap::parse(content).map_err(|e| e.to_string())?;
+    let mut hosts = HostsBuilder::default();
+    for host in run.hosts.iter().filter(|host| host.up) {
+        let Some(ip) = host.address else {
+            continue;
+        };
+        hosts.add(ip, None);
+        for port in &host.ports {
+            if port.protocol == Protocol::Tcp && port.state == "open" {
+                hosts.add(ip, Some(port.port));
+            }
+        }
+    }
+    Ok(hosts.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_nmap_xml;
+
+    #[test]
+    fn skips_down_hosts_closed_ports_and_udp() {
+        let xml = r#"<?xml version="1.0"?>
+<!-- Nmap 7.94 scan <initiated> -->
+<nmaprun scanner="nmap">
+<host><status state="down"/><address addr="10.0.0.9" addrtype="ipv4"/></host>
+<host><status state="up" reason='syn-ack'/>
+<address addr="00:11:22:33:44:55" addrtype="mac"/>
+<address addr="10.0.0.1" addrtype="ipv4"/>
+<ports>
+<port protocol="tcp" portid="22"><state state="open" reason="syn-ack"/></port>
+<port protocol="tcp" portid="25"><state state="closed" reason="reset"/></port>
+<port protocol="udp" portid="53"><state state="open"/></port>
+</ports>
+</host>
+</nmaprun>"#;
+        let hosts = parse_nmap_xml(xml).unwrap();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].ip.to_string(), "10.0.0.1");
+        assert_eq!(hosts[0].ports, vec![22]);
+
+        assert!(parse_nmap_xml("<html></html>").is_err());
+    }
+}
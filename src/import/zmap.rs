@@ -0,0 +1,79 @@
+use super::{HostsBuilder, ImportedHost};
+
+use std::net::IpAddr;
+
+/// Reads the hosts of a zmap result file along with the port they answered
+/// on.
+///
+/// Both of zmap's outputs are accepted: the default one address per line,
+/// and CSV with a header naming the fields (`-O csv -f saddr,sport,success`).
+/// In CSV, rows whose `success` field is not set are skipped, and `sport`,
+/// the port the answer came from, is taken as the open port.
+///
+/// ```rust
+/// # use rustscan::import::parse_zmap_csv;
+/// let csv = "saddr,sport,classification,success\n10.0.0.1,443,synack,1\n10.0.0.2,443,rst,0\n";
+/// let hosts = parse_zmap_csv(csv).unwrap();
+/// assert_eq!(hosts.len(), 1);
+/// assert_eq!(hosts[0].ports, vec![443]);
+/// ```
+pub fn parse_zmap_csv(content: &str) -> Result<Vec<ImportedHost>, String> {
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+
+    let header: Option<Vec<&str>> = match lines.peek() {
+        Some(first) if first.split(',').any(|field| field.trim() == "saddr") => lines
+            .next()
+            .map(|line| line.split(',').map(str::trim).collect()),
+        _ => None,
+    };
+    let column = |name: &str| {
+        header
+            .as_ref()
+            .and_then(|header| header.iter().position(|field| *field == name))
+    };
+    let saddr = column("saddr").unwrap_or(0);
+    let sport = column("sport");
+    let success = column("success");
+
+    let mut hosts = HostsBuilder::default();
+    for (number, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let field = |index: usize| fields.get(index).copied().unwrap_or_default();
+
+        if success.is_some_and(|index| !matches!(field(index), "1" | "true")) {
+            continue;
+        }
+        let ip = field(saddr)
+            .parse::<IpAddr>()
+            .map_err(|_| format!("invalid address on line {}: {line}", number + 1))?;
+        let port = match sport {
+            Some(index) => Some(
+                field(index)
+                    .parse::<u16>()
+                    .map_err(|_| format!("invalid port on line {}: {line}", number + 1))?,
+            ),
+            None => None,
+        };
+        hosts.add(ip, port);
+    }
+    Ok(hosts.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_zmap_csv;
+
+    #[test]
+    fn reads_plain_address_lists() {
+        let hosts = parse_zmap_csv("10.0.0.1\n10.0.0.2\n10.0.0.1\n").unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert!(hosts.iter().all(|host| host.ports.is_empty()));
+
+        assert!(parse_zmap_csv("saddr,sport\n10.0.0.1,http\n").is_err());
+        assert!(parse_zmap_csv("not an address\n").is_err());
+    }
+}
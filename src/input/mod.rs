@@ -0,0 +1,2658 @@
+//! Provides a means to read, parse and hold configuration options for scans.
+use crate::output::Protocol;
+use crate::policy::Policy;
+use crate::scanner::RetryPolicy;
+use crate::services;
+#[cfg(feature = "cli")]
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io::IsTerminal;
+use std::net::{IpAddr, Ipv4Addr};
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+mod top_ports;
+pub use top_ports::{parse_top_port_count, top_ports, TOP_PORTS, TOP_PORT_COUNT};
+
+mod port_groups;
+pub use port_groups::{parse_port_group, port_group, port_group_names, port_group_ports};
+
+#[cfg(feature = "cli")]
+const LOWEST_PORT_NUMBER: u16 = 1;
+#[cfg(feature = "cli")]
+const TOP_PORT_NUMBER: u16 = 65535;
+
+/// Default command line arguments, the ones given override them.
+pub const OPTS_ENV: &str = "RUSTSCAN_OPTS";
+/// Where an interrupted scan writes the targets it has left, see
+/// [`Opts::resume`].
+pub const RESUME_FILE: &str = "rustscan.resume";
+/// The path of the config file when `--config-path` isn't given.
+pub const CONFIG_PATH_ENV: &str = "RUSTSCAN_CONFIG_PATH";
+
+/// Represents the strategy in which the port scanning will run.
+///   - Serial will run from start to end, for example 1 to 1_000.
+///   - Random will randomize the order in which ports will be scanned.
+///   - Adaptive will scan first the ports most often found open in past
+///     scans of the same networks.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum ScanOrder {
+    Serial,
+    Random,
+    Adaptive,
+}
+
+/// The order sockets are probed in, across hosts and their ports.
+///   - PortMajor probes one port across every host, then the next port.
+///   - HostMajor probes every port of a host, then the next host.
+///   - Interleaved probes them in a random order, mixing hosts and ports.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum IterationOrder {
+    #[default]
+    PortMajor,
+    HostMajor,
+    Interleaved,
+}
+
+/// Represents how ports are probed.
+///   - connect will open a full TCP connection to each port.
+///   - syn will only send a SYN and read the answer, without finishing the
+///     handshake, like `nmap -sS`. It needs raw sockets, so root or
+///     CAP_NET_RAW, and falls back to connect when they are unavailable.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum ScanType {
+    Connect,
+    Syn,
+}
+
+/// Represents the scripts variant.
+///   - none will avoid running any script, only portscan results will be shown.
+///   - default will run the default embedded nmap script, that's part of RustScan since the beginning.
+///   - custom will read the ScriptConfig file and the available scripts in the predefined folders
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Copy)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum ScriptsRequired {
+    None,
+    Default,
+    Custom,
+}
+
+/// Represents what to do with hosts that look like tarpits.
+///   - off will not look for tarpits.
+///   - warn will report hosts that answer on nearly every port or never send data.
+///   - suppress will also leave the open ports of those hosts out of the results.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum TarpitDetection {
+    Off,
+    Warn,
+    Suppress,
+}
+
+/// Represents what to do about wildcard DNS found while expanding hostnames.
+///   - off will not look for wildcard domains.
+///   - warn will report domains where any label resolves.
+///   - collapse will also scan the wildcard addresses once, instead of once
+///     per hostname that only resolves to them.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum WildcardDns {
+    Off,
+    Warn,
+    Collapse,
+}
+
+/// How many targets `--sample` scans: a number of addresses, e.g. `1000`,
+/// or a share of the target space, e.g. `0.5%`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleSize {
+    Count(u64),
+    Percent(f64),
+}
+
+impl FromStr for SampleSize {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        match input.strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<f64>() {
+                Ok(percent) if percent > 0.0 && percent <= 100.0 => {
+                    Ok(SampleSize::Percent(percent))
+                }
+                _ => Err(format!("'{input}' is not a percentage between 0 and 100.")),
+            },
+            None => match input.parse::<u64>() {
+                Ok(count) if count > 0 => Ok(SampleSize::Count(count)),
+                _ => Err(format!(
+                    "'{input}' is not a number of addresses or a percentage like 1%."
+                )),
+            },
+        }
+    }
+}
+
+/// Represents how results are grouped in the report.
+///   - host lists the open ports of each host.
+///   - service lists the hosts exposing each open port.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum GroupBy {
+    Host,
+    Service,
+}
+
+/// Represents the layout of the greppable output.
+///   - rustscan prints "ip -> [ports]", one host per line.
+///   - nmap prints the lines of `nmap -oG`, for the parsers expecting them.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum GreppableFormat {
+    Rustscan,
+    Nmap,
+}
+
+/// Represents the range of ports to be scanned.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+/// Represents an address in the config file, either a plain string or a
+/// table with tags: `{ host = "10.0.0.5", tags = ["prod", "db"] }`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ConfigAddress {
+    Plain(String),
+    Tagged { host: String, tags: Vec<String> },
+}
+
+impl ConfigAddress {
+    /// The address in the annotated form accepted by `--addresses`,
+    /// `10.0.0.5 # prod db`.
+    pub fn annotated(&self) -> String {
+        match self {
+            ConfigAddress::Plain(address) => address.clone(),
+            ConfigAddress::Tagged { host, tags } if tags.is_empty() => host.clone(),
+            ConfigAddress::Tagged { host, tags } => format!("{host} # {}", tags.join(" ")),
+        }
+    }
+}
+
+/// Represents an extra destination for results, as accepted by `--output`:
+/// `json:results.jsonl` or `webhook:http://hooks.local/rustscan` to stream
+/// them, `json` or `report:report.json` for a report of the whole scan,
+/// `xml` or `xml:report.xml` for the same report in nmap's XML format,
+/// `csv` or `csv:scan.csv` for its open ports as CSV.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub enum OutputSpec {
+    Json(PathBuf),
+    Webhook(String),
+    /// A [`ScanReport`](crate::output::ScanReport), written to stdout
+    /// without a path.
+    Report(Option<PathBuf>),
+    /// The same report as nmap XML, see [`crate::output::nmap_xml`].
+    NmapXml(Option<PathBuf>),
+    /// The open ports of the report as CSV, see [`crate::output::csv`].
+    Csv(Option<PathBuf>),
+}
+
+impl OutputSpec {
+    /// Whether this is a report of the whole scan, written once it is over.
+    pub fn is_report(&self) -> bool {
+        matches!(
+            self,
+            OutputSpec::Report(_) | OutputSpec::NmapXml(_) | OutputSpec::Csv(_)
+        )
+    }
+}
+
+impl FromStr for OutputSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        match spec {
+            "json" => return Ok(OutputSpec::Report(None)),
+            "xml" => return Ok(OutputSpec::NmapXml(None)),
+            "csv" => return Ok(OutputSpec::Csv(None)),
+            _ => {}
+        }
+        match spec.split_once(':') {
+            Some(("json", path)) if !path.is_empty() => Ok(OutputSpec::Json(PathBuf::from(path))),
+            Some(("webhook", url)) if !url.is_empty() => Ok(OutputSpec::Webhook(url.to_owned())),
+            Some(("report", path)) if !path.is_empty() => {
+                Ok(OutputSpec::Report(Some(PathBuf::from(path))))
+            }
+            Some(("xml", path)) if !path.is_empty() => {
+                Ok(OutputSpec::NmapXml(Some(PathBuf::from(path))))
+            }
+            Some(("csv", path)) if !path.is_empty() => {
+                Ok(OutputSpec::Csv(Some(PathBuf::from(path))))
+            }
+            _ => Err(format!(
+                "Invalid output {spec}, expected json, json:<path>, report:<path>, xml, xml:<path>, csv, csv:<path> or webhook:<url>"
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for OutputSpec {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        spec.parse()
+    }
+}
+
+/// Represents a port, a range of ports (`1000-2000`) or a service name
+/// (`ssh`), as accepted by `--exclude-ports`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum PortSpec {
+    Port(u16),
+    Named(String),
+}
+
+impl PortSpec {
+    /// Expands the spec into the ports it stands for.
+    pub fn ports(&self) -> Result<Vec<u16>, String> {
+        let spec = match self {
+            PortSpec::Port(port) => return Ok(vec![*port]),
+            PortSpec::Named(spec) => spec.trim(),
+        };
+
+        if let Ok(port) = spec.parse::<u16>() {
+            return Ok(vec![port]);
+        }
+        if let Some(port) = services::port_by_name(spec) {
+            return Ok(vec![port]);
+        }
+        match parse_range(spec) {
+            Ok(range) if range.start <= range.end => Ok((range.start..=range.end).collect()),
+            _ => Err(format!(
+                "'{spec}' is not a port, a range like 1000-2000 or a known service name."
+            )),
+        }
+    }
+
+    /// Reads specs from a port file, see [`PortFile`].
+    pub fn read_file(path: &Path) -> Result<Vec<PortSpec>, String> {
+        PortFile::read(path)?.specs()
+    }
+
+    /// Expands a list of specs into the ports they stand for.
+    pub fn expand(specs: &[PortSpec]) -> Result<Vec<u16>, String> {
+        let mut ports = Vec::new();
+        for spec in specs {
+            ports.extend(spec.ports()?);
+        }
+        Ok(ports)
+    }
+}
+
+/// A file of port specs, as used by `--ports-file` and
+/// `--exclude-ports-file`:
+///
+/// ```text
+/// # Anything after a # is a comment.
+/// 22, 80 443 8000-8100 ssh      # one or more specs per line
+/// @web                          # the ports of a group
+/// include common.ports          # the specs of another file
+///
+/// [web]                         # the lines that follow make up a group
+/// http https 8080 8443
+/// ```
+///
+/// Lines before the first `[group]` make up the scope of the file. Groups
+/// can be used before they are defined and by the files including the file
+/// they are defined in. Included paths are relative to the including file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PortFile {
+    scope: Vec<PortEntry>,
+    groups: BTreeMap<String, Vec<PortEntry>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PortEntry {
+    Spec(PortSpec),
+    Group(String),
+}
+
+impl PortFile {
+    /// Reads a port file along with the files it includes.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let mut file = PortFile::default();
+        file.scope = file.load(path, &mut Vec::new())?;
+        Ok(file)
+    }
+
+    /// Reads `path` into the scope it defines, adding its groups to `self`.
+    /// `including` are the files currently being read, to catch cycles.
+    fn load(
+        &mut self,
+        path: &Path,
+        including: &mut Vec<PathBuf>,
+    ) -> Result<Vec<PortEntry>, String> {
+        let canonical = fs::canonicalize(path)
+            .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+        if including.contains(&canonical) {
+            return Err(format!("{} includes itself", path.display()));
+        }
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+        including.push(canonical);
+
+        let mut scope = Vec::new();
+        let mut group: Option<String> = None;
+        for (number, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let at = |e: String| format!("{}:{}: {e}", path.display(), number + 1);
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(at("empty group name".to_owned()));
+                }
+                self.groups.entry(name.to_owned()).or_default();
+                group = Some(name.to_owned());
+                continue;
+            }
+
+            let entries = if let Some(included) = line.strip_prefix("include ") {
+                let included = path
+                    .parent()
+                    .unwrap_or_else(|| Path::new(""))
+                    .join(included.trim());
+                self.load(&included, including).map_err(at)?
+            } else {
+                line.split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|spec| !spec.is_empty())
+                    .map(|spec| match spec.strip_prefix('@') {
+                        Some(name) => Ok(PortEntry::Group(name.to_owned())),
+                        None => parse_port_spec(spec).map(PortEntry::Spec),
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+                    .map_err(at)?
+            };
+            match &group {
+                Some(name) => self.groups.entry(name.clone()).or_default().extend(entries),
+                None => scope.extend(entries),
+            }
+        }
+
+        including.pop();
+        Ok(scope)
+    }
+
+    /// The names of the groups defined by the file and its includes.
+    pub fn groups(&self) -> Vec<&str> {
+        self.groups.keys().map(String::as_str).collect()
+    }
+
+    /// The specs of the scope of the file, with groups expanded.
+    pub fn specs(&self) -> Result<Vec<PortSpec>, String> {
+        self.expand(&self.scope, &mut Vec::new())
+    }
+
+    /// The specs of a group, with the groups it uses expanded.
+    pub fn group(&self, name: &str) -> Result<Vec<PortSpec>, String> {
+        self.expand(&[PortEntry::Group(name.to_owned())], &mut Vec::new())
+    }
+
+    fn expand(
+        &self,
+        entries: &[PortEntry],
+        expanding: &mut Vec<String>,
+    ) -> Result<Vec<PortSpec>, String> {
+        let mut specs = Vec::new();
+        for entry in entries {
+            match entry {
+                PortEntry::Spec(spec) => specs.push(spec.clone()),
+                PortEntry::Group(name) => {
+                    if expanding.contains(name) {
+                        return Err(format!("group {name} uses itself"));
+                    }
+                    let group = self
+                        .groups
+                        .get(name)
+                        .ok_or_else(|| format!("unknown port group {name}"))?;
+                    expanding.push(name.clone());
+                    specs.extend(self.expand(group, expanding)?);
+                    expanding.pop();
+                }
+            }
+        }
+        Ok(specs)
+    }
+}
+
+fn parse_port_spec(input: &str) -> Result<PortSpec, String> {
+    let spec = match input.trim().parse::<u16>() {
+        Ok(port) => PortSpec::Port(port),
+        Err(_) => PortSpec::Named(input.trim().to_owned()),
+    };
+    spec.ports()?;
+    Ok(spec)
+}
+
+/// A do-not-scan list, as used by `--exclude-file`, like nmap's
+/// `--excludefile` with ports on top:
+///
+/// ```text
+/// # Anything after a # is a comment.
+/// 10.0.0.0/8, printer.example.com   # addresses, networks and hostnames
+/// 9100 8000-8100                    # ports and ranges
+/// port:telnet                       # ports by service name
+/// ```
+///
+/// Service names need the `port:` prefix, as they can't be told apart from
+/// hostnames otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExcludeFile {
+    pub addresses: Vec<String>,
+    pub ports: Vec<PortSpec>,
+}
+
+impl ExcludeFile {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+        Self::parse(&content).map_err(|e| format!("{}:{e}", path.display()))
+    }
+
+    /// Parses the content of an exclude file, errors start with the line
+    /// number.
+    fn parse(content: &str) -> Result<Self, String> {
+        let mut file = Self::default();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            for entry in line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|entry| !entry.is_empty())
+            {
+                let is_port = entry
+                    .split('-')
+                    .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()));
+                let port = match entry.strip_prefix("port:") {
+                    Some(spec) => Some(spec),
+                    None if is_port => Some(entry),
+                    None => None,
+                };
+                match port {
+                    Some(spec) => file
+                        .ports
+                        .push(parse_port_spec(spec).map_err(|e| format!("{}: {e}", number + 1))?),
+                    None => file.addresses.push(entry.to_owned()),
+                }
+            }
+        }
+        Ok(file)
+    }
+}
+
+/// Targets scanned on their own ports, as used by `--targets-file`:
+///
+/// ```text
+/// # Anything after a # is a comment.
+/// 10.0.0.5:22,80,443
+/// web.example.com:80,8000-8100,https
+/// 10.0.1.0/24:3389
+/// [2001:db8::1]:22
+/// ```
+///
+/// Ports are ports, ranges or service names. IPv6 addresses and networks
+/// go between brackets.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetsFile {
+    pub targets: Vec<(String, Vec<PortSpec>)>,
+}
+
+impl TargetsFile {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+        Self::parse(&content).map_err(|e| format!("{}:{e}", path.display()))
+    }
+
+    /// The targets of `ports`, runs of ports being written as ranges.
+    pub fn from_ports(ports: &BTreeMap<IpAddr, Vec<u16>>) -> Self {
+        let targets = ports
+            .iter()
+            .filter(|(_, ports)| !ports.is_empty())
+            .map(|(ip, ports)| {
+                let mut runs: Vec<(u16, u16)> = Vec::new();
+                for &port in ports {
+                    match runs.last_mut() {
+                        Some((_, end)) if end.checked_add(1) == Some(port) => *end = port,
+                        _ => runs.push((port, port)),
+                    }
+                }
+                let specs = runs
+                    .into_iter()
+                    .map(|(start, end)| {
+                        if start == end {
+                            PortSpec::Port(start)
+                        } else {
+                            PortSpec::Named(format!("{start}-{end}"))
+                        }
+                    })
+                    .collect();
+                (ip.to_string(), specs)
+            })
+            .collect();
+        Self { targets }
+    }
+
+    /// Parses the content of a targets file, errors start with the line
+    /// number.
+    fn parse(content: &str) -> Result<Self, String> {
+        let mut file = Self::default();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |e: String| format!("{}: {e}", number + 1);
+            let (address, ports) = split_target(line)
+                .ok_or_else(|| error(format!("expected <host>:<ports>, got '{line}'")))?;
+            let ports = ports
+                .split(',')
+                .filter(|port| !port.trim().is_empty())
+                .map(parse_port_spec)
+                .collect::<Result<Vec<PortSpec>, String>>()
+                .map_err(error)?;
+            if address.is_empty() || ports.is_empty() {
+                return Err(error(format!("expected <host>:<ports>, got '{line}'")));
+            }
+            file.targets.push((address.to_owned(), ports));
+        }
+        Ok(file)
+    }
+}
+
+impl fmt::Display for TargetsFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (address, specs) in &self.targets {
+            let specs: Vec<String> = specs
+                .iter()
+                .map(|spec| match spec {
+                    PortSpec::Port(port) => port.to_string(),
+                    PortSpec::Named(spec) => spec.clone(),
+                })
+                .collect();
+            if address.contains(':') {
+                writeln!(f, "[{address}]:{}", specs.join(","))?;
+            } else {
+                writeln!(f, "{address}:{}", specs.join(","))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits `host:ports` into the host and the ports, IPv6 hosts being
+/// between brackets.
+fn split_target(line: &str) -> Option<(&str, &str)> {
+    let (address, ports) = match line.strip_prefix('[') {
+        Some(rest) => rest.split_once("]:")?,
+        None => line
+            .rsplit_once(':')
+            .filter(|(address, _)| !address.contains(':'))?,
+    };
+    Some((address.trim(), ports))
+}
+
+#[cfg(not(tarpaulin_include))]
+fn parse_range(input: &str) -> Result<PortRange, String> {
+    let range = input
+        .split('-')
+        .map(str::parse)
+        .collect::<Result<Vec<u16>, std::num::ParseIntError>>();
+
+    if range.is_err() {
+        return Err(String::from(
+            "the range format must be 'start-end'. Example: 1-1000.",
+        ));
+    }
+
+    match range.unwrap().as_slice() {
+        [start, end] => Ok(PortRange {
+            start: *start,
+            end: *end,
+        }),
+        _ => Err(String::from(
+            "the range format must be 'start-end'. Example: 1-1000.",
+        )),
+    }
+}
+
+/// Subcommands that run instead of a regular scan.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "cli", derive(Subcommand))]
+pub enum Commands {
+    /// Run the built-in benchmark suite against localhost.
+    Bench {
+        /// JSON results of a previous `rustscan bench --save` run to compare against.
+        #[cfg_attr(feature = "cli", arg(long, value_parser))]
+        compare: Option<PathBuf>,
+
+        /// Save the results of this run as JSON to the given path.
+        #[cfg_attr(feature = "cli", arg(long, value_parser))]
+        save: Option<PathBuf>,
+
+        /// Percentage a phase has to change by before it is reported as a
+        /// regression or an improvement.
+        #[cfg_attr(feature = "cli", arg(long, default_value = "5"))]
+        threshold: f64,
+    },
+
+    /// Compare two reports written with --output report:<path> and list the
+    /// ports that opened and closed on each host. Exits with 1 when any
+    /// did, like diff.
+    Diff {
+        /// The report of the earlier scan.
+        old: PathBuf,
+
+        /// The report of the later scan.
+        new: PathBuf,
+    },
+
+    /// Answer a few questions about the scan and get the matching command
+    /// line explained, and optionally run it.
+    Wizard,
+
+    /// Start an interactive shell to set targets and ports, run scans,
+    /// inspect their results and run scripts on selected hosts.
+    Shell,
+}
+
+/// Parses a TOS byte given either as a decimal or as a `0x` prefixed
+/// hexadecimal number.
+#[cfg(feature = "cli")]
+fn parse_tos(input: &str) -> Result<u8, String> {
+    let parsed = match input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+    {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => input.parse(),
+    };
+
+    parsed.map_err(|_| String::from("the TOS must be a number between 0 and 255, e.g. 40 or 0x28."))
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+#[cfg_attr(
+    feature = "cli",
+    command(
+        name = "rustscan",
+        version = env!("CARGO_PKG_VERSION"),
+        max_term_width = 120,
+        help_template = "{bin} {version}\n{about}\n\nUSAGE:\n    {usage}\n\nOPTIONS:\n{options}",
+        group(clap::ArgGroup::new("previous_results").multiple(true)),
+    )
+)]
+#[allow(clippy::struct_excessive_bools)]
+/// Fast Port Scanner built in Rust.
+/// WARNING Do not use this program against sensitive infrastructure since the
+/// specified server may not be able to handle this many socket connections at once.
+/// - Discord  <http://discord.skerritt.blog>
+/// - GitHub <https://github.com/RustScan/RustScan>
+pub struct Opts {
+    /// A comma-delimited list or newline-delimited file of separated CIDRs, IPs, or hosts to be scanned.
+    /// Targets can be tagged after a '#', e.g. "10.0.0.5 # db-prod", and the
+    /// tags are shown with the results. IPv6 networks larger than a /116
+    /// need --sample, and link-local addresses take a zone, e.g. "fe80::1%eth0".
+    #[cfg_attr(feature = "cli", arg(short, long, value_delimiter = ','))]
+    pub addresses: Vec<String>,
+
+    /// A list of comma separated ports to be scanned. Example: 80,443,8080.
+    #[cfg_attr(feature = "cli", arg(short, long, value_delimiter = ','))]
+    pub ports: Option<Vec<u16>>,
+
+    /// A range of ports with format start-end. Example: 1-1000.
+    #[cfg_attr(feature = "cli", arg(short, long, conflicts_with = "ports", value_parser = parse_range))]
+    pub range: Option<PortRange>,
+
+    /// A file of the ports to scan: ports, ranges or service names, one or
+    /// more per line, with # comments, named [groups] used as @group and
+    /// "include <file>" lines. Example: --ports-file scope.ports.
+    #[cfg_attr(feature = "cli", arg(long, value_parser, conflicts_with_all = ["ports", "range"]))]
+    pub ports_file: Option<PathBuf>,
+
+    /// An nmap XML report (-oX) or a masscan JSON file (-oJ) of an earlier
+    /// scan, whose hosts are scanned again along with --addresses.
+    #[cfg_attr(feature = "cli", arg(long, value_parser, group = "previous_results"))]
+    pub from_nmap: Option<PathBuf>,
+
+    /// A zmap result file, one address per line or CSV with a saddr field,
+    /// whose hosts are scanned again along with --addresses. Example:
+    /// --from-zmap results.csv --rescan-ports to verify a single port sweep.
+    #[cfg_attr(feature = "cli", arg(long, value_parser, group = "previous_results"))]
+    pub from_zmap: Option<PathBuf>,
+
+    /// Only scan the hosts of the --from-nmap or --from-zmap results, each on
+    /// the ports it had open. Other targets are scanned on the usual ports.
+    #[cfg_attr(feature = "cli", arg(long, requires = "previous_results", conflicts_with_all = ["ports", "range", "ports_file"]))]
+    pub rescan_ports: bool,
+
+    /// Allow port 0 in --ports and --range. It is reserved and normally
+    /// rejected, but misconfigured services and firewalls can still answer on it.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub allow_port_zero: bool,
+
+    /// Whether to ignore the configuration file or not.
+    #[cfg_attr(feature = "cli", arg(short, long))]
+    pub no_config: bool,
+
+    /// Hide the banner
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_banner: bool,
+
+    /// Slow down for hosts that suddenly stop answering mid-scan, e.g. when
+    /// rate limiting kicks in, instead of probing them at full speed.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub backoff: bool,
+
+    /// Keep the whole batch in flight to hosts that start dropping probes,
+    /// instead of probing them with fewer sockets at once.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_adaptive_rate: bool,
+
+    /// Keep scanning at full speed when the local conntrack table is close
+    /// to full, instead of slowing down until it drains. Linux only.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_conntrack_throttle: bool,
+
+    /// Open the raw sockets up front, then drop root or any capability
+    /// before scanning, so the scan and scripts run unprivileged. Raw
+    /// sockets can also be passed in through LISTEN_FDS.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub drop_privileges: bool,
+
+    /// Run scripts in their own process group, without inherited sockets
+    /// or a way to gain privileges, under the limits set in their script
+    /// file and, on Linux, a seccomp profile.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub sandbox_scripts: bool,
+
+    /// How many scripts run at once, across the hosts and the scripts of
+    /// each host. Their outputs are printed as they finish.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", default_value = "1"))]
+    pub script_concurrency: NonZeroUsize,
+
+    /// Report what the scan used once it is done: peak probes in flight,
+    /// connection attempts, bytes sent and received, system-wide
+    /// retransmissions and wall/CPU time. Helps tuning the batch size and
+    /// timeout.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub stats: bool,
+
+    /// Show a progress bar with the sockets scanned so far and an estimate
+    /// of the time left. Only drawn when stderr is a terminal.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub progress: bool,
+
+    /// Custom path to config file
+    #[cfg_attr(feature = "cli", arg(short, long, value_parser))]
+    pub config_path: Option<PathBuf>,
+
+    /// Apply the [profile.<NAME>] section of the config file over the rest
+    /// of it, e.g. --profile stealth.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "NAME", conflicts_with = "no_config")
+    )]
+    pub profile: Option<String>,
+
+    /// Greppable mode. Only output the ports. No Nmap. Useful for grep or outputting to a file.
+    #[cfg_attr(feature = "cli", arg(short, long))]
+    pub greppable: bool,
+
+    /// The layout of the greppable output: "rustscan" prints "ip -> [ports]"
+    /// while "nmap" prints the lines of nmap's -oG, with the services
+    /// usually running on the ports.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_enum, ignore_case = true, default_value = "rustscan")
+    )]
+    pub greppable_format: GreppableFormat,
+
+    /// Accessible mode. Turns off features which negatively affect screen readers.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub accessible: bool,
+
+    /// A comma-delimited list or file of DNS resolvers. Lookups rotate
+    /// across them and skip resolvers that stop answering or lag behind.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub resolver: Option<String>,
+
+    /// How many hostnames to resolve at the same time. Large host files are
+    /// bound by resolver latency, so raising this speeds them up.
+    #[cfg_attr(feature = "cli", arg(long, default_value = "10"))]
+    pub resolve_concurrency: usize,
+
+    /// Scan a random sample of the targets instead of all of them: a number
+    /// of addresses, e.g. 10000, or a share of them, e.g. 0.1%. Networks are
+    /// sampled without being expanded, so huge IPv6 ranges can be assessed.
+    /// The summary extrapolates the results to the whole target space.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub sample: Option<SampleSize>,
+
+    /// Seed of --sample, the same seed picks the same sample.
+    #[cfg_attr(feature = "cli", arg(long, requires = "sample"))]
+    pub seed: Option<u64>,
+
+    /// The batch size for port scanning, it increases or slows the speed of
+    /// scanning. Depends on the open file limit of your OS.  If you do 65535
+    /// it will do every port at the same time. Although, your OS may not
+    /// support this.
+    #[cfg_attr(feature = "cli", arg(short, long, default_value = "4500"))]
+    pub batch_size: usize,
+
+    /// Before scanning, probe a sample of the targets at increasing batch
+    /// sizes to find the highest one that works without errors on this
+    /// machine and network, and scan with it. Never goes above --batch-size.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub warm_up: bool,
+
+    /// Print how many sockets the scan probes and how long it takes at
+    /// most, then exit without scanning.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub dry_run: bool,
+
+    /// Ask before starting a scan estimated to take longer than this many
+    /// seconds, when run from a terminal. 0 never asks.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "SECONDS", default_value = "3600")
+    )]
+    pub confirm_above: u64,
+
+    /// The timeout in milliseconds before a port is assumed to be closed.
+    #[cfg_attr(feature = "cli", arg(short, long, default_value = "1500"))]
+    pub timeout: u32,
+
+    /// How long UDP probes wait for an answer in milliseconds, --timeout
+    /// bounding only sending them. Defaults to --timeout.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "MS"))]
+    pub read_timeout: Option<u32>,
+
+    /// Pick the timeout from the round trip times seen in previous scans of
+    /// the same networks, falling back to --timeout for unknown networks.
+    /// Round trip times observed during this scan are remembered for next time.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub adaptive_timeout: bool,
+
+    /// Lower the batch size on networks where probes of previous scans
+    /// failed on the way, e.g. with unreachable hosts. Errors observed
+    /// during this scan are remembered for next time.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub adaptive_batch: bool,
+
+    /// The number of tries before a port is assumed to be closed.
+    /// If set to 0, rustscan will correct it to 1.
+    #[cfg_attr(feature = "cli", arg(long, default_value = "1"))]
+    pub tries: u8,
+
+    /// How long to wait before each retry of a probe: "none",
+    /// "fixed:<ms>", "linear:<ms>" or "exponential:<ms>:<cap ms>". Half of
+    /// every delay is random.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, default_value = "exponential:50:1000", value_parser = RetryPolicy::from_str)
+    )]
+    pub retry_backoff: RetryPolicy,
+
+    /// Start at most this many probes per second, whatever the batch size
+    /// and timeout. The control socket can change it while the scan runs.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PPS"))]
+    pub rate: Option<NonZeroU32>,
+
+    /// Keep at most this many probes in flight per host, filling the batch
+    /// with other hosts meanwhile, so no single host is flooded.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    pub max_per_host: Option<NonZeroUsize>,
+
+    /// Automatically ups the ULIMIT with the value you provided.
+    #[cfg_attr(feature = "cli", arg(short, long))]
+    pub ulimit: Option<usize>,
+
+    /// The order of scanning to be performed. The "serial" option will
+    /// scan ports in ascending order while the "random" option will scan
+    /// ports randomly. The "adaptive" option scans first the ports that
+    /// were open most often in past scans of the same networks.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_enum, ignore_case = true, default_value = "serial")
+    )]
+    pub scan_order: ScanOrder,
+
+    /// The order hosts and ports are combined in. "port-major" probes one
+    /// port across every host before the next port, e.g. to sweep a /16 for
+    /// a single service, "host-major" every port of a host before the next
+    /// host and "interleaved" mixes both randomly.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_enum, ignore_case = true, default_value = "port-major")
+    )]
+    pub iteration_order: IterationOrder,
+
+    /// How ports are probed. "connect" opens a full connection while "syn"
+    /// only sends a SYN, which is faster and stays out of application logs
+    /// but needs root or CAP_NET_RAW. IPv6 targets are always connect scanned.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_enum, ignore_case = true, default_value = "connect")
+    )]
+    pub scan_type: ScanType,
+
+    /// Level of scripting required for the run.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_enum, ignore_case = true, default_value = "default")
+    )]
+    pub scripts: ScriptsRequired,
+
+    /// Scan the N most common ports, up to 100, or the 1000 ports nmap scans
+    /// by default without a number. The `ports` of the config file replace
+    /// the built-in list, most common first.
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            alias = "top",
+            value_name = "N",
+            num_args = 0..=1,
+            default_missing_value = "1000",
+            value_parser = parse_top_port_count,
+            conflicts_with_all = ["ports", "range"]
+        )
+    )]
+    pub top_ports: Option<usize>,
+
+    /// Scan the ports of these built-in groups, e.g. "web,database". The
+    /// groups are web, database, mail, remote, file, directory, scada, voip
+    /// and printer.
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_name = "GROUP",
+            value_delimiter = ',',
+            value_parser = parse_port_group,
+            conflicts_with_all = ["ports", "range", "top_ports"]
+        )
+    )]
+    pub port_group: Option<Vec<String>>,
+
+    /// Scan the top ports first, then the rest of the ports in the same
+    /// run. The top ports are the `ports` of the config file, or a built-in
+    /// list of common services without one.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub top_first: bool,
+
+    /// Scan in two phases: a fast sweep with a quarter of the timeout and a
+    /// single try, then a second look with the normal timeout and tries at
+    /// the ports that looked open or timed out on hosts that otherwise
+    /// answer. TCP only.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub sweep: bool,
+
+    /// Connect once more to every open port, with twice the timeout, and
+    /// drop the ones that no longer answer before reporting them. Weeds out
+    /// phantom open ports produced by high batch sizes on flaky networks.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub verify: bool,
+
+    /// Probe the open TCP ports to tell the service and version behind
+    /// them, like a lightweight nmap -sV: SSH and SMTP greetings, HTTP HEAD
+    /// and a TLS ClientHello.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub probe_services: bool,
+
+    /// Collect the certificate of every open TCP port answering a TLS
+    /// handshake: its subject, SANs, issuer and expiry.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub tls_info: bool,
+
+    /// Find the hosts that are up first, with ICMP echo requests, TCP
+    /// connects to a few common ports and the ARP table, and only scan
+    /// those.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub skip_dead_hosts: bool,
+
+    /// Stop probing hosts that accept connections on nearly every port,
+    /// like CDN edges and some firewalls, once this many of their ports
+    /// were probed. Such hosts are flagged in the output, without their
+    /// ports. Hosts are only recognised after 100 probed ports, smaller
+    /// samples act as 100, and 0 scans every port of every host.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", default_value = "200"))]
+    pub all_open_sample: Option<usize>,
+
+    /// Listen on a unix socket at this path to steer the running scan from
+    /// another terminal, e.g. `echo pause | nc -U <path>`. Supported
+    /// commands: status, pause, resume, set-rate <n|unlimited>, dump-partial.
+    #[cfg_attr(feature = "cli", arg(long, value_parser))]
+    pub control_socket: Option<PathBuf>,
+
+    /// Show open ports as numbers only, without the name of the service
+    /// usually running on them, e.g. "22" instead of "22 (ssh)".
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub numeric: bool,
+
+    /// How to group the results: "host" lists the open ports of each host,
+    /// "service" lists the hosts exposing each port, e.g. "22/ssh: 41 hosts".
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_enum, ignore_case = true, default_value = "host")
+    )]
+    pub group_by: GroupBy,
+
+    /// Also send results to a JSON lines file, "json:<path>", or POST them
+    /// in batches to a webhook, "webhook:http://<host>/<path>", as they are
+    /// found.
+    /// "json" prints a JSON report of the whole scan once it is over, and
+    /// "report:<path>" writes it to a file. "xml" and "xml:<path>" do the
+    /// same in nmap's XML format, "csv" and "csv:<path>" as CSV rows. Can be
+    /// repeated, every output gets every result.
+    #[cfg_attr(feature = "cli", arg(long, value_parser = OutputSpec::from_str))]
+    pub output: Vec<OutputSpec>,
+
+    /// Write the reports given to --output without a path, "json", "xml"
+    /// or "csv", to this file instead of stdout.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATH"))]
+    pub output_file: Option<PathBuf>,
+
+    /// Ports scanned first with --top-first, read from the config file.
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub first_ports: Option<Vec<u16>>,
+
+    /// Per-network overrides, read from the `policy` tables of the config
+    /// file.
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub policies: BTreeMap<String, Policy>,
+
+    /// Extra script arguments for targets with a tag, read from the
+    /// `tag_command` table of the config file.
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub tag_command: BTreeMap<String, Vec<String>>,
+
+    /// The Script arguments to run.
+    /// To use the argument -A, end RustScan's args with '-- -A'.
+    /// Example: 'rustscan -t 1500 -a 127.0.0.1 -- -A -sC'.
+    /// This command adds -Pn -vvv -p $PORTS automatically to nmap.
+    /// For things like --script '(safe and vuln)' enclose it in quotations marks \"'(safe and vuln)'\"
+    #[cfg_attr(feature = "cli", arg(last = true))]
+    pub command: Vec<String>,
+
+    /// A list of comma separated ports, ranges or service names to be
+    /// excluded from scanning. Example: 80,8000-8100,ssh,rdp.
+    #[cfg_attr(feature = "cli", arg(short, long, value_delimiter = ',', value_parser = parse_port_spec))]
+    pub exclude_ports: Option<Vec<PortSpec>>,
+
+    /// A file of ports, ranges or service names to be excluded from
+    /// scanning, one or more per line. Lines starting with # are ignored.
+    #[cfg_attr(feature = "cli", arg(long, value_parser))]
+    pub exclude_ports_file: Option<PathBuf>,
+
+    /// A file of CIDRs, IPs, hosts and ports to be excluded from scanning,
+    /// like a do-not-scan list. Service names take a port: prefix, e.g.
+    /// port:telnet. Lines starting with # are ignored.
+    #[cfg_attr(feature = "cli", arg(long, value_parser))]
+    pub exclude_file: Option<PathBuf>,
+
+    /// A file of targets scanned on their own ports instead of the others,
+    /// one "<host>:<ports>" per line, e.g. "10.0.0.5:22,80,8000-8100".
+    /// IPv6 addresses go between brackets. Lines starting with # are ignored.
+    #[cfg_attr(feature = "cli", arg(long, value_parser))]
+    pub targets_file: Option<PathBuf>,
+
+    /// Scan only what an interrupted scan had left, as written to its
+    /// resume file, instead of the given targets. The other options should
+    /// be the ones of the interrupted scan. Example: --resume rustscan.resume
+    #[cfg_attr(feature = "cli", arg(long, value_parser, conflicts_with_all = ["addresses", "targets_file", "previous_results"]))]
+    pub resume: Option<PathBuf>,
+
+    /// Ports of the targets of `--targets-file` and `--rescan-ports`, by
+    /// address.
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub target_ports: BTreeMap<String, Vec<u16>>,
+
+    /// A list of comma separated CIDRs, IPs, or hosts to be excluded from scanning.
+    #[cfg_attr(
+        feature = "cli",
+        arg(short = 'x', long = "exclude-addresses", value_delimiter = ',')
+    )]
+    pub exclude_addresses: Option<Vec<String>>,
+
+    /// Look for wildcard DNS when many hostnames share a domain, such as
+    /// subdomain lists, by resolving random names under it. "warn" reports
+    /// it, "collapse" also drops the hostnames that only resolve to the
+    /// wildcard. Off by default.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_enum, ignore_case = true, default_value = "off")
+    )]
+    pub wildcard_dns: WildcardDns,
+
+    /// UDP scanning mode, finds UDP ports that send back responses. With
+    /// raw sockets, ICMP unreachables tell closed and filtered ports apart
+    /// from the ones that stayed silent.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub udp: bool,
+
+    /// The protocols to probe every port over, e.g. "tcp,udp" to find both
+    /// in a single run. Overrides --udp.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_enum, value_delimiter = ',', ignore_case = true)
+    )]
+    pub protocols: Option<Vec<Protocol>>,
+
+    /// A file of UDP payloads in nmap's nmap-payloads format, sent to their
+    /// ports instead of the built-in ones. Ports it doesn't list keep the
+    /// built-in payloads.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub udp_payloads: Option<PathBuf>,
+
+    /// Close connections with SO_LINGER set to 0, resetting them instead of
+    /// lingering in the close handshake. Frees sockets faster at high batch sizes.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub linger_zero: bool,
+
+    /// Set TCP_NODELAY on scanning sockets.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub tcp_nodelay: bool,
+
+    /// Size in bytes of the send buffer (SO_SNDBUF) of scanning sockets.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub send_buffer_size: Option<usize>,
+
+    /// Size in bytes of the receive buffer (SO_RCVBUF) of scanning sockets.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub recv_buffer_size: Option<usize>,
+
+    /// Set the TOS byte (IPv4) or traffic class (IPv6) of scan traffic so it
+    /// can be identified on the network. This is the DSCP value shifted left
+    /// by two, e.g. 0x28 for DSCP CS1. Decimal or 0x prefixed hexadecimal.
+    #[cfg_attr(feature = "cli", arg(long, value_parser = parse_tos))]
+    pub tos: Option<u8>,
+
+    /// Send the scan from this local address instead of the one the system
+    /// picks, e.g. on multi-homed hosts. Only used for targets of the same
+    /// IP version.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "IP"))]
+    pub source_ip: Option<IpAddr>,
+
+    /// Send the scan from this local port, e.g. 53 or 443, which some
+    /// firewalls let through. Connections from it are closed like with
+    /// --linger-zero, so retries to the same port of a target can reuse it.
+    /// Ports below 1024 can't be used with --drop-privileges.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PORT"))]
+    pub source_port: Option<u16>,
+
+    /// Along with every SYN of --scan-type syn, send one from each of these
+    /// addresses, so the scan seems to come from all of them. IPv4 only.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "IP", value_delimiter = ','))]
+    pub decoys: Option<Vec<Ipv4Addr>>,
+
+    /// Send the scan out of this network interface whatever the routing
+    /// table says, e.g. past a VPN's split tunnel. Linux and macOS only.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "NAME"))]
+    pub interface: Option<String>,
+
+    /// Request TCP Fast Open cookies from open ports after the scan and
+    /// report which ones handed one out. Linux only, needs the client bit
+    /// set in net.ipv4.tcp_fastopen (the default).
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub fast_open: bool,
+
+    /// Ask open ports for Multipath TCP with the MP_CAPABLE option of the
+    /// SYN scan, and report which ones agree. Needs --scan-type syn.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub mptcp: bool,
+
+    /// Look for tarpits and frontends that accept connections on nearly
+    /// every port, or hold them all open without a word or with the same
+    /// greeting. "warn" reports them, "suppress" also leaves their open
+    /// ports out of the results.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_enum, ignore_case = true, default_value = "off")
+    )]
+    pub tarpits: TarpitDetection,
+
+    /// Ask open ports for Explicit Congestion Notification with the ECE and
+    /// CWR flags of the SYN scan, and report which ones agree. Needs
+    /// --scan-type syn.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub ecn: bool,
+
+    #[cfg_attr(feature = "cli", command(subcommand))]
+    pub subcommand: Option<Commands>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl Opts {
+    #[cfg(feature = "cli")]
+    pub fn read() -> Self {
+        Self::read_from(std::env::args_os())
+    }
+
+    /// Like [`Opts::read`], from the given command line instead of the one
+    /// RustScan was started with.
+    ///
+    /// The arguments in `RUSTSCAN_OPTS` are defaults the ones given
+    /// override, and `RUSTSCAN_CONFIG_PATH` is the config file when
+    /// `--config-path` isn't given. Both help containers and CI jobs that
+    /// can't pass long command lines or keep a config file at home.
+    #[cfg(feature = "cli")]
+    pub fn read_from<I, T>(args: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        Self::read_from_with_env(args, std::env::var(OPTS_ENV).ok().as_deref())
+    }
+
+    /// Like [`Opts::read_from`], with `env_opts` in place of `RUSTSCAN_OPTS`.
+    ///
+    /// An option given on the command line replaces the same option of
+    /// `env_opts`, and the command after `--` of `env_opts` is only used
+    /// when the command line has none.
+    #[cfg(feature = "cli")]
+    pub fn read_from_with_env<I, T>(args: I, env_opts: Option<&str>) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let mut args: Vec<std::ffi::OsString> = args.into_iter().map(Into::into).collect();
+        if let Some(env_opts) = env_opts {
+            match split_args(env_opts) {
+                Ok(env_args) => args = merge_env_args(args, env_args),
+                Err(e) => {
+                    Opts::command()
+                        .error(
+                            clap::error::ErrorKind::InvalidValue,
+                            format!("invalid {OPTS_ENV}: {e}"),
+                        )
+                        .exit();
+                }
+            }
+        }
+        let mut opts = Opts::parse_from(args);
+        if opts.config_path.is_none() {
+            opts.config_path = std::env::var_os(CONFIG_PATH_ENV)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from);
+        }
+
+        if let Some(count) = opts.top_ports {
+            opts.ports = Some(top_ports(count));
+        }
+        if let Some(groups) = &opts.port_group {
+            opts.ports = Some(port_group_ports(groups));
+        }
+        if opts.ports.is_none() && opts.range.is_none() && opts.ports_file.is_none() {
+            opts.range = Some(PortRange {
+                start: LOWEST_PORT_NUMBER,
+                end: TOP_PORT_NUMBER,
+            });
+        }
+
+        opts
+    }
+
+    /// The extra script arguments for a target with `tags`: the ones given
+    /// after `--`, then the ones of each tag in `tag_command`, in the order
+    /// of the tags.
+    pub fn command_for(&self, tags: &[String]) -> Vec<String> {
+        let mut command = self.command.clone();
+        for tag in tags {
+            if let Some(args) = self.tag_command.get(tag) {
+                command.extend(args.iter().cloned());
+            }
+        }
+        command
+    }
+
+    /// The protocols ports are probed over: --protocols, or TCP or UDP
+    /// following --udp.
+    pub fn protocols(&self) -> Vec<Protocol> {
+        match &self.protocols {
+            Some(protocols) if !protocols.is_empty() => protocols.clone(),
+            _ if self.udp => vec![Protocol::Udp],
+            _ => vec![Protocol::Tcp],
+        }
+    }
+
+    /// Whether the scan uses what earlier scans learned, and adds to it:
+    /// with --adaptive-timeout, --adaptive-batch or --scan-order adaptive.
+    pub fn learns(&self) -> bool {
+        self.adaptive_timeout || self.adaptive_batch || self.scan_order == ScanOrder::Adaptive
+    }
+
+    /// Whether the scan sends SYNs through a raw TCP socket: with
+    /// --scan-type syn, when TCP is scanned.
+    pub fn syn_scans(&self) -> bool {
+        self.scan_type == ScanType::Syn && self.protocols().contains(&Protocol::Tcp)
+    }
+
+    /// Whether the scan reads ICMP through a raw socket: to tell closed UDP
+    /// ports apart, and to find hosts up with --skip-dead-hosts.
+    pub fn listens_for_icmp(&self) -> bool {
+        self.protocols().contains(&Protocol::Udp) || self.skip_dead_hosts
+    }
+
+    /// Whether a progress bar is drawn: with --progress on a terminal,
+    /// unless the output is greppable or accessible.
+    pub fn shows_progress_bar(&self) -> bool {
+        self.progress && !self.greppable && !self.accessible && std::io::stderr().is_terminal()
+    }
+
+    /// Whether the requested ports or range include the reserved port 0.
+    pub fn includes_port_zero(&self) -> bool {
+        self.ports.as_ref().is_some_and(|ports| ports.contains(&0))
+            || self.range.as_ref().is_some_and(|range| range.start == 0)
+    }
+
+    /// Replaces the ports to scan with the ones of `--ports-file`, if given.
+    pub fn read_ports_file(&mut self) -> Result<(), String> {
+        if let Some(path) = &self.ports_file {
+            let ports = PortSpec::expand(&PortSpec::read_file(path)?)?;
+            let mut seen = std::collections::BTreeSet::new();
+            self.ports = Some(
+                ports
+                    .into_iter()
+                    .filter(|port| seen.insert(*port))
+                    .collect(),
+            );
+            self.range = None;
+        }
+        Ok(())
+    }
+
+    /// Adds the addresses and ports of `--exclude-file`, if given, to the
+    /// excluded ones.
+    pub fn read_exclude_file(&mut self) -> Result<(), String> {
+        if let Some(path) = &self.exclude_file {
+            let file = ExcludeFile::read(path)?;
+            self.exclude_addresses
+                .get_or_insert_with(Vec::new)
+                .extend(file.addresses);
+            self.exclude_ports
+                .get_or_insert_with(Vec::new)
+                .extend(file.ports);
+        }
+        Ok(())
+    }
+
+    /// Adds the targets of `--targets-file`, if given, to the scanned ones,
+    /// along with their ports.
+    pub fn read_targets_file(&mut self) -> Result<(), String> {
+        let Some(path) = &self.targets_file else {
+            return Ok(());
+        };
+        let file = TargetsFile::read(path)?;
+        self.add_targets(file)
+    }
+
+    /// Replaces the targets with the ones of the resume file of `--resume`,
+    /// if given, along with their ports.
+    pub fn read_resume_file(&mut self) -> Result<(), String> {
+        let Some(path) = &self.resume else {
+            return Ok(());
+        };
+        let file = TargetsFile::read(path)?;
+        self.addresses.clear();
+        self.target_ports.clear();
+        self.add_targets(file)
+    }
+
+    fn add_targets(&mut self, file: TargetsFile) -> Result<(), String> {
+        for (address, specs) in file.targets {
+            let ports = self.target_ports.entry(address.clone()).or_default();
+            for port in PortSpec::expand(&specs)? {
+                if !ports.contains(&port) {
+                    ports.push(port);
+                }
+            }
+            if !self.addresses.contains(&address) {
+                self.addresses.push(address);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the hosts of `--from-nmap` and `--from-zmap` to the targets and,
+    /// with `--rescan-ports`, only the ones with open ports, each with the
+    /// ports it had open as its own, see [`Opts::target_ports`].
+    pub fn read_previous_results(&mut self) -> Result<(), String> {
+        let mut hosts = Vec::new();
+        if let Some(path) = &self.from_nmap {
+            hosts.extend(crate::import::read_scan_results(path)?);
+        }
+        if let Some(path) = &self.from_zmap {
+            hosts.extend(crate::import::read_zmap_results(path)?);
+        }
+        if self.from_nmap.is_none() && self.from_zmap.is_none() {
+            return Ok(());
+        }
+        if hosts.is_empty() {
+            return Err("no hosts were up in the previous results".to_owned());
+        }
+        if self.rescan_ports {
+            hosts.retain(|host| !host.ports.is_empty());
+            if hosts.is_empty() {
+                return Err("no open ports in the previous results".to_owned());
+            }
+            for host in &hosts {
+                let ports = self.target_ports.entry(host.ip.to_string()).or_default();
+                for port in &host.ports {
+                    if !ports.contains(port) {
+                        ports.push(*port);
+                    }
+                }
+            }
+        }
+        let mut seen = std::collections::HashSet::new();
+        self.addresses.extend(
+            hosts
+                .iter()
+                .filter(|host| seen.insert(host.ip))
+                .map(|host| host.ip.to_string()),
+        );
+        Ok(())
+    }
+
+    /// Resolves every port excluded through `--exclude-ports` and
+    /// `--exclude-ports-file`.
+    pub fn excluded_ports(&self) -> Result<Vec<u16>, String> {
+        let mut ports = PortSpec::expand(self.exclude_ports.as_deref().unwrap_or_default())?;
+        if let Some(path) = &self.exclude_ports_file {
+            ports.extend(PortSpec::expand(&PortSpec::read_file(path)?)?);
+        }
+        Ok(ports)
+    }
+
+    /// Reads the command line arguments into an Opts struct and merge
+    /// values found within the user configuration file.
+    pub fn merge(&mut self, config: &Config) {
+        if !self.no_config {
+            self.merge_required(config);
+            self.merge_optional(config);
+        }
+    }
+
+    /// Merges the profile chosen with `--profile` over the rest of the
+    /// configuration file, failing when the file has no such profile.
+    pub fn merge_profile(&mut self, config: &Config) -> Result<(), String> {
+        let Some(name) = &self.profile else {
+            return Ok(());
+        };
+        let Some(profile) = config
+            .profile
+            .as_ref()
+            .and_then(|profiles| profiles.get(name))
+        else {
+            let known: Vec<&str> = config
+                .profile
+                .iter()
+                .flat_map(BTreeMap::keys)
+                .map(String::as_str)
+                .collect();
+            return Err(match known.as_slice() {
+                [] => format!("no profile named {name}, the config file has none"),
+                known => format!("no profile named {name}, try one of {}", known.join(", ")),
+            });
+        };
+        self.merge_required(profile);
+        self.merge_optional(profile);
+        Ok(())
+    }
+
+    fn merge_required(&mut self, config: &Config) {
+        macro_rules! merge_required {
+            ($($field: ident),+) => {
+                $(
+                    if let Some(e) = &config.$field {
+                        self.$field = e.clone();
+                    }
+                )+
+            }
+        }
+
+        if let Some(addresses) = &config.addresses {
+            self.addresses = addresses.iter().map(ConfigAddress::annotated).collect();
+        }
+
+        merge_required!(
+            greppable,
+            accessible,
+            batch_size,
+            timeout,
+            tries,
+            retry_backoff,
+            scan_order,
+            iteration_order,
+            scan_type,
+            scripts,
+            command,
+            udp,
+            no_banner,
+            adaptive_timeout,
+            adaptive_batch,
+            warm_up,
+            linger_zero,
+            tcp_nodelay,
+            fast_open,
+            mptcp,
+            ecn,
+            tarpits,
+            backoff,
+            no_adaptive_rate,
+            no_conntrack_throttle,
+            drop_privileges,
+            sandbox_scripts,
+            script_concurrency,
+            stats,
+            progress,
+            sweep,
+            verify,
+            probe_services,
+            tls_info,
+            skip_dead_hosts,
+            group_by,
+            greppable_format,
+            output,
+            allow_port_zero,
+            numeric,
+            wildcard_dns,
+            resolve_concurrency
+        );
+    }
+
+    fn merge_optional(&mut self, config: &Config) {
+        macro_rules! merge_optional {
+            ($($field: ident),+) => {
+                $(
+                    if config.$field.is_some() {
+                        self.$field = config.$field.clone();
+                    }
+                )+
+            }
+        }
+
+        // Only use top ports when the user asks for them
+        if let (Some(count), Some(ports)) = (self.top_ports, &config.ports) {
+            self.ports = Some(ports.iter().take(count).copied().collect());
+        }
+        if self.top_first {
+            self.first_ports = config.ports.clone();
+        }
+        if let Some(policy) = &config.policy {
+            self.policies = policy.clone();
+        }
+        if let Some(tag_command) = &config.tag_command {
+            self.tag_command = tag_command.clone();
+        }
+
+        merge_optional!(
+            range,
+            resolver,
+            ulimit,
+            ports_file,
+            exclude_ports,
+            exclude_ports_file,
+            exclude_file,
+            targets_file,
+            output_file,
+            exclude_addresses,
+            udp_payloads,
+            rate,
+            max_per_host,
+            read_timeout,
+            protocols,
+            send_buffer_size,
+            recv_buffer_size,
+            tos,
+            source_ip,
+            source_port,
+            decoys,
+            interface,
+            all_open_sample
+        );
+    }
+}
+
+impl Default for Opts {
+    fn default() -> Self {
+        Self {
+            addresses: vec![],
+            ports: None,
+            range: None,
+            ports_file: None,
+            from_nmap: None,
+            from_zmap: None,
+            rescan_ports: false,
+            greppable: true,
+            batch_size: 0,
+            warm_up: false,
+            dry_run: false,
+            confirm_above: 3600,
+            timeout: 0,
+            adaptive_timeout: false,
+            adaptive_batch: false,
+            tries: 0,
+            retry_backoff: RetryPolicy::default(),
+            ulimit: None,
+            command: vec![],
+            accessible: false,
+            resolver: None,
+            resolve_concurrency: 10,
+            sample: None,
+            seed: None,
+            scan_order: ScanOrder::Serial,
+            iteration_order: IterationOrder::default(),
+            scan_type: ScanType::Connect,
+            no_config: true,
+            no_banner: false,
+            backoff: false,
+            no_adaptive_rate: false,
+            no_conntrack_throttle: false,
+            drop_privileges: false,
+            sandbox_scripts: false,
+            script_concurrency: NonZeroUsize::MIN,
+            stats: false,
+            progress: false,
+            sweep: false,
+            verify: false,
+            probe_services: false,
+            tls_info: false,
+            skip_dead_hosts: false,
+            all_open_sample: Some(200),
+            control_socket: None,
+            numeric: false,
+            group_by: GroupBy::Host,
+            greppable_format: GreppableFormat::Rustscan,
+            output: vec![],
+            output_file: None,
+            allow_port_zero: false,
+            top_ports: None,
+            port_group: None,
+            top_first: false,
+            first_ports: None,
+            policies: BTreeMap::new(),
+            tag_command: BTreeMap::new(),
+            scripts: ScriptsRequired::Default,
+            config_path: None,
+            profile: None,
+            exclude_ports: None,
+            exclude_ports_file: None,
+            exclude_file: None,
+            targets_file: None,
+            resume: None,
+            target_ports: BTreeMap::new(),
+            exclude_addresses: None,
+            wildcard_dns: WildcardDns::Off,
+            udp: false,
+            udp_payloads: None,
+            rate: None,
+            max_per_host: None,
+            read_timeout: None,
+            protocols: None,
+            linger_zero: false,
+            tcp_nodelay: false,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            tos: None,
+            source_ip: None,
+            source_port: None,
+            decoys: None,
+            interface: None,
+            fast_open: false,
+            mptcp: false,
+            ecn: false,
+            tarpits: TarpitDetection::Off,
+            subcommand: None,
+        }
+    }
+}
+
+/// Struct used to deserialize the options specified within our config file.
+/// These will be further merged with our command line arguments in order to
+/// generate the final Opts struct.
+#[cfg(not(tarpaulin_include))]
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    addresses: Option<Vec<ConfigAddress>>,
+    ports: Option<Vec<u16>>,
+    range: Option<PortRange>,
+    greppable: Option<bool>,
+    accessible: Option<bool>,
+    batch_size: Option<usize>,
+    warm_up: Option<bool>,
+    timeout: Option<u32>,
+    adaptive_timeout: Option<bool>,
+    adaptive_batch: Option<bool>,
+    tries: Option<u8>,
+    retry_backoff: Option<RetryPolicy>,
+    ulimit: Option<usize>,
+    resolver: Option<String>,
+    resolve_concurrency: Option<usize>,
+    scan_order: Option<ScanOrder>,
+    iteration_order: Option<IterationOrder>,
+    scan_type: Option<ScanType>,
+    command: Option<Vec<String>>,
+    scripts: Option<ScriptsRequired>,
+    exclude_ports: Option<Vec<PortSpec>>,
+    ports_file: Option<PathBuf>,
+    exclude_ports_file: Option<PathBuf>,
+    exclude_file: Option<PathBuf>,
+    targets_file: Option<PathBuf>,
+    exclude_addresses: Option<Vec<String>>,
+    wildcard_dns: Option<WildcardDns>,
+    udp: Option<bool>,
+    udp_payloads: Option<PathBuf>,
+    rate: Option<NonZeroU32>,
+    max_per_host: Option<NonZeroUsize>,
+    read_timeout: Option<u32>,
+    protocols: Option<Vec<Protocol>>,
+    no_banner: Option<bool>,
+    linger_zero: Option<bool>,
+    tcp_nodelay: Option<bool>,
+    send_buffer_size: Option<usize>,
+    recv_buffer_size: Option<usize>,
+    tos: Option<u8>,
+    source_ip: Option<IpAddr>,
+    source_port: Option<u16>,
+    decoys: Option<Vec<Ipv4Addr>>,
+    interface: Option<String>,
+    fast_open: Option<bool>,
+    mptcp: Option<bool>,
+    ecn: Option<bool>,
+    tarpits: Option<TarpitDetection>,
+    backoff: Option<bool>,
+    no_adaptive_rate: Option<bool>,
+    no_conntrack_throttle: Option<bool>,
+    drop_privileges: Option<bool>,
+    sandbox_scripts: Option<bool>,
+    script_concurrency: Option<NonZeroUsize>,
+    stats: Option<bool>,
+    progress: Option<bool>,
+    sweep: Option<bool>,
+    verify: Option<bool>,
+    probe_services: Option<bool>,
+    tls_info: Option<bool>,
+    skip_dead_hosts: Option<bool>,
+    all_open_sample: Option<usize>,
+    group_by: Option<GroupBy>,
+    greppable_format: Option<GreppableFormat>,
+    output: Option<Vec<OutputSpec>>,
+    output_file: Option<PathBuf>,
+    allow_port_zero: Option<bool>,
+    numeric: Option<bool>,
+    policy: Option<BTreeMap<String, Policy>>,
+    tag_command: Option<BTreeMap<String, Vec<String>>>,
+    /// Named sets of settings applied over the others with `--profile`.
+    profile: Option<BTreeMap<String, Config>>,
+}
+
+#[cfg(not(tarpaulin_include))]
+#[allow(clippy::doc_link_with_quotes)]
+#[allow(clippy::manual_unwrap_or_default)]
+impl Config {
+    /// Reads the configuration file with TOML format and parses it into a
+    /// Config struct.
+    ///
+    /// # Format
+    ///
+    /// addresses = ["127.0.0.1", { host = "10.0.0.5", tags = ["prod", "db"] }]
+    /// ports = [80, 443, 8080]
+    /// greppable = true
+    /// scan_order = "Serial"
+    /// exclude_ports = [8080, 9090, 80, "ssh", "1000-2000"]
+    /// exclude_file = "/etc/rustscan/do-not-scan.txt"
+    /// udp = false
+    ///
+    /// [policy."10.0.0.0/8"]
+    /// batch_size = 500
+    /// timeout = 3000
+    ///
+    /// [tag_command]
+    /// windows = ["--script", "smb*"]
+    ///
+    /// [profile.stealth]
+    /// batch_size = 100
+    /// timeout = 5000
+    /// scan_order = "Random"
+    ///
+    pub fn read(custom_config_path: Option<PathBuf>) -> Self {
+        let mut content = String::new();
+        let config_path = custom_config_path.unwrap_or_else(|| {
+            let path = default_config_path();
+            match path.exists() {
+                true => path,
+                false => old_default_config_path(),
+            }
+        });
+
+        if config_path.exists() {
+            content = match fs::read_to_string(config_path) {
+                Ok(content) => content,
+                Err(_) => String::new(),
+            }
+        }
+
+        let config: Config = match toml::from_str(&content) {
+            Ok(config) => config,
+            Err(e) => {
+                println!("Found {e} in configuration file.\nAborting scan.\n");
+                std::process::exit(1);
+            }
+        };
+
+        config
+    }
+}
+
+/// Splits `input` into arguments the way a POSIX shell would, with quotes
+/// and backslash escapes but no expansions.
+///
+/// ```rust
+/// # use rustscan::input::split_args;
+/// assert_eq!(
+///     split_args(r#"-b 500 -- --script "safe and vuln""#).unwrap(),
+///     ["-b", "500", "--", "--script", "safe and vuln"]
+/// );
+/// ```
+pub fn split_args(input: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    // Whether an argument was started, as quotes can start an empty one.
+    let mut started = false;
+    let mut quote = None;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('"'), '\\') => match chars.next() {
+                Some(escaped @ ('"' | '\\')) => arg.push(escaped),
+                Some(other) => {
+                    arg.push('\\');
+                    arg.push(other);
+                }
+                None => return Err("unterminated double quote".to_owned()),
+            },
+            (Some(_), c) => arg.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                started = true;
+            }
+            (None, '\\') => {
+                arg.push(chars.next().ok_or("trailing backslash")?);
+                started = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if started {
+                    args.push(std::mem::take(&mut arg));
+                    started = false;
+                }
+            }
+            (None, c) => {
+                arg.push(c);
+                started = true;
+            }
+        }
+    }
+    match quote {
+        Some('"') => Err("unterminated double quote".to_owned()),
+        Some(_) => Err("unterminated single quote".to_owned()),
+        None => {
+            if started {
+                args.push(arg);
+            }
+            Ok(args)
+        }
+    }
+}
+
+/// Puts the arguments of `RUSTSCAN_OPTS` in front of the command line
+/// `args`, without the options `args` sets again, and with its command
+/// after `--` only when `args` has none.
+#[cfg(feature = "cli")]
+fn merge_env_args(args: Vec<std::ffi::OsString>, env_args: Vec<String>) -> Vec<std::ffi::OsString> {
+    let mut command = Opts::command();
+    command.build();
+
+    let end = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    let options: Vec<String> = args[..end]
+        .iter()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    let given: Vec<clap::Id> = split_options(&command, &options)
+        .into_iter()
+        .filter_map(|(id, _)| id)
+        .collect();
+
+    let env_end = env_args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(env_args.len());
+    let mut merged: Vec<std::ffi::OsString> = args.iter().take(1).cloned().collect();
+    for (id, tokens) in split_options(&command, &env_args[..env_end]) {
+        if id.map_or(true, |id| !given.contains(&id)) {
+            merged.extend(tokens.into_iter().map(Into::into));
+        }
+    }
+    merged.extend(args[..end].iter().skip(1).cloned());
+    if end < args.len() {
+        merged.extend(args[end..].iter().cloned());
+    } else {
+        merged.extend(env_args[env_end..].iter().map(Into::into));
+    }
+    merged
+}
+
+/// Groups `args` into the options of `command` they set, each with its
+/// values. Short flags put together, like `-gn`, are taken apart. What is
+/// not an option, from the first unknown argument on, is kept as is
+/// without an option.
+#[cfg(feature = "cli")]
+fn split_options(command: &clap::Command, args: &[String]) -> Vec<(Option<clap::Id>, Vec<String>)> {
+    let values = |arg: &clap::Arg| {
+        arg.get_num_args()
+            .filter(|_| arg.get_action().takes_values())
+            .map_or((0, 0), |range| (range.min_values(), range.max_values()))
+    };
+    let mut options = Vec::new();
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        let (option, mut tokens, attached) = if let Some(long) = arg.strip_prefix("--") {
+            let name = long.split_once('=').map_or(long, |(name, _)| name);
+            let option = command.get_arguments().find(|option| {
+                option.get_long() == Some(name)
+                    || option
+                        .get_all_aliases()
+                        .is_some_and(|aliases| aliases.contains(&name))
+            });
+            (option, vec![arg.clone()], long.contains('='))
+        } else if let Some(shorts) = arg.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+            let before = options.len();
+            let mut option = None;
+            let mut tokens = Vec::new();
+            let mut attached = false;
+            for (i, short) in shorts.char_indices() {
+                let Some(found) = command.get_arguments().find(|option| {
+                    option.get_short() == Some(short)
+                        || option
+                            .get_all_short_aliases()
+                            .is_some_and(|aliases| aliases.contains(&short))
+                }) else {
+                    option = None;
+                    break;
+                };
+                if let Some(previous) = option.replace(found) {
+                    options.push((Some(previous.get_id().clone()), std::mem::take(&mut tokens)));
+                }
+                tokens = vec![format!("-{short}")];
+                let rest = &shorts[i + short.len_utf8()..];
+                if values(found).1 > 0 && !rest.is_empty() {
+                    tokens.push(rest.strip_prefix('=').unwrap_or(rest).to_owned());
+                    attached = true;
+                    break;
+                }
+            }
+            if option.is_none() {
+                options.truncate(before);
+                tokens = vec![arg.clone()];
+            }
+            (option, tokens, attached)
+        } else {
+            (None, vec![arg.clone()], false)
+        };
+
+        let Some(option) = option else {
+            tokens.extend(args.by_ref().cloned());
+            options.push((None, tokens));
+            break;
+        };
+        if !attached {
+            let (min, max) = values(option);
+            while tokens.len() <= max {
+                match args.peek() {
+                    Some(value) if tokens.len() <= min || !value.starts_with('-') => {
+                        tokens.extend(args.next().cloned());
+                    }
+                    _ => break,
+                }
+            }
+        }
+        options.push((Some(option.get_id().clone()), tokens));
+    }
+    options
+}
+
+/// Constructs default path to config toml
+pub fn default_config_path() -> PathBuf {
+    let Some(mut config_path) = dirs::config_dir() else {
+        panic!("Could not infer config file path.");
+    };
+    config_path.push(".rustscan.toml");
+    config_path
+}
+
+/// Returns the deprecated home directory config path used for backwards compatibility.
+pub fn old_default_config_path() -> PathBuf {
+    let Some(mut config_path) = dirs::home_dir() else {
+        panic!("Could not infer config file path.");
+    };
+    config_path.push(".rustscan.toml");
+    config_path
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use clap::{CommandFactory, Parser};
+    use parameterized::parameterized;
+
+    use super::{
+        split_args, Commands, Config, ConfigAddress, ExcludeFile, Opts, OutputSpec, PortFile,
+        PortRange, PortSpec, Protocol, ScanOrder, ScriptsRequired, TargetsFile,
+    };
+    use std::collections::BTreeMap;
+    use std::num::NonZeroU32;
+    use std::path::Path;
+
+    impl Config {
+        fn default() -> Self {
+            Self {
+                addresses: Some(vec![ConfigAddress::Plain("127.0.0.1".to_owned())]),
+                ports: None,
+                range: None,
+                greppable: Some(true),
+                batch_size: Some(25_000),
+                warm_up: None,
+                timeout: Some(1_000),
+                adaptive_timeout: None,
+                adaptive_batch: None,
+                tries: Some(1),
+                retry_backoff: None,
+                ulimit: None,
+                command: Some(vec!["-A".to_owned()]),
+                accessible: Some(true),
+                resolver: None,
+                resolve_concurrency: None,
+                scan_order: Some(ScanOrder::Random),
+                iteration_order: None,
+                scan_type: None,
+                scripts: None,
+                exclude_ports: None,
+                ports_file: None,
+                exclude_ports_file: None,
+                exclude_file: None,
+                targets_file: None,
+                exclude_addresses: None,
+                wildcard_dns: None,
+                udp: Some(false),
+                udp_payloads: None,
+                rate: None,
+                max_per_host: None,
+                read_timeout: None,
+                protocols: None,
+                no_banner: None,
+                linger_zero: None,
+                tcp_nodelay: None,
+                send_buffer_size: None,
+                recv_buffer_size: None,
+                tos: None,
+                source_ip: None,
+                source_port: None,
+                decoys: None,
+                interface: None,
+                fast_open: None,
+                mptcp: None,
+                ecn: None,
+                tarpits: None,
+                backoff: None,
+                no_adaptive_rate: None,
+                no_conntrack_throttle: None,
+                drop_privileges: None,
+                sandbox_scripts: None,
+                script_concurrency: None,
+                stats: None,
+                progress: None,
+                sweep: None,
+                verify: None,
+                probe_services: None,
+                tls_info: None,
+                skip_dead_hosts: None,
+                all_open_sample: None,
+                group_by: None,
+                greppable_format: None,
+                output: None,
+                output_file: None,
+                allow_port_zero: None,
+                numeric: None,
+                policy: None,
+                tag_command: None,
+                profile: None,
+            }
+        }
+    }
+
+    #[test]
+    fn verify_cli() {
+        Opts::command().debug_assert();
+    }
+
+    #[parameterized(input = {
+        vec!["rustscan", "--addresses", "127.0.0.1"],
+        vec!["rustscan", "--addresses", "127.0.0.1", "--", "-sCV"],
+        vec!["rustscan", "--addresses", "127.0.0.1", "--", "-A"],
+        vec!["rustscan", "-t", "1500", "-a", "127.0.0.1", "--", "-A", "-sC"],
+        vec!["rustscan", "--addresses", "127.0.0.1", "--", "--script", r#""'(safe and vuln)'""#],
+    }, command = {
+        vec![],
+        vec!["-sCV".to_owned()],
+        vec!["-A".to_owned()],
+        vec!["-A".to_owned(), "-sC".to_owned()],
+        vec!["--script".to_owned(), "\"'(safe and vuln)'\"".to_owned()],
+    })]
+    fn parse_trailing_command(input: Vec<&str>, command: Vec<String>) {
+        let opts = Opts::parse_from(input);
+
+        assert_eq!(vec!["127.0.0.1".to_owned()], opts.addresses);
+        assert_eq!(command, opts.command);
+    }
+
+    #[test]
+    fn parse_exclude_ports_by_number_range_and_name() {
+        let opts = Opts::parse_from(["rustscan", "--exclude-ports", "80,1000-1002,SSH"]);
+        let specs = opts.exclude_ports.unwrap();
+
+        assert_eq!(PortSpec::expand(&specs), Ok(vec![80, 1000, 1001, 1002, 22]));
+        assert!(Opts::try_parse_from(["rustscan", "--exclude-ports", "nope"]).is_err());
+        assert!(Opts::try_parse_from(["rustscan", "--exclude-ports", "20-10"]).is_err());
+    }
+
+    #[test]
+    fn port_groups_expand_to_their_ports() {
+        let opts = Opts::read_from_with_env(["rustscan", "--port-group", "printer,voip"], None);
+        assert_eq!(
+            opts.port_group,
+            Some(vec!["printer".to_owned(), "voip".to_owned()])
+        );
+        assert_eq!(
+            opts.ports,
+            Some(vec![9100, 631, 515, 5060, 5061, 1720, 1719, 2000])
+        );
+        assert_eq!(opts.range, None);
+    }
+
+    #[test]
+    fn top_ports_with_and_without_count() {
+        let opts = Opts::read_from_with_env(["rustscan", "--top-ports", "100"], None);
+        assert_eq!(opts.ports.map(|ports| ports.len()), Some(100));
+        assert_eq!(opts.range, None);
+
+        let opts = Opts::read_from_with_env(["rustscan", "--top"], None);
+        assert_eq!(opts.top_ports, Some(1000));
+
+        let mut opts = Opts::read_from_with_env(["rustscan", "--top-ports", "2"], None);
+        let mut config = Config::default();
+        config.ports = Some(vec![8080, 22, 443]);
+        opts.merge_optional(&config);
+        assert_eq!(opts.ports, Some(vec![8080, 22]));
+
+        assert!(Opts::try_parse_from(["rustscan", "--top-ports", "10", "-p", "22"]).is_err());
+        assert!(Opts::try_parse_from(["rustscan", "--top-ports", "500"]).is_err());
+    }
+
+    #[test]
+    fn excluded_ports_from_file() {
+        let opts = Opts {
+            exclude_ports: Some(vec![PortSpec::Port(8080)]),
+            exclude_ports_file: Some("fixtures/exclude_ports.txt".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            opts.excluded_ports(),
+            Ok(vec![8080, 23, 502, 3389, 9100, 9101])
+        );
+    }
+
+    #[test]
+    fn exclude_file_adds_addresses_and_ports() {
+        let mut opts = Opts {
+            exclude_addresses: Some(vec!["192.168.0.1".to_owned()]),
+            exclude_file: Some("fixtures/exclude.txt".into()),
+            ..Default::default()
+        };
+        opts.read_exclude_file().unwrap();
+
+        assert_eq!(
+            opts.exclude_addresses,
+            Some(vec![
+                "192.168.0.1".to_owned(),
+                "10.0.0.0/8".to_owned(),
+                "printer.example.com".to_owned(),
+                "fe80::1".to_owned(),
+            ])
+        );
+        assert_eq!(opts.excluded_ports(), Ok(vec![9100, 8000, 8001, 23]));
+        assert!(ExcludeFile::parse("10.0.0.1\nport:nope")
+            .unwrap_err()
+            .starts_with("2: "));
+    }
+
+    #[test]
+    fn targets_file_adds_targets_with_their_ports() {
+        let mut opts = Opts {
+            addresses: vec!["10.0.0.5".to_owned()],
+            targets_file: Some("fixtures/targets.txt".into()),
+            ..Default::default()
+        };
+        opts.read_targets_file().unwrap();
+
+        assert_eq!(
+            opts.addresses,
+            vec!["10.0.0.5", "web.example.com", "2001:db8::/120"]
+        );
+        assert_eq!(opts.target_ports["10.0.0.5"], vec![22, 80, 443]);
+        assert_eq!(opts.target_ports["web.example.com"], vec![8000, 8001, 22]);
+        assert_eq!(opts.target_ports["2001:db8::/120"], vec![3389]);
+
+        assert!(TargetsFile::parse(
+            "10.0.0.1:22
+2001:db8::1:22"
+        )
+        .unwrap_err()
+        .starts_with("2: "));
+        assert!(TargetsFile::parse("10.0.0.1").is_err());
+        assert!(TargetsFile::parse("10.0.0.1:").is_err());
+    }
+
+    #[test]
+    fn resume_files_round_trip() {
+        let mut unscanned = BTreeMap::new();
+        unscanned.insert("10.0.0.1".parse().unwrap(), vec![22, 80, 81, 82, 443]);
+        unscanned.insert("2001:db8::1".parse().unwrap(), vec![65535]);
+        let file = TargetsFile::from_ports(&unscanned);
+
+        assert_eq!(
+            file.to_string(),
+            "10.0.0.1:22,80-82,443\n[2001:db8::1]:65535\n"
+        );
+        assert_eq!(TargetsFile::parse(&file.to_string()).unwrap(), file);
+    }
+
+    #[test]
+    fn previous_results_add_hosts_and_ports() {
+        let mut opts = Opts::read_from_with_env(
+            [
+                "rustscan",
+                "-a",
+                "127.0.0.1",
+                "--from-nmap",
+                "fixtures/import/nmap.xml",
+                "--rescan-ports",
+            ],
+            None,
+        );
+        opts.read_previous_results().unwrap();
+        assert_eq!(opts.addresses, vec!["127.0.0.1", "10.0.0.1", "10.0.0.2"]);
+        assert_eq!(opts.ports, None);
+        assert!(opts.range.is_some());
+        assert_eq!(opts.target_ports.get("127.0.0.1"), None);
+        assert_eq!(opts.target_ports["10.0.0.1"], vec![22, 80]);
+        assert_eq!(opts.target_ports["10.0.0.2"], vec![443]);
+
+        assert!(Opts::try_parse_from(["rustscan", "--rescan-ports"]).is_err());
+
+        let mut opts = Opts::read_from_with_env(
+            [
+                "rustscan",
+                "--from-zmap",
+                "fixtures/import/zmap.csv",
+                "--from-nmap",
+                "fixtures/import/nmap.xml",
+                "--rescan-ports",
+            ],
+            None,
+        );
+        opts.read_previous_results().unwrap();
+        assert_eq!(opts.addresses, vec!["10.0.0.1", "10.0.0.2", "10.0.0.3"]);
+        assert_eq!(opts.target_ports["10.0.0.1"], vec![22, 80]);
+        assert_eq!(opts.target_ports["10.0.0.3"], vec![22]);
+    }
+
+    #[test]
+    fn ports_file_with_groups_and_includes() {
+        let mut opts = Opts::parse_from(["rustscan", "--ports-file", "fixtures/ports/scope.ports"]);
+        assert_eq!(opts.range, None);
+
+        opts.read_ports_file().unwrap();
+        assert_eq!(
+            opts.ports,
+            Some(vec![22, 80, 443, 8000, 8001, 8002, 3306, 5432, 6379, 11211])
+        );
+
+        let file = PortFile::read(Path::new("fixtures/ports/scope.ports")).unwrap();
+        assert_eq!(file.groups(), ["cache", "database", "web"]);
+        assert_eq!(
+            PortSpec::expand(&file.group("database").unwrap()),
+            Ok(vec![3306, 5432, 6379, 11211])
+        );
+        assert!(file.group("mail").is_err());
+    }
+
+    #[test]
+    fn ports_file_rejects_cycles() {
+        let error = PortFile::read(Path::new("fixtures/ports/cycle.ports")).unwrap_err();
+        assert!(error.contains("includes itself"), "{}", error);
+        assert!(
+            Opts::try_parse_from(["rustscan", "-p", "80", "--ports-file", "scope.ports"]).is_err()
+        );
+    }
+
+    #[test]
+    fn detects_port_zero() {
+        let mut opts = Opts::parse_from(["rustscan", "-p", "0,80"]);
+        assert!(opts.includes_port_zero());
+
+        opts.ports = None;
+        opts.range = Some(PortRange { start: 0, end: 10 });
+        assert!(opts.includes_port_zero());
+
+        opts.range = Some(PortRange { start: 1, end: 10 });
+        assert!(!opts.includes_port_zero());
+    }
+
+    #[test]
+    fn parse_tos_in_decimal_and_hex() {
+        let opts = Opts::parse_from(["rustscan", "--tos", "0x28"]);
+        assert_eq!(opts.tos, Some(40));
+
+        let opts = Opts::parse_from(["rustscan", "--tos", "184"]);
+        assert_eq!(opts.tos, Some(0xb8));
+
+        assert!(Opts::try_parse_from(["rustscan", "--tos", "256"]).is_err());
+    }
+
+    #[test]
+    fn parse_bench_subcommand() {
+        let opts = Opts::parse_from(["rustscan", "bench", "--compare", "old.json"]);
+
+        assert_eq!(
+            opts.subcommand,
+            Some(Commands::Bench {
+                compare: Some("old.json".into()),
+                save: None,
+                threshold: 5.0,
+            })
+        );
+    }
+
+    #[test]
+    fn splits_env_opts_like_a_shell() {
+        assert_eq!(
+            split_args("  -b 500\t--ulimit 5000 "),
+            Ok(vec![
+                "-b".to_owned(),
+                "500".to_owned(),
+                "--ulimit".to_owned(),
+                "5000".to_owned()
+            ])
+        );
+        assert_eq!(
+            split_args(r#"-- 'a b' "c \"d\"" e\ f ''"#),
+            Ok(vec![
+                "--".to_owned(),
+                "a b".to_owned(),
+                "c \"d\"".to_owned(),
+                "e f".to_owned(),
+                String::new()
+            ])
+        );
+        assert!(split_args("'unterminated").is_err());
+        assert!(split_args("trailing\\").is_err());
+    }
+
+    #[test]
+    fn command_line_overrides_env_opts() {
+        let opts = Opts::read_from_with_env(
+            ["rustscan", "-b", "1000", "-a", "127.0.0.1", "--", "-sV"],
+            Some("-gb 500 --ulimit=5000 -- -A"),
+        );
+        assert_eq!(opts.batch_size, 1000);
+        assert!(opts.greppable);
+        assert_eq!(opts.ulimit, Some(5000));
+        assert_eq!(opts.addresses, vec!["127.0.0.1"]);
+        assert_eq!(opts.command, vec!["-sV"]);
+
+        let opts = Opts::read_from_with_env(
+            ["rustscan", "-a", "127.0.0.1", "-b", "1000"],
+            Some("-b 500 -- --script vuln"),
+        );
+        assert_eq!(opts.batch_size, 1000);
+        assert_eq!(opts.command, vec!["--script", "vuln"]);
+
+        assert!(Opts::try_parse_from(["rustscan", "-b", "500", "-b", "1000"]).is_err());
+    }
+
+    #[test]
+    fn parse_diff_subcommand() {
+        let opts = Opts::parse_from(["rustscan", "diff", "monday.json", "tuesday.json"]);
+
+        assert_eq!(
+            opts.subcommand,
+            Some(Commands::Diff {
+                old: "monday.json".into(),
+                new: "tuesday.json".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_protocols() {
+        let opts = Opts::parse_from(["rustscan", "--protocols", "tcp,UDP"]);
+        assert_eq!(opts.protocols(), vec![Protocol::Tcp, Protocol::Udp]);
+
+        let opts = Opts::parse_from(["rustscan", "--udp"]);
+        assert_eq!(opts.protocols(), vec![Protocol::Udp]);
+        assert_eq!(Opts::default().protocols(), vec![Protocol::Tcp]);
+    }
+
+    #[test]
+    fn opts_no_merge_when_config_is_ignored() {
+        let mut opts = Opts::default();
+        let config = Config::default();
+
+        opts.merge(&config);
+
+        assert_eq!(opts.addresses, vec![] as Vec<String>);
+        assert!(opts.greppable);
+        assert!(!opts.accessible);
+        assert_eq!(opts.timeout, 0);
+        assert_eq!(opts.command, vec![] as Vec<String>);
+        assert_eq!(opts.scan_order, ScanOrder::Serial);
+    }
+
+    #[test]
+    fn opts_merge_required_arguments() {
+        let mut opts = Opts::default();
+        let config = Config::default();
+
+        opts.merge_required(&config);
+
+        assert_eq!(opts.addresses, vec!["127.0.0.1".to_owned()]);
+        assert_eq!(opts.greppable, config.greppable.unwrap());
+        assert_eq!(opts.timeout, config.timeout.unwrap());
+        assert_eq!(opts.command, config.command.unwrap());
+        assert_eq!(opts.accessible, config.accessible.unwrap());
+        assert_eq!(opts.scan_order, config.scan_order.unwrap());
+        assert_eq!(opts.scripts, ScriptsRequired::Default);
+    }
+
+    #[test]
+    fn opts_merge_optional_arguments() {
+        let mut opts = Opts::default();
+        let mut config = Config::default();
+        config.range = Some(PortRange {
+            start: 1,
+            end: 1_000,
+        });
+        config.ulimit = Some(1_000);
+        config.resolver = Some("1.1.1.1".to_owned());
+
+        opts.merge_optional(&config);
+
+        assert_eq!(opts.range, config.range);
+        assert_eq!(opts.ulimit, config.ulimit);
+        assert_eq!(opts.resolver, config.resolver);
+    }
+
+    #[test]
+    fn opts_merge_policies() {
+        let mut opts = Opts::default();
+        let config: Config = toml::from_str(
+            r#"
+            [policy."10.0.0.0/8"]
+            batch_size = 500
+            timeout = 3000
+            "#,
+        )
+        .unwrap();
+
+        opts.merge_optional(&config);
+
+        let policy = &opts.policies["10.0.0.0/8"];
+        assert_eq!(policy.batch_size, Some(500));
+        assert_eq!(policy.timeout, Some(3000));
+        assert_eq!(policy.udp, None);
+    }
+
+    #[test]
+    fn opts_merge_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            batch_size = 4500
+            timeout = 1500
+
+            [profile.stealth]
+            batch_size = 100
+            scan_order = "Random"
+            rate = 50
+            "#,
+        )
+        .unwrap();
+        let mut opts = Opts {
+            no_config: false,
+            profile: Some("stealth".to_owned()),
+            ..Opts::default()
+        };
+
+        opts.merge(&config);
+        opts.merge_profile(&config).unwrap();
+
+        assert_eq!(opts.batch_size, 100);
+        assert_eq!(opts.timeout, 1500);
+        assert_eq!(opts.scan_order, ScanOrder::Random);
+        assert_eq!(opts.rate.map(NonZeroU32::get), Some(50));
+
+        opts.profile = Some("fast".to_owned());
+        assert_eq!(
+            opts.merge_profile(&config),
+            Err("no profile named fast, try one of stealth".to_owned())
+        );
+    }
+
+    #[test]
+    fn opts_merge_tagged_addresses() {
+        let mut opts = Opts::default();
+        let config: Config = toml::from_str(
+            r#"addresses = ["127.0.0.1", { host = "10.0.0.5", tags = ["prod", "db"] }]"#,
+        )
+        .unwrap();
+
+        opts.merge_required(&config);
+
+        assert_eq!(opts.addresses, vec!["127.0.0.1", "10.0.0.5 # prod db"]);
+    }
+
+    #[test]
+    fn command_for_tags() {
+        let mut opts = Opts {
+            command: vec!["-sV".to_owned()],
+            ..Opts::default()
+        };
+        let config: Config = toml::from_str(
+            r#"
+            [tag_command]
+            windows = ["--script", "smb*"]
+            db = ["-p-"]
+            "#,
+        )
+        .unwrap();
+
+        opts.merge_optional(&config);
+
+        assert_eq!(
+            opts.command_for(&["windows".to_owned(), "prod".to_owned()]),
+            vec!["-sV", "--script", "smb*"]
+        );
+        assert_eq!(opts.command_for(&[]), vec!["-sV"]);
+    }
+
+    #[test]
+    fn parse_outputs() {
+        assert_eq!(
+            "json:results.jsonl".parse::<OutputSpec>(),
+            Ok(OutputSpec::Json("results.jsonl".into()))
+        );
+        assert_eq!(
+            "webhook:http://hooks.local/rustscan".parse::<OutputSpec>(),
+            Ok(OutputSpec::Webhook(
+                "http://hooks.local/rustscan".to_owned()
+            ))
+        );
+        assert_eq!("json".parse::<OutputSpec>(), Ok(OutputSpec::Report(None)));
+        assert_eq!(
+            "report:scan.json".parse::<OutputSpec>(),
+            Ok(OutputSpec::Report(Some("scan.json".into())))
+        );
+        assert!("json:".parse::<OutputSpec>().is_err());
+        assert_eq!(
+            "xml:results.xml".parse::<OutputSpec>(),
+            Ok(OutputSpec::NmapXml(Some("results.xml".into())))
+        );
+        assert_eq!("xml".parse::<OutputSpec>(), Ok(OutputSpec::NmapXml(None)));
+        assert_eq!(
+            "csv:scan.csv".parse::<OutputSpec>(),
+            Ok(OutputSpec::Csv(Some("scan.csv".into())))
+        );
+        assert_eq!("csv".parse::<OutputSpec>(), Ok(OutputSpec::Csv(None)));
+        assert!("yaml:results.yaml".parse::<OutputSpec>().is_err());
+    }
+}
@@ -0,0 +1,81 @@
+//! Named groups of common ports, for `--port-group`.
+//!
+//! The groups are read from the `port-groups` file by `build.rs`, e.g.
+//! `web` for HTTP servers and proxies or `database` for SQL and NoSQL
+//! servers, their ports most common first.
+use crate::generated::get_port_groups;
+
+/// The ports of the group called `name`, ignoring case.
+///
+/// ```rust
+/// # use rustscan::input::port_group;
+/// assert_eq!(port_group("printer"), Some(&[9100, 631, 515][..]));
+/// assert_eq!(port_group("Web").map(|ports| ports[0]), Some(80));
+/// assert_eq!(port_group("games"), None);
+/// ```
+pub fn port_group(name: &str) -> Option<&'static [u16]> {
+    get_port_groups()
+        .iter()
+        .find(|(group, _)| group.eq_ignore_ascii_case(name))
+        .map(|(_, ports)| *ports)
+}
+
+/// The names of every group, in the order of the `port-groups` file.
+pub fn port_group_names() -> Vec<&'static str> {
+    get_port_groups().iter().map(|(name, _)| *name).collect()
+}
+
+/// Parses the name of a group for `--port-group`, listing the groups when
+/// there is none by that name.
+pub fn parse_port_group(name: &str) -> Result<String, String> {
+    match port_group(name) {
+        Some(_) => Ok(name.to_lowercase()),
+        None => Err(format!(
+            "no port group named {name}, try one of {}",
+            port_group_names().join(", ")
+        )),
+    }
+}
+
+/// The ports of every group of `names`, in order, each port once.
+pub fn port_group_ports(names: &[String]) -> Vec<u16> {
+    let mut ports: Vec<u16> = Vec::new();
+    for port in names.iter().filter_map(|name| port_group(name)).flatten() {
+        if !ports.contains(port) {
+            ports.push(*port);
+        }
+    }
+    ports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_port_group, port_group, port_group_names, port_group_ports};
+    use std::collections::HashSet;
+
+    #[test]
+    fn groups_have_unique_ports() {
+        for name in port_group_names() {
+            let ports = port_group(name).unwrap();
+            assert!(!ports.is_empty(), "{name} has no ports");
+            let unique: HashSet<&u16> = ports.iter().collect();
+            assert_eq!(unique.len(), ports.len(), "{name} repeats a port");
+        }
+    }
+
+    #[test]
+    fn merges_groups_in_order() {
+        let ports = port_group_ports(&["printer".to_owned(), "voip".to_owned()]);
+        assert_eq!(ports, vec![9100, 631, 515, 5060, 5061, 1720, 1719, 2000]);
+
+        let ports = port_group_ports(&["web".to_owned(), "web".to_owned()]);
+        assert_eq!(ports, port_group("web").unwrap());
+    }
+
+    #[test]
+    fn rejects_unknown_groups() {
+        assert_eq!(parse_port_group("DataBase"), Ok("database".to_owned()));
+        let error = parse_port_group("games").unwrap_err();
+        assert!(error.starts_with("no port group named games, try one of web, database"));
+    }
+}
@@ -0,0 +1,155 @@
+use super::network_key;
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+/// Counts are halved once a network has seen this many probes, so old
+/// scans weigh less than recent ones.
+const MAX_PROBES: u64 = 100_000;
+
+/// Networks with fewer probes than this say too little to lower the batch
+/// size on.
+const MIN_PROBES: u64 = 100;
+
+/// Error rates below this are noise and leave the batch size alone.
+const TOLERATED_RATE: f64 = 0.01;
+
+/// The batch size picked from the error rates is never lower than this.
+const MIN_BATCH_SIZE: usize = 100;
+
+/// How many probes of a single network failed on the way, e.g. with an
+/// unreachable host or an exhausted connection table of a firewall.
+/// Closed ports and timeouts are answers, not errors, and are not counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorRate {
+    pub probes: u64,
+    pub errors: u64,
+}
+
+impl ErrorRate {
+    pub fn record(&mut self, failed: bool) {
+        self.probes += 1;
+        if failed {
+            self.errors += 1;
+        }
+    }
+
+    /// The share of probes that failed, between 0 and 1.
+    pub fn rate(&self) -> f64 {
+        if self.probes == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.probes as f64
+        }
+    }
+
+    fn decay(&mut self) {
+        while self.probes > MAX_PROBES {
+            self.probes /= 2;
+            self.errors /= 2;
+        }
+    }
+}
+
+/// Error rates keyed by [`network_key`].
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ErrorRates(BTreeMap<String, ErrorRate>);
+
+impl ErrorRates {
+    pub fn record(&mut self, ip: &IpAddr, failed: bool) {
+        self.0.entry(network_key(ip)).or_default().record(failed);
+    }
+
+    pub fn get(&self, ip: &IpAddr) -> Option<&ErrorRate> {
+        self.0.get(&network_key(ip))
+    }
+
+    /// Adds the counts of another set of error rates, typically the ones
+    /// observed during the scan that just finished.
+    pub fn merge(&mut self, other: ErrorRates) {
+        for (network, rate) in other.0 {
+            let entry = self.0.entry(network).or_default();
+            entry.probes += rate.probes;
+            entry.errors += rate.errors;
+            entry.decay();
+        }
+    }
+
+    /// Lowers `batch_size` for a scan of `ips` by the share of probes that
+    /// failed on the worst known network.
+    ///
+    /// Returns `None` when none of the targets' networks have seen enough
+    /// probes, or their error rates are too low to matter.
+    pub fn suggested_batch_size(&self, ips: &[IpAddr], batch_size: usize) -> Option<usize> {
+        let worst = ips
+            .iter()
+            .filter_map(|ip| self.get(ip))
+            .filter(|rate| rate.probes >= MIN_PROBES)
+            .map(ErrorRate::rate)
+            .fold(0.0, f64::max);
+        if worst < TOLERATED_RATE {
+            return None;
+        }
+
+        let lowered = ((batch_size as f64 * (1.0 - worst)) as usize).max(MIN_BATCH_SIZE);
+        (lowered < batch_size).then_some(lowered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorRates, MAX_PROBES, MIN_BATCH_SIZE};
+    use std::net::IpAddr;
+
+    fn observed(ip: &IpAddr, probes: u64, errors: u64) -> ErrorRates {
+        let mut rates = ErrorRates::default();
+        for n in 0..probes {
+            rates.record(ip, n < errors);
+        }
+        rates
+    }
+
+    #[test]
+    fn suggested_batch_size_follows_worst_network() {
+        let clean: IpAddr = "10.0.0.1".parse().unwrap();
+        let lossy: IpAddr = "10.0.1.1".parse().unwrap();
+        let unknown: IpAddr = "10.0.2.1".parse().unwrap();
+        let mut rates = observed(&clean, 1000, 0);
+        rates.merge(observed(&lossy, 1000, 250));
+
+        assert_eq!(rates.suggested_batch_size(&[clean], 4000), None);
+        assert_eq!(
+            rates.suggested_batch_size(&[clean, lossy, unknown], 4000),
+            Some(3000)
+        );
+        assert_eq!(
+            rates.suggested_batch_size(&[lossy], 120),
+            Some(MIN_BATCH_SIZE)
+        );
+        assert_eq!(rates.suggested_batch_size(&[lossy], 50), None);
+        assert_eq!(rates.suggested_batch_size(&[unknown], 4000), None);
+    }
+
+    #[test]
+    fn ignores_networks_with_few_probes() {
+        let ip: IpAddr = "192.168.0.1".parse().unwrap();
+        let rates = observed(&ip, 10, 10);
+
+        assert_eq!(rates.suggested_batch_size(&[ip], 4000), None);
+    }
+
+    #[test]
+    fn merge_decays_old_counts() {
+        let ip: IpAddr = "192.168.0.1".parse().unwrap();
+        let mut stored = observed(&ip, MAX_PROBES, MAX_PROBES);
+
+        stored.merge(observed(&ip, 2, 0));
+
+        let rate = stored.get(&ip).unwrap();
+        assert!(rate.probes <= MAX_PROBES);
+        assert_eq!(rate.probes, (MAX_PROBES + 2) / 2);
+        assert_eq!(rate.errors, MAX_PROBES / 2);
+    }
+}
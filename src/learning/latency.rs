@@ -0,0 +1,178 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// How many RTT samples are kept per network. Older samples are dropped
+/// first so the profile follows changes in the network over time.
+const MAX_SAMPLES: usize = 512;
+
+/// The timeout picked from a profile is never lower than this, since very
+/// fast networks would otherwise get timeouts too tight to absorb jitter.
+const MIN_TIMEOUT_MS: u64 = 100;
+
+/// Multiplier applied to the 95th percentile RTT of a network.
+const TIMEOUT_MULTIPLIER: u64 = 3;
+
+/// Returns the network an address is grouped under: its /24 for IPv4 and
+/// its /48 for IPv6.
+///
+/// ```rust
+/// # use rustscan::learning::network_key;
+/// assert_eq!(network_key(&"192.168.1.20".parse().unwrap()), "192.168.1.0/24");
+/// assert_eq!(network_key(&"2001:db8:1:2::1".parse().unwrap()), "2001:db8:1::/48");
+/// ```
+pub fn network_key(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{a}.{b}.{c}.0/24")
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            let network =
+                std::net::Ipv6Addr::new(segments[0], segments[1], segments[2], 0, 0, 0, 0, 0);
+            format!("{network}/48")
+        }
+    }
+}
+
+/// Observed round trip times, in milliseconds, for a single network.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencyProfile {
+    samples: Vec<u32>,
+}
+
+impl LatencyProfile {
+    pub fn record(&mut self, rtt: Duration) {
+        let millis = u32::try_from(rtt.as_millis()).unwrap_or(u32::MAX);
+        self.samples.push(millis);
+
+        // Trimming in chunks avoids shifting the vector on every sample.
+        if self.samples.len() >= MAX_SAMPLES * 2 {
+            self.trim();
+        }
+    }
+
+    fn trim(&mut self) {
+        if self.samples.len() > MAX_SAMPLES {
+            let excess = self.samples.len() - MAX_SAMPLES;
+            self.samples.drain(..excess);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns the RTT below which `percentile` percent of the samples fall.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+
+        let index = ((sorted.len() - 1) as f64 * percentile / 100.0).round() as usize;
+        Some(Duration::from_millis(
+            sorted[index.min(sorted.len() - 1)].into(),
+        ))
+    }
+
+    /// A timeout that comfortably covers the RTTs seen on this network.
+    pub fn suggested_timeout(&self) -> Option<Duration> {
+        let p95 = u64::try_from(self.percentile(95.0)?.as_millis()).unwrap_or(u64::MAX);
+        Some(Duration::from_millis(
+            p95.saturating_mul(TIMEOUT_MULTIPLIER).max(MIN_TIMEOUT_MS),
+        ))
+    }
+}
+
+/// Latency profiles keyed by [`network_key`].
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LatencyProfiles(BTreeMap<String, LatencyProfile>);
+
+impl LatencyProfiles {
+    pub fn record(&mut self, ip: &IpAddr, rtt: Duration) {
+        self.0.entry(network_key(ip)).or_default().record(rtt);
+    }
+
+    pub fn get(&self, ip: &IpAddr) -> Option<&LatencyProfile> {
+        self.0.get(&network_key(ip))
+    }
+
+    /// Adds the samples of another set of profiles, typically the ones
+    /// observed during the scan that just finished.
+    pub fn merge(&mut self, other: LatencyProfiles) {
+        for (network, profile) in other.0 {
+            let entry = self.0.entry(network).or_default();
+            entry.samples.extend(profile.samples);
+            entry.trim();
+        }
+    }
+
+    /// Picks a timeout for a scan of `ips` from the known profiles.
+    ///
+    /// The slowest known network wins so no target gets a timeout that is
+    /// too tight. Returns `None` when none of the targets' networks have
+    /// been seen before.
+    pub fn suggested_timeout(&self, ips: &[IpAddr]) -> Option<Duration> {
+        ips.iter()
+            .filter_map(|ip| self.get(ip))
+            .filter_map(LatencyProfile::suggested_timeout)
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LatencyProfiles, MAX_SAMPLES};
+    use std::net::IpAddr;
+    use std::time::Duration;
+
+    #[test]
+    fn suggested_timeout_uses_slowest_network() {
+        let fast: IpAddr = "10.0.0.1".parse().unwrap();
+        let slow: IpAddr = "10.0.1.1".parse().unwrap();
+        let unknown: IpAddr = "10.0.2.1".parse().unwrap();
+        let mut profiles = LatencyProfiles::default();
+        for _ in 0..10 {
+            profiles.record(&fast, Duration::from_millis(10));
+            profiles.record(&slow, Duration::from_millis(200));
+        }
+
+        assert_eq!(
+            profiles.suggested_timeout(&[fast]),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            profiles.suggested_timeout(&[fast, slow, unknown]),
+            Some(Duration::from_millis(600))
+        );
+        assert_eq!(profiles.suggested_timeout(&[unknown]), None);
+    }
+
+    #[test]
+    fn merge_keeps_samples_bounded() {
+        let ip: IpAddr = "192.168.0.1".parse().unwrap();
+        let mut stored = LatencyProfiles::default();
+        let mut observed = LatencyProfiles::default();
+        for _ in 0..MAX_SAMPLES {
+            stored.record(&ip, Duration::from_millis(1));
+            observed.record(&ip, Duration::from_millis(2));
+        }
+
+        stored.merge(observed);
+
+        let profile = stored.get(&ip).unwrap();
+        assert_eq!(profile.len(), MAX_SAMPLES);
+        assert_eq!(profile.percentile(0.0), Some(Duration::from_millis(2)));
+    }
+}
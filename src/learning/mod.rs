@@ -0,0 +1,64 @@
+//! Adaptive learning: things RustScan remembers between scans.
+//!
+//! Observations made during a scan are stored in a small TOML file,
+//! `~/.rustscan/learning.toml`, and used to pick better defaults the next
+//! time the same networks are scanned.
+//!
+//! Right now this covers per-network latency profiles, which are used to
+//! select an initial timeout with `--adaptive-timeout`, error rates, which
+//! lower the batch size with `--adaptive-batch`, and the ports found open
+//! on each network, which `--scan-order adaptive` scans first.
+use anyhow::{anyhow, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+mod latency;
+pub use latency::{network_key, LatencyProfile, LatencyProfiles};
+
+mod errors;
+pub use errors::{ErrorRate, ErrorRates};
+
+mod ports;
+pub use ports::{NetworkPorts, PortCount, PortHistory};
+
+/// Everything RustScan has learned from previous scans.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LearningStore {
+    #[serde(default)]
+    pub latency: LatencyProfiles,
+    #[serde(default)]
+    pub errors: ErrorRates,
+    #[serde(default)]
+    pub ports: PortHistory,
+}
+
+impl LearningStore {
+    /// Default location of the learning store, `~/.rustscan/learning.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let mut path = dirs::home_dir()?;
+        path.push(".rustscan");
+        path.push("learning.toml");
+        Some(path)
+    }
+
+    /// Reads the store from disk. A missing or unreadable store means we
+    /// have not learned anything yet, so an empty store is returned.
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the store to disk, creating its parent directory if needed.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string(self)
+            .map_err(|e| anyhow!("Could not serialize learning store: {e}"))?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
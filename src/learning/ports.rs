@@ -0,0 +1,143 @@
+use super::network_key;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+
+/// How often a port was found open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortCount {
+    pub port: u16,
+    pub count: u32,
+}
+
+/// Ports found open on a single network.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkPorts {
+    #[serde(default)]
+    open: Vec<PortCount>,
+}
+
+impl NetworkPorts {
+    fn add(&mut self, port: u16, count: u32) {
+        match self.open.iter_mut().find(|known| known.port == port) {
+            Some(known) => known.count = known.count.saturating_add(count),
+            None => self.open.push(PortCount { port, count }),
+        }
+    }
+
+    fn count(&self, port: u16) -> u32 {
+        self.open
+            .iter()
+            .find(|known| known.port == port)
+            .map_or(0, |known| known.count)
+    }
+}
+
+/// Open ports seen in past scans, keyed by [`network_key`].
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PortHistory(BTreeMap<String, NetworkPorts>);
+
+impl PortHistory {
+    /// Remembers that `port` was open on `ip`.
+    pub fn record(&mut self, ip: &IpAddr, port: u16) {
+        self.0.entry(network_key(ip)).or_default().add(port, 1);
+    }
+
+    /// Adds the open ports of another history, typically the one built
+    /// from the scan that just finished.
+    pub fn merge(&mut self, other: PortHistory) {
+        for (network, ports) in other.0 {
+            let entry = self.0.entry(network).or_default();
+            for known in ports.open {
+                entry.add(known.port, known.count);
+            }
+        }
+    }
+
+    /// Orders `ports` so the ones most often open in past scans come
+    /// first.
+    ///
+    /// Ports open on the networks of `ips` rank first, then ports open on
+    /// any known network, which covers targets RustScan hasn't scanned
+    /// before. Ports that were never seen open keep their relative order.
+    pub fn rank(&self, ips: &[IpAddr], mut ports: Vec<u16>) -> Vec<u16> {
+        let mut networks: Vec<String> = ips.iter().map(network_key).collect();
+        networks.sort_unstable();
+        networks.dedup();
+
+        let mut nearby: HashMap<u16, u32> = HashMap::new();
+        let mut anywhere: HashMap<u16, u32> = HashMap::new();
+        for (network, known) in &self.0 {
+            let is_nearby = networks.binary_search(network).is_ok();
+            for port in &known.open {
+                *anywhere.entry(port.port).or_default() += port.count;
+                if is_nearby {
+                    *nearby.entry(port.port).or_default() += port.count;
+                }
+            }
+        }
+
+        ports.sort_by_key(|port| {
+            std::cmp::Reverse((
+                nearby.get(port).copied().unwrap_or_default(),
+                anywhere.get(port).copied().unwrap_or_default(),
+            ))
+        });
+        ports
+    }
+
+    /// How often `port` was open on the network of `ip`.
+    pub fn count(&self, ip: &IpAddr, port: u16) -> u32 {
+        self.0
+            .get(&network_key(ip))
+            .map_or(0, |known| known.count(port))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PortHistory;
+    use std::net::IpAddr;
+
+    #[test]
+    fn ranks_nearby_ports_first() {
+        let target: IpAddr = "10.0.0.5".parse().unwrap();
+        let elsewhere: IpAddr = "172.16.0.1".parse().unwrap();
+        let mut history = PortHistory::default();
+        history.record(&"10.0.0.1".parse().unwrap(), 8080);
+        for _ in 0..5 {
+            history.record(&elsewhere, 22);
+        }
+
+        assert_eq!(
+            history.rank(&[target], vec![21, 22, 80, 8080]),
+            vec![8080, 22, 21, 80]
+        );
+    }
+
+    #[test]
+    fn merge_adds_counts() {
+        let ip: IpAddr = "192.168.1.1".parse().unwrap();
+        let mut stored = PortHistory::default();
+        let mut observed = PortHistory::default();
+        stored.record(&ip, 443);
+        observed.record(&ip, 443);
+        observed.record(&ip, 80);
+
+        stored.merge(observed);
+
+        assert_eq!(stored.count(&ip, 443), 2);
+        assert_eq!(stored.count(&ip, 80), 1);
+        assert_eq!(stored.count(&ip, 22), 0);
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut history = PortHistory::default();
+        history.record(&"192.168.1.1".parse().unwrap(), 443);
+
+        let content = toml::to_string(&history).unwrap();
+        assert_eq!(toml::from_str::<PortHistory>(&content).unwrap(), history);
+    }
+}
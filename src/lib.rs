@@ -36,9 +36,26 @@
 //!
 //!     let scan_result = block_on(scanner.run());
 //!
-//!     println!("{:?}", scan_result);
+//!     println!("{:?}", scan_result.open());
 //! }
 //! ```
+//!
+//! ## Features
+//!
+//! - `cli`, on by default: argument parsing with clap, the interactive
+//!   [`shell`] and [`wizard`], and everything else the `rustscan` binary
+//!   needs. Without it [`Opts`](crate::input::Opts) is a plain struct to
+//!   fill in.
+//! - `scripts`, on with `cli`: running [`scripts`], nmap by default,
+//!   against the open ports.
+//! - `tui`, on with `cli`: printing open ports and the progress bar to the
+//!   terminal while scanning, see [`tui`].
+//! - `nmap`, on by default: [`nmap::run_nmap`], running nmap and reading
+//!   back what it found.
+//! - `banner`, on by default: the logo and quote the binary opens with.
+//!
+//! Embedding only the scanner, address parsing and port strategies takes
+//! `rustscan = { version = "2", default-features = false }`.
 #![allow(clippy::needless_doctest_main)]
 
 pub mod tui;
@@ -49,10 +66,44 @@ pub mod scanner;
 
 pub mod port_strategy;
 
+pub mod policy;
+
 pub mod benchmark;
 
+pub mod learning;
+
+#[cfg(feature = "scripts")]
 pub mod scripts;
 
+#[cfg(feature = "cli")]
+pub mod shell;
+
+#[cfg(feature = "cli")]
+pub mod wizard;
+
+pub mod sinks;
+
+pub mod output;
+
+#[cfg(unix)]
+pub mod control;
+
 pub mod address;
 
+pub mod discovery;
+
+pub mod import;
+
+pub mod services;
+
+pub mod nmap;
+
+pub mod probes;
+
+pub mod system;
+
+pub mod capabilities;
+
+pub mod privsep;
+
 pub mod generated;
@@ -2,31 +2,62 @@
 #![warn(clippy::pedantic)]
 #![allow(clippy::doc_markdown, clippy::if_not_else, clippy::non_ascii_literal)]
 
-use rustscan::benchmark::{Benchmark, NamedTimer};
-use rustscan::input::{self, Config, Opts, ScriptsRequired};
+use rustscan::benchmark::{self, Benchmark, BenchmarkResults, NamedTimer};
+use rustscan::capabilities::{has_cap_net_raw, Capabilities};
+#[cfg(unix)]
+use rustscan::control::ControlServer;
+use rustscan::discovery::{self, Discovery, HardwareAddress};
+#[cfg(feature = "banner")]
+use rustscan::funny_opening;
+use rustscan::input::{
+    self, Commands, Config, GreppableFormat, GroupBy, Opts, OutputSpec, ScanOrder, ScriptsRequired,
+    TargetsFile, TarpitDetection, RESUME_FILE,
+};
+use rustscan::learning::LearningStore;
+use rustscan::nmap;
+use rustscan::output::{csv, diff::ReportDiff, nmap_grep, nmap_xml, Protocol, ScanReport};
 use rustscan::port_strategy::PortStrategy;
-use rustscan::scanner::Scanner;
-use rustscan::scripts::{init_scripts, Script, ScriptFile};
-use rustscan::{detail, funny_opening, output, warning};
+use rustscan::privsep;
+use rustscan::probes::{ServiceInfo, ServiceProber};
+use rustscan::scanner::{
+    report_progress, CancellationToken, ProbeError, ScanProgress, Scanner, TcpExtensions,
+};
+use rustscan::scripts::{
+    check_scripts_user, init_scripts, Script, ScriptContext, ScriptFile, ScriptInput,
+};
+use rustscan::services::{common_ports, service_name};
+use rustscan::shell;
+use rustscan::system::cgroup::CgroupLimits;
+#[cfg(target_os = "macos")]
+use rustscan::system::macos::FileLimits;
+#[cfg(unix)]
+use rustscan::system::resource_limits;
+#[cfg(not(windows))]
+use rustscan::system::resource_limits::AVERAGE_BATCH_SIZE;
+#[cfg(windows)]
+use rustscan::system::windows::SocketLimits;
+use rustscan::tui::ProgressBar;
+use rustscan::wizard;
+use rustscan::{detail, output, warning};
 
+use async_std::task;
+#[cfg(feature = "banner")]
 use colorful::{Color, Colorful};
 use futures::executor::block_on;
-use std::collections::HashMap;
-use std::net::IpAddr;
+use futures::stream::{self, StreamExt};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, IsTerminal};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
 use std::string::ToString;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
-use rustscan::address::parse_addresses;
+use rustscan::address::{parse_targets, Target, Targets};
 
+#[cfg(feature = "banner")]
 extern crate colorful;
 extern crate dirs;
 
-// Average value for Ubuntu
-#[cfg(unix)]
-const DEFAULT_FILE_DESCRIPTORS_LIMIT: usize = 8000;
-// Safest batch size based on experimentation
-const AVERAGE_BATCH_SIZE: usize = 3000;
-
 #[macro_use]
 extern crate log;
 
@@ -43,11 +74,87 @@ fn main() {
     let mut rustscan_bench = NamedTimer::start("RustScan");
 
     let mut opts: Opts = Opts::read();
+    if let Some(Commands::Wizard) = &opts.subcommand {
+        match wizard::run() {
+            Ok(Some(args)) => opts = Opts::read_from(args),
+            Ok(None) => return,
+            Err(e) => {
+                warning!(format!("Wizard stopped: {e}"));
+                std::process::exit(1);
+            }
+        }
+    }
     let config = Config::read(opts.config_path.clone());
     opts.merge(&config);
+    if let Err(e) = opts.merge_profile(&config) {
+        warning!(
+            format!("Invalid profile: {e}"),
+            opts.greppable,
+            opts.accessible
+        );
+        std::process::exit(1);
+    }
+    if let Some(Commands::Diff { old, new }) = &opts.subcommand {
+        run_diff(&opts, old, new);
+        return;
+    }
+    if let Err(e) = opts.read_ports_file() {
+        warning!(
+            format!("Invalid ports file: {e}"),
+            opts.greppable,
+            opts.accessible
+        );
+        std::process::exit(1);
+    }
+    if let Err(e) = opts.read_exclude_file() {
+        warning!(
+            format!("Invalid exclude file: {e}"),
+            opts.greppable,
+            opts.accessible
+        );
+        std::process::exit(1);
+    }
+    if let Err(e) = opts.read_targets_file() {
+        warning!(
+            format!("Invalid targets file: {e}"),
+            opts.greppable,
+            opts.accessible
+        );
+        std::process::exit(1);
+    }
+    if let Err(e) = opts.read_previous_results() {
+        warning!(
+            format!("Invalid previous results: {e}"),
+            opts.greppable,
+            opts.accessible
+        );
+        std::process::exit(1);
+    }
+    if let Err(e) = opts.read_resume_file() {
+        warning!(
+            format!("Invalid resume file: {e}"),
+            opts.greppable,
+            opts.accessible
+        );
+        std::process::exit(1);
+    }
 
     debug!("Main() `opts` arguments are {opts:?}");
 
+    if let Some(Commands::Bench {
+        compare,
+        save,
+        threshold,
+    }) = &opts.subcommand
+    {
+        run_bench(&opts, compare.as_deref(), save.as_deref(), *threshold);
+        return;
+    }
+    if let Some(Commands::Shell) = &opts.subcommand {
+        shell::run(opts);
+        return;
+    }
+
     let scripts_to_run: Vec<ScriptFile> = match init_scripts(&opts.scripts) {
         Ok(scripts_to_run) => scripts_to_run,
         Err(e) => {
@@ -61,12 +168,70 @@ fn main() {
     };
 
     debug!("Scripts initialized {:?}", &scripts_to_run);
+    if let Err(e) = check_scripts_user(&scripts_to_run, opts.drop_privileges) {
+        warning!(e.to_string(), opts.greppable, opts.accessible);
+        std::process::exit(1);
+    }
+    // Binding a port below 1024 takes the privileges that are dropped
+    // before the scan starts.
+    if let Some(port) = opts.source_port.filter(|&port| port < 1024) {
+        if opts.drop_privileges {
+            warning!(
+                format!("--source-port {port} needs privileges, it can't be used with --drop-privileges."),
+                opts.greppable,
+                opts.accessible
+            );
+            std::process::exit(1);
+        }
+    }
+
+    let capabilities = Capabilities::detect();
+    debug!("Capabilities {capabilities:?}");
 
     if !opts.greppable && !opts.accessible && !opts.no_banner {
         print_opening(&opts);
+        let through = if capabilities.raw_sockets && has_cap_net_raw() {
+            " (raw sockets through CAP_NET_RAW)"
+        } else {
+            ""
+        };
+        detail!(
+            format!("Privileges available: {capabilities}{through}."),
+            opts.greppable,
+            opts.accessible
+        );
     }
 
-    let ips: Vec<IpAddr> = parse_addresses(&opts);
+    let targets = parse_targets(&opts);
+    let named_targets: HashMap<IpAddr, Target> = targets
+        .targets()
+        .into_iter()
+        .map(|target| (target.ip, target))
+        .collect();
+    let Targets {
+        ips,
+        hosts,
+        tags,
+        overlaps,
+        coverage,
+        scopes,
+        ports: target_ports,
+    } = targets;
+
+    if !overlaps.is_empty() {
+        for overlap in &overlaps {
+            debug!("Target {overlap}");
+        }
+        detail!(
+            format!(
+                "Collapsed {} targets already covered by others, e.g. {}.",
+                overlaps.len(),
+                overlaps[0]
+            ),
+            opts.greppable,
+            opts.accessible
+        );
+    }
 
     if ips.is_empty() {
         warning!(
@@ -80,27 +245,412 @@ fn main() {
     #[cfg(unix)]
     let batch_size: usize = infer_batch_size(&opts, adjust_ulimit_size(&opts));
 
-    #[cfg(not(unix))]
+    #[cfg(windows)]
+    let batch_size: usize = infer_batch_size_windows(&opts, &SocketLimits::detect());
+
+    #[cfg(not(any(unix, windows)))]
     let batch_size: usize = AVERAGE_BATCH_SIZE;
 
+    // Raising the file limit above needs the privileges dropped here. The
+    // raw sockets stay open for raw scan modes, everything after this runs
+    // as the user that ran RustScan, or sudo.
+    let mut raw_sockets = if opts.drop_privileges {
+        match privsep::separate(opts.syn_scans(), opts.listens_for_icmp()) {
+            Ok(sockets) => Some(sockets),
+            Err(e) => {
+                warning!(
+                    format!("Failed to drop privileges, aborting scan: {e}"),
+                    opts.greppable,
+                    opts.accessible
+                );
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let cgroup = CgroupLimits::detect();
+    let mut batch_size = cap_batch_size_to_cgroup(&opts, batch_size, cgroup.as_ref());
+
+    let learning_path = LearningStore::default_path();
+    let learn = opts.learns();
+    let mut learning = match (&learning_path, learn) {
+        (Some(path), true) => LearningStore::load(path),
+        _ => LearningStore::default(),
+    };
+
+    let mut timeout = Duration::from_millis(opts.timeout.into());
+    if opts.adaptive_timeout {
+        if let Some(learned) = learning.latency.suggested_timeout(&ips) {
+            detail!(
+                format!(
+                    "Using a timeout of {}ms learned from previous scans.",
+                    learned.as_millis()
+                ),
+                opts.greppable,
+                opts.accessible
+            );
+            timeout = learned;
+        }
+    }
+    if opts.adaptive_batch {
+        if let Some(learned) = learning.errors.suggested_batch_size(&ips, batch_size) {
+            detail!(
+                format!("Using a batch size of {learned} learned from errors of previous scans."),
+                opts.greppable,
+                opts.accessible
+            );
+            batch_size = learned;
+        }
+    }
+
+    if opts.includes_port_zero() && !opts.allow_port_zero {
+        warning!(
+            "Port 0 is reserved and is only scanned with --allow-port-zero.",
+            opts.greppable,
+            opts.accessible
+        );
+        std::process::exit(1);
+    }
+
+    let exclude_ports = match opts.excluded_ports() {
+        Ok(ports) => ports,
+        Err(e) => {
+            warning!(
+                format!("Invalid excluded ports: {e}"),
+                opts.greppable,
+                opts.accessible
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let ips = if opts.skip_dead_hosts {
+        let mut discovery = Discovery::new(timeout).with_batch_size(batch_size);
+        if let Some(socket) = raw_sockets
+            .as_ref()
+            .and_then(|sockets| sockets.icmp_v4.as_ref())
+            .and_then(|socket| socket.try_clone().ok())
+        {
+            discovery = discovery.with_icmp_socket(socket);
+        }
+        let alive = block_on(discovery.run(&ips));
+        for (ip, reason) in &alive {
+            debug!("{ip} is up: {reason}");
+        }
+        detail!(
+            format!(
+                "{} of {} hosts are up, skipping the others.",
+                alive.len(),
+                ips.len()
+            ),
+            opts.greppable,
+            opts.accessible
+        );
+        if alive.is_empty() {
+            warning!(
+                "No host answered host discovery, aborting scan.",
+                opts.greppable,
+                opts.accessible
+            );
+            std::process::exit(1);
+        }
+        ips.into_iter()
+            .filter(|ip| alive.contains_key(ip))
+            .collect()
+    } else {
+        ips
+    };
+
+    let mut port_strategy = PortStrategy::pick(&opts.range, opts.ports.clone(), opts.scan_order);
+    if opts.scan_order == ScanOrder::Adaptive {
+        port_strategy = port_strategy.learned(&learning.ports, &ips);
+    }
+    if opts.top_first {
+        port_strategy = port_strategy.tiered(opts.first_ports.clone().unwrap_or_else(common_ports));
+    }
+
+    let protocols = opts.protocols();
+    let progress_bar = opts.shows_progress_bar();
+
+    // The first Ctrl-C stops the scan and reports what it found so far.
+    let cancel = CancellationToken::new();
+    #[cfg(unix)]
+    cancel.cancel_on_interrupt();
     let scanner = Scanner::new(
         &ips,
         batch_size,
-        Duration::from_millis(opts.timeout.into()),
+        timeout,
         opts.tries,
         opts.greppable,
-        PortStrategy::pick(&opts.range, opts.ports, opts.scan_order),
+        port_strategy,
         opts.accessible,
-        opts.exclude_ports.unwrap_or_default(),
+        exclude_ports,
         opts.udp,
-    );
+    )
+    .with_scopes(&scopes)
+    .with_target_ports(&target_ports)
+    .with_cancellation(cancel.clone())
+    .with_sample_coverage(coverage)
+    .with_opts(&opts, raw_sockets.as_mut());
+    let mut scanner = match scanner {
+        Ok(scanner) => scanner,
+        Err(e) => {
+            warning!(e, opts.greppable, opts.accessible);
+            std::process::exit(1);
+        }
+    };
     debug!("Scanner finished building: {scanner:?}");
+    let estimate = scanner.estimate();
+    debug!("Scan estimate: {estimate}");
+    if opts.dry_run {
+        output!(
+            format!("The scan would probe {estimate}."),
+            opts.greppable,
+            opts.accessible
+        );
+        return;
+    }
+    if opts.confirm_above > 0
+        && estimate.duration > Duration::from_secs(opts.confirm_above)
+        && io::stdin().is_terminal()
+    {
+        let question = format!("The scan probes {estimate}. Start it?");
+        if !wizard::confirm(&mut io::stdin().lock(), &question).unwrap_or(false) {
+            return;
+        }
+    }
+
+    if opts.warm_up {
+        let mut warm_up_bench = NamedTimer::start("Warm-up");
+        let tuned_batch_size = block_on(scanner.warm_up());
+        warm_up_bench.end();
+        benchmarks.push(warm_up_bench);
+        detail!(
+            format!("Warm-up picked a batch size of {tuned_batch_size}."),
+            opts.greppable,
+            opts.accessible
+        );
+    }
+
+    #[cfg(unix)]
+    let control_server = opts.control_socket.as_ref().and_then(|path| {
+        match ControlServer::bind(path, scanner.control()) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                warning!(
+                    format!("Could not listen on control socket {}: {e}", path.display()),
+                    opts.greppable,
+                    opts.accessible
+                );
+                None
+            }
+        }
+    });
+    #[cfg(not(unix))]
+    if opts.control_socket.is_some() {
+        warning!(
+            "The control socket is only supported on unix.",
+            opts.greppable,
+            opts.accessible
+        );
+    }
 
+    let started = SystemTime::now();
     let mut portscan_bench = NamedTimer::start("Portscan");
-    let scan_result = block_on(scanner.run());
+    let control = scanner.control();
+    let bar = ProgressBar::default();
+    let show_progress = |progress: ScanProgress| {
+        if progress_bar {
+            bar.draw(&progress);
+        }
+    };
+    let states = if opts.sweep {
+        block_on(report_progress(&control, scanner.sweep(), show_progress))
+    } else {
+        block_on(report_progress(&control, scanner.run(), show_progress))
+    };
+    let mut scan_result = states.open();
+    let mut open_tagged = states.open_tagged();
+    if progress_bar {
+        bar.clear();
+    }
     portscan_bench.end();
     benchmarks.push(portscan_bench);
 
+    let filtered_hosts = states.filtered_hosts();
+    if protocols.contains(&Protocol::Tcp) && !filtered_hosts.is_empty() {
+        detail!(
+            format!(
+                "{} hosts answered on none of their ports, they are down or drop every probe.",
+                filtered_hosts.len()
+            ),
+            opts.greppable,
+            opts.accessible
+        );
+    }
+
+    // Only a sample of the ports of hosts answering on every port was
+    // scanned, and all of them look open: none are reported.
+    let mut suppressed_ips = HashSet::new();
+    for host in scanner.sampled_hosts() {
+        warning!(
+            format!("{host}. Only a sample of its ports was scanned, none are reported."),
+            opts.greppable,
+            opts.accessible
+        );
+        suppressed_ips.insert(host.ip);
+    }
+    scan_result.retain(|socket| !suppressed_ips.contains(&socket.ip()));
+    open_tagged.retain(|(socket, _)| !suppressed_ips.contains(&socket.ip()));
+
+    if opts.verify {
+        // Only TCP ports can be connected to again.
+        let tcp_open: Vec<SocketAddr> = open_tagged
+            .iter()
+            .filter(|(_, protocol)| *protocol == Protocol::Tcp)
+            .map(|(socket, _)| *socket)
+            .collect();
+        let mut verify_bench = NamedTimer::start("Verification");
+        let verified = block_on(scanner.verify(&tcp_open));
+        verify_bench.end();
+        benchmarks.push(verify_bench);
+        for socket in tcp_open.iter().filter(|socket| !verified.contains(socket)) {
+            warning!(
+                format!("{socket} no longer answers, dropping it from the results."),
+                opts.greppable,
+                opts.accessible
+            );
+        }
+        open_tagged
+            .retain(|(socket, protocol)| *protocol != Protocol::Tcp || verified.contains(socket));
+        scan_result = open_tagged.iter().map(|(socket, _)| *socket).collect();
+        scan_result.dedup();
+    }
+
+    // A partial scan would skew what was learned so far.
+    let interrupted = scanner.is_cancelled();
+    if let (Some(path), true) = (&learning_path, learn && !interrupted) {
+        learning.latency.merge(scanner.latency_profiles());
+        learning.errors.merge(scanner.error_rates());
+        for socket in &scan_result {
+            learning.ports.record(&socket.ip(), socket.port());
+        }
+        if let Err(e) = learning.save(path) {
+            warning!(
+                format!("Could not save what was learned from this scan to {path:?}: {e}"),
+                opts.greppable,
+                opts.accessible
+            );
+        }
+    }
+
+    if opts.stats {
+        output!(
+            format!("Scan statistics:\n{}", scanner.stats()),
+            opts.greppable,
+            opts.accessible
+        );
+    }
+
+    let errors = scanner.error_summary();
+    if !opts.greppable && !errors.is_empty() {
+        detail!(errors.to_string(), opts.greppable, opts.accessible);
+    }
+    if let Some(exhaustion) = scanner.exhaustion() {
+        warning!(exhaustion.to_string(), opts.greppable, opts.accessible);
+    }
+    if errors.count(ProbeError::ResourceExhausted) > 0 {
+        warning!(
+            format!("{} probes failed because this machine ran out of resources, e.g. too many open files, so some open ports may have been missed. Please reduce the batch size, e.g. -b {}.", errors.count(ProbeError::ResourceExhausted), (batch_size / 2).max(1)),
+            opts.greppable,
+            opts.accessible
+        );
+    }
+
+    for event in scanner.backoff_events() {
+        warning!(event.to_string(), opts.greppable, opts.accessible);
+    }
+
+    for limit in scanner.rate_limits() {
+        warning!(limit.to_string(), opts.greppable, opts.accessible);
+    }
+
+    if let Some(table) = scanner.conntrack_pressure() {
+        warning!(
+            format!("The conntrack table filled up to {table} during the scan, so it was slowed down. SYNs dropped before that look like filtered ports, consider raising net.netfilter.nf_conntrack_max or lowering the batch size."),
+            opts.greppable,
+            opts.accessible
+        );
+    }
+
+    detail!(
+        scanner.summary().to_string(),
+        opts.greppable,
+        opts.accessible
+    );
+    if interrupted {
+        warning!(
+            "Interrupted, only the ports found so far are reported and no scripts are run. Press Ctrl-C again to quit right away.",
+            opts.greppable,
+            opts.accessible
+        );
+        let unscanned = scanner.unscanned();
+        if !unscanned.is_empty() {
+            match std::fs::write(RESUME_FILE, TargetsFile::from_ports(&unscanned).to_string()) {
+                Ok(()) => warning!(
+                    format!("The targets left to scan were written to {RESUME_FILE}, rerun with --resume {RESUME_FILE} to scan them."),
+                    opts.greppable,
+                    opts.accessible
+                ),
+                Err(e) => warning!(
+                    format!("Could not write the targets left to scan to {RESUME_FILE}: {e}"),
+                    opts.greppable,
+                    opts.accessible
+                ),
+            }
+        }
+    }
+
+    if opts.tarpits != TarpitDetection::Off {
+        for tarpit in block_on(scanner.detect_tarpits(&scan_result)) {
+            warning!(tarpit.to_string(), opts.greppable, opts.accessible);
+            if opts.tarpits == TarpitDetection::Suppress {
+                suppressed_ips.insert(tarpit.ip);
+            }
+        }
+        scan_result.retain(|socket| !suppressed_ips.contains(&socket.ip()));
+    }
+
+    if opts.fast_open && !cfg!(target_os = "linux") {
+        warning!(
+            "TCP Fast Open probing is only supported on Linux.",
+            opts.greppable,
+            opts.accessible
+        );
+    } else if opts.fast_open {
+        let fast_open = block_on(scanner.fast_open_support(&scan_result));
+        print_transport_support("TCP Fast Open", &fast_open, &scan_result, &opts);
+    }
+    let asked = scanner.tcp_extensions();
+    let agreed = scanner.agreed_tcp_extensions();
+    let extensions: [(&str, bool, fn(&TcpExtensions) -> bool); 2] = [
+        ("Multipath TCP", asked.mptcp, |agreed| agreed.mptcp),
+        ("ECN", asked.ecn, |agreed| agreed.ecn),
+    ];
+    for (feature, _, agreed_to) in extensions.iter().filter(|(_, asked, _)| *asked) {
+        let supported: Vec<SocketAddr> = scan_result
+            .iter()
+            .filter(|socket| agreed.get(socket).is_some_and(agreed_to))
+            .copied()
+            .collect();
+        print_transport_support(feature, &supported, &scan_result, &opts);
+    }
+
+    let kept: HashSet<SocketAddr> = scan_result.iter().copied().collect();
+    open_tagged.retain(|(socket, _)| kept.contains(socket));
+
     let mut ports_per_ip = HashMap::new();
 
     for socket in scan_result {
@@ -110,8 +660,28 @@ fn main() {
             .push(socket.port());
     }
 
-    for ip in ips {
-        if ports_per_ip.contains_key(&ip) {
+    if let Some(coverage) = coverage {
+        let (estimate, margin) = coverage.extrapolate(ports_per_ip.len());
+        detail!(
+            format!(
+                "Sampled {coverage}: {} hosts with open ports, about {estimate:.0} ± {margin:.0} in the whole target space.",
+                ports_per_ip.len()
+            ),
+            opts.greppable,
+            opts.accessible
+        );
+    }
+
+    // The probes of the scan filled the ARP table for the targets on the
+    // local networks.
+    let hardware = discovery::hardware_addresses(&ips);
+
+    // Sampled targets without open ports are expected, don't warn about each.
+    for ip in ips
+        .into_iter()
+        .filter(|_| coverage.is_none() && !interrupted)
+    {
+        if ports_per_ip.contains_key(&ip) || suppressed_ips.contains(&ip) {
             continue;
         }
 
@@ -127,32 +697,144 @@ fn main() {
         warning!(x, opts.greppable, opts.accessible);
     }
 
+    if !opts.greppable {
+        print_hosts(&hosts, &tags, &ports_per_ip, &opts);
+        for (ip, address) in &hardware {
+            if ports_per_ip.contains_key(ip) {
+                output!(
+                    format!("{ip} is at {address}"),
+                    opts.greppable,
+                    opts.accessible
+                );
+            }
+        }
+    }
+    if opts.group_by == GroupBy::Service {
+        print_services(&open_tagged, &opts);
+    }
+
+    let tcp_open: Vec<SocketAddr> = open_tagged
+        .iter()
+        .filter(|(_, protocol)| *protocol == Protocol::Tcp)
+        .map(|(socket, _)| *socket)
+        .collect();
+    let services = if opts.probe_services {
+        let mut probe_bench = NamedTimer::start("Service probes");
+        let services = block_on(
+            ServiceProber::new(timeout)
+                .with_cancellation(cancel.clone())
+                .probe_all(&tcp_open),
+        );
+        probe_bench.end();
+        benchmarks.push(probe_bench);
+        if !opts.greppable {
+            for (socket, service) in &services {
+                output!(
+                    format!("{socket} {service}"),
+                    opts.greppable,
+                    opts.accessible
+                );
+            }
+        }
+        services
+    } else {
+        BTreeMap::new()
+    };
+    let certificates = if opts.tls_info {
+        let mut tls_bench = NamedTimer::start("TLS certificates");
+        let certificates = block_on(
+            ServiceProber::new(timeout)
+                .with_cancellation(cancel.clone())
+                .certificates(&tcp_open),
+        );
+        tls_bench.end();
+        benchmarks.push(tls_bench);
+        if !opts.greppable {
+            for (socket, certificate) in &certificates {
+                output!(
+                    format!("{socket} {certificate}"),
+                    opts.greppable,
+                    opts.accessible
+                );
+            }
+        }
+        certificates
+    } else {
+        BTreeMap::new()
+    };
+
+    let mut report = scan_report(
+        &scanner,
+        started,
+        &open_tagged,
+        &hosts,
+        &tags,
+        &hardware,
+        &services,
+    );
+    report.add_certificates(&certificates);
+    report.interrupted = interrupted;
+    let report_on_stdout = opts.output_file.is_none()
+        && opts
+            .output
+            .iter()
+            .any(|spec| spec.is_report() && report_path(spec).is_none());
+    // nmap's grepable lines cover every host at once, in place of the
+    // "ip -> [ports]" lines.
+    let nmap_greppable = opts.greppable
+        && opts.greppable_format == GreppableFormat::Nmap
+        && opts.group_by == GroupBy::Host
+        && !report_on_stdout;
+    if nmap_greppable {
+        let args: Vec<String> = std::env::args().collect();
+        print!("{}", nmap_grep::render(&report, &args.join(" ")));
+    }
+
     let mut script_bench = NamedTimer::start("Scripts");
+    let mut scripts = Vec::new();
     for (ip, ports) in &ports_per_ip {
-        let vec_str_ports: Vec<String> = ports.iter().map(ToString::to_string).collect();
-
-        // nmap port style is 80,443. Comma separated with no spaces.
-        let ports_str = vec_str_ports.join(",");
+        let target = named_targets
+            .get(ip)
+            .cloned()
+            .unwrap_or_else(|| Target::new(*ip));
+        let ports_str = nmap::port_list(ports);
 
         // if option scripts is none, no script will be spawned
-        if opts.greppable || opts.scripts == ScriptsRequired::None {
-            println!("{} -> [{}]", &ip, ports_str);
+        if opts.greppable || opts.scripts == ScriptsRequired::None || interrupted {
+            if opts.group_by == GroupBy::Host && !report_on_stdout && !nmap_greppable {
+                println!("{} -> [{}]{}", target, ports_str, tag_suffix(ip, &tags));
+            }
             continue;
         }
+        if let Some(ip_tags) = tags.get(ip) {
+            detail!(
+                format!("{ip} is tagged {}", ip_tags.join(", ")),
+                opts.greppable,
+                opts.accessible
+            );
+        }
         detail!("Starting Script(s)", opts.greppable, opts.accessible);
 
+        let command = opts.command_for(tags.get(ip).map_or(&[][..], Vec::as_slice));
+
+        let host_report = report.hosts.iter().find(|host| host.ip == *ip);
         // Run all the scripts we found and parsed based on the script config file tags field.
         for mut script_f in scripts_to_run.clone() {
+            let script_ports = script_f.ports_for(ports, host_report);
+            if script_ports.is_empty() {
+                debug!("No service of {ip} triggers {:?}", script_f.path);
+                continue;
+            }
             // This part allows us to add commandline arguments to the Script call_format, appending them to the end of the command.
-            if !opts.command.is_empty() {
-                let user_extra_args = &opts.command.join(" ");
+            if !command.is_empty() {
+                let user_extra_args = &command.join(" ");
                 debug!("Extra args vec {user_extra_args:?}");
                 if script_f.call_format.is_some() {
                     let mut call_f = script_f.call_format.unwrap();
                     call_f.push(' ');
                     call_f.push_str(user_extra_args);
                     output!(
-                        format!("Running script {:?} on ip {}\nDepending on the complexity of the script, results may take some time to appear.", call_f, &ip),
+                        format!("Running script {:?} on {}\nDepending on the complexity of the script, results may take some time to appear.", call_f, &target),
                         opts.greppable,
                         opts.accessible
                     );
@@ -161,40 +843,285 @@ fn main() {
                 }
             }
 
+            let sandbox = script_f.sandbox();
+
             // Building the script with the arguments from the ScriptFile, and ip-ports.
-            let script = Script::build(
+            let mut script = Script::build(
                 script_f.path,
                 *ip,
-                ports.clone(),
+                script_ports,
                 script_f.port,
                 script_f.ports_separator,
                 script_f.tags,
                 script_f.call_format,
             );
-            match script.run() {
-                Ok(script_result) => {
-                    detail!(script_result.clone(), opts.greppable, opts.accessible);
-                }
-                Err(e) => {
-                    warning!(&format!("Error {e}"), opts.greppable, opts.accessible);
+            // nmap only scans the first address of a hostname, so the
+            // addresses of hostnames resolving to several are given as is.
+            if let Some(host) = target
+                .host
+                .as_ref()
+                .filter(|host| hosts.get(*host).is_some_and(|ips| ips.len() == 1))
+            {
+                script = script.with_host(host.clone());
+            }
+            if opts.sandbox_scripts {
+                script = script.with_sandbox(sandbox);
+            }
+            if let Some(user) = script_f.user {
+                script = script.with_user(user);
+            }
+            if script_f.input == Some(ScriptInput::Json) {
+                if let Some(host) = host_report {
+                    script = script.with_json_input(&ScriptContext {
+                        host,
+                        started_at: report.started_at,
+                        summary: &report.summary,
+                    });
                 }
             }
+            scripts.push((*ip, script));
         }
     }
+    run_scripts(scripts, &mut report, report_on_stdout, &opts);
 
     // To use the runtime benchmark, run the process as: RUST_LOG=info ./rustscan
     script_bench.end();
     benchmarks.push(script_bench);
+    report.metrics = Some(scanner.metrics().with_phases(&benchmarks));
+    write_reports(&scanner, &report, &opts);
     rustscan_bench.end();
     benchmarks.push(rustscan_bench);
     debug!("Benchmarks raw {benchmarks:?}");
     info!("{}", benchmarks.summary());
+    if interrupted {
+        // exit skips destructors, the control socket removes its file.
+        #[cfg(unix)]
+        drop(control_server);
+        std::process::exit(130);
+    }
+}
+
+/// Runs the `scripts` of each IP, `--script-concurrency` at once, adding
+/// their outputs to `report` as they finish. The outputs are printed too,
+/// unless the report itself goes to stdout.
+fn run_scripts(
+    scripts: Vec<(IpAddr, Script)>,
+    report: &mut ScanReport,
+    report_on_stdout: bool,
+    opts: &Opts,
+) {
+    block_on(async {
+        let mut finished = stream::iter(scripts)
+            .map(|(ip, script)| task::spawn_blocking(move || (ip, script.execute())))
+            .buffer_unordered(opts.script_concurrency.get());
+        while let Some((ip, result)) = finished.next().await {
+            match result {
+                Ok(script) if script.exit_code == 0 => {
+                    if !report_on_stdout {
+                        detail!(script.output.clone(), opts.greppable, opts.accessible);
+                    }
+                    report.add_script(ip, script);
+                }
+                Ok(script) => {
+                    let mut error = format!("Error Exit code = {}", script.exit_code);
+                    let stderr = script.stderr.trim();
+                    if !stderr.is_empty() {
+                        error = format!("{error}: {stderr}");
+                    }
+                    warning!(&error, opts.greppable, opts.accessible);
+                    report.add_script(ip, script);
+                }
+                Err(e) => {
+                    warning!(&format!("Error {e}"), opts.greppable, opts.accessible);
+                }
+            }
+        }
+    });
+}
+
+/// The report of the scan, with the `open` sockets, the IPs of the `hosts`,
+/// the `tags` of the IPs, the `hardware` addresses of local ones and the
+/// `services` the probes found.
+fn scan_report(
+    scanner: &Scanner,
+    started: SystemTime,
+    open: &[(SocketAddr, Protocol)],
+    hosts: &BTreeMap<String, Vec<IpAddr>>,
+    tags: &BTreeMap<IpAddr, Vec<String>>,
+    hardware: &BTreeMap<IpAddr, HardwareAddress>,
+    services: &BTreeMap<SocketAddr, ServiceInfo>,
+) -> ScanReport {
+    let mut report = ScanReport::new(started, scanner.summary());
+    for (socket, protocol) in open {
+        report.add(*socket, *protocol);
+    }
+    report.add_hostnames(hosts);
+    report.add_tags(tags);
+    report.add_hardware(hardware);
+    report.add_services(services);
+    report.add_tcp_extensions(scanner.tcp_extensions(), &scanner.agreed_tcp_extensions());
+    report.add_uptimes(&scanner.uptimes());
+    report.add_backoff_events(&scanner.backoff_events());
+    report
+}
+
+/// The path an `--output` report is written to, `None` for stdout.
+fn report_path(spec: &OutputSpec) -> Option<&Path> {
+    match spec {
+        OutputSpec::Report(path) | OutputSpec::NmapXml(path) | OutputSpec::Csv(path) => {
+            path.as_deref()
+        }
+        OutputSpec::Json(_) | OutputSpec::Webhook(_) => None,
+    }
+}
+
+/// Writes the report of the scan for each `--output json`,
+/// `--output report:<path>`, `--output xml[:<path>]` and
+/// `--output csv[:<path>]`, the ones without a path to `--output-file`
+/// if given.
+fn write_reports(scanner: &Scanner, report: &ScanReport, opts: &Opts) {
+    let reports: Vec<&OutputSpec> = opts.output.iter().filter(|spec| spec.is_report()).collect();
+    if reports.is_empty() {
+        return;
+    }
+
+    let args: Vec<String> = std::env::args().collect();
+    for spec in reports {
+        let path = report_path(spec).or(opts.output_file.as_deref());
+        let written = match spec {
+            OutputSpec::NmapXml(_) => {
+                nmap_xml::write(report, &args.join(" "), scanner.scan_type(), path)
+            }
+            OutputSpec::Csv(_) => csv::write(report, path),
+            OutputSpec::Report(_) => report.write(path),
+            _ => Ok(()),
+        };
+        if let Err(e) = written {
+            warning!(
+                format!("Could not write the report: {e}"),
+                opts.greppable,
+                opts.accessible
+            );
+        }
+    }
+}
+
+/// Groups the results of hostnames that resolved to several IPs under the
+/// hostname, so they can be told apart from the flattened per-IP output.
+fn print_hosts(
+    hosts: &BTreeMap<String, Vec<IpAddr>>,
+    tags: &BTreeMap<IpAddr, Vec<String>>,
+    ports_per_ip: &HashMap<IpAddr, Vec<u16>>,
+    opts: &Opts,
+) {
+    for (host, ips) in hosts.iter().filter(|(_, ips)| ips.len() > 1) {
+        let breakdown: Vec<String> = ips
+            .iter()
+            .map(|ip| match ports_per_ip.get(ip) {
+                Some(ports) => {
+                    let ports: Vec<String> = ports.iter().map(ToString::to_string).collect();
+                    format!("  {ip} -> [{}]{}", ports.join(","), tag_suffix(ip, tags))
+                }
+                None => format!("  {ip} -> no open ports"),
+            })
+            .collect();
+        output!(
+            format!(
+                "{host} resolved to {} addresses:\n{}",
+                ips.len(),
+                breakdown.join("\n")
+            ),
+            opts.greppable,
+            opts.accessible
+        );
+    }
+}
+
+/// Inverts the results into the hosts exposing each port, by port and
+/// protocol.
+fn group_by_service(open: &[(SocketAddr, Protocol)]) -> BTreeMap<(u16, Protocol), Vec<IpAddr>> {
+    let mut hosts_per_port: BTreeMap<(u16, Protocol), Vec<IpAddr>> = BTreeMap::new();
+    for (socket, protocol) in open {
+        hosts_per_port
+            .entry((socket.port(), *protocol))
+            .or_default()
+            .push(socket.ip());
+    }
+    for ips in hosts_per_port.values_mut() {
+        ips.sort_unstable();
+        ips.dedup();
+    }
+    hosts_per_port
+}
+
+/// Prints the hosts exposing each open port, "22/ssh: 41 hosts", which is
+/// how remediation is usually planned. Scanning several protocols, the
+/// protocol is named too, "53/udp/domain".
+fn print_services(open: &[(SocketAddr, Protocol)], opts: &Opts) {
+    let show_protocol = opts.protocols().len() > 1;
+    for ((port, protocol), ips) in group_by_service(open) {
+        let name = service_name(port, protocol).unwrap_or("unknown");
+        let service = if show_protocol {
+            format!("{port}/{protocol}/{name}")
+        } else {
+            format!("{port}/{name}")
+        };
+        let ips: Vec<String> = ips.iter().map(ToString::to_string).collect();
+        if opts.greppable {
+            println!("{service} -> [{}]", ips.join(","));
+        } else {
+            let hosts = if ips.len() == 1 { "host" } else { "hosts" };
+            output!(
+                format!("{service}: {} {hosts}\n  {}", ips.len(), ips.join(", ")),
+                opts.greppable,
+                opts.accessible
+            );
+        }
+    }
+}
+
+/// The tags of an IP in the annotated form they were given in, " # prod db",
+/// or nothing for untagged IPs.
+fn tag_suffix(ip: &IpAddr, tags: &BTreeMap<IpAddr, Vec<String>>) -> String {
+    match tags.get(ip) {
+        Some(ip_tags) if !ip_tags.is_empty() => format!(" # {}", ip_tags.join(" ")),
+        _ => String::new(),
+    }
 }
 
 /// Prints the opening title of RustScan
-#[allow(clippy::items_after_statements, clippy::needless_raw_string_hashes)]
 fn print_opening(opts: &Opts) {
     debug!("Printing opening");
+    #[cfg(feature = "banner")]
+    print_banner();
+
+    let config_path = opts
+        .config_path
+        .clone()
+        .unwrap_or_else(input::default_config_path);
+
+    detail!(
+        format!("The config file is expected to be at {config_path:?}"),
+        opts.greppable,
+        opts.accessible
+    );
+
+    if opts.config_path.is_none() {
+        let old_config_path = input::old_default_config_path();
+        detail!(
+            format!(
+                "For backwards compatibility, the config file may also be at {old_config_path:?}"
+            ),
+            opts.greppable,
+            opts.accessible
+        );
+    }
+}
+
+/// Prints the RustScan logo and a random quote.
+#[cfg(feature = "banner")]
+#[allow(clippy::items_after_statements, clippy::needless_raw_string_hashes)]
+fn print_banner() {
     let s = r#".----. .-. .-. .----..---.  .----. .---.   .--.  .-. .-.
 | {}  }| { } |{ {__ {_   _}{ {__  /  ___} / {} \ |  `| |
 | .-. \| {_} |.-._} } | |  .-._} }\     }/  /\  \| |\  |
@@ -208,82 +1135,182 @@ The Modern Day Port Scanner."#;
  --------------------------------------"#;
     println!("{}", info.gradient(Color::Yellow).bold());
     funny_opening!();
+}
 
-    let config_path = opts
-        .config_path
-        .clone()
-        .unwrap_or_else(input::default_config_path);
+/// Prints which open sockets support a transport-layer feature.
+fn print_transport_support(
+    feature: &str,
+    supported: &[SocketAddr],
+    open_sockets: &[SocketAddr],
+    opts: &Opts,
+) {
+    let sockets: Vec<String> = supported.iter().map(ToString::to_string).collect();
+    detail!(
+        format!(
+            "{feature} supported by {} of {} open ports: [{}]",
+            supported.len(),
+            open_sockets.len(),
+            sockets.join(", ")
+        ),
+        opts.greppable,
+        opts.accessible
+    );
+}
 
+/// Runs the benchmark suite, optionally saving the results and comparing
+/// them against the results of a previous run.
+#[cfg(not(tarpaulin_include))]
+fn run_bench(opts: &Opts, compare: Option<&Path>, save: Option<&Path>, threshold: f64) {
     detail!(
-        format!("The config file is expected to be at {config_path:?}"),
+        "Running the benchmark suite against localhost.",
         opts.greppable,
         opts.accessible
     );
+    let results = benchmark::suite::run().results();
 
-    if opts.config_path.is_none() {
-        let old_config_path = input::old_default_config_path();
+    for (phase, secs) in &results.phases {
+        println!("{phase: <20} | {secs:.3}s");
+    }
+
+    if let Some(path) = save {
+        if let Err(e) = results.save(path) {
+            warning!(
+                format!("Could not save benchmark results to {path:?}: {e}"),
+                opts.greppable,
+                opts.accessible
+            );
+        }
+    }
+
+    let Some(path) = compare else {
+        return;
+    };
+
+    match BenchmarkResults::load(path) {
+        Ok(old) => {
+            output!(
+                format!("Comparing against {path:?} (RustScan {})", old.version),
+                opts.greppable,
+                opts.accessible
+            );
+            for phase in results.compare(&old, threshold) {
+                println!("{phase}");
+            }
+        }
+        Err(e) => {
+            warning!(
+                format!("Could not read benchmark results from {path:?}: {e}"),
+                opts.greppable,
+                opts.accessible
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Prints the hosts whose ports changed between the reports at `old` and
+/// `new`, and exits with 1 if any did.
+fn run_diff(opts: &Opts, old: &Path, new: &Path) {
+    let read = |path: &Path| match ScanReport::read(path) {
+        Ok(report) => report,
+        Err(e) => {
+            warning!(
+                format!("Could not read the report {path:?}: {e}"),
+                opts.greppable,
+                opts.accessible
+            );
+            std::process::exit(2);
+        }
+    };
+    let diff = ReportDiff::new(&read(old), &read(new));
+
+    for host in &diff.hosts {
+        if host.has_changes() || !opts.greppable {
+            println!("{host}");
+        }
+    }
+    if !diff.has_changes() {
         detail!(
-            format!(
-                "For backwards compatibility, the config file may also be at {old_config_path:?}"
-            ),
+            "No ports opened or closed.",
             opts.greppable,
             opts.accessible
         );
+        return;
     }
+    std::process::exit(1);
 }
 
 #[cfg(unix)]
 fn adjust_ulimit_size(opts: &Opts) -> usize {
-    use rlimit::Resource;
     use std::convert::TryInto;
 
     if let Some(limit) = opts.ulimit {
         let limit = limit as u64;
-        if Resource::NOFILE.set(limit, limit).is_ok() {
-            detail!(
-                format!("Automatically increasing ulimit value to {limit}."),
+        match resource_limits::set_open_file_limit(limit) {
+            // macOS refuses anything above kern.maxfilesperproc outright.
+            Ok(set) if set < limit => warning!(
+                format!("This system allows at most {set} open files per process, using that instead of {limit}."),
                 opts.greppable,
                 opts.accessible
-            );
-        } else {
-            warning!(
+            ),
+            Ok(set) => detail!(
+                format!("Automatically increasing ulimit value to {set}."),
+                opts.greppable,
+                opts.accessible
+            ),
+            Err(_) => warning!(
                 "ERROR. Failed to set ulimit value.",
                 opts.greppable,
                 opts.accessible
-            );
+            ),
+        }
+    }
+
+    // macOS starts processes with only 256 open files even though the
+    // kernel allows far more, so raise the soft limit as far as it goes.
+    #[cfg(target_os = "macos")]
+    if opts.ulimit.is_none() {
+        if let Ok((soft, raised)) = resource_limits::raise_soft_limit() {
+            if raised > soft {
+                detail!(
+                    format!("Raised the open file limit from {soft} to {raised}."),
+                    opts.greppable,
+                    opts.accessible
+                );
+            }
         }
     }
 
-    let (soft, _) = Resource::NOFILE.get().unwrap();
-    soft.try_into().unwrap_or(usize::MAX)
+    let (soft, _) = resource_limits::open_file_limits().unwrap();
+    let soft = soft.try_into().unwrap_or(usize::MAX);
+
+    #[cfg(target_os = "macos")]
+    if soft < AVERAGE_BATCH_SIZE {
+        warning!(
+            FileLimits::detect().guidance(),
+            opts.greppable,
+            opts.accessible
+        );
+    }
+
+    soft
 }
 
 #[cfg(unix)]
 fn infer_batch_size(opts: &Opts, ulimit: usize) -> usize {
-    let mut batch_size = opts.batch_size;
+    let batch_size = resource_limits::fit_batch_size(opts.batch_size, ulimit);
 
     // Adjust the batch size when the ulimit value is lower than the desired batch size
-    if ulimit < batch_size {
+    if ulimit < opts.batch_size {
         warning!("File limit is lower than default batch size. Consider upping with --ulimit. May cause harm to sensitive servers",
             opts.greppable, opts.accessible
         );
 
-        // When the OS supports high file limits like 8000, but the user
-        // selected a batch size higher than this we should reduce it to
-        // a lower number.
+        // The batch size is halved when the file limit is very small.
         if ulimit < AVERAGE_BATCH_SIZE {
-            // ulimit is smaller than aveage batch size
-            // user must have very small ulimit
-            // decrease batch size to half of ulimit
             warning!("Your file limit is very small, which negatively impacts RustScan's speed. Use the Docker image, or up the Ulimit with '--ulimit 5000'. ", opts.greppable, opts.accessible);
-            info!("Halving batch_size because ulimit is smaller than average batch size");
-            batch_size = ulimit / 2;
-        } else if ulimit > DEFAULT_FILE_DESCRIPTORS_LIMIT {
-            info!("Batch size is now average batch size");
-            batch_size = AVERAGE_BATCH_SIZE;
-        } else {
-            batch_size = ulimit - 100;
         }
+        info!("Batch size is now {batch_size}");
     }
     // When the ulimit is higher than the batch size let the user know that the
     // batch size can be increased unless they specified the ulimit themselves.
@@ -295,11 +1322,82 @@ fn infer_batch_size(opts: &Opts, ulimit: usize) -> usize {
     batch_size
 }
 
+/// Containers often have far less memory than their file limit suggests,
+/// and going over the cgroup memory limit gets the scan killed.
+fn cap_batch_size_to_cgroup(
+    opts: &Opts,
+    batch_size: usize,
+    cgroup: Option<&CgroupLimits>,
+) -> usize {
+    let (Some(max_batch_size), Some(available)) = (
+        cgroup.and_then(CgroupLimits::max_batch_size),
+        cgroup.and_then(CgroupLimits::available_memory),
+    ) else {
+        return batch_size;
+    };
+    if batch_size <= max_batch_size {
+        return batch_size;
+    }
+
+    warning!(
+        format!(
+            "Only {}MiB of memory is left in this container, lowering the batch size to {max_batch_size}. Give the container more memory to scan faster.",
+            available / 1024 / 1024
+        ),
+        opts.greppable,
+        opts.accessible
+    );
+    max_batch_size
+}
+
+/// Windows has no ulimit, so the batch size is capped by the dynamic ports
+/// available for outgoing connections instead.
+#[cfg(windows)]
+fn infer_batch_size_windows(opts: &Opts, limits: &SocketLimits) -> usize {
+    if opts.ulimit.is_some() {
+        warning!(
+            "--ulimit has no effect on Windows, the batch size is limited by the dynamic port range instead.",
+            opts.greppable,
+            opts.accessible
+        );
+    }
+    if limits.handle_pressure() {
+        warning!(
+            format!(
+                "RustScan already has {} handles open, scanning may slow the system down. Consider lowering the batch size with '-b'.",
+                limits.open_handles.unwrap_or_default()
+            ),
+            opts.greppable,
+            opts.accessible
+        );
+    }
+
+    let safe_batch_size = limits.safe_batch_size();
+    if opts.batch_size <= safe_batch_size {
+        return opts.batch_size;
+    }
+
+    warning!(
+        format!(
+            "Windows only has {} dynamic ports for outgoing connections, lowering the batch size to {safe_batch_size}. Widen the range from an administrator prompt with 'netsh int ipv4 set dynamicport tcp start=10000 num=55535'.",
+            limits.dynamic_ports
+        ),
+        opts.greppable,
+        opts.accessible
+    );
+    safe_batch_size
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(unix)]
     use super::{adjust_ulimit_size, infer_batch_size};
-    use super::{print_opening, Opts};
+    use super::{
+        cap_batch_size_to_cgroup, group_by_service, print_opening, tag_suffix, CgroupLimits, Opts,
+        Protocol,
+    };
+    use std::collections::BTreeMap;
+    use std::net::{IpAddr, SocketAddr};
 
     #[test]
     #[cfg(unix)]
@@ -365,6 +1463,20 @@ mod tests {
         assert!(batch_size == opts.batch_size);
     }
 
+    #[test]
+    fn batch_size_fits_cgroup_memory() {
+        let opts = Opts::default();
+        let cgroup = CgroupLimits {
+            memory_max: Some(128 * 1024 * 1024),
+            memory_current: Some(0),
+            pids_max: None,
+        };
+
+        assert_eq!(cap_batch_size_to_cgroup(&opts, 4_500, Some(&cgroup)), 2_048);
+        assert_eq!(cap_batch_size_to_cgroup(&opts, 1_000, Some(&cgroup)), 1_000);
+        assert_eq!(cap_batch_size_to_cgroup(&opts, 4_500, None), 4_500);
+    }
+
     #[test]
     fn test_print_opening_no_panic() {
         let opts = Opts {
@@ -374,4 +1486,42 @@ mod tests {
         // print opening should not panic
         print_opening(&opts);
     }
+
+    #[test]
+    fn tags_follow_the_results() {
+        let tagged = "10.0.0.5".parse().unwrap();
+        let untagged = "10.0.0.6".parse().unwrap();
+        let mut tags = BTreeMap::new();
+        tags.insert(tagged, vec!["db-prod".to_owned(), "prod".to_owned()]);
+
+        assert_eq!(tag_suffix(&tagged, &tags), " # db-prod prod");
+        assert_eq!(tag_suffix(&untagged, &tags), "");
+    }
+
+    #[test]
+    fn results_grouped_by_service() {
+        let web: IpAddr = "10.0.0.1".parse().unwrap();
+        let db: IpAddr = "10.0.0.2".parse().unwrap();
+        let open = [
+            (SocketAddr::new(web, 22), Protocol::Tcp),
+            (SocketAddr::new(web, 443), Protocol::Tcp),
+            (SocketAddr::new(db, 5432), Protocol::Tcp),
+            (SocketAddr::new(db, 22), Protocol::Tcp),
+            (SocketAddr::new(db, 53), Protocol::Udp),
+        ];
+
+        let grouped = group_by_service(&open);
+
+        assert_eq!(
+            grouped.keys().copied().collect::<Vec<_>>(),
+            vec![
+                (22, Protocol::Tcp),
+                (53, Protocol::Udp),
+                (443, Protocol::Tcp),
+                (5432, Protocol::Tcp)
+            ]
+        );
+        assert_eq!(grouped[&(22, Protocol::Tcp)], vec![web, db]);
+        assert_eq!(grouped[&(443, Protocol::Tcp)], vec![web]);
+    }
 }
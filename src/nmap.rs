@@ -0,0 +1,413 @@
+//! Running nmap on the open ports of a scan and reading back what it found.
+//!
+//! The scripts engine hands the open ports to nmap and prints whatever it
+//! says, which is all the command line needs. [`run_nmap`] runs it with
+//! `-oX -` instead and parses the XML it writes into a [`NmapRun`], so
+//! library users get the services and versions nmap detected as values.
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "nmap")] {
+//! # use rustscan::nmap::run_nmap;
+//! let run = run_nmap(&["10.0.0.1".parse().unwrap()], &[22, 80], &["-sV".to_string()]).unwrap();
+//! for host in &run.hosts {
+//!     for port in &host.ports {
+//!         println!("{:?} {}/{:?} {:?}", host.address, port.port, port.protocol, port.service);
+//!     }
+//! }
+//! # }
+//! ```
+use crate::output::Protocol;
+
+use serde_derive::Serialize;
+use std::io;
+use std::net::IpAddr;
+#[cfg(feature = "nmap")]
+use std::process::Command;
+
+/// What one nmap run found.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NmapRun {
+    /// The command line nmap was run with.
+    pub args: Option<String>,
+    pub hosts: Vec<NmapHost>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NmapHost {
+    /// Whether nmap found the host up, the default when it doesn't say.
+    pub up: bool,
+    /// The first IP address of the host.
+    pub address: Option<IpAddr>,
+    pub hostnames: Vec<String>,
+    pub ports: Vec<NmapPort>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NmapPort {
+    pub port: u16,
+    pub protocol: Protocol,
+    /// The state as nmap names it, like `open` or `closed|filtered`.
+    pub state: String,
+    pub service: Option<NmapService>,
+}
+
+/// A service as identified by nmap, from the services table or, with
+/// `-sV`, by probing it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NmapService {
+    pub name: String,
+    pub product: Option<String>,
+    pub version: Option<String>,
+    pub extra_info: Option<String>,
+}
+
+/// Runs nmap with `args` on `ports` of `targets` and parses what it found.
+/// IPv6 targets can't be mixed with IPv4 ones in a single nmap run. Needs
+/// the `nmap` feature.
+#[cfg(feature = "nmap")]
+pub fn run_nmap(targets: &[IpAddr], ports: &[u16], args: &[String]) -> io::Result<NmapRun> {
+    let mut command = Command::new("nmap");
+    command
+        .args(args)
+        .args(["-oX", "-", "-p", &port_list(ports)]);
+    if !targets.is_empty() && targets.iter().all(IpAddr::is_ipv6) {
+        command.arg("-6");
+    }
+    command.args(targets.iter().map(ToString::to_string));
+
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "nmap exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// `ports` the way nmap takes them, comma separated without spaces.
+///
+/// ```rust
+/// # use rustscan::nmap::port_list;
+/// assert_eq!(port_list(&[80, 443]), "80,443");
+/// ```
+pub fn port_list(ports: &[u16]) -> String {
+    let ports: Vec<String> = ports.iter().map(ToString::to_string).collect();
+    ports.join(",")
+}
+
+/// Parses the XML nmap writes with `-oX`. Elements other than the hosts,
+/// their status, addresses, hostnames and ports are skipped, so this is a
+/// scanner for the tags of the report rather than a full XML parser.
+pub fn parse(xml: &str) -> io::Result<NmapRun> {
+    let mut run = NmapRun::default();
+    let mut host: Option<NmapHost> = None;
+    let mut port: Option<NmapPort> = None;
+
+    for tag in tags(xml)? {
+        match (tag.name, tag.closing) {
+            ("nmaprun", false) => run.args = tag.attribute("args"),
+            ("host", false) => {
+                host = Some(NmapHost {
+                    up: true,
+                    ..NmapHost::default()
+                })
+            }
+            ("host", true) => run.hosts.extend(host.take()),
+            ("status", false) => {
+                if let Some(host) = host.as_mut() {
+                    host.up = tag.attribute("state").as_deref() != Some("down");
+                }
+            }
+            ("address", false) => {
+                let Some(host) = host.as_mut() else {
+                    continue;
+                };
+                if host.address.is_none()
+                    && matches!(tag.attribute("addrtype").as_deref(), Some("ipv4" | "ipv6"))
+                {
+                    let addr = tag.attribute("addr").unwrap_or_default();
+                    let ip = addr
+                        .parse()
+                        .map_err(|_| invalid(&format!("an invalid address {addr}")))?;
+                    host.address = Some(ip);
+                }
+            }
+            ("hostname", false) => {
+                if let (Some(host), Some(name)) = (host.as_mut(), tag.attribute("name")) {
+                    host.hostnames.push(name);
+                }
+            }
+            ("port", false) => {
+                port = parse_port(&tag)?;
+                if tag.self_closing {
+                    if let (Some(host), Some(port)) = (host.as_mut(), port.take()) {
+                        host.ports.push(port);
+                    }
+                }
+            }
+            ("port", true) => {
+                if let (Some(host), Some(port)) = (host.as_mut(), port.take()) {
+                    host.ports.push(port);
+                }
+            }
+            ("state", false) => {
+                if let (Some(port), Some(state)) = (port.as_mut(), tag.attribute("state")) {
+                    port.state = state;
+                }
+            }
+            ("service", false) => {
+                if let Some(port) = port.as_mut() {
+                    port.service = Some(NmapService {
+                        name: tag.attribute("name").unwrap_or_default(),
+                        product: tag.attribute("product"),
+                        version: tag.attribute("version"),
+                        extra_info: tag.attribute("extrainfo"),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(run)
+}
+
+/// The port of a `<port>` tag, `None` for the protocols RustScan doesn't
+/// scan, like SCTP.
+fn parse_port(tag: &Tag<'_>) -> io::Result<Option<NmapPort>> {
+    let protocol = match tag.attribute("protocol").as_deref() {
+        Some("tcp") => Protocol::Tcp,
+        Some("udp") => Protocol::Udp,
+        _ => return Ok(None),
+    };
+    let portid = tag.attribute("portid").unwrap_or_default();
+    let port = portid
+        .parse()
+        .map_err(|_| invalid(&format!("an invalid port {portid}")))?;
+    Ok(Some(NmapPort {
+        port,
+        protocol,
+        state: String::new(),
+        service: None,
+    }))
+}
+
+/// An XML tag, opening, closing or both.
+#[derive(Debug)]
+struct Tag<'a> {
+    name: &'a str,
+    closing: bool,
+    self_closing: bool,
+    attributes: Vec<(&'a str, String)>,
+}
+
+impl Tag<'_> {
+    fn attribute(&self, name: &str) -> Option<String> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+/// The tags of `xml` in document order, without the declaration, doctype,
+/// comments and processing instructions.
+fn tags(xml: &str) -> io::Result<Vec<Tag<'_>>> {
+    let mut tags = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            let end = comment
+                .find("-->")
+                .ok_or_else(|| invalid("an unterminated comment"))?;
+            rest = &comment[end + 3..];
+            continue;
+        }
+        let end = tag_end(rest).ok_or_else(|| invalid("an unterminated tag"))?;
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        if !tag.starts_with(['?', '!']) {
+            tags.push(parse_tag(tag)?);
+        }
+    }
+    Ok(tags)
+}
+
+/// The index of the `>` ending the tag `rest` starts with, skipping those
+/// in attribute values.
+fn tag_end(rest: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in rest.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('>', None) => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parses what is between the `<` and `>` of a tag.
+fn parse_tag(tag: &str) -> io::Result<Tag<'_>> {
+    let (tag, closing) = match tag.strip_prefix('/') {
+        Some(tag) => (tag, true),
+        None => (tag, false),
+    };
+    let (tag, self_closing) = match tag.strip_suffix('/') {
+        Some(tag) => (tag, true),
+        None => (tag, false),
+    };
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = &tag[..name_end];
+
+    let mut attributes = Vec::new();
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let (key, value) = rest
+            .split_once('=')
+            .ok_or_else(|| invalid(&format!("an attribute without a value in <{name}>")))?;
+        let value = value.trim_start();
+        let quote = value
+            .chars()
+            .next()
+            .filter(|c| matches!(c, '"' | '\''))
+            .ok_or_else(|| invalid(&format!("an unquoted attribute in <{name}>")))?;
+        let value = &value[1..];
+        let end = value
+            .find(quote)
+            .ok_or_else(|| invalid(&format!("an unterminated attribute in <{name}>")))?;
+        attributes.push((key.trim(), unescape(&value[..end])));
+        rest = value[end + 1..].trim_start();
+    }
+
+    Ok(Tag {
+        name,
+        closing,
+        self_closing,
+        attributes,
+    })
+}
+
+/// Replaces the predefined XML entities and character references of
+/// `value`, leaving unknown entities as they are.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let c = entity.and_then(|(entity, _)| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        });
+        match (c, entity) {
+            (Some(c), Some((_, end))) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid nmap XML: {what}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, unescape, NmapService};
+    use crate::input::ScanType;
+    use crate::output::{nmap_xml, Protocol, ScanReport};
+    use crate::scanner::ScanSummary;
+    use std::fs;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn parses_services_and_versions() {
+        let xml = fs::read_to_string("fixtures/nmap.xml").unwrap();
+        let run = parse(&xml).unwrap();
+
+        assert_eq!(
+            run.args.as_deref(),
+            Some("nmap -sV -oX - -p 22,80,5353 10.0.0.1")
+        );
+        assert_eq!(run.hosts.len(), 1);
+        let host = &run.hosts[0];
+        assert!(host.up);
+        assert_eq!(host.address, Some("10.0.0.1".parse().unwrap()));
+        assert_eq!(host.hostnames, vec!["db.example.com"]);
+
+        let ports: Vec<_> = host
+            .ports
+            .iter()
+            .map(|port| (port.port, port.protocol, port.state.as_str()))
+            .collect();
+        assert_eq!(
+            ports,
+            vec![
+                (22, Protocol::Tcp, "open"),
+                (80, Protocol::Tcp, "closed"),
+                (5353, Protocol::Udp, "open|filtered"),
+            ]
+        );
+        assert_eq!(
+            host.ports[0].service,
+            Some(NmapService {
+                name: "ssh".to_string(),
+                product: Some("OpenSSH".to_string()),
+                version: Some("8.9p1 Ubuntu 3ubuntu0.1".to_string()),
+                extra_info: Some("Ubuntu Linux; protocol 2.0".to_string()),
+            })
+        );
+        assert_eq!(host.ports[1].service, None);
+    }
+
+    #[test]
+    fn reads_rustscan_xml() {
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        report.add("10.0.0.2:443".parse().unwrap(), Protocol::Tcp);
+        let xml = nmap_xml::render(&report, "rustscan -a \"10.0.0.2\"", ScanType::Connect);
+
+        let run = parse(&xml).unwrap();
+        assert_eq!(run.args.as_deref(), Some("rustscan -a \"10.0.0.2\""));
+        assert_eq!(run.hosts[0].ports[0].port, 443);
+        assert_eq!(
+            run.hosts[0].ports[0]
+                .service
+                .as_ref()
+                .map(|s| s.name.as_str()),
+            Some("https")
+        );
+    }
+
+    #[test]
+    fn rejects_broken_xml() {
+        assert!(parse("<nmaprun><host").is_err());
+        assert!(parse("<port protocol=tcp portid=\"22\">").is_err());
+        assert_eq!(
+            unescape("a &amp; b &#x41;&#66; &unknown;"),
+            "a & b AB &unknown;"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! A [`ScanReport`] as CSV, one row per open port, for spreadsheets.
+//!
+//! ```text
+//! host,port,protocol,state,timestamp
+//! 10.0.0.1,22,tcp,open,2023-11-14T22:13:20Z
+//! 10.0.0.1,53,udp,open,2023-11-14T22:13:20Z
+//! ```
+//!
+//! The timestamp is when the scan started, so the rows of several scans
+//! can be told apart once pasted together. When the SYN scan asked the
+//! ports for Multipath TCP or ECN, an `mptcp` or `ecn` column tells
+//! whether each agreed.
+use super::{civil_from_days, PortReport, PortState, ScanReport};
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const HEADER: &str = "host,port,protocol,state,timestamp";
+
+/// The columns of the TCP extensions, only written when a port was asked.
+type Extension = (&'static str, fn(&PortReport) -> Option<bool>);
+const EXTENSIONS: [Extension; 2] = [("mptcp", |port| port.mptcp), ("ecn", |port| port.ecn)];
+
+/// Renders `report` as CSV, with a header row.
+pub fn render(report: &ScanReport) -> String {
+    let timestamp = rfc3339(report.started_at);
+    let extensions: Vec<&Extension> = EXTENSIONS
+        .iter()
+        .filter(|(_, value)| {
+            report
+                .hosts
+                .iter()
+                .flat_map(|host| &host.ports)
+                .any(|port| value(port).is_some())
+        })
+        .collect();
+    let mut csv = HEADER.to_owned();
+    for (name, _) in &extensions {
+        let _ = write!(csv, ",{name}");
+    }
+    csv.push('\n');
+    for host in &report.hosts {
+        for port in &host.ports {
+            let state = match port.state {
+                PortState::Open => "open",
+            };
+            let _ = write!(
+                csv,
+                "{},{},{},{state},{timestamp}",
+                host.ip, port.port, port.protocol
+            );
+            for (_, value) in &extensions {
+                let _ = write!(csv, ",{}", flag(value(port)));
+            }
+            csv.push('\n');
+        }
+    }
+    csv
+}
+
+/// `true` or `false`, or an empty field for a port that wasn't asked.
+fn flag(value: Option<bool>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Writes `report` as CSV to `path`, or to stdout without one.
+pub fn write(report: &ScanReport, path: Option<&Path>) -> io::Result<()> {
+    let csv = render(report);
+    match path {
+        Some(path) => fs::write(path, csv),
+        None => {
+            print!("{csv}");
+            Ok(())
+        }
+    }
+}
+
+/// `secs` since the epoch in UTC, e.g. `1970-01-01T00:00:00Z`.
+fn rfc3339(secs: u64) -> String {
+    let (year, month, day) = civil_from_days(secs / 86_400);
+    let time = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, rfc3339};
+    use crate::output::{Protocol, ScanReport};
+    use crate::scanner::{ScanSummary, TcpExtensions};
+    use std::collections::BTreeMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn writes_a_row_per_open_port() {
+        let started = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut report = ScanReport::new(started, ScanSummary::default());
+        report.add("10.0.0.2:80".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.1:53".parse().unwrap(), Protocol::Udp);
+        report.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+
+        assert_eq!(
+            render(&report),
+            "host,port,protocol,state,timestamp\n\
+             10.0.0.1,22,tcp,open,2023-11-14T22:13:20Z\n\
+             10.0.0.1,53,udp,open,2023-11-14T22:13:20Z\n\
+             10.0.0.2,80,tcp,open,2023-11-14T22:13:20Z\n"
+        );
+    }
+
+    #[test]
+    fn adds_a_column_per_asked_extension() {
+        let started = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut report = ScanReport::new(started, ScanSummary::default());
+        report.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.1:53".parse().unwrap(), Protocol::Udp);
+        let mut agreed = BTreeMap::new();
+        agreed.insert(
+            "10.0.0.1:22".parse().unwrap(),
+            TcpExtensions {
+                mptcp: true,
+                ecn: false,
+            },
+        );
+        let asked = TcpExtensions {
+            mptcp: true,
+            ecn: true,
+        };
+        report.add_tcp_extensions(asked, &agreed);
+
+        assert_eq!(
+            render(&report),
+            "host,port,protocol,state,timestamp,mptcp,ecn\n\
+             10.0.0.1,22,tcp,open,2023-11-14T22:13:20Z,true,false\n\
+             10.0.0.1,53,udp,open,2023-11-14T22:13:20Z,,\n"
+        );
+    }
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+    }
+}
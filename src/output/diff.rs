@@ -0,0 +1,186 @@
+//! Changes between the reports of two scans of the same targets.
+//!
+//! `rustscan diff <old> <new>` compares two reports written with
+//! `--output report:<path>` and lists, per host, the ports that opened and
+//! closed since the older scan, along with the ones still open. A host
+//! missing from a report had no open ports in that scan.
+//!
+//! ```text
+//! 10.0.0.1 (db.example.com)
+//!   + 5432/tcp postgresql
+//!   - 23/tcp telnet
+//!   = 22/tcp ssh
+//! ```
+use super::{HostReport, PortReport, ScanReport};
+
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::IpAddr;
+
+/// The changes of the ports of one host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HostDiff {
+    pub ip: IpAddr,
+    /// The hostnames of the host in the newer report, or in the older one
+    /// when the host has no open ports anymore.
+    pub hostnames: Vec<String>,
+    /// Open in the newer report only.
+    pub opened: Vec<PortReport>,
+    /// Open in the older report only.
+    pub closed: Vec<PortReport>,
+    /// Open in both reports.
+    pub unchanged: Vec<PortReport>,
+}
+
+impl HostDiff {
+    /// Whether a port opened or closed on the host.
+    pub fn has_changes(&self) -> bool {
+        !self.opened.is_empty() || !self.closed.is_empty()
+    }
+}
+
+/// The changes between two reports, by host in the order of their IPs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ReportDiff {
+    pub hosts: Vec<HostDiff>,
+}
+
+impl ReportDiff {
+    /// Compares the `new` report against the `old` one.
+    ///
+    /// ```rust
+    /// # use rustscan::output::{diff::ReportDiff, Protocol, ScanReport};
+    /// # use rustscan::scanner::ScanSummary;
+    /// # use std::time::UNIX_EPOCH;
+    /// let mut old = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+    /// old.add("10.0.0.1:23".parse().unwrap(), Protocol::Tcp);
+    /// let mut new = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+    /// new.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+    ///
+    /// let diff = ReportDiff::new(&old, &new);
+    /// assert_eq!(diff.hosts[0].opened[0].port, 22);
+    /// assert_eq!(diff.hosts[0].closed[0].port, 23);
+    /// ```
+    pub fn new(old: &ScanReport, new: &ScanReport) -> Self {
+        let mut hosts: BTreeMap<IpAddr, (Option<&HostReport>, Option<&HostReport>)> =
+            BTreeMap::new();
+        for host in &old.hosts {
+            hosts.entry(host.ip).or_default().0 = Some(host);
+        }
+        for host in &new.hosts {
+            hosts.entry(host.ip).or_default().1 = Some(host);
+        }
+
+        let hosts = hosts
+            .into_iter()
+            .map(|(ip, (old, new))| {
+                let old_ports = old.map_or(&[][..], |host| host.ports.as_slice());
+                let new_ports = new.map_or(&[][..], |host| host.ports.as_slice());
+                let (unchanged, opened) = new_ports
+                    .iter()
+                    .cloned()
+                    .partition(|port| contains(old_ports, port));
+                HostDiff {
+                    ip,
+                    hostnames: new
+                        .or(old)
+                        .map(|host| host.hostnames.clone())
+                        .unwrap_or_default(),
+                    opened,
+                    closed: old_ports
+                        .iter()
+                        .filter(|port| !contains(new_ports, port))
+                        .cloned()
+                        .collect(),
+                    unchanged,
+                }
+            })
+            .collect();
+        Self { hosts }
+    }
+
+    /// Whether a port opened or closed on any host.
+    pub fn has_changes(&self) -> bool {
+        self.hosts.iter().any(HostDiff::has_changes)
+    }
+}
+
+/// Whether `port` is open over the same protocol in `ports`.
+fn contains(ports: &[PortReport], port: &PortReport) -> bool {
+    ports
+        .iter()
+        .any(|other| other.port == port.port && other.protocol == port.protocol)
+}
+
+impl fmt::Display for HostDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.ip)?;
+        if !self.hostnames.is_empty() {
+            write!(f, " ({})", self.hostnames.join(", "))?;
+        }
+        for (sign, ports) in [
+            ('+', &self.opened),
+            ('-', &self.closed),
+            ('=', &self.unchanged),
+        ] {
+            for port in ports {
+                write!(f, "\n  {sign} {}/{}", port.port, port.protocol)?;
+                if let Some(service) = &port.service {
+                    write!(f, " {service}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReportDiff;
+    use crate::output::{Protocol, ScanReport};
+    use crate::scanner::ScanSummary;
+    use std::time::UNIX_EPOCH;
+
+    fn report(sockets: &[(&str, Protocol)]) -> ScanReport {
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        for (socket, protocol) in sockets {
+            report.add(socket.parse().unwrap(), *protocol);
+        }
+        report
+    }
+
+    #[test]
+    fn reports_opened_closed_and_unchanged_ports() {
+        let old = report(&[
+            ("10.0.0.1:22", Protocol::Tcp),
+            ("10.0.0.1:23", Protocol::Tcp),
+            ("10.0.0.2:80", Protocol::Tcp),
+        ]);
+        let new = report(&[
+            ("10.0.0.1:22", Protocol::Tcp),
+            ("10.0.0.1:22", Protocol::Udp),
+            ("10.0.0.3:443", Protocol::Tcp),
+        ]);
+
+        let diff = ReportDiff::new(&old, &new);
+
+        assert!(diff.has_changes());
+        assert_eq!(diff.hosts.len(), 3);
+        assert_eq!(
+            diff.hosts[0].to_string(),
+            "10.0.0.1\n  + 22/udp\n  - 23/tcp telnet\n  = 22/tcp ssh"
+        );
+        assert_eq!(diff.hosts[1].to_string(), "10.0.0.2\n  - 80/tcp http");
+        assert_eq!(diff.hosts[2].to_string(), "10.0.0.3\n  + 443/tcp https");
+
+        assert!(!ReportDiff::new(&new, &new).has_changes());
+    }
+
+    #[test]
+    fn reads_reports_back() {
+        let old = report(&[("10.0.0.1:22", Protocol::Tcp)]);
+        let read: ScanReport = serde_json::from_str(&old.to_json()).unwrap();
+        assert_eq!(read, old);
+    }
+}
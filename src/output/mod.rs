@@ -0,0 +1,638 @@
+//! Machine readable reports of a whole scan.
+//!
+//! Unlike the `json:<path>` output, which streams events while the scan
+//! runs, a report is written once the scan is over and holds every host
+//! with its open ports along with the summary of the scan. `--output json`
+//! prints it to stdout and `--output report:<path>` writes it to a file.
+//! [`nmap_xml`] writes the same report in the XML format of nmap, for
+//! `--output xml` and `--output xml:<path>`, [`csv`] its open ports as CSV
+//! rows, and [`diff`] compares two reports.
+//!
+//! ```json
+//! {
+//!   "started_at": 1700000000,
+//!   "hosts": [
+//!     {
+//!       "ip": "10.0.0.1",
+//!       "hostnames": ["db.example.com"],
+//!       "tags": [],
+//!       "mac": "00:50:56:c0:00:08",
+//!       "vendor": "VMware",
+//!       "ports": [{ "port": 5432, "protocol": "tcp", "state": "open", "service": "postgresql" }]
+//!     }
+//!   ],
+//!   "summary": { "hosts_scanned": 1, "open_ports": 1, "duration_ms": 1520, ... },
+//!   "metrics": { "duration_ms": 1520, "phases": { "Portscan": 1.52 }, "pps": 658.0, ... },
+//!   "backoff_events": [{ "ip": "10.0.0.1", "elapsed_ms": 840, "delay_ms": 100, ... }]
+//! }
+//! ```
+use crate::address::SampleCoverage;
+use crate::benchmark::ScanMetrics;
+use crate::discovery::HardwareAddress;
+use crate::probes::{ServiceInfo, TlsCertificate};
+use crate::scanner::uptime::UptimeEstimate;
+use crate::scanner::{BackoffEvent, ScanSummary, TcpExtensions};
+use crate::services;
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub mod csv;
+pub mod diff;
+pub mod nmap_grep;
+pub mod nmap_xml;
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortState {
+    Open,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortReport {
+    pub port: u16,
+    pub protocol: Protocol,
+    pub state: PortState,
+    /// The service usually running on the port, or the one the service
+    /// probes found.
+    pub service: Option<String>,
+    /// The software behind the port, found by the service probes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The certificate the port sent in a TLS handshake, with `--tls-info`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsCertificate>,
+    /// Whether the port agreed to Multipath TCP, when the SYN scan asked
+    /// for it with `--mptcp`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mptcp: Option<bool>,
+    /// Whether the port agreed to Explicit Congestion Notification, when
+    /// the SYN scan asked for it with `--ecn`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecn: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostReport {
+    pub ip: IpAddr,
+    /// The hostnames given as targets that resolved to the IP.
+    pub hostnames: Vec<String>,
+    pub tags: Vec<String>,
+    /// The MAC address of a host on a local network.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    /// The vendor of the network card with the MAC address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<String>,
+    pub ports: Vec<PortReport>,
+    /// How long the host has been up, estimated from the TCP timestamps
+    /// of a SYN scan.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime: Option<UptimeReport>,
+    /// The scripts run on the host, in the order they finished.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scripts: Vec<ScriptReport>,
+}
+
+/// An [`UptimeEstimate`] in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UptimeReport {
+    pub seconds: u64,
+    /// The tick rate of the TCP timestamp clock the estimate comes from.
+    pub frequency_hz: u32,
+}
+
+impl From<UptimeEstimate> for UptimeReport {
+    fn from(estimate: UptimeEstimate) -> Self {
+        Self {
+            seconds: estimate.uptime.as_secs(),
+            frequency_hz: estimate.frequency_hz,
+        }
+    }
+}
+
+/// How a script run on a host went.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptReport {
+    /// The command the script ran as, once its call format was filled in.
+    pub command: String,
+    /// The exit code of the script, or the signal that killed it.
+    pub exit_code: i32,
+    /// What the script printed on stdout.
+    pub output: String,
+    #[serde(default)]
+    pub stderr: String,
+}
+
+/// [`ScanSummary`] with its duration in milliseconds and its rate.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SummaryReport {
+    pub hosts_scanned: usize,
+    pub hosts_with_open_ports: usize,
+    pub open_ports: usize,
+    pub probes: u64,
+    pub closed: u64,
+    pub timed_out: u64,
+    pub errors: u64,
+    pub duration_ms: u64,
+    /// Sockets probed per second.
+    pub rate: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<SampleCoverage>,
+}
+
+impl From<ScanSummary> for SummaryReport {
+    fn from(summary: ScanSummary) -> Self {
+        Self {
+            hosts_scanned: summary.hosts_scanned,
+            hosts_with_open_ports: summary.hosts_with_open_ports,
+            open_ports: summary.open_ports,
+            probes: summary.probes,
+            closed: summary.closed,
+            timed_out: summary.timed_out,
+            errors: summary.errors,
+            duration_ms: u64::try_from(summary.duration.as_millis()).unwrap_or(u64::MAX),
+            rate: summary.rate(),
+            coverage: summary.coverage,
+        }
+    }
+}
+
+/// A [`BackoffEvent`] with its times in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackoffReport {
+    pub ip: IpAddr,
+    /// Time since the start of the scan.
+    pub elapsed_ms: u64,
+    /// The share of probes the host answered in its first window.
+    pub baseline_rate: f64,
+    /// The share of probes the host answered when it was slowed down.
+    pub recent_rate: f64,
+    /// Delay applied before every probe to the host from then on.
+    pub delay_ms: u64,
+}
+
+impl From<BackoffEvent> for BackoffReport {
+    fn from(event: BackoffEvent) -> Self {
+        Self {
+            ip: event.ip,
+            elapsed_ms: u64::try_from(event.elapsed.as_millis()).unwrap_or(u64::MAX),
+            baseline_rate: event.baseline_rate,
+            recent_rate: event.recent_rate,
+            delay_ms: u64::try_from(event.delay.as_millis()).unwrap_or(u64::MAX),
+        }
+    }
+}
+
+/// The results of a scan, by host, in the order of their IPs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanReport {
+    /// When the scan started, in seconds since the Unix epoch.
+    pub started_at: u64,
+    pub hosts: Vec<HostReport>,
+    pub summary: SummaryReport,
+    /// Per-phase timing and error counts, see [`Scanner::metrics`](crate::scanner::Scanner::metrics).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<ScanMetrics>,
+    /// Whether the scan was stopped early, so only some ports were probed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    /// Hosts slowed down because their answer rate suddenly dropped, see
+    /// [`Scanner::backoff_events`](crate::scanner::Scanner::backoff_events).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backoff_events: Vec<BackoffReport>,
+}
+
+impl ScanReport {
+    /// An empty report of a scan started at `started`.
+    pub fn new(started: SystemTime, summary: ScanSummary) -> Self {
+        Self {
+            started_at: started
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs())
+                .unwrap_or_default(),
+            hosts: Vec::new(),
+            summary: summary.into(),
+            metrics: None,
+            interrupted: false,
+            backoff_events: Vec::new(),
+        }
+    }
+
+    /// Adds an open port.
+    pub fn add(&mut self, socket: SocketAddr, protocol: Protocol) {
+        let host = self.host(socket.ip());
+        let port = PortReport {
+            port: socket.port(),
+            protocol,
+            state: PortState::Open,
+            service: services::service_name(socket.port(), protocol).map(ToOwned::to_owned),
+            product: None,
+            version: None,
+            tls: None,
+            mptcp: None,
+            ecn: None,
+        };
+        if let Err(index) = host
+            .ports
+            .binary_search_by_key(&(port.port, protocol as u8), |port| {
+                (port.port, port.protocol as u8)
+            })
+        {
+            host.ports.insert(index, port);
+        }
+    }
+
+    /// Names the hosts with the hostnames that resolved to them.
+    pub fn add_hostnames(&mut self, hostnames: &BTreeMap<String, Vec<IpAddr>>) {
+        for host in &mut self.hosts {
+            host.hostnames = hostnames
+                .iter()
+                .filter(|(_, ips)| ips.contains(&host.ip))
+                .map(|(hostname, _)| hostname.clone())
+                .collect();
+        }
+    }
+
+    /// Adds the tags each host was annotated with.
+    pub fn add_tags(&mut self, tags: &BTreeMap<IpAddr, Vec<String>>) {
+        for host in &mut self.hosts {
+            host.tags = tags.get(&host.ip).cloned().unwrap_or_default();
+        }
+    }
+
+    /// Adds what the service probes found on the TCP ports.
+    pub fn add_services(&mut self, services: &BTreeMap<SocketAddr, ServiceInfo>) {
+        for host in &mut self.hosts {
+            for port in &mut host.ports {
+                let socket = SocketAddr::new(host.ip, port.port);
+                let Some(service) = services.get(&socket) else {
+                    continue;
+                };
+                if port.protocol == Protocol::Tcp {
+                    port.service = Some(service.name.clone());
+                    port.product.clone_from(&service.product);
+                    port.version.clone_from(&service.version);
+                }
+            }
+        }
+    }
+
+    /// Adds the certificates the TCP ports sent in a TLS handshake.
+    pub fn add_certificates(&mut self, certificates: &BTreeMap<SocketAddr, TlsCertificate>) {
+        for host in &mut self.hosts {
+            for port in &mut host.ports {
+                if port.protocol == Protocol::Tcp {
+                    port.tls = certificates
+                        .get(&SocketAddr::new(host.ip, port.port))
+                        .cloned();
+                }
+            }
+        }
+    }
+
+    /// Adds whether the TCP ports agreed to the `asked` extensions, for the
+    /// ports the SYN scan found open.
+    pub fn add_tcp_extensions(
+        &mut self,
+        asked: TcpExtensions,
+        agreed: &BTreeMap<SocketAddr, TcpExtensions>,
+    ) {
+        for host in &mut self.hosts {
+            for port in &mut host.ports {
+                let socket = SocketAddr::new(host.ip, port.port);
+                let Some(agreed) = agreed.get(&socket) else {
+                    continue;
+                };
+                if port.protocol == Protocol::Tcp {
+                    port.mptcp = asked.mptcp.then_some(agreed.mptcp);
+                    port.ecn = asked.ecn.then_some(agreed.ecn);
+                }
+            }
+        }
+    }
+
+    /// Adds the uptime estimated for each host.
+    pub fn add_uptimes(&mut self, uptimes: &BTreeMap<IpAddr, UptimeEstimate>) {
+        for host in &mut self.hosts {
+            host.uptime = uptimes.get(&host.ip).copied().map(UptimeReport::from);
+        }
+    }
+
+    /// Adds the hosts that were slowed down during the scan.
+    pub fn add_backoff_events(&mut self, events: &[BackoffEvent]) {
+        self.backoff_events
+            .extend(events.iter().copied().map(BackoffReport::from));
+    }
+
+    /// Adds a script that ran on `ip`.
+    pub fn add_script(&mut self, ip: IpAddr, script: ScriptReport) {
+        if let Ok(index) = self.hosts.binary_search_by_key(&ip, |host| host.ip) {
+            self.hosts[index].scripts.push(script);
+        }
+    }
+
+    /// Adds the MAC addresses of the hosts on the local networks.
+    pub fn add_hardware(&mut self, hardware: &BTreeMap<IpAddr, HardwareAddress>) {
+        for host in &mut self.hosts {
+            let address = hardware.get(&host.ip);
+            host.mac = address.map(|address| address.mac.clone());
+            host.vendor = address.and_then(|address| address.vendor.map(ToOwned::to_owned));
+        }
+    }
+
+    /// The report as pretty printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Reads a report written with [`ScanReport::write`].
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(io::Error::from)
+    }
+
+    /// Writes the report to `path`, or to stdout without one.
+    pub fn write(&self, path: Option<&Path>) -> io::Result<()> {
+        match path {
+            Some(path) => fs::write(path, self.to_json() + "\n"),
+            None => {
+                println!("{}", self.to_json());
+                Ok(())
+            }
+        }
+    }
+
+    fn host(&mut self, ip: IpAddr) -> &mut HostReport {
+        let index = match self.hosts.binary_search_by_key(&ip, |host| host.ip) {
+            Ok(index) => index,
+            Err(index) => {
+                self.hosts.insert(
+                    index,
+                    HostReport {
+                        ip,
+                        hostnames: Vec::new(),
+                        tags: Vec::new(),
+                        mac: None,
+                        vendor: None,
+                        ports: Vec::new(),
+                        uptime: None,
+                        scripts: Vec::new(),
+                    },
+                );
+                index
+            }
+        };
+        &mut self.hosts[index]
+    }
+}
+
+/// The year, month and day of `days` since the epoch, see
+/// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: u64) -> (u64, usize, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month as usize, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Protocol, ScanReport, ScriptReport};
+    use crate::discovery::HardwareAddress;
+    use crate::probes::{ServiceInfo, TlsCertificate};
+    use crate::scanner::uptime::UptimeEstimate;
+    use crate::scanner::{BackoffEvent, ScanSummary, TcpExtensions};
+    use std::collections::BTreeMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn reports_hosts_in_order() {
+        let summary = ScanSummary {
+            hosts_scanned: 2,
+            open_ports: 3,
+            duration: Duration::from_millis(1500),
+            ..Default::default()
+        };
+        let mut report = ScanReport::new(UNIX_EPOCH + Duration::from_secs(42), summary);
+        report.add("10.0.0.2:443".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.1:80".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+        let mut hostnames = BTreeMap::new();
+        hostnames.insert(
+            "web.example.com".to_owned(),
+            vec!["10.0.0.2".parse().unwrap()],
+        );
+        report.add_hostnames(&hostnames);
+
+        assert_eq!(report.hosts.len(), 2);
+        assert_eq!(report.hosts[0].ports.len(), 2);
+        assert_eq!(report.hosts[0].ports[0].port, 22);
+        assert_eq!(report.hosts[1].hostnames, vec!["web.example.com"]);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["started_at"], 42);
+        assert_eq!(json["hosts"][0]["ip"], "10.0.0.1");
+        assert_eq!(json["hosts"][0]["ports"][0]["protocol"], "tcp");
+        assert_eq!(json["hosts"][0]["ports"][0]["state"], "open");
+        assert_eq!(json["hosts"][0]["ports"][0]["service"], "ssh");
+        assert_eq!(json["summary"]["duration_ms"], 1500);
+        assert!(json["summary"].get("coverage").is_none());
+        assert!(json["hosts"][0].get("mac").is_none());
+    }
+
+    #[test]
+    fn reports_hardware_addresses() {
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        report.add("192.168.1.7:631".parse().unwrap(), Protocol::Tcp);
+        let mut hardware = BTreeMap::new();
+        hardware.insert(
+            "192.168.1.7".parse().unwrap(),
+            HardwareAddress {
+                mac: "3c:d9:2b:01:02:03".to_owned(),
+                vendor: Some("Hewlett Packard"),
+            },
+        );
+        report.add_hardware(&hardware);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["hosts"][0]["mac"], "3c:d9:2b:01:02:03");
+        assert_eq!(json["hosts"][0]["vendor"], "Hewlett Packard");
+    }
+
+    #[test]
+    fn reports_probed_services() {
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        report.add("10.0.0.1:2222".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.1:2222".parse().unwrap(), Protocol::Udp);
+        let mut services = BTreeMap::new();
+        services.insert(
+            "10.0.0.1:2222".parse().unwrap(),
+            ServiceInfo {
+                name: "ssh".to_owned(),
+                product: Some("OpenSSH".to_owned()),
+                version: Some("9.6".to_owned()),
+            },
+        );
+        report.add_services(&services);
+
+        let tcp = &report.hosts[0].ports[0];
+        assert_eq!(tcp.service.as_deref(), Some("ssh"));
+        assert_eq!(tcp.product.as_deref(), Some("OpenSSH"));
+        assert_eq!(tcp.version.as_deref(), Some("9.6"));
+        assert_eq!(report.hosts[0].ports[1].product, None);
+    }
+
+    #[test]
+    fn reports_scripts() {
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        report.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.2:22".parse().unwrap(), Protocol::Tcp);
+        report.add_script(
+            "10.0.0.1".parse().unwrap(),
+            ScriptReport {
+                command: "nmap -p 22 10.0.0.1".to_owned(),
+                exit_code: 0,
+                output: "22/tcp open ssh\n".to_owned(),
+                stderr: String::new(),
+            },
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["hosts"][0]["scripts"][0]["exit_code"], 0);
+        assert_eq!(json["hosts"][1].get("scripts"), None);
+    }
+
+    #[test]
+    fn reports_tls_certificates() {
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        report.add("10.0.0.1:443".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.1:80".parse().unwrap(), Protocol::Tcp);
+        let mut certificates = BTreeMap::new();
+        certificates.insert(
+            "10.0.0.1:443".parse().unwrap(),
+            TlsCertificate {
+                subject: "CN=example.com".to_owned(),
+                issuer: "CN=Example CA".to_owned(),
+                sans: vec!["example.com".to_owned()],
+                not_after: "2035-01-31T12:00:00Z".to_owned(),
+            },
+        );
+        report.add_certificates(&certificates);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        let ports = &json["hosts"][0]["ports"];
+        assert_eq!(ports[0].get("tls"), None);
+        assert_eq!(ports[1]["tls"]["subject"], "CN=example.com");
+        assert_eq!(ports[1]["tls"]["sans"][0], "example.com");
+        assert_eq!(ports[1]["tls"]["not_after"], "2035-01-31T12:00:00Z");
+    }
+
+    #[test]
+    fn reports_uptimes() {
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        report.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.2:22".parse().unwrap(), Protocol::Tcp);
+        let mut uptimes = BTreeMap::new();
+        uptimes.insert(
+            "10.0.0.1".parse().unwrap(),
+            UptimeEstimate {
+                frequency_hz: 1000,
+                uptime: Duration::from_millis(86_400_500),
+            },
+        );
+        report.add_uptimes(&uptimes);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["hosts"][0]["uptime"]["seconds"], 86_400);
+        assert_eq!(json["hosts"][0]["uptime"]["frequency_hz"], 1000);
+        assert_eq!(json["hosts"][1].get("uptime"), None);
+    }
+
+    #[test]
+    fn reports_backoff_events() {
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json.get("backoff_events"), None);
+
+        report.add_backoff_events(&[BackoffEvent {
+            ip: "10.0.0.1".parse().unwrap(),
+            elapsed: Duration::from_millis(840),
+            baseline_rate: 0.5,
+            recent_rate: 0.125,
+            delay: Duration::from_millis(100),
+        }]);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        let event = &json["backoff_events"][0];
+        assert_eq!(event["ip"], "10.0.0.1");
+        assert_eq!(event["elapsed_ms"], 840);
+        assert_eq!(event["baseline_rate"], 0.5);
+        assert_eq!(event["recent_rate"], 0.125);
+        assert_eq!(event["delay_ms"], 100);
+    }
+
+    #[test]
+    fn reports_tcp_extensions() {
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        report.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.1:80".parse().unwrap(), Protocol::Tcp);
+        report.add("[2001:db8::1]:80".parse().unwrap(), Protocol::Tcp);
+        let mut agreed = BTreeMap::new();
+        agreed.insert(
+            "10.0.0.1:22".parse().unwrap(),
+            TcpExtensions {
+                mptcp: true,
+                ecn: true,
+            },
+        );
+        agreed.insert("10.0.0.1:80".parse().unwrap(), TcpExtensions::default());
+        let asked = TcpExtensions {
+            mptcp: true,
+            ecn: false,
+        };
+        report.add_tcp_extensions(asked, &agreed);
+
+        assert_eq!(report.hosts[0].ports[0].mptcp, Some(true));
+        assert_eq!(report.hosts[0].ports[0].ecn, None);
+        assert_eq!(report.hosts[0].ports[1].mptcp, Some(false));
+        // Connect scanned, as the SYN scan only supports IPv4.
+        assert_eq!(report.hosts[1].ports[0].mptcp, None);
+    }
+}
@@ -0,0 +1,150 @@
+//! A [`ScanReport`] in the grepable format of `nmap -oG`, for the parsers
+//! and one-liners written against nmap's layout.
+//!
+//! ```text
+//! # RustScan 2.4.1 scan initiated Thu Jan  1 00:01:40 1970 as: rustscan -g -a 10.0.0.1
+//! Host: 10.0.0.1 (db.example.com)	Status: Up
+//! Host: 10.0.0.1 (db.example.com)	Ports: 22/open/tcp//ssh///, 53/open/udp//domain///
+//! # RustScan done at Thu Jan  1 00:01:42 1970 -- 1 IP address (1 host up) scanned in 2.50 seconds
+//! ```
+//!
+//! Like nmap's, the lines of a host are tab separated fields, ports being
+//! `port/state/protocol/owner/service/rpc info/version/`. Only hosts with
+//! open ports are listed.
+use super::{civil_from_days, ScanReport};
+
+use std::fmt::Write as _;
+
+/// Renders `report` of a scan run as `args`.
+pub fn render(report: &ScanReport, args: &str) -> String {
+    let finished = report.started_at + report.summary.duration_ms / 1000;
+    let mut grep = String::new();
+    let _ = writeln!(
+        grep,
+        "# RustScan {} scan initiated {} as: {args}",
+        env!("CARGO_PKG_VERSION"),
+        ctime(report.started_at),
+    );
+    for host in &report.hosts {
+        let name = host.hostnames.first().map_or("", String::as_str);
+        let _ = writeln!(grep, "Host: {} ({name})\tStatus: Up", host.ip);
+        let ports: Vec<String> = host
+            .ports
+            .iter()
+            .map(|port| {
+                let version: Vec<&str> = [&port.product, &port.version]
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str)
+                    .collect();
+                // Slashes separate the fields, nmap writes them as pipes.
+                format!(
+                    "{}/open/{}//{}//{}/",
+                    port.port,
+                    port.protocol,
+                    port.service
+                        .as_deref()
+                        .unwrap_or_default()
+                        .replace('/', "|"),
+                    version.join(" ").replace('/', "|")
+                )
+            })
+            .collect();
+        let _ = writeln!(
+            grep,
+            "Host: {} ({name})\tPorts: {}",
+            host.ip,
+            ports.join(", ")
+        );
+    }
+
+    let up = report.hosts.len();
+    let total = report.summary.hosts_scanned.max(up);
+    let _ = writeln!(
+        grep,
+        "# RustScan done at {} -- {total} IP {} ({up} {} up) scanned in {:.2} seconds",
+        ctime(finished),
+        if total == 1 { "address" } else { "addresses" },
+        if up == 1 { "host" } else { "hosts" },
+        report.summary.duration_ms as f64 / 1000.0,
+    );
+    grep
+}
+
+/// `secs` since the epoch the way C's `ctime` writes them, in UTC, e.g.
+/// `Thu Jan  1 00:00:00 1970`.
+pub(super) fn ctime(secs: u64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let days = secs / 86_400;
+    let time = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{} {} {day:>2} {:02}:{:02}:{:02} {year}",
+        WEEKDAYS[(days % 7) as usize],
+        MONTHS[month - 1],
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ctime, render};
+    use crate::output::{Protocol, ScanReport};
+    use crate::scanner::ScanSummary;
+    use std::collections::BTreeMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn writes_nmap_grepable_lines() {
+        let summary = ScanSummary {
+            hosts_scanned: 3,
+            open_ports: 3,
+            duration: Duration::from_millis(2500),
+            ..Default::default()
+        };
+        let mut report = ScanReport::new(UNIX_EPOCH + Duration::from_secs(100), summary);
+        report.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.1:53".parse().unwrap(), Protocol::Udp);
+        report.add("10.0.0.2:4".parse().unwrap(), Protocol::Tcp);
+        let mut hostnames = BTreeMap::new();
+        hostnames.insert(
+            "db.example.com".to_owned(),
+            vec!["10.0.0.1".parse().unwrap()],
+        );
+        report.add_hostnames(&hostnames);
+
+        let grep = render(&report, "rustscan -g -a 10.0.0.0/30");
+        let lines: Vec<&str> = grep.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            format!(
+                "# RustScan {} scan initiated Thu Jan  1 00:01:40 1970 as: rustscan -g -a 10.0.0.0/30",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        assert_eq!(lines[1], "Host: 10.0.0.1 (db.example.com)\tStatus: Up");
+        assert_eq!(
+            lines[2],
+            "Host: 10.0.0.1 (db.example.com)\tPorts: 22/open/tcp//ssh///, 53/open/udp//domain///"
+        );
+        assert_eq!(lines[4], "Host: 10.0.0.2 ()\tPorts: 4/open/tcp/////");
+        assert_eq!(
+            lines[5],
+            "# RustScan done at Thu Jan  1 00:01:42 1970 -- 3 IP addresses (2 hosts up) scanned in 2.50 seconds"
+        );
+    }
+
+    #[test]
+    fn formats_dates_like_ctime() {
+        assert_eq!(ctime(0), "Thu Jan  1 00:00:00 1970");
+        assert_eq!(ctime(951_782_400), "Tue Feb 29 00:00:00 2000");
+        assert_eq!(ctime(1_704_067_199), "Sun Dec 31 23:59:59 2023");
+    }
+}
@@ -0,0 +1,352 @@
+//! A [`ScanReport`] in the XML format of `nmap -oX`, for the tools that
+//! import nmap results, like Metasploit's `db_import`.
+//!
+//! Only port states are known, so every host with open ports is reported
+//! up with `-Pn` style reasons, and services are named from the services
+//! table like nmap does without `-sV`. Certificates collected with
+//! `--tls-info` are written as the output of the `ssl-cert` script, and
+//! whether a port agreed to Multipath TCP or ECN as that of an `mptcp` or
+//! `ecn` script. Uptimes estimated by the SYN scan go in `<uptime>`.
+use super::nmap_grep::ctime;
+use super::{Protocol, ScanReport};
+use crate::input::ScanType;
+use crate::probes::TlsCertificate;
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// The version of the nmap XML format written.
+const XML_OUTPUT_VERSION: &str = "1.05";
+
+/// Renders `report` of a scan run as `args` with `scan_type`.
+pub fn render(report: &ScanReport, args: &str, scan_type: ScanType) -> String {
+    let (scan, reason) = match scan_type {
+        ScanType::Connect => ("connect", "syn-ack"),
+        ScanType::Syn => ("syn", "syn-ack"),
+    };
+    let finished = report.started_at + report.summary.duration_ms / 1000;
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE nmaprun>\n");
+    let _ = writeln!(
+        xml,
+        "<nmaprun scanner=\"rustscan\" args=\"{}\" start=\"{}\" version=\"{}\" xmloutputversion=\"{XML_OUTPUT_VERSION}\">",
+        escape(args),
+        report.started_at,
+        env!("CARGO_PKG_VERSION"),
+    );
+    for protocol in [Protocol::Tcp, Protocol::Udp] {
+        let ports = scanned_ports(report, protocol);
+        if ports.is_empty() {
+            continue;
+        }
+        let (kind, name) = match protocol {
+            Protocol::Tcp => (scan, "tcp"),
+            Protocol::Udp => ("udp", "udp"),
+        };
+        let list: Vec<String> = ports.iter().map(ToString::to_string).collect();
+        let _ = writeln!(
+            xml,
+            "<scaninfo type=\"{kind}\" protocol=\"{name}\" numservices=\"{}\" services=\"{}\"/>",
+            ports.len(),
+            list.join(",")
+        );
+    }
+    xml.push_str("<verbose level=\"0\"/>\n<debugging level=\"0\"/>\n");
+
+    for host in &report.hosts {
+        let _ = writeln!(
+            xml,
+            "<host starttime=\"{}\" endtime=\"{finished}\"><status state=\"up\" reason=\"user-set\" reason_ttl=\"0\"/>",
+            report.started_at
+        );
+        let family = match host.ip {
+            IpAddr::V4(_) => "ipv4",
+            IpAddr::V6(_) => "ipv6",
+        };
+        let _ = writeln!(xml, "<address addr=\"{}\" addrtype=\"{family}\"/>", host.ip);
+        if let Some(mac) = &host.mac {
+            let _ = write!(
+                xml,
+                "<address addr=\"{}\" addrtype=\"mac\"",
+                escape(&mac.to_uppercase())
+            );
+            if let Some(vendor) = &host.vendor {
+                let _ = write!(xml, " vendor=\"{}\"", escape(vendor));
+            }
+            xml.push_str("/>\n");
+        }
+        xml.push_str("<hostnames>\n");
+        for hostname in &host.hostnames {
+            let _ = writeln!(
+                xml,
+                "<hostname name=\"{}\" type=\"user\"/>",
+                escape(hostname)
+            );
+        }
+        xml.push_str("</hostnames>\n<ports>\n");
+        for port in &host.ports {
+            let (protocol, reason) = match port.protocol {
+                Protocol::Tcp => ("tcp", reason),
+                Protocol::Udp => ("udp", "udp-response"),
+            };
+            let _ = write!(
+                xml,
+                "<port protocol=\"{protocol}\" portid=\"{}\"><state state=\"open\" reason=\"{reason}\" reason_ttl=\"0\"/>",
+                port.port
+            );
+            if let Some(service) = &port.service {
+                let _ = write!(xml, "<service name=\"{}\"", escape(service));
+                for (attribute, value) in [("product", &port.product), ("version", &port.version)] {
+                    if let Some(value) = value {
+                        let _ = write!(xml, " {attribute}=\"{}\"", escape(value));
+                    }
+                }
+                // Like nmap, services the probes found are certain.
+                if port.product.is_some() || port.version.is_some() {
+                    xml.push_str(" method=\"probed\" conf=\"10\"/>");
+                } else {
+                    xml.push_str(" method=\"table\" conf=\"3\"/>");
+                }
+            }
+            if let Some(certificate) = &port.tls {
+                let _ = write!(
+                    xml,
+                    "<script id=\"ssl-cert\" output=\"{}\"/>",
+                    escape(&ssl_cert(certificate))
+                );
+            }
+            for (id, agreed) in [("mptcp", port.mptcp), ("ecn", port.ecn)] {
+                if let Some(agreed) = agreed {
+                    let _ = write!(
+                        xml,
+                        "<script id=\"{id}\" output=\"{}\"/>",
+                        if agreed { "supported" } else { "not supported" }
+                    );
+                }
+            }
+            xml.push_str("</port>\n");
+        }
+        xml.push_str("</ports>\n");
+        if let Some(uptime) = &host.uptime {
+            let _ = writeln!(
+                xml,
+                "<uptime seconds=\"{}\" lastboot=\"{}\"/>",
+                uptime.seconds,
+                ctime(report.started_at.saturating_sub(uptime.seconds))
+            );
+        }
+        xml.push_str("</host>\n");
+    }
+
+    let up = report.hosts.len();
+    let total = report.summary.hosts_scanned.max(up);
+    let sampled = report
+        .summary
+        .coverage
+        .map(|coverage| format!(", sampled {coverage}"))
+        .unwrap_or_default();
+    let _ = writeln!(
+        xml,
+        "<runstats><finished time=\"{finished}\" elapsed=\"{:.2}\" summary=\"RustScan done: {total} IP addresses ({up} hosts up) scanned{sampled}\" exit=\"success\"/><hosts up=\"{up}\" down=\"{}\" total=\"{total}\"/></runstats>",
+        report.summary.duration_ms as f64 / 1000.0,
+        total - up,
+    );
+    xml.push_str("</nmaprun>\n");
+    xml
+}
+
+/// Writes `report` as nmap XML to `path`, or to stdout without one.
+pub fn write(
+    report: &ScanReport,
+    args: &str,
+    scan_type: ScanType,
+    path: Option<&Path>,
+) -> io::Result<()> {
+    let xml = render(report, args, scan_type);
+    match path {
+        Some(path) => fs::write(path, xml),
+        None => {
+            print!("{xml}");
+            Ok(())
+        }
+    }
+}
+
+/// The open ports of every host over `protocol`, sorted.
+fn scanned_ports(report: &ScanReport, protocol: Protocol) -> Vec<u16> {
+    let mut ports: Vec<u16> = report
+        .hosts
+        .iter()
+        .flat_map(|host| &host.ports)
+        .filter(|port| port.protocol == protocol)
+        .map(|port| port.port)
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+/// The certificate the way the `ssl-cert` script of nmap writes it.
+fn ssl_cert(certificate: &TlsCertificate) -> String {
+    let mut output = format!("Subject: {}\n", certificate.subject);
+    if !certificate.sans.is_empty() {
+        let sans: Vec<String> = certificate
+            .sans
+            .iter()
+            .map(|san| match san.parse::<IpAddr>() {
+                Ok(_) => format!("IP Address:{san}"),
+                Err(_) => format!("DNS:{san}"),
+            })
+            .collect();
+        let _ = writeln!(output, "Subject Alternative Name: {}", sans.join(", "));
+    }
+    let _ = write!(
+        output,
+        "Issuer: {}\nNot valid after:  {}",
+        certificate.issuer,
+        certificate.not_after.trim_end_matches('Z')
+    );
+    output
+}
+
+/// Escapes `value` for an attribute.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#xa;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, render, ssl_cert};
+    use crate::import::parse_nmap_xml;
+    use crate::input::ScanType;
+    use crate::output::{Protocol, ScanReport};
+    use crate::probes::TlsCertificate;
+    use crate::scanner::uptime::UptimeEstimate;
+    use crate::scanner::{ScanSummary, TcpExtensions};
+    use std::collections::BTreeMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn nmap_reads_it_back() {
+        let summary = ScanSummary {
+            hosts_scanned: 3,
+            open_ports: 3,
+            duration: Duration::from_millis(2500),
+            ..Default::default()
+        };
+        let mut report = ScanReport::new(UNIX_EPOCH + Duration::from_secs(100), summary);
+        report.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.1:80".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.2:53".parse().unwrap(), Protocol::Udp);
+        let mut hostnames = BTreeMap::new();
+        hostnames.insert(
+            "a&b.example.com".to_owned(),
+            vec!["10.0.0.1".parse().unwrap()],
+        );
+        report.add_hostnames(&hostnames);
+
+        let xml = render(&report, "rustscan -a 10.0.0.0/30", ScanType::Connect);
+
+        assert!(xml.contains(
+            "<scaninfo type=\"connect\" protocol=\"tcp\" numservices=\"2\" services=\"22,80\"/>"
+        ));
+        assert!(xml.contains("<hostname name=\"a&amp;b.example.com\" type=\"user\"/>"));
+        assert!(xml.contains("<service name=\"ssh\" method=\"table\" conf=\"3\"/>"));
+        assert!(xml.contains("<finished time=\"102\" elapsed=\"2.50\""));
+        assert!(xml.contains("<hosts up=\"2\" down=\"1\" total=\"3\"/>"));
+
+        let hosts = parse_nmap_xml(&xml).unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].ports, vec![22, 80]);
+    }
+
+    #[test]
+    fn writes_uptimes() {
+        let mut report = ScanReport::new(
+            UNIX_EPOCH + Duration::from_secs(90_000),
+            ScanSummary::default(),
+        );
+        report.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+        let mut uptimes = BTreeMap::new();
+        uptimes.insert(
+            "10.0.0.1".parse().unwrap(),
+            UptimeEstimate {
+                frequency_hz: 1000,
+                uptime: Duration::from_secs(86_400),
+            },
+        );
+        report.add_uptimes(&uptimes);
+
+        let xml = render(&report, "rustscan", ScanType::Syn);
+
+        assert!(xml.contains(
+            "</ports>\n<uptime seconds=\"86400\" lastboot=\"Thu Jan  1 01:00:00 1970\"/>\n</host>"
+        ));
+    }
+
+    #[test]
+    fn writes_tcp_extensions_as_scripts() {
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        report.add("10.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+        report.add("10.0.0.1:80".parse().unwrap(), Protocol::Tcp);
+        let mut agreed = BTreeMap::new();
+        agreed.insert(
+            "10.0.0.1:22".parse().unwrap(),
+            TcpExtensions {
+                mptcp: true,
+                ecn: false,
+            },
+        );
+        let asked = TcpExtensions {
+            mptcp: true,
+            ecn: true,
+        };
+        report.add_tcp_extensions(asked, &agreed);
+
+        let xml = render(&report, "rustscan", ScanType::Syn);
+
+        assert!(xml.contains(
+            "<script id=\"mptcp\" output=\"supported\"/><script id=\"ecn\" output=\"not supported\"/></port>"
+        ));
+        assert_eq!(xml.matches("id=\"mptcp\"").count(), 1);
+    }
+
+    #[test]
+    fn writes_certificates_like_ssl_cert() {
+        let certificate = TlsCertificate {
+            subject: "CN=example.com".to_owned(),
+            issuer: "CN=Example CA".to_owned(),
+            sans: vec!["example.com".to_owned(), "10.0.0.1".to_owned()],
+            not_after: "2035-01-31T12:00:00Z".to_owned(),
+        };
+        assert_eq!(
+            ssl_cert(&certificate),
+            "Subject: CN=example.com\nSubject Alternative Name: DNS:example.com, IP Address:10.0.0.1\nIssuer: CN=Example CA\nNot valid after:  2035-01-31T12:00:00"
+        );
+        assert_eq!(escape("a\nb"), "a&#xa;b");
+    }
+
+    #[test]
+    fn escapes_attributes() {
+        assert_eq!(
+            escape(r#"-- --script "x<y" 'z'"#),
+            "-- --script &quot;x&lt;y&quot; &apos;z&apos;"
+        );
+    }
+}
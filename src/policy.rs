@@ -0,0 +1,204 @@
+//! Per-target scan policies.
+//!
+//! The config file can override some settings for the targets of a
+//! network, so a single scan can treat e.g. a DMZ and internal ranges
+//! differently:
+//!
+//! ```toml
+//! [policy."10.0.0.0/8"]
+//! batch_size = 500
+//! timeout = 3000
+//!
+//! [policy."192.168.1.0/24"]
+//! udp = true
+//! ```
+//!
+//! When several policies match a target, the most specific network wins.
+use cidr_utils::cidr::IpCidr;
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Settings overridden for the targets of a network. Settings that aren't
+/// set keep their global value.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Policy {
+    pub batch_size: Option<usize>,
+    /// In milliseconds, like `--timeout`.
+    pub timeout: Option<u32>,
+    pub tries: Option<u8>,
+    pub udp: Option<bool>,
+}
+
+impl Policy {
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+            .map(|timeout| Duration::from_millis(timeout.into()))
+    }
+}
+
+/// The policies of the config file, by network.
+#[derive(Debug, Clone, Default)]
+pub struct Policies(Vec<(IpCidr, Policy)>);
+
+impl Policies {
+    /// Parses the networks policies are keyed by, most specific first. A
+    /// batch size of 0 is rejected, as it would never scan its targets.
+    ///
+    /// ```rust
+    /// # use rustscan::policy::{Policies, Policy};
+    /// # use std::collections::BTreeMap;
+    /// let mut config = BTreeMap::new();
+    /// config.insert("10.0.0.0/8".to_owned(), Policy { tries: Some(3), ..Policy::default() });
+    /// let policies = Policies::new(&config).unwrap();
+    /// assert_eq!(policies.get(&"10.1.2.3".parse().unwrap()).unwrap().tries, Some(3));
+    /// assert!(policies.get(&"192.168.0.1".parse().unwrap()).is_none());
+    /// ```
+    pub fn new(config: &BTreeMap<String, Policy>) -> Result<Self, String> {
+        let mut policies = config
+            .iter()
+            .map(|(network, policy)| {
+                if policy.batch_size == Some(0) {
+                    return Err(format!("Invalid batch size 0 in policy: {network}"));
+                }
+                IpCidr::from_str(network)
+                    .map(|cidr| (cidr, policy.clone()))
+                    .map_err(|_| format!("Invalid network in policy: {network}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        policies.sort_by_key(|(cidr, _)| std::cmp::Reverse(cidr.network_length()));
+        Ok(Self(policies))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Lowers the batch size of every policy with `cap`, the way the global
+    /// batch size is lowered to fit the open file and memory limits.
+    pub fn cap_batch_sizes(&mut self, mut cap: impl FnMut(usize) -> usize) {
+        for (_, policy) in &mut self.0 {
+            if let Some(batch_size) = policy.batch_size.as_mut() {
+                *batch_size = cap(*batch_size);
+            }
+        }
+    }
+
+    /// The policy of the most specific network containing `ip`.
+    pub fn get(&self, ip: &IpAddr) -> Option<&Policy> {
+        self.0
+            .iter()
+            .find(|(cidr, _)| cidr.contains(ip))
+            .map(|(_, policy)| policy)
+    }
+
+    /// Splits `ips` into the groups scanned together, each with its batch
+    /// size. Targets without a policy come first, in a single group.
+    pub fn groups(&self, ips: &[IpAddr], batch_size: usize) -> Vec<(Vec<IpAddr>, usize)> {
+        let mut groups: Vec<(Vec<IpAddr>, usize)> = vec![(Vec::new(), batch_size)];
+        let mut by_policy: BTreeMap<usize, usize> = BTreeMap::new();
+        for ip in ips {
+            let policy = self.0.iter().position(|(cidr, _)| cidr.contains(ip));
+            let group = match policy {
+                None => 0,
+                Some(index) => *by_policy.entry(index).or_insert_with(|| {
+                    let size = self.0[index].1.batch_size.unwrap_or(batch_size);
+                    groups.push((Vec::new(), size));
+                    groups.len() - 1
+                }),
+            };
+            groups[group].0.push(*ip);
+        }
+        groups.retain(|(ips, _)| !ips.is_empty());
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Policies, Policy};
+    use std::collections::BTreeMap;
+    use std::net::IpAddr;
+
+    fn policies() -> Policies {
+        let mut config = BTreeMap::new();
+        config.insert(
+            "10.0.0.0/8".to_owned(),
+            Policy {
+                batch_size: Some(500),
+                timeout: Some(3000),
+                ..Policy::default()
+            },
+        );
+        config.insert(
+            "10.1.0.0/16".to_owned(),
+            Policy {
+                udp: Some(true),
+                ..Policy::default()
+            },
+        );
+        Policies::new(&config).unwrap()
+    }
+
+    #[test]
+    fn most_specific_policy_wins() {
+        let policies = policies();
+        let internal: IpAddr = "10.2.0.1".parse().unwrap();
+        let lab: IpAddr = "10.1.0.1".parse().unwrap();
+
+        assert_eq!(policies.get(&internal).unwrap().timeout, Some(3000));
+        assert_eq!(policies.get(&lab).unwrap().udp, Some(true));
+        assert_eq!(policies.get(&lab).unwrap().timeout, None);
+    }
+
+    #[test]
+    fn groups_targets_by_policy() {
+        let ips: Vec<IpAddr> = ["10.2.0.1", "192.168.0.1", "10.1.0.1", "10.3.0.1"]
+            .iter()
+            .map(|ip| ip.parse().unwrap())
+            .collect();
+
+        assert_eq!(
+            policies().groups(&ips, 4500),
+            vec![
+                (vec![ips[1]], 4500),
+                (vec![ips[0], ips[3]], 500),
+                (vec![ips[2]], 4500),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_networks() {
+        let mut config = BTreeMap::new();
+        config.insert("not-a-network".to_owned(), Policy::default());
+        assert!(Policies::new(&config).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_batches() {
+        let mut config = BTreeMap::new();
+        config.insert(
+            "10.0.0.0/8".to_owned(),
+            Policy {
+                batch_size: Some(0),
+                ..Policy::default()
+            },
+        );
+        assert_eq!(
+            Policies::new(&config).unwrap_err(),
+            "Invalid batch size 0 in policy: 10.0.0.0/8"
+        );
+    }
+
+    #[test]
+    fn caps_batch_sizes() {
+        let mut policies = policies();
+        policies.cap_batch_sizes(|batch_size| batch_size.min(100));
+        let ips: Vec<IpAddr> = vec!["10.0.0.1".parse().unwrap()];
+
+        assert_eq!(policies.groups(&ips, 4500), vec![(ips.clone(), 100)]);
+    }
+}
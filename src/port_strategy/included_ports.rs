@@ -0,0 +1,87 @@
+/// The ports of a range minus the excluded ones, addressable by index.
+///
+/// Strategies iterate over indexes instead of ports, so excluded ports are
+/// never generated: a random permutation of `0..len` maps onto the included
+/// ports only, and its length is the number of ports actually scanned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludedPorts {
+    /// Runs of consecutive included ports, as the index of their first port
+    /// and that port, in ascending order.
+    runs: Vec<(u32, u16)>,
+    len: u32,
+}
+
+impl IncludedPorts {
+    /// The ports from `start` to `end`, both included, but `excluded`.
+    pub fn new(start: u16, end: u16, excluded: &[u16]) -> Self {
+        let mut excluded: Vec<u16> = excluded
+            .iter()
+            .copied()
+            .filter(|port| (start..=end).contains(port))
+            .collect();
+        excluded.sort_unstable();
+        excluded.dedup();
+
+        let mut runs = Vec::new();
+        let mut len = 0;
+        let mut cursor = u32::from(start);
+        for port in excluded.into_iter().map(u32::from) {
+            if port > cursor {
+                runs.push((len, cursor as u16));
+                len += port - cursor;
+            }
+            cursor = port + 1;
+        }
+        if cursor <= u32::from(end) && start <= end {
+            runs.push((len, cursor as u16));
+            len += u32::from(end) - cursor + 1;
+        }
+
+        Self { runs, len }
+    }
+
+    /// The number of included ports.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// The included port at `index`, counting from the lowest.
+    pub fn get(&self, index: u32) -> Option<u16> {
+        if index >= self.len {
+            return None;
+        }
+        let run = self.runs.partition_point(|(first, _)| *first <= index) - 1;
+        let (first, port) = self.runs[run];
+        Some(port + (index - first) as u16)
+    }
+
+    /// The included ports in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.len).filter_map(move |index| self.get(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IncludedPorts;
+
+    #[test]
+    fn skips_excluded_ports() {
+        let ports = IncludedPorts::new(1, 10, &[1, 4, 5, 10, 80]);
+        assert_eq!(ports.len(), 6);
+        assert_eq!(ports.iter().collect::<Vec<_>>(), vec![2, 3, 6, 7, 8, 9]);
+        assert_eq!(ports.get(2), Some(6));
+        assert_eq!(ports.get(6), None);
+    }
+
+    #[test]
+    fn handles_the_edges_of_the_port_space() {
+        let all = IncludedPorts::new(1, 65_535, &[]);
+        assert_eq!(all.len(), 65_535);
+        assert_eq!(all.get(65_534), Some(65_535));
+
+        let none = IncludedPorts::new(1, 3, &[1, 2, 3]);
+        assert_eq!(none.len(), 0);
+        assert_eq!(none.iter().count(), 0);
+    }
+}
@@ -1,9 +1,14 @@
 //! Provides a means to hold configuration options specifically for port scanning.
+mod included_ports;
 mod range_iterator;
 use crate::input::{PortRange, ScanOrder};
+use crate::learning::PortHistory;
+use included_ports::IncludedPorts;
 use rand::rng;
 use rand::seq::SliceRandom;
 use range_iterator::RangeIterator;
+use std::collections::HashSet;
+use std::net::IpAddr;
 
 /// Represents options of port scanning.
 ///
@@ -14,16 +19,18 @@ pub enum PortStrategy {
     Manual(Vec<u16>),
     Serial(SerialRange),
     Random(RandomRange),
+    Tiered(TieredPorts),
 }
 
 impl PortStrategy {
     pub fn pick(range: &Option<PortRange>, ports: Option<Vec<u16>>, order: ScanOrder) -> Self {
         match order {
-            ScanOrder::Serial if ports.is_none() => {
+            ScanOrder::Serial | ScanOrder::Adaptive if ports.is_none() => {
                 let range = range.as_ref().unwrap();
                 PortStrategy::Serial(SerialRange {
                     start: range.start,
                     end: range.end,
+                    excluded: Vec::new(),
                 })
             }
             ScanOrder::Random if ports.is_none() => {
@@ -31,9 +38,10 @@ impl PortStrategy {
                 PortStrategy::Random(RandomRange {
                     start: range.start,
                     end: range.end,
+                    excluded: Vec::new(),
                 })
             }
-            ScanOrder::Serial => PortStrategy::Manual(ports.unwrap()),
+            ScanOrder::Serial | ScanOrder::Adaptive => PortStrategy::Manual(ports.unwrap()),
             ScanOrder::Random => {
                 let mut rng = rng();
                 let mut ports = ports.unwrap();
@@ -43,11 +51,76 @@ impl PortStrategy {
         }
     }
 
+    /// Reorders the ports so the ones most often open in past scans of
+    /// `ips` come first, for [`ScanOrder::Adaptive`].
+    #[must_use]
+    pub fn learned(self, history: &PortHistory, ips: &[IpAddr]) -> Self {
+        PortStrategy::Manual(history.rank(ips, self.order()))
+    }
+
+    /// Scans `first` before the rest of the ports of this strategy. Ports
+    /// of `first` that this strategy wouldn't scan are left out.
+    #[must_use]
+    pub fn tiered(self, first: Vec<u16>) -> Self {
+        PortStrategy::Tiered(TieredPorts {
+            first,
+            rest: Box::new(self),
+        })
+    }
+
+    /// Leaves `excluded` out of the ports to scan. Ranges skip them while
+    /// generating their order, so no work is spent on excluded ports.
+    #[must_use]
+    pub fn excluding(self, excluded: &[u16]) -> Self {
+        if excluded.is_empty() {
+            return self;
+        }
+        match self {
+            PortStrategy::Manual(ports) => PortStrategy::Manual(
+                ports
+                    .into_iter()
+                    .filter(|port| !excluded.contains(port))
+                    .collect(),
+            ),
+            PortStrategy::Serial(range) => PortStrategy::Serial(SerialRange {
+                excluded: excluded.to_vec(),
+                ..range
+            }),
+            PortStrategy::Random(range) => PortStrategy::Random(RandomRange {
+                excluded: excluded.to_vec(),
+                ..range
+            }),
+            PortStrategy::Tiered(tiers) => PortStrategy::Tiered(TieredPorts {
+                first: tiers
+                    .first
+                    .into_iter()
+                    .filter(|port| !excluded.contains(port))
+                    .collect(),
+                rest: Box::new(tiers.rest.excluding(excluded)),
+            }),
+        }
+    }
+
+    /// The number of ports scanned, without generating their order for
+    /// ranges.
+    pub fn len(&self) -> usize {
+        match self {
+            PortStrategy::Serial(range) => range.included().len() as usize,
+            PortStrategy::Random(range) => range.included().len() as usize,
+            PortStrategy::Manual(_) | PortStrategy::Tiered(_) => self.order().len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn order(&self) -> Vec<u16> {
         match self {
             PortStrategy::Manual(ports) => ports.clone(),
             PortStrategy::Serial(range) => range.generate(),
             PortStrategy::Random(range) => range.generate(),
+            PortStrategy::Tiered(tiers) => tiers.generate(),
         }
     }
 }
@@ -64,11 +137,18 @@ trait RangeOrder {
 pub struct SerialRange {
     start: u16,
     end: u16,
+    excluded: Vec<u16>,
+}
+
+impl SerialRange {
+    fn included(&self) -> IncludedPorts {
+        IncludedPorts::new(self.start, self.end, &self.excluded)
+    }
 }
 
 impl RangeOrder for SerialRange {
     fn generate(&self) -> Vec<u16> {
-        (self.start..=self.end).collect()
+        self.included().iter().collect()
     }
 }
 
@@ -78,6 +158,13 @@ impl RangeOrder for SerialRange {
 pub struct RandomRange {
     start: u16,
     end: u16,
+    excluded: Vec<u16>,
+}
+
+impl RandomRange {
+    fn included(&self) -> IncludedPorts {
+        IncludedPorts::new(self.start, self.end, &self.excluded)
+    }
 }
 
 impl RangeOrder for RandomRange {
@@ -90,8 +177,45 @@ impl RangeOrder for RandomRange {
     // a certain distance between the items in the Array. The chances of having
     // port numbers close to each other are pretty slim due to the way the
     // algorithm works.
+    //
+    // The permutation runs over the indexes of the included ports, so
+    // excluded ports are skipped without being generated.
     fn generate(&self) -> Vec<u16> {
-        RangeIterator::new(self.start.into(), self.end.into()).collect()
+        let included = self.included();
+        if included.len() == 0 {
+            return Vec::new();
+        }
+        RangeIterator::new(0, included.len() - 1)
+            .filter_map(|index| included.get(index.into()))
+            .collect()
+    }
+}
+
+/// TieredPorts scans a priority list, e.g. the top ports, before the rest
+/// of another strategy, so quick wins show up early in a complete scan.
+#[derive(Debug)]
+pub struct TieredPorts {
+    first: Vec<u16>,
+    rest: Box<PortStrategy>,
+}
+
+impl RangeOrder for TieredPorts {
+    fn generate(&self) -> Vec<u16> {
+        let rest = self.rest.order();
+        let included: HashSet<u16> = rest.iter().copied().collect();
+        let mut seen = HashSet::with_capacity(self.first.len());
+        let first: Vec<u16> = self
+            .first
+            .iter()
+            .copied()
+            .filter(|port| included.contains(port) && seen.insert(*port))
+            .collect();
+
+        first
+            .iter()
+            .copied()
+            .chain(rest.into_iter().filter(|port| !seen.contains(port)))
+            .collect()
     }
 }
 
@@ -99,6 +223,7 @@ impl RangeOrder for RandomRange {
 mod tests {
     use super::PortStrategy;
     use crate::input::{PortRange, ScanOrder};
+    use crate::learning::PortHistory;
 
     #[test]
     fn serial_strategy_with_range() {
@@ -137,4 +262,49 @@ mod tests {
         result.sort_unstable();
         assert_eq!(expected_range, result);
     }
+
+    #[test]
+    fn adaptive_strategy_uses_history() {
+        let ip = "10.0.0.1".parse().unwrap();
+        let mut history = PortHistory::default();
+        history.record(&ip, 3);
+
+        let range = PortRange { start: 1, end: 5 };
+        let strategy =
+            PortStrategy::pick(&Some(range), None, ScanOrder::Adaptive).learned(&history, &[ip]);
+        assert_eq!(strategy.order(), vec![3, 1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn excluded_ports_are_never_generated() {
+        let range = PortRange { start: 1, end: 100 };
+        let excluded: Vec<u16> = (10..=90).collect();
+
+        let serial =
+            PortStrategy::pick(&Some(range.clone()), None, ScanOrder::Serial).excluding(&excluded);
+        assert_eq!(serial.len(), 19);
+        assert_eq!(
+            serial.order(),
+            (1..10).chain(91..=100).collect::<Vec<u16>>()
+        );
+
+        let random = PortStrategy::pick(&Some(range), None, ScanOrder::Random).excluding(&excluded);
+        assert_eq!(random.len(), 19);
+        let mut order = random.order();
+        order.sort_unstable();
+        assert_eq!(order, (1..10).chain(91..=100).collect::<Vec<u16>>());
+
+        let tiered = PortStrategy::pick(&None, Some(vec![22, 80, 443]), ScanOrder::Serial)
+            .tiered(vec![443, 80])
+            .excluding(&[80]);
+        assert_eq!(tiered.order(), vec![443, 22]);
+    }
+
+    #[test]
+    fn tiered_strategy_scans_priority_ports_first() {
+        let range = PortRange { start: 1, end: 10 };
+        let strategy =
+            PortStrategy::pick(&Some(range), None, ScanOrder::Serial).tiered(vec![8, 3, 8, 443]);
+        assert_eq!(strategy.order(), vec![8, 3, 1, 2, 4, 5, 6, 7, 9, 10]);
+    }
 }
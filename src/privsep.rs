@@ -0,0 +1,155 @@
+//! Privilege separation for raw socket modes.
+//!
+//! Raw sockets are the only thing a scan needs privileges for, and only
+//! when they are created. They are acquired first, from a privileged parent
+//! or systemd through the `LISTEN_FDS` socket passing protocol when there is
+//! one, by opening them directly otherwise. Privileges are then dropped, so
+//! the scan itself, scripts and output code all run unprivileged.
+//!
+//! A wrapper passes sockets the way systemd does: open them as fds 3, 4, ...,
+//! set `LISTEN_FDS` to their count and `LISTEN_PID` to the pid of RustScan,
+//! then exec it.
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+
+use crate::capabilities::drop_privileges;
+
+/// The first fd passed under the `LISTEN_FDS` protocol.
+#[cfg(target_os = "linux")]
+const LISTEN_FDS_START: i32 = 3;
+
+/// Raw sockets acquired before dropping privileges. Each is `None` when it
+/// could not be acquired, or was not needed.
+#[derive(Debug, Default)]
+pub struct RawSockets {
+    pub tcp_v4: Option<Socket>,
+    pub icmp_v4: Option<Socket>,
+}
+
+impl RawSockets {
+    /// Takes the raw sockets passed through `LISTEN_FDS`, or opens the ones
+    /// needed: a TCP socket for `tcp_v4`, an ICMP one for `icmp_v4`.
+    pub fn acquire(tcp_v4: bool, icmp_v4: bool) -> Self {
+        Self::from_listen_fds().unwrap_or_else(|| Self {
+            tcp_v4: if tcp_v4 { open_tcp_v4() } else { None },
+            icmp_v4: if icmp_v4 { open_icmp_v4() } else { None },
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tcp_v4.is_none() && self.icmp_v4.is_none()
+    }
+
+    /// Takes the sockets passed by systemd or a privileged wrapper, sorting
+    /// them by what they are. Sockets that aren't raw are closed.
+    #[cfg(target_os = "linux")]
+    fn from_listen_fds() -> Option<Self> {
+        use std::os::unix::io::FromRawFd;
+
+        let count = listen_fds(
+            std::env::var("LISTEN_PID").ok().as_deref(),
+            std::env::var("LISTEN_FDS").ok().as_deref(),
+            std::process::id(),
+        )?;
+        // The sockets are ours now, children must not take them too.
+        std::env::remove_var("LISTEN_PID");
+        std::env::remove_var("LISTEN_FDS");
+
+        let mut sockets = Self::default();
+        for fd in LISTEN_FDS_START..LISTEN_FDS_START + count {
+            // SAFETY: under the LISTEN_FDS protocol, these fds were opened
+            // for this process and nothing else in it owns them.
+            let socket = unsafe { Socket::from_raw_fd(fd) };
+            if socket.set_cloexec(true).is_err() || socket.r#type().ok() != Some(Type::RAW) {
+                continue;
+            }
+            match (socket.domain().ok(), socket.protocol().ok().flatten()) {
+                (Some(Domain::IPV4), Some(Protocol::TCP)) => sockets.tcp_v4 = Some(socket),
+                (Some(Domain::IPV4), Some(Protocol::ICMPV4)) => sockets.icmp_v4 = Some(socket),
+                _ => {}
+            }
+        }
+        Some(sockets)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn from_listen_fds() -> Option<Self> {
+        None
+    }
+}
+
+/// Opens a raw IPv4 TCP socket, for the SYN scan. Needs root or
+/// `CAP_NET_RAW`.
+pub fn open_tcp_v4() -> Option<Socket> {
+    Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::TCP)).ok()
+}
+
+/// Opens a raw IPv4 ICMP socket, to read unreachables and echo replies.
+/// Needs root or `CAP_NET_RAW`.
+pub fn open_icmp_v4() -> Option<Socket> {
+    Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)).ok()
+}
+
+/// Acquires the raw sockets needed, see [`RawSockets::acquire`], then drops
+/// every privilege the process has, root going back to the user that ran
+/// sudo. Fails rather than scanning on as root when there is no such user.
+pub fn separate(tcp_v4: bool, icmp_v4: bool) -> io::Result<RawSockets> {
+    let sockets = RawSockets::acquire(tcp_v4, icmp_v4);
+    drop_privileges()?;
+    Ok(sockets)
+}
+
+/// The number of fds passed to `pid`, when `LISTEN_PID` names it.
+#[cfg(target_os = "linux")]
+fn listen_fds(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> Option<i32> {
+    if listen_pid?.parse::<u32>().ok()? != pid {
+        return None;
+    }
+    listen_fds?.parse().ok().filter(|&count| count > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn listen_fds_must_be_meant_for_us() {
+        use super::listen_fds;
+
+        assert_eq!(listen_fds(Some("42"), Some("2"), 42), Some(2));
+        assert_eq!(listen_fds(Some("41"), Some("2"), 42), None);
+        assert_eq!(listen_fds(Some("42"), Some("0"), 42), None);
+        assert_eq!(listen_fds(None, Some("2"), 42), None);
+    }
+
+    /// Runs in a child process of its own, as dropping privileges can't be
+    /// undone and would affect the other tests.
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn separate_drops_to_the_sudo_user() {
+        use std::process::Command;
+
+        const CHILD: &str = "RUSTSCAN_TEST_PRIVSEP_CHILD";
+
+        if std::env::var_os(CHILD).is_some() {
+            // SAFETY: getuid can't fail.
+            let expected = match unsafe { libc::getuid() } {
+                0 => 65534,
+                uid => uid,
+            };
+            super::separate(false, false).unwrap();
+            // SAFETY: geteuid can't fail.
+            assert_eq!(unsafe { libc::geteuid() }, expected);
+            return;
+        }
+
+        let status = Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "privsep::tests::separate_drops_to_the_sudo_user"])
+            .env(CHILD, "1")
+            .env("SUDO_UID", "65534")
+            .env("SUDO_GID", "65534")
+            .status()
+            .unwrap();
+
+        assert!(status.success());
+    }
+}
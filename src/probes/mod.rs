@@ -0,0 +1,412 @@
+//! Lightweight version detection: a few protocol-aware probes sent to open
+//! TCP ports to tell what runs behind them, without running nmap.
+//!
+//! Each port is first given a moment to greet, as SSH, SMTP, FTP, POP3 and
+//! IMAP servers do, and servers greeting like SMTP ones are greeted back
+//! with `EHLO`. Ports that stay silent get an HTTP `HEAD` request, and
+//! those not answering it a TLS ClientHello. Ports usually speaking TLS
+//! right away get the ClientHello first.
+//!
+//! ```text
+//! 10.0.0.1:22 ssh OpenSSH 8.9p1
+//! 10.0.0.1:25 smtp Postfix
+//! 10.0.0.1:80 http nginx 1.18.0
+//! 10.0.0.1:443 ssl TLSv1.2
+//! ```
+//!
+//! The certificates of TLS ports can be collected as well, see
+//! [`ServiceProber::certificates`].
+use crate::scanner::CancellationToken;
+use async_std::io::{self, ReadExt, WriteExt};
+use async_std::net::TcpStream;
+use futures::future;
+use futures::stream::{self, StreamExt};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+mod tls;
+
+pub use tls::TlsCertificate;
+
+/// How long a port is given to greet before it is sent a probe.
+const GREETING_WAIT: Duration = Duration::from_secs(2);
+
+/// Bytes read from an answer at most.
+const MAX_ANSWER: usize = 4096;
+
+/// Bytes of a TLS handshake read at most while waiting for the
+/// certificate, enough for long chains.
+const MAX_HANDSHAKE: usize = 64 * 1024;
+
+/// Ports usually speaking TLS right away: HTTPS, SMTPS, LDAPS, DNS over
+/// TLS, IMAPS, POP3S, WinRM over HTTPS and HTTPS alternate.
+const TLS_PORTS: [u16; 8] = [443, 465, 636, 853, 993, 995, 5986, 8443];
+
+/// Mail and file transfer servers named in greetings, as they write
+/// themselves.
+const PRODUCTS: [&str; 10] = [
+    "Postfix",
+    "Exim",
+    "Sendmail",
+    "OpenSMTPD",
+    "Microsoft ESMTP",
+    "vsFTPd",
+    "ProFTPD",
+    "Pure-FTPd",
+    "Dovecot",
+    "Courier",
+];
+
+/// What runs behind a port, as far as the probes could tell.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceInfo {
+    /// The protocol, named like nmap names it, e.g. `ssh` or `http`.
+    pub name: String,
+    pub product: Option<String>,
+    pub version: Option<String>,
+}
+
+impl ServiceInfo {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            product: None,
+            version: None,
+        }
+    }
+}
+
+impl fmt::Display for ServiceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        for detail in [&self.product, &self.version].into_iter().flatten() {
+            write!(f, " {detail}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Sends the probes to open ports.
+#[derive(Debug, Clone)]
+pub struct ServiceProber {
+    timeout: Duration,
+    batch_size: usize,
+    cancel: CancellationToken,
+}
+
+impl ServiceProber {
+    /// Waits `timeout` for connections and answers.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            batch_size: 64,
+            cancel: CancellationToken::default(),
+        }
+    }
+
+    /// How many ports are probed at once.
+    #[must_use]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Probes no more ports once `token` is cancelled, the ones probed so
+    /// far being returned.
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// The services of the `sockets` the probes could tell.
+    pub async fn probe_all(&self, sockets: &[SocketAddr]) -> BTreeMap<SocketAddr, ServiceInfo> {
+        stream::iter(sockets.iter().copied())
+            .take_while(|_| future::ready(!self.cancel.is_cancelled()))
+            .map(|socket| async move { (socket, self.probe(socket).await) })
+            .buffer_unordered(self.batch_size)
+            .filter_map(|(socket, service)| async move { service.map(|service| (socket, service)) })
+            .collect()
+            .await
+    }
+
+    /// The service of `socket`, `None` when no probe got an answer it
+    /// understood.
+    pub async fn probe(&self, socket: SocketAddr) -> Option<ServiceInfo> {
+        if TLS_PORTS.contains(&socket.port()) {
+            if let Some(service) = self.tls(socket).await {
+                return Some(service);
+            }
+        }
+
+        let mut stream = self.connect(socket).await?;
+        if let Some(greeting) = self
+            .read(&mut stream, self.timeout.min(GREETING_WAIT))
+            .await
+        {
+            let greeting = String::from_utf8_lossy(&greeting);
+            return self.greeted(&mut stream, &greeting).await;
+        }
+        drop(stream);
+
+        let request = format!("HEAD / HTTP/1.0\r\nHost: {}\r\n\r\n", socket.ip());
+        if let Some(service) = self
+            .exchange(socket, request.as_bytes())
+            .await
+            .and_then(|answer| parse_http(&String::from_utf8_lossy(&answer)))
+        {
+            return Some(service);
+        }
+        if TLS_PORTS.contains(&socket.port()) {
+            return None;
+        }
+        self.tls(socket).await
+    }
+
+    /// The certificates of the `sockets` answering a TLS handshake.
+    pub async fn certificates(
+        &self,
+        sockets: &[SocketAddr],
+    ) -> BTreeMap<SocketAddr, TlsCertificate> {
+        stream::iter(sockets.iter().copied())
+            .take_while(|_| future::ready(!self.cancel.is_cancelled()))
+            .map(|socket| async move { (socket, self.certificate(socket).await) })
+            .buffer_unordered(self.batch_size)
+            .filter_map(|(socket, certificate)| async move {
+                certificate.map(|certificate| (socket, certificate))
+            })
+            .collect()
+            .await
+    }
+
+    /// The certificate `socket` sends in a TLS handshake, `None` when it
+    /// doesn't speak TLS up to 1.2 or sent one that couldn't be read.
+    pub async fn certificate(&self, socket: SocketAddr) -> Option<TlsCertificate> {
+        let mut stream = self.connect(socket).await?;
+        io::timeout(self.timeout, stream.write_all(&tls::client_hello()))
+            .await
+            .ok()?;
+        let mut answer = Vec::new();
+        while answer.len() < MAX_HANDSHAKE {
+            answer.extend(self.read(&mut stream, self.timeout).await?);
+            if let Some(certificate) = tls::certificate(&answer) {
+                return Some(certificate);
+            }
+            if tls::finished(&answer) {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Tells the service from its greeting, saying `EHLO` to what looks
+    /// like a mail server.
+    async fn greeted(&self, stream: &mut TcpStream, greeting: &str) -> Option<ServiceInfo> {
+        let service = parse_greeting(greeting);
+        if service.is_some() || !greeting.starts_with("220") {
+            return service;
+        }
+        io::timeout(self.timeout, stream.write_all(b"EHLO rustscan\r\n"))
+            .await
+            .ok()?;
+        let answer = self.read(stream, self.timeout).await?;
+        String::from_utf8_lossy(&answer)
+            .starts_with("250")
+            .then(|| named(ServiceInfo::new("smtp"), greeting))
+    }
+
+    async fn tls(&self, socket: SocketAddr) -> Option<ServiceInfo> {
+        let answer = self.exchange(socket, &tls::client_hello()).await?;
+        tls::parse_tls(&answer)
+    }
+
+    async fn connect(&self, socket: SocketAddr) -> Option<TcpStream> {
+        io::timeout(self.timeout, TcpStream::connect(socket))
+            .await
+            .ok()
+    }
+
+    /// Sends `request` on a new connection and reads the answer.
+    async fn exchange(&self, socket: SocketAddr, request: &[u8]) -> Option<Vec<u8>> {
+        let mut stream = self.connect(socket).await?;
+        io::timeout(self.timeout, stream.write_all(request))
+            .await
+            .ok()?;
+        self.read(&mut stream, self.timeout).await
+    }
+
+    /// What the other end sent within `wait`, `None` for nothing.
+    async fn read(&self, stream: &mut TcpStream, wait: Duration) -> Option<Vec<u8>> {
+        let mut answer = vec![0; MAX_ANSWER];
+        let read = io::timeout(wait, stream.read(&mut answer)).await.ok()?;
+        answer.truncate(read);
+        (read > 0).then_some(answer)
+    }
+}
+
+/// Tells the service from the greeting a server sends on its own.
+fn parse_greeting(greeting: &str) -> Option<ServiceInfo> {
+    let lower = greeting.to_ascii_lowercase();
+    if let Some(banner) = greeting.strip_prefix("SSH-") {
+        // SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.1
+        let software = banner
+            .split_once('-')
+            .map(|(_, software)| software)?
+            .split_whitespace()
+            .next()?;
+        let (product, version) = match software.split_once('_') {
+            Some((product, version)) => (product, Some(version.to_owned())),
+            None => (software, None),
+        };
+        return Some(ServiceInfo {
+            name: "ssh".to_owned(),
+            product: Some(product.to_owned()),
+            version,
+        });
+    }
+    let name = if greeting.starts_with("220") && lower.contains("ftp") {
+        "ftp"
+    } else if greeting.starts_with("220") && lower.contains("smtp") {
+        "smtp"
+    } else if greeting.starts_with("+OK") {
+        "pop3"
+    } else if greeting.starts_with("* OK") {
+        "imap"
+    } else if let Some(version) = greeting.strip_prefix("RFB ") {
+        return Some(ServiceInfo {
+            name: "vnc".to_owned(),
+            product: None,
+            version: Some(version.trim().to_owned()),
+        });
+    } else {
+        return None;
+    };
+    Some(named(ServiceInfo::new(name), greeting))
+}
+
+/// Adds the product named in `greeting` and the version following it.
+fn named(mut service: ServiceInfo, greeting: &str) -> ServiceInfo {
+    let lower = greeting.to_ascii_lowercase();
+    let Some((product, start)) = PRODUCTS
+        .iter()
+        .find_map(|product| Some((product, lower.find(&product.to_ascii_lowercase())?)))
+    else {
+        return service;
+    };
+    service.product = Some((*product).to_owned());
+    // Lowercasing ASCII only keeps the offsets of `lower` those of `greeting`.
+    service.version = greeting[start + product.len()..]
+        .split_whitespace()
+        .next()
+        .map(|version| version.trim_end_matches([')', ',']))
+        .filter(|version| version.starts_with(|c: char| c.is_ascii_digit()))
+        .map(ToOwned::to_owned);
+    service
+}
+
+/// Tells an HTTP server, and its software from the `Server` header, from
+/// the answer to a `HEAD` request.
+fn parse_http(answer: &str) -> Option<ServiceInfo> {
+    if !answer.starts_with("HTTP/") {
+        return None;
+    }
+    let mut service = ServiceInfo::new("http");
+    let server = answer.lines().find_map(|line| {
+        let (header, value) = line.split_once(':')?;
+        header
+            .trim()
+            .eq_ignore_ascii_case("server")
+            .then(|| value.trim())
+    });
+    if let Some(server) = server.filter(|server| !server.is_empty()) {
+        // nginx/1.18.0 (Ubuntu)
+        let software = server.split_whitespace().next().unwrap_or(server);
+        match software.split_once('/') {
+            Some((product, version)) => {
+                service.product = Some(product.to_owned());
+                service.version = Some(version.to_owned());
+            }
+            None => service.product = Some(server.to_owned()),
+        }
+    }
+    Some(service)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_greeting, parse_http, ServiceInfo, ServiceProber};
+    use async_std::task::block_on;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    fn service(name: &str, product: Option<&str>, version: Option<&str>) -> Option<ServiceInfo> {
+        Some(ServiceInfo {
+            name: name.to_owned(),
+            product: product.map(ToOwned::to_owned),
+            version: version.map(ToOwned::to_owned),
+        })
+    }
+
+    #[test]
+    fn tells_services_from_greetings() {
+        assert_eq!(
+            parse_greeting("SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.1\r\n"),
+            service("ssh", Some("OpenSSH"), Some("8.9p1"))
+        );
+        assert_eq!(
+            parse_greeting("SSH-2.0-dropbear\r\n"),
+            service("ssh", Some("dropbear"), None)
+        );
+        assert_eq!(
+            parse_greeting("220 mx.example.com ESMTP Exim 4.94.2 Mon, 01 Jan 2024\r\n"),
+            service("smtp", Some("Exim"), Some("4.94.2"))
+        );
+        assert_eq!(
+            parse_greeting("220 (vsFTPd 3.0.3)\r\n"),
+            service("ftp", Some("vsFTPd"), Some("3.0.3"))
+        );
+        assert_eq!(
+            parse_greeting("* OK [CAPABILITY IMAP4rev1] Dovecot ready.\r\n"),
+            service("imap", Some("Dovecot"), None)
+        );
+        assert_eq!(parse_greeting("220 mail.example.com\r\n"), None);
+        assert_eq!(parse_greeting("\u{0}\u{1}garbage"), None);
+    }
+
+    #[test]
+    fn tells_http_servers() {
+        assert_eq!(
+            parse_http("HTTP/1.1 200 OK\r\nserver: nginx/1.18.0 (Ubuntu)\r\n\r\n"),
+            service("http", Some("nginx"), Some("1.18.0"))
+        );
+        assert_eq!(
+            parse_http("HTTP/1.0 404 Not Found\r\nServer: Caddy\r\n\r\n"),
+            service("http", Some("Caddy"), None)
+        );
+        assert_eq!(parse_http("SSH-2.0-OpenSSH_8.9p1"), None);
+    }
+
+    #[test]
+    fn probes_silent_ports_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 512];
+                if stream.read(&mut request).is_ok_and(|read| read > 0) {
+                    let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nServer: Apache/2.4.41\r\n\r\n");
+                }
+            }
+        });
+
+        let prober = ServiceProber::new(Duration::from_millis(300));
+        assert_eq!(
+            block_on(prober.probe(socket)),
+            service("http", Some("Apache"), Some("2.4.41"))
+        );
+    }
+}
@@ -0,0 +1,533 @@
+//! The TLS handshake of the probes, and the certificates servers send in
+//! it.
+//!
+//! Only a TLS 1.2 ClientHello is sent: TLS 1.3 encrypts the certificate,
+//! while up to TLS 1.2 the server sends it in the clear right after its
+//! ServerHello. The certificate is read with a minimal DER reader, as only
+//! the names, SANs and expiry are wanted from it.
+use super::ServiceInfo;
+
+use rand::RngExt;
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+const HANDSHAKE: u8 = 0x16;
+const ALERT: u8 = 0x15;
+const CERTIFICATE: u8 = 11;
+const SERVER_HELLO_DONE: u8 = 14;
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const BMP_STRING: u8 = 0x1e;
+/// `[0]`, the version of a certificate.
+const VERSION: u8 = 0xa0;
+/// `[3]`, the extensions of a certificate.
+const EXTENSIONS: u8 = 0xa3;
+/// `dNSName` and `iPAddress` in a SAN.
+const DNS_NAME: u8 = 0x82;
+const IP_ADDRESS: u8 = 0x87;
+
+/// 2.5.29.17, the subject alternative names extension.
+const SUBJECT_ALT_NAME: [u8; 3] = [0x55, 0x1d, 0x11];
+
+/// The name attributes shown, by the last byte of their 2.5.4 OID.
+const ATTRIBUTES: [(u8, &str); 6] = [
+    (3, "CN"),
+    (10, "O"),
+    (11, "OU"),
+    (7, "L"),
+    (8, "ST"),
+    (6, "C"),
+];
+
+/// What the certificate of a TLS port says about it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsCertificate {
+    /// The distinguished name of the subject, e.g. `CN=example.com, O=Example`.
+    pub subject: String,
+    pub issuer: String,
+    /// The DNS names and IP addresses the certificate is also valid for.
+    pub sans: Vec<String>,
+    /// When the certificate expires, e.g. `2025-01-31T12:00:00Z`.
+    pub not_after: String,
+}
+
+impl fmt::Display for TlsCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "subject: {}, issuer: {}", self.subject, self.issuer)?;
+        if !self.sans.is_empty() {
+            write!(f, ", SANs: {}", self.sans.join(" "))?;
+        }
+        write!(f, ", expires: {}", self.not_after)
+    }
+}
+
+/// Tells a TLS server, and the version it picked, from the answer to
+/// [`client_hello`]. Alerts prove TLS as much as a ServerHello does.
+pub(super) fn parse_tls(answer: &[u8]) -> Option<ServiceInfo> {
+    match answer {
+        // A handshake record starting with a ServerHello.
+        [HANDSHAKE, 0x03, _, _, _, 0x02, _, _, _, major, minor, ..] => {
+            let version = match (*major, *minor) {
+                (3, 0) => "SSLv3",
+                (3, 1) => "TLSv1.0",
+                (3, 2) => "TLSv1.1",
+                (3, 3) => "TLSv1.2",
+                _ => return Some(ServiceInfo::new("ssl")),
+            };
+            Some(ServiceInfo {
+                version: Some(version.to_owned()),
+                ..ServiceInfo::new("ssl")
+            })
+        }
+        [ALERT, 0x03, ..] => Some(ServiceInfo::new("ssl")),
+        _ => None,
+    }
+}
+
+/// The certificate of the server, once the records answering
+/// [`client_hello`] hold all of it.
+pub(super) fn certificate(answer: &[u8]) -> Option<TlsCertificate> {
+    let handshake = handshake(answer);
+    let (_, message) = messages(&handshake).find(|(kind, _)| *kind == CERTIFICATE)?;
+    // The list of certificates and the leaf one, both with 3 byte lengths.
+    let (leaf, _) = split_u24(message.get(3..)?)?;
+    parse_certificate(leaf)
+}
+
+/// Whether `answer` holds all the server will send before the client
+/// answers back: the end of its hello messages, an alert, or something
+/// other than TLS.
+pub(super) fn finished(answer: &[u8]) -> bool {
+    match answer.first() {
+        None => false,
+        Some(&HANDSHAKE) => messages(&handshake(answer)).any(|(kind, _)| kind == SERVER_HELLO_DONE),
+        Some(_) => true,
+    }
+}
+
+/// The handshake messages in the complete records of `answer`, one after
+/// another.
+fn handshake(mut answer: &[u8]) -> Vec<u8> {
+    let mut handshake = Vec::new();
+    while let [kind, _, _, high, low, rest @ ..] = answer {
+        let length = usize::from(u16::from_be_bytes([*high, *low]));
+        let Some(fragment) = rest.get(..length) else {
+            break;
+        };
+        if *kind == HANDSHAKE {
+            handshake.extend_from_slice(fragment);
+        }
+        answer = &rest[length..];
+    }
+    handshake
+}
+
+/// The type and body of the complete messages of `handshake`.
+fn messages(mut handshake: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    std::iter::from_fn(move || {
+        let (&kind, rest) = handshake.split_first()?;
+        let (message, rest) = split_u24(rest)?;
+        handshake = rest;
+        Some((kind, message))
+    })
+}
+
+/// Splits what a 3 byte length says off the start of `bytes`.
+fn split_u24(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let [a, b, c, rest @ ..] = bytes else {
+        return None;
+    };
+    let length = usize::try_from(u32::from_be_bytes([0, *a, *b, *c])).ok()?;
+    (rest.len() >= length).then(|| rest.split_at(length))
+}
+
+/// Reads the subject, issuer, SANs and expiry of a DER encoded X.509
+/// certificate.
+fn parse_certificate(der: &[u8]) -> Option<TlsCertificate> {
+    let certificate = Der(der).expect(SEQUENCE)?;
+    let mut tbs = Der(Der(certificate).expect(SEQUENCE)?);
+    // The version is optional, the serial number is not.
+    if tbs.next()?.0 == VERSION {
+        tbs.next()?;
+    }
+    // The signature algorithm.
+    tbs.next()?;
+    let issuer = name(tbs.expect(SEQUENCE)?);
+    let mut validity = Der(tbs.expect(SEQUENCE)?);
+    validity.next()?;
+    let (kind, not_after) = validity.next()?;
+    let not_after =
+        time(kind, not_after).unwrap_or_else(|| String::from_utf8_lossy(not_after).into_owned());
+    let subject = name(tbs.expect(SEQUENCE)?);
+
+    let mut sans = Vec::new();
+    for (tag, value) in tbs {
+        if tag == EXTENSIONS {
+            sans = subject_alt_names(Der(value).expect(SEQUENCE)?);
+        }
+    }
+    Some(TlsCertificate {
+        subject,
+        issuer,
+        sans,
+        not_after,
+    })
+}
+
+/// Writes a distinguished name the way OpenSSL does, most specific first,
+/// with the attributes shown in [`ATTRIBUTES`].
+fn name(rdns: &[u8]) -> String {
+    let mut attributes = Vec::new();
+    for (_, rdn) in Der(rdns).filter(|(tag, _)| *tag == SET) {
+        for (_, attribute) in Der(rdn).filter(|(tag, _)| *tag == SEQUENCE) {
+            let mut attribute = Der(attribute);
+            let Some((_, oid)) = attribute.next() else {
+                continue;
+            };
+            let Some((tag, value)) = attribute.next() else {
+                continue;
+            };
+            let short = match oid {
+                [0x55, 0x04, last] => ATTRIBUTES
+                    .iter()
+                    .find_map(|(id, short)| (id == last).then_some(*short)),
+                _ => None,
+            };
+            if let Some(short) = short {
+                attributes.push(format!("{short}={}", text(tag, value)));
+            }
+        }
+    }
+    attributes.reverse();
+    attributes.join(", ")
+}
+
+/// The DNS names and IP addresses of the subject alternative names
+/// extension, if `extensions` has it.
+fn subject_alt_names(extensions: &[u8]) -> Vec<String> {
+    let Some(names) = Der(extensions).find_map(|(_, extension)| {
+        let mut extension = Der(extension);
+        let (_, oid) = extension.next()?;
+        if oid != SUBJECT_ALT_NAME {
+            return None;
+        }
+        // The value comes last, after an optional critical flag.
+        let (_, value) = extension.last()?;
+        Der(value).expect(SEQUENCE)
+    }) else {
+        return Vec::new();
+    };
+    Der(names)
+        .filter_map(|(tag, value)| match tag {
+            DNS_NAME => Some(String::from_utf8_lossy(value).into_owned()),
+            IP_ADDRESS => match <[u8; 4]>::try_from(value) {
+                Ok(ip) => Some(Ipv4Addr::from(ip).to_string()),
+                Err(_) => <[u8; 16]>::try_from(value)
+                    .ok()
+                    .map(|ip| Ipv6Addr::from(ip).to_string()),
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// A name attribute as text, whatever string type it was written as.
+fn text(tag: u8, value: &[u8]) -> String {
+    if tag == BMP_STRING {
+        let units = value
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]));
+        return char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+    }
+    String::from_utf8_lossy(value).into_owned()
+}
+
+/// A UTCTime or GeneralizedTime as `2025-01-31T12:00:00Z`.
+fn time(tag: u8, value: &[u8]) -> Option<String> {
+    let digits = std::str::from_utf8(value).ok()?.strip_suffix('Z')?;
+    let full = match tag {
+        // Two digit years from 50 on are 19xx, RFC 5280 4.1.2.5.1.
+        UTC_TIME if digits.get(..2)? < "50" => format!("20{digits}"),
+        UTC_TIME => format!("19{digits}"),
+        GENERALIZED_TIME => digits.to_owned(),
+        _ => return None,
+    };
+    if full.len() != 14 || !full.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        &full[..4],
+        &full[4..6],
+        &full[6..8],
+        &full[8..10],
+        &full[10..12],
+        &full[12..]
+    ))
+}
+
+/// The DER elements of some bytes, as their tag and contents.
+struct Der<'a>(&'a [u8]);
+
+impl<'a> Der<'a> {
+    /// The contents of the next element, if it has the `tag` expected.
+    fn expect(&mut self, tag: u8) -> Option<&'a [u8]> {
+        self.next()
+            .filter(|(found, _)| *found == tag)
+            .map(|(_, value)| value)
+    }
+}
+
+impl<'a> Iterator for Der<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&tag, rest) = self.0.split_first()?;
+        let (&first, rest) = rest.split_first()?;
+        let (length, rest) = if first < 0x80 {
+            (usize::from(first), rest)
+        } else {
+            // The long form: the length in the next `first & 0x7f` bytes.
+            let count = usize::from(first & 0x7f);
+            if count == 0 || count > 4 || rest.len() < count {
+                return None;
+            }
+            let length = rest[..count]
+                .iter()
+                .fold(0, |length, byte| (length << 8) | usize::from(*byte));
+            (length, &rest[count..])
+        };
+        if rest.len() < length {
+            self.0 = &[];
+            return None;
+        }
+        let (value, rest) = rest.split_at(length);
+        self.0 = rest;
+        Some((tag, value))
+    }
+}
+
+/// A TLS 1.2 ClientHello with the cipher suites and extensions most
+/// servers expect, and no server name as only the IP is known.
+pub(super) fn client_hello() -> Vec<u8> {
+    const CIPHER_SUITES: [u16; 9] = [
+        0xc02f, 0xc030, 0xc02b, 0xc02c, 0xcca8, 0x009c, 0x009d, 0x002f, 0x0035,
+    ];
+    let extensions: [(u16, &[u8]); 3] = [
+        // supported_groups: x25519, secp256r1, secp384r1.
+        (0x000a, &[0x00, 0x06, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x18]),
+        // ec_point_formats: uncompressed.
+        (0x000b, &[0x01, 0x00]),
+        // signature_algorithms: ECDSA, RSA-PSS and RSA with SHA-256 to 512.
+        (
+            0x000d,
+            &[
+                0x00, 0x0e, 0x04, 0x03, 0x08, 0x04, 0x04, 0x01, 0x05, 0x03, 0x08, 0x05, 0x05, 0x01,
+                0x06, 0x01,
+            ],
+        ),
+    ];
+
+    let random: [u8; 32] = rand::rng().random();
+    let mut hello = vec![0x03, 0x03];
+    hello.extend_from_slice(&random);
+    // No session to resume.
+    hello.push(0);
+    push_length(&mut hello, CIPHER_SUITES.len() * 2);
+    for suite in CIPHER_SUITES {
+        hello.extend_from_slice(&suite.to_be_bytes());
+    }
+    // The null compression method only.
+    hello.extend_from_slice(&[0x01, 0x00]);
+    let mut encoded = Vec::new();
+    for (kind, data) in extensions {
+        encoded.extend_from_slice(&kind.to_be_bytes());
+        push_length(&mut encoded, data.len());
+        encoded.extend_from_slice(data);
+    }
+    push_length(&mut hello, encoded.len());
+    hello.extend(encoded);
+
+    let mut handshake = vec![0x01];
+    handshake.extend_from_slice(&u32::try_from(hello.len()).unwrap_or(0).to_be_bytes()[1..]);
+    handshake.extend(hello);
+    let mut record = vec![HANDSHAKE, 0x03, 0x01];
+    push_length(&mut record, handshake.len());
+    record.extend(handshake);
+    record
+}
+
+fn push_length(bytes: &mut Vec<u8>, length: usize) {
+    bytes.extend_from_slice(&u16::try_from(length).unwrap_or(u16::MAX).to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{certificate, client_hello, finished, parse_tls, time, TlsCertificate};
+    use crate::probes::ServiceInfo;
+    use std::convert::TryFrom;
+
+    fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut element = vec![tag];
+        match u8::try_from(contents.len()) {
+            Ok(length) if length < 0x80 => element.push(length),
+            _ => {
+                let length = u16::try_from(contents.len()).unwrap();
+                element.push(0x82);
+                element.extend_from_slice(&length.to_be_bytes());
+            }
+        }
+        element.extend_from_slice(contents);
+        element
+    }
+
+    fn name(attributes: &[(u8, &str)]) -> Vec<u8> {
+        let rdns: Vec<u8> = attributes
+            .iter()
+            .flat_map(|(id, value)| {
+                let oid = der(0x06, &[0x55, 0x04, *id]);
+                let attribute = [oid, der(0x0c, value.as_bytes())].concat();
+                der(0x31, &der(0x30, &attribute))
+            })
+            .collect();
+        der(0x30, &rdns)
+    }
+
+    fn with_u24(kind: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![kind];
+        message.extend_from_slice(&u32::try_from(body.len()).unwrap().to_be_bytes()[1..]);
+        message.extend_from_slice(body);
+        message
+    }
+
+    fn record(kind: u8, fragment: &[u8]) -> Vec<u8> {
+        let mut record = vec![kind, 0x03, 0x03];
+        record.extend_from_slice(&u16::try_from(fragment.len()).unwrap().to_be_bytes());
+        record.extend_from_slice(fragment);
+        record
+    }
+
+    fn test_certificate() -> Vec<u8> {
+        let algorithm = der(
+            0x30,
+            &der(0x06, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02]),
+        );
+        let validity = der(
+            0x30,
+            &[der(0x17, b"240101000000Z"), der(0x18, b"20350131120000Z")].concat(),
+        );
+        let names = der(
+            0x30,
+            &[
+                der(0x82, b"example.com"),
+                der(0x82, b"www.example.com"),
+                der(0x87, &[10, 0, 0, 1]),
+            ]
+            .concat(),
+        );
+        let san = der(
+            0x30,
+            &[der(0x06, &[0x55, 0x1d, 0x11]), der(0x04, &names)].concat(),
+        );
+        let tbs = der(
+            0x30,
+            &[
+                der(0xa0, &der(0x02, &[2])),
+                der(0x02, &[0x10, 0x01]),
+                algorithm.clone(),
+                name(&[(6, "US"), (10, "Example CA")]),
+                validity,
+                name(&[(6, "US"), (10, "Example"), (3, "example.com")]),
+                der(0x30, &[der(0x30, &[]), der(0x03, &[0])].concat()),
+                der(0xa3, &der(0x30, &san)),
+            ]
+            .concat(),
+        );
+        der(0x30, &[tbs, algorithm, der(0x03, &[0])].concat())
+    }
+
+    #[test]
+    fn tells_tls_servers() {
+        let hello = client_hello();
+        assert_eq!(&hello[..3], &[0x16, 0x03, 0x01]);
+        assert_eq!(
+            usize::from(u16::from_be_bytes([hello[3], hello[4]])),
+            hello.len() - 5
+        );
+
+        let server_hello = [
+            0x16, 0x03, 0x03, 0x00, 0x4a, 0x02, 0x00, 0x00, 0x46, 0x03, 0x03,
+        ];
+        assert_eq!(
+            parse_tls(&server_hello),
+            Some(ServiceInfo {
+                version: Some("TLSv1.2".to_owned()),
+                ..ServiceInfo::new("ssl")
+            })
+        );
+        assert_eq!(
+            parse_tls(&[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x28]),
+            Some(ServiceInfo::new("ssl"))
+        );
+        assert_eq!(parse_tls(b"HTTP/1.1 400 Bad Request"), None);
+    }
+
+    #[test]
+    fn reads_certificates_across_records() {
+        let leaf = with_u24(0, &test_certificate())[1..].to_vec();
+        let handshake = [
+            with_u24(2, &[0x03, 0x03]),
+            with_u24(11, &with_u24(0, &leaf)[1..]),
+            with_u24(14, &[]),
+        ]
+        .concat();
+        let (first, second) = handshake.split_at(40);
+        let answer = [record(0x16, first), record(0x16, second)].concat();
+
+        let partial = &answer[..answer.len() - 10];
+        assert_eq!(certificate(partial), None);
+        assert!(!finished(partial));
+
+        assert!(finished(&answer));
+        assert_eq!(
+            certificate(&answer),
+            Some(TlsCertificate {
+                subject: "CN=example.com, O=Example, C=US".to_owned(),
+                issuer: "O=Example CA, C=US".to_owned(),
+                sans: vec![
+                    "example.com".to_owned(),
+                    "www.example.com".to_owned(),
+                    "10.0.0.1".to_owned()
+                ],
+                not_after: "2035-01-31T12:00:00Z".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn alerts_and_other_protocols_end_the_handshake() {
+        assert!(finished(&[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x46]));
+        assert!(finished(b"SSH-2.0-OpenSSH_9.6\r\n"));
+        assert!(!finished(&[]));
+    }
+
+    #[test]
+    fn formats_certificate_times() {
+        assert_eq!(
+            time(0x17, b"491231235959Z").as_deref(),
+            Some("2049-12-31T23:59:59Z")
+        );
+        assert_eq!(
+            time(0x17, b"500101000000Z").as_deref(),
+            Some("1950-01-01T00:00:00Z")
+        );
+        assert_eq!(time(0x18, b"20350131120000.5Z"), None);
+    }
+}
@@ -0,0 +1,182 @@
+//! Per-host backoff when a host suddenly stops answering.
+//!
+//! Every TCP probe either gets an answer (the port accepted or refused the
+//! connection) or not (timeouts, unreachable errors). The answer rate of
+//! each host is tracked in windows of probes, and a sudden drop compared to
+//! the first window is a sign of dynamic rate limiting or an IPS stepping
+//! in. Probes to that host are then delayed, and the event is recorded so
+//! it can be reported after the scan.
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Number of probes in a window.
+const WINDOW: usize = 50;
+
+/// Hosts answering less than this in their first window (mostly filtered
+/// hosts) are not tracked, there is no answer rate to lose.
+const MIN_BASELINE_RATE: f64 = 0.2;
+
+/// A window answering less than this fraction of the baseline rate is
+/// treated as a sudden change in behaviour.
+const DROP_RATIO: f64 = 0.3;
+
+/// A window answering at least this fraction of the baseline rate lets the
+/// delay shrink again.
+const RECOVERY_RATIO: f64 = 0.8;
+
+const INITIAL_DELAY: Duration = Duration::from_millis(100);
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// A host whose answer rate suddenly dropped during the scan.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackoffEvent {
+    pub ip: IpAddr,
+    /// Time since the start of the scan.
+    pub elapsed: Duration,
+    pub baseline_rate: f64,
+    pub recent_rate: f64,
+    /// Delay now applied before every probe to the host.
+    pub delay: Duration,
+}
+
+impl fmt::Display for BackoffEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} stopped answering {:.1}s into the scan ({:.0}% answered, down from {:.0}%), possibly rate limiting or an IPS. Probes are now delayed by {}ms.",
+            self.ip,
+            self.elapsed.as_secs_f64(),
+            self.recent_rate * 100.0,
+            self.baseline_rate * 100.0,
+            self.delay.as_millis()
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct HostResponsiveness {
+    probes: usize,
+    answered: usize,
+    baseline_rate: Option<f64>,
+    delay: Duration,
+}
+
+#[derive(Debug)]
+pub(super) struct ResponsivenessTracker {
+    started: Instant,
+    hosts: HashMap<IpAddr, HostResponsiveness>,
+    events: Vec<BackoffEvent>,
+}
+
+impl Default for ResponsivenessTracker {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            hosts: HashMap::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl ResponsivenessTracker {
+    /// Records the outcome of a probe to `ip`.
+    pub(super) fn record(&mut self, ip: IpAddr, answered: bool) {
+        let host = self.hosts.entry(ip).or_default();
+        host.probes += 1;
+        if answered {
+            host.answered += 1;
+        }
+        if host.probes < WINDOW {
+            return;
+        }
+
+        let rate = host.answered as f64 / host.probes as f64;
+        host.probes = 0;
+        host.answered = 0;
+
+        let Some(baseline_rate) = host.baseline_rate else {
+            host.baseline_rate = Some(rate);
+            return;
+        };
+        if baseline_rate < MIN_BASELINE_RATE {
+            return;
+        }
+
+        if rate < baseline_rate * DROP_RATIO {
+            host.delay = if host.delay.is_zero() {
+                INITIAL_DELAY
+            } else {
+                (host.delay * 2).min(MAX_DELAY)
+            };
+            self.events.push(BackoffEvent {
+                ip,
+                elapsed: self.started.elapsed(),
+                baseline_rate,
+                recent_rate: rate,
+                delay: host.delay,
+            });
+        } else if rate >= baseline_rate * RECOVERY_RATIO {
+            host.delay /= 2;
+            if host.delay < INITIAL_DELAY {
+                host.delay = Duration::ZERO;
+            }
+        }
+    }
+
+    /// The delay to wait before the next probe to `ip`.
+    pub(super) fn delay(&self, ip: &IpAddr) -> Duration {
+        self.hosts.get(ip).map_or(Duration::ZERO, |host| host.delay)
+    }
+
+    pub(super) fn events(&self) -> &[BackoffEvent] {
+        &self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ResponsivenessTracker, INITIAL_DELAY, WINDOW};
+    use std::net::IpAddr;
+    use std::time::Duration;
+
+    fn record_window(tracker: &mut ResponsivenessTracker, ip: IpAddr, answered: usize) {
+        for probe in 0..WINDOW {
+            tracker.record(ip, probe < answered);
+        }
+    }
+
+    #[test]
+    fn backs_off_when_answer_rate_drops() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let mut tracker = ResponsivenessTracker::default();
+
+        record_window(&mut tracker, ip, WINDOW);
+        record_window(&mut tracker, ip, WINDOW - 5);
+        assert_eq!(tracker.delay(&ip), Duration::ZERO);
+
+        record_window(&mut tracker, ip, 2);
+        assert_eq!(tracker.delay(&ip), INITIAL_DELAY);
+        record_window(&mut tracker, ip, 0);
+        assert_eq!(tracker.delay(&ip), INITIAL_DELAY * 2);
+        assert_eq!(tracker.events().len(), 2);
+
+        record_window(&mut tracker, ip, WINDOW);
+        assert_eq!(tracker.delay(&ip), INITIAL_DELAY);
+        record_window(&mut tracker, ip, WINDOW);
+        assert_eq!(tracker.delay(&ip), Duration::ZERO);
+    }
+
+    #[test]
+    fn ignores_hosts_that_never_answered() {
+        let ip: IpAddr = "10.0.0.2".parse().unwrap();
+        let mut tracker = ResponsivenessTracker::default();
+
+        record_window(&mut tracker, ip, 1);
+        record_window(&mut tracker, ip, 0);
+
+        assert_eq!(tracker.delay(&ip), Duration::ZERO);
+        assert!(tracker.events().is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+//! Stopping a scan early.
+//!
+//! A [`CancellationToken`] is handed to the scanner with
+//! [`Scanner::with_cancellation`]. Once cancelled, the scan starts no new
+//! probes, drops the ones in flight and returns the results found so far,
+//! which are summed up and sent to the sinks like those of a full scan.
+use super::Scanner;
+
+#[cfg(unix)]
+use once_cell::sync::OnceCell;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag shared by the scanner and whoever may stop it. Clones share the
+/// flag, cancelling one cancels all of them.
+///
+/// ```rust
+/// # use rustscan::scanner::CancellationToken;
+/// let token = CancellationToken::new();
+/// let handle = token.clone();
+/// handle.cancel();
+/// assert!(token.is_cancelled());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+/// The token the first interrupt cancels, see
+/// [`CancellationToken::cancel_on_interrupt`].
+#[cfg(unix)]
+static INTERRUPT: OnceCell<CancellationToken> = OnceCell::new();
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Cancels the token on Ctrl-C (SIGINT) instead of killing the process,
+    /// which a second Ctrl-C still does, with exit code 130. Only the first
+    /// token this is called on is cancelled.
+    #[cfg(unix)]
+    pub fn cancel_on_interrupt(&self) {
+        extern "C" fn interrupted(_signal: libc::c_int) {
+            match INTERRUPT.get() {
+                Some(token) if !token.is_cancelled() => token.cancel(),
+                // SAFETY: _exit is async-signal-safe.
+                _ => unsafe { libc::_exit(130) },
+            }
+        }
+
+        if INTERRUPT.set(self.clone()).is_ok() {
+            // SAFETY: the handler only touches atomics and calls _exit.
+            unsafe {
+                libc::signal(libc::SIGINT, interrupted as libc::sighandler_t);
+            }
+        }
+    }
+}
+
+impl Scanner {
+    /// Stops the scan once `token` is cancelled, [`Scanner::run`] then
+    /// returning the results found so far.
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Whether the last scan was stopped early, its results being partial.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// The ports of each host the last scan didn't get to, or got no answer
+    /// from before it was cancelled, over any of its protocols. Empty when
+    /// the scan ran to the end.
+    pub fn unscanned(&self) -> BTreeMap<IpAddr, Vec<u16>> {
+        self.unscanned
+            .lock()
+            .map(|unscanned| unscanned.clone())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,493 @@
+//! Creates the sockets used for scanning.
+//!
+//! Sockets are built with `socket2` so options can be applied before they
+//! connect or bind, which the plain `async_std` constructors do not allow.
+use async_io::Async;
+use async_std::io;
+use async_std::net::{TcpStream, UdpSocket};
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+/// Options applied to every socket the scanner creates.
+///
+/// The defaults leave the operating system defaults untouched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// Close TCP connections with SO_LINGER(0), sending a RST instead of
+    /// going through the FIN handshake. This frees file descriptors and
+    /// ports right away at high batch sizes.
+    pub linger_zero: bool,
+    /// Set TCP_NODELAY on TCP sockets.
+    pub nodelay: bool,
+    /// Size of the socket send buffer (SO_SNDBUF), in bytes.
+    pub send_buffer_size: Option<usize>,
+    /// Size of the socket receive buffer (SO_RCVBUF), in bytes.
+    pub recv_buffer_size: Option<usize>,
+    /// TOS byte (IPv4) or traffic class (IPv6) of outgoing packets, the
+    /// DSCP value shifted left by two bits.
+    pub tos: Option<u8>,
+    /// Local address sockets bind to, instead of letting the system pick
+    /// one. Only used for targets of the same family.
+    pub source_ip: Option<IpAddr>,
+    /// Local port sockets bind to, e.g. 53 or 443 which some firewalls
+    /// trust. TCP sockets bind to it with SO_REUSEADDR, so connections to
+    /// different targets can share it. UDP sockets don't, other sockets of
+    /// the host must not receive their answers.
+    pub source_port: Option<u16>,
+    /// Network interface packets go out of, whatever the routing table
+    /// says (Linux and macOS only).
+    pub interface: Option<String>,
+}
+
+impl SocketOptions {
+    /// Applies the options to a socket of the same family as `target`
+    /// without connecting it.
+    ///
+    /// Every probe applies them, and one the system rejects (e.g. `--tos`
+    /// on IPv6 where the traffic class can't be set) fails every probe,
+    /// which reports every port as closed. Checking once up front turns
+    /// that into a single clear error. A socket that can't be created at
+    /// all is not reported here, the probes report it as usual.
+    ///
+    /// ```rust
+    /// # use rustscan::scanner::SocketOptions;
+    /// # use std::net::{IpAddr, Ipv4Addr};
+    /// let options = SocketOptions::default();
+    /// assert!(options.check(IpAddr::V4(Ipv4Addr::LOCALHOST)).is_ok());
+    /// ```
+    pub fn check(&self, target: IpAddr) -> io::Result<()> {
+        let target = SocketAddr::new(target, 0);
+        match Socket::new(
+            Domain::for_address(target),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        ) {
+            Ok(socket) => self.apply_tcp(&socket, target),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// The address sockets to `target` bind to: the source IP when it is of
+    /// the same family, the unspecified address of the family otherwise,
+    /// with the source port or any port.
+    fn local_addr(&self, target: SocketAddr) -> SocketAddr {
+        let port = self.source_port.unwrap_or(0);
+        match (self.source_ip, target) {
+            (Some(source @ IpAddr::V4(_)), SocketAddr::V4(_))
+            | (Some(source @ IpAddr::V6(_)), SocketAddr::V6(_)) => SocketAddr::new(source, port),
+            (_, SocketAddr::V4(_)) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)),
+            (_, SocketAddr::V6(_)) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)),
+        }
+    }
+
+    /// Applies the options shared by TCP and UDP sockets.
+    fn apply_common(&self, socket: &Socket, target: SocketAddr) -> io::Result<()> {
+        if let Some(interface) = &self.interface {
+            bind_interface(socket, interface, target)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(tos) = self.tos {
+            match target {
+                SocketAddr::V4(_) => socket.set_tos(u32::from(tos))?,
+                SocketAddr::V6(_) => set_tclass_v6(socket, tos)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_tcp(&self, socket: &Socket, target: SocketAddr) -> io::Result<()> {
+        if self.linger_zero {
+            socket.set_linger(Some(Duration::ZERO))?;
+        }
+        if self.nodelay {
+            socket.set_nodelay(true)?;
+        }
+        if self.source_port.is_some() {
+            socket.set_reuse_address(true)?;
+        }
+        self.apply_common(socket, target)?;
+        if self.source_ip.is_some() || self.source_port.is_some() {
+            socket.bind(&SockAddr::from(self.local_addr(target)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Opens a TCP connection to `target` with the given socket options.
+pub(super) async fn connect_tcp(
+    target: SocketAddr,
+    options: &SocketOptions,
+) -> io::Result<TcpStream> {
+    let socket = Socket::new(
+        Domain::for_address(target),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    options.apply_tcp(&socket, target)?;
+
+    Ok(TcpStream::from(connect_socket(socket, target).await?))
+}
+
+/// Connects an already configured stream socket without blocking.
+async fn connect_socket(socket: Socket, target: SocketAddr) -> io::Result<std::net::TcpStream> {
+    socket.set_nonblocking(true)?;
+
+    match socket.connect(&SockAddr::from(target)) {
+        Ok(()) => {}
+        Err(e) if is_connect_in_progress(&e) => {}
+        Err(e) => return Err(e),
+    }
+
+    // The connection is established (or refused) once the socket becomes
+    // writable, the outcome is then read from SO_ERROR.
+    let stream = Async::new(std::net::TcpStream::from(socket))?;
+    stream.writable().await?;
+    if let Some(e) = stream.get_ref().take_error()? {
+        return Err(e);
+    }
+
+    stream.into_inner()
+}
+
+/// Binds a UDP socket on the unspecified address of the same family as
+/// `target`, with the given socket options.
+pub(super) fn bind_udp(target: SocketAddr, options: &SocketOptions) -> io::Result<UdpSocket> {
+    let local_addr = options.local_addr(target);
+
+    let socket = Socket::new(
+        Domain::for_address(target),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    options.apply_common(&socket, target)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SockAddr::from(local_addr))?;
+
+    Ok(UdpSocket::from(std::net::UdpSocket::from(socket)))
+}
+
+/// Connects to `target` with TCP_FASTOPEN_CONNECT and sends a byte, so
+/// the SYN carries a TCP Fast Open cookie request (or the cookie, when the
+/// kernel already holds one). The cookie the target hands out is then
+/// cached by the kernel, see [`has_fast_open_cookie`].
+///
+/// Scan sockets never ask for cookies: with a cookie cached the connect of
+/// such a socket succeeds before any packet is sent, which would report
+/// every port of the host as open.
+#[cfg(target_os = "linux")]
+pub(super) async fn request_fast_open_cookie(
+    target: SocketAddr,
+    options: &SocketOptions,
+) -> io::Result<()> {
+    let socket = Socket::new(
+        Domain::for_address(target),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    options.apply_tcp(&socket, target)?;
+    set_fast_open_connect(&socket)?;
+    socket.set_nonblocking(true)?;
+
+    // The connect is deferred until the first write, which sends the SYN.
+    match socket.connect(&SockAddr::from(target)) {
+        Ok(()) => {}
+        Err(e) if is_connect_in_progress(&e) => {}
+        Err(e) => return Err(e),
+    }
+    match socket.send(b"\n") {
+        Ok(_) => {}
+        Err(e) if is_connect_in_progress(&e) => {}
+        Err(e) => return Err(e),
+    }
+
+    let stream = Async::new(std::net::TcpStream::from(socket))?;
+    stream.writable().await?;
+    match stream.get_ref().take_error()? {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) async fn request_fast_open_cookie(
+    _target: SocketAddr,
+    _options: &SocketOptions,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "TCP Fast Open detection is only supported on Linux",
+    ))
+}
+
+/// Checks whether the kernel holds a TCP Fast Open cookie for `target`,
+/// meaning the target handed one out to [`request_fast_open_cookie`].
+///
+/// With TCP_FASTOPEN_CONNECT a non-blocking connect is deferred until the
+/// first write when a cookie is available, and therefore succeeds right
+/// away. Without a cookie it starts a regular handshake instead.
+#[cfg(target_os = "linux")]
+pub(super) fn has_fast_open_cookie(target: SocketAddr) -> io::Result<bool> {
+    let socket = Socket::new(
+        Domain::for_address(target),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    set_fast_open_connect(&socket)?;
+    socket.set_nonblocking(true)?;
+
+    match socket.connect(&SockAddr::from(target)) {
+        Ok(()) => Ok(true),
+        Err(e) if is_connect_in_progress(&e) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) fn has_fast_open_cookie(_target: SocketAddr) -> io::Result<bool> {
+    Ok(false)
+}
+
+#[cfg(target_os = "linux")]
+fn set_fast_open_connect(socket: &Socket) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let enable: libc::c_int = 1;
+    // SAFETY: the file descriptor is owned by `socket` and `enable` outlives the call.
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_FASTOPEN_CONNECT,
+            std::ptr::addr_of!(enable).cast(),
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Sends the packets of `socket` out of `interface` with SO_BINDTODEVICE.
+#[cfg(target_os = "linux")]
+fn bind_interface(socket: &Socket, interface: &str, _target: SocketAddr) -> io::Result<()> {
+    socket.bind_device(Some(interface.as_bytes()))
+}
+
+/// Sends the packets of `socket` out of `interface` with IP_BOUND_IF or
+/// IPV6_BOUND_IF.
+#[cfg(target_os = "macos")]
+fn bind_interface(socket: &Socket, interface: &str, target: SocketAddr) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let name = std::ffi::CString::new(interface)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `name` is a valid NUL terminated string for the whole call.
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if index == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (level, option) = match target {
+        SocketAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_BOUND_IF),
+        SocketAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_BOUND_IF),
+    };
+    let index = index as libc::c_int;
+    // SAFETY: the file descriptor is owned by `socket` and `index` outlives the call.
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            option,
+            std::ptr::addr_of!(index).cast(),
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn bind_interface(_socket: &Socket, _interface: &str, _target: SocketAddr) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "binding to an interface is only supported on Linux and macOS",
+    ))
+}
+
+/// Sets IPV6_TCLASS, which `socket2` only exposes on some platforms.
+#[cfg(unix)]
+fn set_tclass_v6(socket: &Socket, tclass: u8) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let value = libc::c_int::from(tclass);
+    // SAFETY: the file descriptor is owned by `socket` and `value` outlives the call.
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IPV6,
+            libc::IPV6_TCLASS,
+            std::ptr::addr_of!(value).cast(),
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+fn set_tclass_v6(_socket: &Socket, _tclass: u8) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "setting the IPv6 traffic class is not supported on this platform",
+    ))
+}
+
+fn is_connect_in_progress(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        if error.raw_os_error() == Some(libc::EINPROGRESS) {
+            return true;
+        }
+    }
+
+    error.kind() == io::ErrorKind::WouldBlock
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bind_udp, connect_tcp, SocketOptions};
+    use async_std::task::block_on;
+    use std::net::TcpListener;
+
+    #[test]
+    fn connects_with_socket_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let options = SocketOptions {
+            linger_zero: true,
+            nodelay: true,
+            send_buffer_size: Some(4096),
+            recv_buffer_size: Some(4096),
+            tos: Some(0x28),
+            source_ip: Some("127.0.0.1".parse().unwrap()),
+            source_port: None,
+            interface: None,
+        };
+
+        let stream = block_on(connect_tcp(listener.local_addr().unwrap(), &options)).unwrap();
+
+        assert!(stream.nodelay().unwrap());
+        assert_eq!(
+            stream.local_addr().unwrap().ip(),
+            options.source_ip.unwrap()
+        );
+    }
+
+    #[test]
+    fn binds_to_the_source_ip_of_the_target_family() {
+        let options = SocketOptions {
+            source_ip: Some("127.0.0.1".parse().unwrap()),
+            ..SocketOptions::default()
+        };
+
+        let socket = bind_udp("127.0.0.2:53".parse().unwrap(), &options).unwrap();
+        assert_eq!(
+            socket.local_addr().unwrap().ip(),
+            options.source_ip.unwrap()
+        );
+        let v6 = options.local_addr("[::1]:53".parse().unwrap());
+        assert!(v6.ip().is_unspecified());
+        assert!(options.check("127.0.0.1".parse().unwrap()).is_ok());
+
+        let unassigned = SocketOptions {
+            source_ip: Some("192.0.2.1".parse().unwrap()),
+            ..SocketOptions::default()
+        };
+        assert!(unassigned.check("127.0.0.1".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn connections_share_the_source_port() {
+        let free = TcpListener::bind("127.0.0.1:0").unwrap();
+        let source_port = free.local_addr().unwrap().port();
+        drop(free);
+        let options = SocketOptions {
+            source_port: Some(source_port),
+            ..SocketOptions::default()
+        };
+
+        let first = TcpListener::bind("127.0.0.1:0").unwrap();
+        let second = TcpListener::bind("127.0.0.1:0").unwrap();
+        for listener in [first, second] {
+            let stream = block_on(connect_tcp(listener.local_addr().unwrap(), &options)).unwrap();
+            assert_eq!(stream.local_addr().unwrap().port(), source_port);
+        }
+    }
+
+    #[test]
+    fn refused_connection_is_an_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap();
+        drop(listener);
+
+        let result = block_on(connect_tcp(target, &SocketOptions::default()));
+
+        assert_eq!(
+            result.unwrap_err().kind(),
+            std::io::ErrorKind::ConnectionRefused
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn cached_fast_open_cookie_does_not_open_closed_ports() {
+        use super::request_fast_open_cookie;
+        use std::os::unix::io::AsRawFd;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let queue: libc::c_int = 16;
+        // SAFETY: the file descriptor is owned by `listener` and `queue` outlives the call.
+        unsafe {
+            libc::setsockopt(
+                listener.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_FASTOPEN,
+                std::ptr::addr_of!(queue).cast(),
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            );
+        }
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_port = closed.local_addr().unwrap();
+        drop(closed);
+
+        // Cookies are cached per host, so one handed out by the listener
+        // also applies to the closed port.
+        for _ in 0..2 {
+            let _ = block_on(request_fast_open_cookie(
+                listener.local_addr().unwrap(),
+                &SocketOptions::default(),
+            ));
+            let result = block_on(connect_tcp(closed_port, &SocketOptions::default()));
+
+            assert_eq!(
+                result.unwrap_err().kind(),
+                std::io::ErrorKind::ConnectionRefused
+            );
+        }
+    }
+}
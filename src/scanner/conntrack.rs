@@ -0,0 +1,128 @@
+//! Slows the scan down when the local conntrack table is close to full.
+//!
+//! The table is checked every few hundred probes. Above `HIGH_WATER` the
+//! scanner stops refilling its batch until only a tenth of it is in flight,
+//! and goes back to full speed once usage falls under `LOW_WATER`.
+use crate::system::conntrack::Conntrack;
+
+/// Probes completed between two reads of the table.
+const CHECK_INTERVAL: usize = 500;
+
+const HIGH_WATER: f64 = 0.9;
+const LOW_WATER: f64 = 0.8;
+
+/// Fraction of the batch kept in flight while throttled.
+const THROTTLED_WINDOW: usize = 10;
+
+#[derive(Debug, Default)]
+pub(super) struct ConntrackThrottle {
+    enabled: bool,
+    completed: usize,
+    throttled: bool,
+    /// Fullest table seen while throttled.
+    peak: Option<Conntrack>,
+}
+
+impl ConntrackThrottle {
+    pub(super) fn new(enabled: bool) -> Self {
+        Self {
+            enabled: enabled && Conntrack::read().is_some(),
+            ..Self::default()
+        }
+    }
+
+    pub(super) fn peak(&self) -> Option<Conntrack> {
+        self.peak
+    }
+
+    /// Called for every completed probe, tells whether another one can be
+    /// started with `in_flight` probes still running.
+    pub(super) fn should_refill(&mut self, in_flight: usize, batch_size: usize) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        self.completed += 1;
+        if self.completed.is_multiple_of(CHECK_INTERVAL) {
+            if let Some(table) = Conntrack::read() {
+                self.update(table);
+            }
+        }
+
+        !self.throttled || in_flight < self.window(batch_size)
+    }
+
+    /// How many of `batch_size` probes can be in flight.
+    pub(super) fn window(&self, batch_size: usize) -> usize {
+        if self.throttled {
+            (batch_size / THROTTLED_WINDOW).max(1)
+        } else {
+            batch_size
+        }
+    }
+
+    fn update(&mut self, table: Conntrack) {
+        let usage = table.usage();
+        if usage >= HIGH_WATER {
+            self.throttled = true;
+        } else if usage < LOW_WATER {
+            self.throttled = false;
+        }
+
+        if self.throttled && self.peak.is_none_or(|peak| usage > peak.usage()) {
+            self.peak = Some(table);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Conntrack, ConntrackThrottle};
+
+    #[test]
+    fn throttles_between_high_and_low_water() {
+        let mut throttle = ConntrackThrottle {
+            enabled: true,
+            ..ConntrackThrottle::default()
+        };
+        assert!(throttle.should_refill(1_000, 1_000));
+
+        throttle.update(Conntrack {
+            count: 95,
+            max: 100,
+        });
+        assert!(!throttle.should_refill(1_000, 1_000));
+        assert!(throttle.should_refill(99, 1_000));
+
+        // still above low water, stays throttled
+        throttle.update(Conntrack {
+            count: 85,
+            max: 100,
+        });
+        assert!(!throttle.should_refill(1_000, 1_000));
+
+        throttle.update(Conntrack {
+            count: 50,
+            max: 100,
+        });
+        assert!(throttle.should_refill(1_000, 1_000));
+        assert_eq!(
+            throttle.peak(),
+            Some(Conntrack {
+                count: 95,
+                max: 100
+            })
+        );
+    }
+
+    #[test]
+    fn disabled_throttle_always_refills() {
+        let mut throttle = ConntrackThrottle::default();
+        throttle.update(Conntrack {
+            count: 100,
+            max: 100,
+        });
+
+        assert!(throttle.should_refill(1_000, 1_000));
+    }
+}
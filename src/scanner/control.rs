@@ -0,0 +1,242 @@
+//! Control over a running scan.
+//!
+//! A [`ScanControl`] is shared between the scanner and whoever wants to steer
+//! it while it runs, like the control socket: the scan can be paused and
+//! resumed, its probe rate capped, and the open ports found so far read.
+use super::{ScanEstimate, Scanner};
+
+use async_std::task::sleep;
+use std::fmt;
+use std::net::SocketAddr;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often a paused scan checks whether it was resumed.
+const PAUSE_POLL: Duration = Duration::from_millis(100);
+
+/// Shared state to pause, resume and throttle a running scan.
+#[derive(Debug, Default)]
+pub struct ScanControl {
+    paused: AtomicBool,
+    /// Probes per second, 0 is unlimited.
+    rate: AtomicU32,
+    next_probe: Mutex<Option<Instant>>,
+    probed: AtomicU64,
+    estimate: Mutex<ScanEstimate>,
+    open: Mutex<Vec<SocketAddr>>,
+}
+
+/// A snapshot of the progress of a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanStatus {
+    pub paused: bool,
+    pub rate: Option<u32>,
+    pub probed: u64,
+    pub total: u64,
+    /// How long the whole scan takes at most, see [`ScanEstimate`].
+    pub estimated: Duration,
+    pub open: usize,
+}
+
+impl fmt::Display for ScanStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}/{} probes, {} open, rate {}",
+            if self.paused { "paused" } else { "running" },
+            self.probed,
+            self.total,
+            self.open,
+            self.rate
+                .map_or_else(|| "unlimited".to_owned(), |rate| format!("{rate}/s"))
+        )
+    }
+}
+
+impl ScanControl {
+    /// Stops the scan from starting new probes; probes in flight finish.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Caps the scan to `rate` probes per second, `None` lifts the cap.
+    pub fn set_rate(&self, rate: Option<u32>) {
+        self.rate.store(rate.unwrap_or(0), Ordering::SeqCst);
+        if let Ok(mut next_probe) = self.next_probe.lock() {
+            *next_probe = None;
+        }
+    }
+
+    pub fn rate(&self) -> Option<u32> {
+        match self.rate.load(Ordering::SeqCst) {
+            0 => None,
+            rate => Some(rate),
+        }
+    }
+
+    pub fn status(&self) -> ScanStatus {
+        let estimate = self
+            .estimate
+            .lock()
+            .map(|estimate| *estimate)
+            .unwrap_or_default();
+        ScanStatus {
+            paused: self.is_paused(),
+            rate: self.rate(),
+            probed: self.probed.load(Ordering::SeqCst),
+            total: estimate.sockets,
+            estimated: estimate.duration,
+            open: self.open.lock().map(|open| open.len()).unwrap_or_default(),
+        }
+    }
+
+    /// The open sockets found so far by the running scan.
+    pub fn partial_results(&self) -> Vec<SocketAddr> {
+        self.open
+            .lock()
+            .map(|open| open.clone())
+            .unwrap_or_default()
+    }
+
+    /// Adds `estimate` to the one of the running scan, for the probes it
+    /// sends on top of those it started with.
+    pub(super) fn extend(&self, estimate: ScanEstimate) {
+        if let Ok(mut current) = self.estimate.lock() {
+            *current = *current + estimate;
+        }
+    }
+
+    /// Forgets the progress of a previous scan, the next one being `estimate`.
+    pub(super) fn start(&self, estimate: ScanEstimate) {
+        self.probed.store(0, Ordering::SeqCst);
+        if let Ok(mut current) = self.estimate.lock() {
+            *current = estimate;
+        }
+        if let Ok(mut open) = self.open.lock() {
+            open.clear();
+        }
+    }
+
+    pub(super) fn record(&self, open: Option<SocketAddr>) {
+        self.probed.fetch_add(1, Ordering::SeqCst);
+        if let (Some(socket), Ok(mut sockets)) = (open, self.open.lock()) {
+            sockets.push(socket);
+        }
+    }
+
+    /// Waits until the next probe may start: until the scan is resumed when
+    /// paused, and for its slot when the rate is capped.
+    pub(super) async fn throttle(&self) {
+        while self.is_paused() {
+            sleep(PAUSE_POLL).await;
+        }
+        let Some(rate) = self.rate() else {
+            return;
+        };
+        let wait = match self.next_probe.lock() {
+            Ok(mut next_probe) => {
+                let now = Instant::now();
+                let slot = next_probe.map_or(now, |slot| slot.max(now));
+                *next_probe = Some(slot + Duration::from_secs(1) / rate);
+                slot - now
+            }
+            Err(_) => return,
+        };
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+}
+
+impl Scanner {
+    /// Starts at most `rate` probes per second, whatever the batch size.
+    /// `None`, the default, doesn't cap the rate. [`ScanControl::set_rate`]
+    /// changes it while the scan runs.
+    #[must_use]
+    pub fn with_rate(self, rate: Option<NonZeroU32>) -> Self {
+        self.control.set_rate(rate.map(NonZeroU32::get));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScanControl;
+    use crate::input::{PortRange, ScanOrder};
+    use crate::port_strategy::PortStrategy;
+    use crate::scanner::{ScanEstimate, Scanner};
+    use async_std::task::block_on;
+    use std::num::NonZeroU32;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn status_tracks_progress() {
+        let control = ScanControl::default();
+        control.start(ScanEstimate {
+            sockets: 3,
+            duration: Duration::from_secs(3),
+        });
+        control.record(None);
+        control.record(Some("127.0.0.1:80".parse().unwrap()));
+        control.pause();
+
+        let status = control.status();
+        assert!(status.paused);
+        assert_eq!((status.probed, status.total, status.open), (2, 3, 1));
+        assert_eq!(status.estimated, Duration::from_secs(3));
+        assert_eq!(
+            status.to_string(),
+            "paused 2/3 probes, 1 open, rate unlimited"
+        );
+        assert_eq!(
+            control.partial_results(),
+            vec!["127.0.0.1:80".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn rate_spaces_out_probes() {
+        let control = ScanControl::default();
+        control.set_rate(Some(20));
+        let start = Instant::now();
+        block_on(async {
+            for _ in 0..3 {
+                control.throttle().await;
+            }
+        });
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        control.set_rate(None);
+        assert_eq!(control.rate(), None);
+    }
+
+    #[test]
+    fn scanner_starts_with_its_rate() {
+        let range = PortRange { start: 1, end: 10 };
+        let strategy = PortStrategy::pick(&Some(range), None, ScanOrder::Serial);
+        let scanner = Scanner::new(
+            &["127.0.0.1".parse().unwrap()],
+            10,
+            Duration::from_millis(100),
+            1,
+            true,
+            strategy,
+            true,
+            vec![],
+            false,
+        )
+        .with_rate(NonZeroU32::new(500));
+
+        assert_eq!(scanner.control().rate(), Some(500));
+    }
+}
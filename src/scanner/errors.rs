@@ -0,0 +1,172 @@
+//! Bounded summary of the errors probes ran into.
+//!
+//! Errors are counted per class and per host instead of being kept, so
+//! memory stays bounded however many probes fail. Past
+//! [`MAX_TRACKED_HOSTS`] hosts, errors are only counted per class.
+use super::ProbeError;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+
+/// How many hosts get their own error counter.
+pub const MAX_TRACKED_HOSTS: usize = 1024;
+
+/// How many hosts the summary lists.
+const SHOWN_HOSTS: usize = 5;
+
+/// Errors of a scan, per class and per host.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ErrorSummary {
+    per_class: HashMap<ProbeError, u64>,
+    per_host: HashMap<IpAddr, u64>,
+    untracked: u64,
+}
+
+impl ErrorSummary {
+    pub fn record(&mut self, ip: IpAddr, error: &io::Error) {
+        *self
+            .per_class
+            .entry(ProbeError::classify(error))
+            .or_default() += 1;
+        if let Some(count) = self.per_host.get_mut(&ip) {
+            *count += 1;
+        } else if self.per_host.len() < MAX_TRACKED_HOSTS {
+            self.per_host.insert(ip, 1);
+        } else {
+            self.untracked += 1;
+        }
+    }
+
+    /// Takes back an error of `class` recorded for `ip`, e.g. one a later
+    /// probe of the same socket replaces.
+    pub fn forget(&mut self, ip: IpAddr, class: ProbeError) {
+        let Some(count) = self.per_class.get_mut(&class).filter(|count| **count > 0) else {
+            return;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.per_class.remove(&class);
+        }
+        match self.per_host.get_mut(&ip) {
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                self.per_host.remove(&ip);
+            }
+            None => self.untracked = self.untracked.saturating_sub(1),
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.per_class.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.per_class.is_empty()
+    }
+
+    /// How many errors of `class` probes ran into.
+    pub fn count(&self, class: ProbeError) -> u64 {
+        self.per_class.get(&class).copied().unwrap_or_default()
+    }
+
+    /// Errors per class, most frequent first.
+    pub fn classes(&self) -> Vec<(ProbeError, u64)> {
+        let mut classes: Vec<(ProbeError, u64)> = self
+            .per_class
+            .iter()
+            .map(|(class, count)| (*class, *count))
+            .collect();
+        classes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        classes
+    }
+
+    /// Errors per host, most frequent first.
+    pub fn hosts(&self) -> Vec<(IpAddr, u64)> {
+        let mut hosts: Vec<(IpAddr, u64)> = self
+            .per_host
+            .iter()
+            .map(|(ip, count)| (*ip, *count))
+            .collect();
+        hosts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hosts
+    }
+
+    /// Errors of hosts past [`MAX_TRACKED_HOSTS`].
+    pub fn untracked(&self) -> u64 {
+        self.untracked
+    }
+}
+
+impl fmt::Display for ErrorSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let classes: Vec<String> = self
+            .classes()
+            .iter()
+            .map(|(class, count)| format!("{count} {class}"))
+            .collect();
+        write!(f, "{} probe errors: {}", self.total(), classes.join(", "))?;
+
+        let hosts: Vec<String> = self
+            .hosts()
+            .iter()
+            .take(SHOWN_HOSTS)
+            .map(|(ip, count)| format!("{ip} ({count})"))
+            .collect();
+        if !hosts.is_empty() {
+            write!(f, "\nMost errors on: {}", hosts.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorSummary, MAX_TRACKED_HOSTS};
+    use crate::scanner::ProbeError;
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn counts_errors_per_class_and_host() {
+        let mut summary = ErrorSummary::default();
+        let flaky: IpAddr = "10.0.0.1".parse().unwrap();
+        let closed: IpAddr = "10.0.0.2".parse().unwrap();
+        for _ in 0..3 {
+            summary.record(flaky, &io::Error::from(io::ErrorKind::TimedOut));
+        }
+        summary.record(closed, &io::Error::from(io::ErrorKind::ConnectionRefused));
+
+        assert_eq!(summary.total(), 4);
+        assert_eq!(
+            summary.classes(),
+            vec![
+                (ProbeError::TimedOut, 3),
+                (ProbeError::ConnectionRefused, 1)
+            ]
+        );
+        assert_eq!(summary.hosts(), vec![(flaky, 3), (closed, 1)]);
+        assert_eq!(summary.count(ProbeError::TimedOut), 3);
+        assert_eq!(summary.count(ProbeError::ResourceExhausted), 0);
+
+        summary.forget(closed, ProbeError::ConnectionRefused);
+        summary.forget(closed, ProbeError::ConnectionRefused);
+        assert_eq!(summary.total(), 3);
+        assert_eq!(summary.classes(), vec![(ProbeError::TimedOut, 3)]);
+        assert_eq!(summary.hosts(), vec![(flaky, 3)]);
+    }
+
+    #[test]
+    fn bounds_tracked_hosts() {
+        let mut summary = ErrorSummary::default();
+        for host in 0..=MAX_TRACKED_HOSTS as u32 {
+            let ip = IpAddr::V4(Ipv4Addr::from(host));
+            summary.record(ip, &io::Error::from(io::ErrorKind::TimedOut));
+        }
+
+        assert_eq!(summary.hosts().len(), MAX_TRACKED_HOSTS);
+        assert_eq!(summary.untracked(), 1);
+        assert_eq!(summary.total(), MAX_TRACKED_HOSTS as u64 + 1);
+    }
+}
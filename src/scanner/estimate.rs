@@ -0,0 +1,225 @@
+//! How long a scan takes at most.
+//!
+//! The scanner keeps `batch_size` probes in flight and starts a new one as
+//! soon as one finishes. A probe of a port that never answers takes the
+//! timeout for every try and waits out the retry backoff between them, so
+//! the worst case is every socket timing out: the sockets pass through the
+//! batch in `sockets / batch_size` waves, each taking `tries * timeout`
+//! plus the backoff. With a per-host cap, a batch holds at most the cap for
+//! each host. Closed and open ports answer sooner, so real scans usually
+//! finish well before the estimate, which the progress of a running scan
+//! uses as the bound of its time left, see
+//! [`ScanProgress::remaining`](super::ScanProgress::remaining).
+use super::{RetryPolicy, Scanner, SpreadStrategy};
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::Duration;
+
+/// The number of sockets a scan probes and how long it takes at most.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanEstimate {
+    pub sockets: u64,
+    pub duration: Duration,
+}
+
+impl ScanEstimate {
+    /// The time left once `probed` sockets were probed, at the pace of the
+    /// estimate.
+    pub fn remaining(&self, probed: u64) -> Duration {
+        if self.sockets == 0 || probed >= self.sockets {
+            return Duration::ZERO;
+        }
+        let left = self.sockets - probed;
+        Duration::from_secs_f64(self.duration.as_secs_f64() * left as f64 / self.sockets as f64)
+    }
+}
+
+impl std::ops::Add for ScanEstimate {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            sockets: self.sockets.saturating_add(other.sockets),
+            duration: self.duration.saturating_add(other.duration),
+        }
+    }
+}
+
+impl fmt::Display for ScanEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.duration.as_secs();
+        write!(f, "{} sockets, at most ", self.sockets)?;
+        match secs {
+            0..=59 => write!(f, "{}s", secs.max(1)),
+            60..=3599 => write!(f, "{}m{:02}s", secs / 60, secs % 60),
+            _ => write!(f, "{}h{:02}m", secs / 3600, secs % 3600 / 60),
+        }
+    }
+}
+
+/// Estimates a scan of `ports` ports on each of `ips` addresses, probing
+/// `batch_size` sockets at once, each for up to `tries` times `timeout`
+/// with the longest delays of `retry` between the tries.
+///
+/// ```rust
+/// # use rustscan::scanner::{estimate, RetryPolicy};
+/// # use std::time::Duration;
+/// let estimate = estimate(2, 1000, 500, Duration::from_secs(1), 1, RetryPolicy::None);
+/// assert_eq!(estimate.sockets, 2000);
+/// assert_eq!(estimate.duration, Duration::from_secs(4));
+/// ```
+pub fn estimate(
+    ips: usize,
+    ports: usize,
+    batch_size: usize,
+    timeout: Duration,
+    tries: u8,
+    retry: RetryPolicy,
+) -> ScanEstimate {
+    let sockets = u64::try_from(ips)
+        .unwrap_or(u64::MAX)
+        .saturating_mul(u64::try_from(ports).unwrap_or(u64::MAX));
+    let batch_size = u64::try_from(batch_size.max(1)).unwrap_or(u64::MAX);
+    let waves = u32::try_from(sockets.div_ceil(batch_size)).unwrap_or(u32::MAX);
+    let tries = tries.max(1);
+    let backoff = (1..tries).fold(Duration::ZERO, |backoff, retry_nr| {
+        backoff.saturating_add(retry.max_delay(retry_nr))
+    });
+    ScanEstimate {
+        sockets,
+        duration: timeout
+            .saturating_mul(u32::from(tries))
+            .saturating_add(backoff)
+            .saturating_mul(waves),
+    }
+}
+
+impl Scanner {
+    /// Estimates the scan [`Scanner::run`] performs, following the policies
+    /// of each group of targets, the per-host cap of [`Scanner::with_spread`]
+    /// and the rate cap of [`Scanner::control`].
+    pub fn estimate(&self) -> ScanEstimate {
+        self.estimate_ports(self.port_strategy.len())
+    }
+
+    /// Like [`Scanner::estimate`], for a scan of `ports` ports per host.
+    pub(super) fn estimate_ports(&self, ports: usize) -> ScanEstimate {
+        let mut total = ScanEstimate::default();
+        for (ips, batch_size) in self.policies.groups(&self.ips, self.batch_size) {
+            let policy = ips.first().and_then(|ip| self.policies.get(ip));
+            let timeout = policy
+                .and_then(|policy| policy.timeout())
+                .unwrap_or(self.timeout);
+            let tries = policy
+                .and_then(|policy| policy.tries)
+                .unwrap_or(self.tries.get());
+            let batch_size = match self.spread {
+                SpreadStrategy::Batch => batch_size,
+                SpreadStrategy::PerHost(per_host) => {
+                    batch_size.min(ips.len().saturating_mul(per_host.get()))
+                }
+            };
+            let sockets = self.socket_count(&ips, ports);
+            total = total + estimate(1, sockets, batch_size, timeout, tries, self.retry);
+        }
+
+        if let Some(rate) = self.control.rate() {
+            let paced = Duration::from_secs(total.sockets / u64::from(rate));
+            total.duration = total.duration.max(paced);
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{estimate, ScanEstimate};
+    use crate::input::{PortRange, ScanOrder};
+    use crate::port_strategy::PortStrategy;
+    use crate::scanner::{RetryPolicy, Scanner, SpreadStrategy};
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+
+    #[test]
+    fn partial_batches_take_a_wave() {
+        let estimate = estimate(
+            1,
+            1001,
+            500,
+            Duration::from_millis(1500),
+            2,
+            RetryPolicy::None,
+        );
+        assert_eq!(estimate.sockets, 1001);
+        assert_eq!(estimate.duration, Duration::from_secs(9));
+        assert_eq!(estimate.to_string(), "1001 sockets, at most 9s");
+        assert_eq!(estimate.remaining(0), Duration::from_secs(9));
+        assert_eq!(estimate.remaining(1001), Duration::ZERO);
+    }
+
+    #[test]
+    fn retries_wait_out_the_backoff() {
+        let retry = RetryPolicy::Fixed(Duration::from_millis(500));
+        let estimate = estimate(1, 1000, 500, Duration::from_secs(1), 3, retry);
+
+        // Two waves of three tries, with two delays between them.
+        assert_eq!(estimate.duration, Duration::from_secs(8));
+    }
+
+    #[test]
+    fn scanner_estimate_follows_exclusions_and_rate() {
+        let ips = vec!["127.0.0.1".parse().unwrap()];
+        let range = PortRange {
+            start: 1,
+            end: 1_000,
+        };
+        let strategy = PortStrategy::pick(&Some(range), None, ScanOrder::Serial);
+        let scanner = Scanner::new(
+            &ips,
+            100,
+            Duration::from_millis(100),
+            1,
+            true,
+            strategy,
+            true,
+            vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10],
+            false,
+        );
+        assert_eq!(
+            scanner.estimate(),
+            ScanEstimate {
+                sockets: 990,
+                duration: Duration::from_secs(1),
+            }
+        );
+
+        scanner.control().set_rate(Some(10));
+        assert_eq!(scanner.estimate().duration, Duration::from_secs(99));
+    }
+
+    #[test]
+    fn per_host_cap_shrinks_the_batch() {
+        let ips = vec!["127.0.0.1".parse().unwrap(), "127.0.0.2".parse().unwrap()];
+        let range = PortRange {
+            start: 1,
+            end: 1_000,
+        };
+        let strategy = PortStrategy::pick(&Some(range), None, ScanOrder::Serial);
+        let scanner = Scanner::new(
+            &ips,
+            1_000,
+            Duration::from_millis(100),
+            1,
+            true,
+            strategy,
+            true,
+            vec![],
+            false,
+        )
+        .with_spread(SpreadStrategy::PerHost(NonZeroUsize::new(100).unwrap()));
+
+        // 2000 sockets, 200 at a time.
+        assert_eq!(scanner.estimate().duration, Duration::from_secs(1));
+    }
+}
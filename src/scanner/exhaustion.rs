@@ -0,0 +1,139 @@
+//! Running out of file descriptors, or other local resources, halfway
+//! through a scan.
+//!
+//! A spike in open files, from the scan or anything else in the process,
+//! makes new sockets fail with "Too many open files". Instead of counting
+//! those probes as errors, new probes wait a moment for sockets to be
+//! released, the scan halves how many probes it keeps in flight, and the
+//! probes that ran out are sent again. Probes still running out after a
+//! few tries count as errors like any other.
+use super::Scanner;
+
+use async_std::task::sleep;
+use log::debug;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// How long new probes wait after running out, and how long further
+/// probes running out count as the same spike.
+const PAUSE: Duration = Duration::from_millis(500);
+
+/// How many times a probe that ran out is sent again.
+pub(super) const MAX_RETRIES: u8 = 3;
+
+/// The scan ran out of local resources and lowered its batch size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exhaustion {
+    pub batch_size: usize,
+    /// The batch size the scan was lowered to.
+    pub lowered_to: usize,
+    /// Probes sent again after running out.
+    pub retried: u64,
+}
+
+impl fmt::Display for Exhaustion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The scan ran out of open files or other local resources, so the batch size was lowered from {} to {} and {} probes were sent again. Raise the file limit with --ulimit or lower the batch size with -b to scan at full speed.",
+            self.batch_size, self.lowered_to, self.retried
+        )
+    }
+}
+
+/// How many probes can be in flight since the scan last ran out.
+#[derive(Debug, Default)]
+pub(super) struct Window {
+    /// `None` until the scan runs out.
+    cap: Option<usize>,
+    paused_until: Option<Instant>,
+    retried: u64,
+}
+
+impl Window {
+    /// Counts a probe that ran out, halving the window once per spike.
+    fn exhausted(&mut self, batch_size: usize, now: Instant) {
+        self.retried += 1;
+        if self.paused_until.is_some_and(|until| now < until) {
+            return;
+        }
+        let cap = self.cap.unwrap_or(batch_size);
+        self.cap = Some((cap / 2).max(1));
+        self.paused_until = Some(now + PAUSE);
+    }
+}
+
+impl Scanner {
+    /// How many of `batch_size` probes can be in flight, fewer once the scan
+    /// ran out of resources.
+    pub(super) fn window(&self, batch_size: usize) -> usize {
+        self.window
+            .lock()
+            .ok()
+            .and_then(|window| window.cap)
+            .map_or(batch_size, |cap| cap.min(batch_size))
+    }
+
+    /// Counts a probe that ran out of resources and is about to be sent
+    /// again.
+    pub(super) fn exhausted(&self) {
+        if let Ok(mut window) = self.window.lock() {
+            window.exhausted(self.batch_size, Instant::now());
+            debug!(
+                "Ran out of resources, {} probes in flight at most",
+                window.cap.unwrap_or(self.batch_size)
+            );
+        }
+    }
+
+    /// Waits for the resources to be released after the scan ran out.
+    pub(super) async fn wait_for_resources(&self) {
+        let until = self
+            .window
+            .lock()
+            .ok()
+            .and_then(|window| window.paused_until);
+        if let Some(until) = until {
+            let wait = until.saturating_duration_since(Instant::now());
+            if !wait.is_zero() {
+                sleep(wait).await;
+            }
+        }
+    }
+
+    /// How the last scan coped with running out of resources, `None` when
+    /// it never did.
+    pub fn exhaustion(&self) -> Option<Exhaustion> {
+        let window = self.window.lock().ok()?;
+        Some(Exhaustion {
+            batch_size: self.batch_size,
+            lowered_to: window.cap?,
+            retried: window.retried,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Window, PAUSE};
+    use std::time::Instant;
+
+    #[test]
+    fn halves_the_window_once_per_spike() {
+        let mut window = Window::default();
+        let now = Instant::now();
+
+        window.exhausted(100, now);
+        window.exhausted(100, now);
+        assert_eq!(window.cap, Some(50));
+
+        window.exhausted(100, now + PAUSE);
+        assert_eq!(window.cap, Some(25));
+        assert_eq!(window.retried, 3);
+
+        for nr in 1..10 {
+            window.exhausted(100, now + PAUSE * (nr + 1));
+        }
+        assert_eq!(window.cap, Some(1));
+    }
+}
@@ -0,0 +1,163 @@
+//! Hooks run on the results of a scan, for library users feeding them into
+//! a database or a UI, and for running scripts on hosts as they complete.
+//!
+//! Unlike a [`ResultSink`](super::ResultSink), which gets every event as
+//! it happens, hooks are told when a host is done with: every one of its
+//! ports was probed, so the open ports they get for it are final.
+use super::{ScanResult, Scanner};
+use crate::output::Protocol;
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+/// Called by the scanner as results come in. Every method does nothing by
+/// default, so hooks only implement the ones they need. Probes run
+/// concurrently, so calls come from several tasks.
+pub trait ResultHook: fmt::Debug + Send + Sync {
+    /// A port was found open over `protocol`.
+    fn on_port_open(&self, _socket: SocketAddr, _protocol: Protocol) {}
+
+    /// Every port of `ip` was probed, `open` being those found open over
+    /// any protocol in ascending order.
+    fn on_host_complete(&self, _ip: IpAddr, _open: &[u16]) {}
+
+    /// The scan is over, with the state of every probed port.
+    fn on_scan_complete(&self, _result: &ScanResult) {}
+}
+
+impl Scanner {
+    /// Registers `hook`, called after the ones registered before it.
+    #[must_use]
+    pub fn with_hook(mut self, hook: Box<dyn ResultHook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    pub(super) fn hook_port_open(&self, socket: SocketAddr, protocol: Protocol) {
+        for hook in &self.hooks {
+            hook.on_port_open(socket, protocol);
+        }
+    }
+
+    /// Tells the hooks `ip` is done with, unless this is the first phase
+    /// of a sweep, after which the ports of the host get a second look.
+    pub(super) fn hook_host_complete(&self, ip: IpAddr, result: &ScanResult) {
+        if self.sweeping || self.hooks.is_empty() {
+            return;
+        }
+        let open = result.open_ports(ip);
+        for hook in &self.hooks {
+            hook.on_host_complete(ip, &open);
+        }
+    }
+
+    pub(super) fn hook_scan_complete(&self, result: &ScanResult) {
+        if self.sweeping {
+            return;
+        }
+        for hook in &self.hooks {
+            hook.on_scan_complete(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResultHook;
+    use crate::output::Protocol;
+    use crate::port_strategy::PortStrategy;
+    use crate::scanner::{ScanResult, Scanner};
+    use async_std::task::block_on;
+    use std::net::{IpAddr, SocketAddr, TcpListener};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Call {
+        PortOpen(SocketAddr, Protocol),
+        HostComplete(IpAddr, Vec<u16>),
+        ScanComplete(usize),
+    }
+
+    #[derive(Debug, Default)]
+    struct Recorder(Arc<Mutex<Vec<Call>>>);
+
+    impl ResultHook for Recorder {
+        fn on_port_open(&self, socket: SocketAddr, protocol: Protocol) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(Call::PortOpen(socket, protocol));
+        }
+
+        fn on_host_complete(&self, ip: IpAddr, open: &[u16]) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(Call::HostComplete(ip, open.to_vec()));
+        }
+
+        fn on_scan_complete(&self, result: &ScanResult) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(Call::ScanComplete(result.len()));
+        }
+    }
+
+    fn scanner(ports: Vec<u16>) -> Scanner {
+        Scanner::new(
+            &["127.0.0.1".parse().unwrap()],
+            10,
+            Duration::from_millis(500),
+            1,
+            true,
+            PortStrategy::Manual(ports),
+            true,
+            vec![],
+            false,
+        )
+    }
+
+    #[test]
+    fn hooks_follow_the_scan() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_port = closed.local_addr().unwrap().port();
+        drop(closed);
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let scanner =
+            scanner(vec![open.port(), closed_port]).with_hook(Box::new(Recorder(calls.clone())));
+        block_on(scanner.run());
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                Call::PortOpen(open, Protocol::Tcp),
+                Call::HostComplete(open.ip(), vec![open.port()]),
+                Call::ScanComplete(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn sweeps_complete_hosts_once() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let mut scanner = scanner(vec![open.port()]).with_hook(Box::new(Recorder(calls.clone())));
+        block_on(scanner.sweep());
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                Call::PortOpen(open, Protocol::Tcp),
+                Call::HostComplete(open.ip(), vec![open.port()]),
+                Call::ScanComplete(1),
+            ]
+        );
+    }
+}
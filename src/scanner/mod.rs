@@ -1,42 +1,175 @@
 //! Core functionality for actual scanning behaviour.
-use crate::generated::get_parsed_data;
+use crate::address::SampleCoverage;
+use crate::input::IterationOrder;
+use crate::learning::{ErrorRates, LatencyProfiles};
+use crate::output::Protocol;
+use crate::policy::Policies;
 use crate::port_strategy::PortStrategy;
+use crate::system::resource_limits;
+#[cfg(feature = "tui")]
+use crate::tui::TerminalSink;
 use log::debug;
 
 mod socket_iterator;
 use socket_iterator::SocketIterator;
 
+mod spread;
+use spread::Spread;
+pub use spread::SpreadStrategy;
+
+mod warmup;
+
+mod connector;
+pub use connector::SocketOptions;
+
+mod transport;
+
+pub mod uptime;
+
+mod tarpit;
+pub use tarpit::{Tarpit, TarpitReason};
+
+mod backoff;
+pub use backoff::BackoffEvent;
+use backoff::ResponsivenessTracker;
+
+mod rate;
+pub use rate::RateLimit;
+use rate::{Outcome, RateController};
+
+mod conntrack;
+use crate::system::conntrack::Conntrack;
+use conntrack::ConntrackThrottle;
+
+mod stats;
+pub use stats::ScanStats;
+use stats::StatsCollector;
+
+mod sweep;
+
+mod verify;
+
+mod sampling;
+use sampling::{is_skipped, AllOpenSampler, Skipped};
+
+mod probe_error;
+pub use probe_error::ProbeError;
+
+mod errors;
+pub use errors::ErrorSummary;
+
+mod exhaustion;
+pub use exhaustion::Exhaustion;
+use exhaustion::Window;
+
+mod sink;
+pub use sink::{MultiSink, NullSink, ResultSink, ScanEvent};
+
+mod hook;
+pub use hook::ResultHook;
+
+mod control;
+pub use control::{ScanControl, ScanStatus};
+
+mod estimate;
+pub use estimate::{estimate, ScanEstimate};
+
+mod summary;
+pub use summary::ScanSummary;
+
+mod progress;
+pub use progress::{report_progress, ScanProgress};
+
+mod result;
+pub use result::{PortState, ScanResult};
+
+mod syn;
+pub use syn::{SynProber, TcpExtensions};
+
+mod cancel;
+pub use cancel::CancellationToken;
+
+mod payloads;
+pub use payloads::UdpPayloads;
+
+mod unreachable;
+pub use unreachable::{IcmpListener, Unreachable};
+
+mod retry;
+pub use retry::RetryPolicy;
+
+mod stream;
+
+mod setup;
+
 use async_std::net::TcpStream;
 use async_std::prelude::*;
 use async_std::{io, net::UdpSocket};
-use colored::Colorize;
+use futures::channel::mpsc::UnboundedSender;
 use futures::stream::FuturesUnordered;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::{
-    collections::HashSet,
-    net::{IpAddr, Shutdown, SocketAddr},
+    net::{IpAddr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV6},
     num::NonZeroU8,
-    time::Duration,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
+/// How often a probe waiting for its host to be under its rate limit checks
+/// again.
+const RATE_SLOT_POLL: Duration = Duration::from_millis(10);
+
 /// The class for the scanner
 /// IP is data type IpAddr and is the IP address
 /// start & end is where the port scan starts and ends
 /// batch_size is how many ports at a time should be scanned
 /// Timeout is the time RustScan should wait before declaring a port closed. As datatype Duration.
 /// greppable is whether or not RustScan should print things, or wait until the end to print only the ip and open ports.
+/// Open ports are printed by the result sink, see [`Scanner::with_sink`], and
+/// handed to the hooks registered with [`Scanner::with_hook`].
 #[cfg(not(tarpaulin_include))]
 #[derive(Debug)]
 pub struct Scanner {
     ips: Vec<IpAddr>,
     batch_size: usize,
     timeout: Duration,
+    read_timeout: Option<Duration>,
     tries: NonZeroU8,
-    greppable: bool,
+    retry: RetryPolicy,
     port_strategy: PortStrategy,
-    accessible: bool,
-    exclude_ports: Vec<u16>,
-    udp: bool,
+    target_ports: HashMap<IpAddr, Vec<u16>>,
+    protocols: Vec<Protocol>,
+    udp_payloads: UdpPayloads,
+    socket_options: SocketOptions,
+    anomaly_backoff: bool,
+    latency: Mutex<LatencyProfiles>,
+    error_rates: Mutex<ErrorRates>,
+    responsiveness: Mutex<ResponsivenessTracker>,
+    adaptive_rate: bool,
+    rates: Mutex<RateController>,
+    conntrack_throttle: bool,
+    conntrack_peak: Mutex<Option<Conntrack>>,
+    stats: StatsCollector,
+    last_stats: Mutex<ScanStats>,
+    last_summary: Mutex<ScanSummary>,
+    sample_coverage: Option<SampleCoverage>,
+    sweeping: bool,
+    timed_out: Mutex<Vec<SocketAddr>>,
+    all_open: Mutex<AllOpenSampler>,
+    policies: Policies,
+    errors: Mutex<ErrorSummary>,
+    window: Mutex<Window>,
+    sink: Box<dyn ResultSink>,
+    hooks: Vec<Box<dyn ResultHook>>,
+    control: Arc<ScanControl>,
+    syn: Option<SynProber>,
+    icmp: Option<IcmpListener>,
+    stream: Mutex<Option<UnboundedSender<SocketAddr>>>,
+    scopes: HashMap<Ipv6Addr, u32>,
+    spread: SpreadStrategy,
+    iteration_order: IterationOrder,
+    cancel: CancellationToken,
+    unscanned: Mutex<BTreeMap<IpAddr, Vec<u16>>>,
 }
 
 // Allowing too many arguments for clippy.
@@ -56,115 +189,635 @@ impl Scanner {
         Self {
             batch_size,
             timeout,
-            tries: NonZeroU8::new(std::cmp::max(tries, 1)).unwrap(),
-            greppable,
-            port_strategy,
+            read_timeout: None,
+            tries: NonZeroU8::new(tries).unwrap_or(NonZeroU8::MIN),
+            retry: RetryPolicy::default(),
+            port_strategy: port_strategy.excluding(&exclude_ports),
+            target_ports: HashMap::new(),
             ips: ips.iter().map(ToOwned::to_owned).collect(),
-            accessible,
-            exclude_ports,
-            udp,
+            protocols: vec![if udp { Protocol::Udp } else { Protocol::Tcp }],
+            udp_payloads: UdpPayloads::builtin(),
+            socket_options: SocketOptions::default(),
+            anomaly_backoff: false,
+            latency: Mutex::new(LatencyProfiles::default()),
+            error_rates: Mutex::new(ErrorRates::default()),
+            responsiveness: Mutex::new(ResponsivenessTracker::default()),
+            adaptive_rate: true,
+            rates: Mutex::new(RateController::default()),
+            conntrack_throttle: true,
+            conntrack_peak: Mutex::new(None),
+            stats: StatsCollector::default(),
+            last_stats: Mutex::new(ScanStats::default()),
+            last_summary: Mutex::new(ScanSummary::default()),
+            sample_coverage: None,
+            sweeping: false,
+            timed_out: Mutex::new(Vec::new()),
+            all_open: Mutex::new(AllOpenSampler::default()),
+            policies: Policies::default(),
+            errors: Mutex::new(ErrorSummary::default()),
+            window: Mutex::new(Window::default()),
+            sink: terminal_sink(greppable, accessible),
+            hooks: Vec::new(),
+            control: Arc::default(),
+            syn: None,
+            icmp: None,
+            stream: Mutex::new(None),
+            scopes: HashMap::new(),
+            spread: SpreadStrategy::default(),
+            iteration_order: IterationOrder::default(),
+            cancel: CancellationToken::default(),
+            unscanned: Mutex::new(BTreeMap::new()),
         }
     }
 
-    /// Runs scan_range with chunk sizes
-    /// If you want to run RustScan normally, this is the entry point used
-    /// Returns all open ports as `Vec<u16>`
-    pub async fn run(&self) -> Vec<SocketAddr> {
-        let ports: Vec<u16> = self
-            .port_strategy
-            .order()
+    /// A handle to pause, resume and throttle the scan while it runs, and
+    /// to read the open ports it found so far.
+    pub fn control(&self) -> Arc<ScanControl> {
+        Arc::clone(&self.control)
+    }
+
+    /// How many probes are in flight at once.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Lowers the batch size to what this system can have open at once, see
+    /// [`resource_limits::safe_batch_size`], so the scan doesn't run out of
+    /// file descriptors halfway. Raise the limit first, e.g. with
+    /// [`resource_limits::raise_soft_limit`], to keep larger batches.
+    #[must_use]
+    pub fn with_auto_batch(mut self) -> Self {
+        let safe = resource_limits::safe_batch_size(self.batch_size);
+        if safe < self.batch_size {
+            debug!("Lowering the batch size from {} to {safe}", self.batch_size);
+            self.batch_size = safe;
+        }
+        self
+    }
+
+    /// How the probes in flight are spread across hosts, see
+    /// [`SpreadStrategy`]. [`SpreadStrategy::PerHost`] keeps a single host
+    /// from taking most of the batch when there are few hosts.
+    #[must_use]
+    pub fn with_spread(mut self, spread: SpreadStrategy) -> Self {
+        self.spread = spread;
+        self
+    }
+
+    /// The order hosts and ports are combined in, one port across every
+    /// host at a time by default, see [`IterationOrder`].
+    #[must_use]
+    pub fn with_iteration_order(mut self, order: IterationOrder) -> Self {
+        self.iteration_order = order;
+        self
+    }
+
+    /// Probes every port over each of `protocols`, e.g. over both TCP and
+    /// UDP, instead of the one picked by [`Scanner::new`]. Networks with a
+    /// policy setting `udp` keep to the protocol of their policy.
+    #[must_use]
+    pub fn with_protocols(mut self, protocols: &[Protocol]) -> Self {
+        if !protocols.is_empty() {
+            self.protocols = protocols.to_vec();
+            self.protocols.dedup();
+        }
+        self
+    }
+
+    /// The protocols the ports of `ip` are probed over, following the
+    /// policy of its network.
+    pub fn protocols(&self, ip: &IpAddr) -> &[Protocol] {
+        match self.policies.get(ip).and_then(|policy| policy.udp) {
+            Some(true) => &[Protocol::Udp][..],
+            Some(false) => &[Protocol::Tcp][..],
+            None => self.protocols.as_slice(),
+        }
+    }
+
+    /// Whether the ports of `ip` are probed over UDP only, following the
+    /// policy of its network.
+    pub fn is_udp(&self, ip: &IpAddr) -> bool {
+        self.protocols(ip) == [Protocol::Udp]
+    }
+
+    /// Whether some ports are probed over `protocol`, policies aside.
+    fn scans(&self, protocol: Protocol) -> bool {
+        self.protocols.contains(&protocol)
+    }
+
+    /// Scans the IPs of `target_ports` on their own ports, in the given
+    /// order, instead of the ones of the port strategy.
+    #[must_use]
+    pub fn with_target_ports(mut self, target_ports: &BTreeMap<IpAddr, Vec<u16>>) -> Self {
+        self.target_ports = target_ports
             .iter()
-            .filter(|&port| !self.exclude_ports.contains(port))
-            .copied()
+            .map(|(ip, ports)| (*ip, ports.clone()))
             .collect();
-        let mut socket_iterator: SocketIterator = SocketIterator::new(&self.ips, &ports);
-        let mut open_sockets: Vec<SocketAddr> = Vec::new();
-        let mut ftrs = FuturesUnordered::new();
-        let mut errors: HashSet<String> = HashSet::new();
-        let udp_map = get_parsed_data();
-
-        for _ in 0..self.batch_size {
-            if let Some(socket) = socket_iterator.next() {
-                ftrs.push(self.scan_socket(socket, udp_map.clone()));
-            } else {
-                break;
-            }
+        self
+    }
+
+    /// Number of probes sent to `ips`, scanning `ports` ports on the IPs
+    /// without ports of their own, once for each protocol.
+    fn socket_count(&self, ips: &[IpAddr], ports: usize) -> usize {
+        ips.iter()
+            .map(|ip| self.target_ports.get(ip).map_or(ports, Vec::len) * self.protocols(ip).len())
+            .sum()
+    }
+
+    /// Adds the UDP payloads of `payloads` to the built-in ones, replacing
+    /// those of the same ports.
+    #[must_use]
+    pub fn with_udp_payloads(mut self, payloads: UdpPayloads) -> Self {
+        self.udp_payloads.extend(payloads);
+        self
+    }
+
+    /// Connects to the link-local IPv6 targets of `scopes` through the
+    /// interface of their scope ID, as parsed from addresses like
+    /// `fe80::1%eth0`.
+    #[must_use]
+    pub fn with_scopes(mut self, scopes: &BTreeMap<IpAddr, u32>) -> Self {
+        self.scopes = scopes
+            .iter()
+            .filter_map(|(ip, scope)| match ip {
+                IpAddr::V6(ip) => Some((*ip, *scope)),
+                IpAddr::V4(_) => None,
+            })
+            .collect();
+        self
+    }
+
+    /// `socket` with the scope ID of its address, when it has one.
+    fn scoped(&self, socket: SocketAddr) -> SocketAddr {
+        match socket {
+            SocketAddr::V6(v6) => match self.scopes.get(v6.ip()) {
+                Some(&scope) => {
+                    SocketAddr::V6(SocketAddrV6::new(*v6.ip(), v6.port(), v6.flowinfo(), scope))
+                }
+                None => socket,
+            },
+            SocketAddr::V4(_) => socket,
+        }
+    }
+
+    /// Sends the open ports and other events of the scan to `sink` as they
+    /// happen. By default they are printed to the terminal, unless the
+    /// scanner is greppable.
+    #[must_use]
+    pub fn with_sink(mut self, sink: Box<dyn ResultSink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// The errors probes of the last scan ran into, per class and per host.
+    pub fn error_summary(&self) -> ErrorSummary {
+        self.errors
+            .lock()
+            .map(|errors| errors.clone())
+            .unwrap_or_default()
+    }
+
+    /// Overrides the batch size, timeout, tries and protocol for the
+    /// targets of some networks. Targets are scanned one policy at a time.
+    #[must_use]
+    pub fn with_policies(mut self, policies: Policies) -> Self {
+        self.policies = policies;
+        self
+    }
+
+    /// Stops probing hosts that accept connections on nearly every port,
+    /// like CDN edges and some firewalls, once `sample` of their ports were
+    /// probed. `None`, the default, scans every port of every host.
+    #[must_use]
+    pub fn with_all_open_sample(mut self, sample: Option<usize>) -> Self {
+        self.all_open = Mutex::new(AllOpenSampler::new(sample));
+        self
+    }
+
+    /// Hosts of the last scan that answered on nearly every port and of
+    /// which only a sample of ports was probed.
+    pub fn sampled_hosts(&self) -> Vec<Tarpit> {
+        self.all_open
+            .lock()
+            .map(|sampler| sampler.sampled())
+            .unwrap_or_default()
+    }
+
+    fn record_all_open(&self, ip: IpAddr, open: bool) {
+        if let Ok(mut sampler) = self.all_open.lock() {
+            sampler.record(ip, open);
         }
+    }
 
-        debug!("Start scanning sockets. \nBatch size {}\nNumber of ip-s {}\nNumber of ports {}\nTargets all together {} ",
-            self.batch_size,
-            self.ips.len(),
-            &ports.len(),
-            (self.ips.len() * ports.len()));
+    /// What the last scan used: time, sockets, connection attempts and
+    /// traffic.
+    pub fn stats(&self) -> ScanStats {
+        self.last_stats
+            .lock()
+            .map(|stats| *stats)
+            .unwrap_or_default()
+    }
+
+    /// Whether the scan slows down when the local conntrack table is close
+    /// to full. Enabled by default, only has an effect on Linux.
+    #[must_use]
+    pub fn with_conntrack_throttle(mut self, conntrack_throttle: bool) -> Self {
+        self.conntrack_throttle = conntrack_throttle;
+        self
+    }
+
+    /// The fullest the conntrack table got while the last scan was slowed
+    /// down for it, `None` when it never had to be.
+    pub fn conntrack_pressure(&self) -> Option<Conntrack> {
+        self.conntrack_peak.lock().ok().and_then(|peak| *peak)
+    }
+
+    /// Whether probes to a host are slowed down when its answer rate
+    /// suddenly drops during the scan. Disabled by default.
+    #[must_use]
+    pub fn with_anomaly_backoff(mut self, anomaly_backoff: bool) -> Self {
+        self.anomaly_backoff = anomaly_backoff;
+        self
+    }
 
-        while let Some(result) = ftrs.next().await {
-            if let Some(socket) = socket_iterator.next() {
-                ftrs.push(self.scan_socket(socket, udp_map.clone()));
+    /// Hosts that were slowed down because their answer rate suddenly
+    /// dropped during the last scan.
+    pub fn backoff_events(&self) -> Vec<BackoffEvent> {
+        self.responsiveness
+            .lock()
+            .map(|tracker| tracker.events().to_vec())
+            .unwrap_or_default()
+    }
+
+    fn record_answer(&self, ip: IpAddr, answered: bool) {
+        if !self.anomaly_backoff {
+            return;
+        }
+        if let Ok(mut tracker) = self.responsiveness.lock() {
+            tracker.record(ip, answered);
+        }
+    }
+
+    fn backoff_delay(&self, ip: &IpAddr) -> Duration {
+        self.responsiveness
+            .lock()
+            .map(|tracker| tracker.delay(ip))
+            .unwrap_or_default()
+    }
+
+    /// Whether fewer probes are kept in flight to a host when it starts
+    /// dropping them, like behind a rate limiting firewall. Enabled by
+    /// default.
+    #[must_use]
+    pub fn with_adaptive_rate(mut self, adaptive_rate: bool) -> Self {
+        self.adaptive_rate = adaptive_rate;
+        self
+    }
+
+    /// Hosts that were probed with fewer sockets at once during the last
+    /// scan because they started dropping probes.
+    pub fn rate_limits(&self) -> Vec<RateLimit> {
+        self.rates
+            .lock()
+            .map(|rates| rates.limited())
+            .unwrap_or_default()
+    }
+
+    /// Waits until another probe can be sent to `ip` under its limit.
+    async fn acquire_rate_slot(&self, ip: IpAddr) {
+        if !self.adaptive_rate {
+            return;
+        }
+        while !self
+            .rates
+            .lock()
+            .is_ok_and(|mut rates| rates.try_acquire(ip))
+        {
+            async_std::task::sleep(RATE_SLOT_POLL).await;
+        }
+    }
+
+    fn release_rate_slot(&self, ip: IpAddr, result: &io::Result<()>) {
+        if !self.adaptive_rate {
+            return;
+        }
+        if let Ok(mut rates) = self.rates.lock() {
+            rates.release(ip, Outcome::of(result));
+        }
+    }
+
+    /// How long UDP probes wait for an answer once sent, the timeout then
+    /// only bounding sending them. `None`, the default, waits as long as
+    /// the timeout.
+    #[must_use]
+    pub fn with_read_timeout(mut self, read_timeout: Option<Duration>) -> Self {
+        self.read_timeout = read_timeout;
+        self
+    }
+
+    /// Sets the options applied to every socket created during the scan.
+    #[must_use]
+    pub fn with_socket_options(mut self, socket_options: SocketOptions) -> Self {
+        self.socket_options = socket_options;
+        self
+    }
+
+    /// Round trip times observed so far, grouped per network.
+    ///
+    /// Only connections that got an answer (accepted or refused) are
+    /// recorded, timeouts say nothing about the latency of a network.
+    pub fn latency_profiles(&self) -> LatencyProfiles {
+        self.latency
+            .lock()
+            .map(|latency| latency.clone())
+            .unwrap_or_default()
+    }
+
+    fn record_latency(&self, ip: &IpAddr, rtt: Duration) {
+        if let Ok(mut latency) = self.latency.lock() {
+            latency.record(ip, rtt);
+        }
+    }
+
+    /// Share of probes that failed on the way observed so far, grouped per
+    /// network.
+    ///
+    /// Closed ports and timeouts are answers of the network, and running
+    /// out of local resources says nothing about it, so neither counts as
+    /// an error.
+    pub fn error_rates(&self) -> ErrorRates {
+        self.error_rates
+            .lock()
+            .map(|rates| rates.clone())
+            .unwrap_or_default()
+    }
+
+    fn record_error_rate(&self, ip: &IpAddr, result: &io::Result<SocketAddr>) {
+        let failed = match result {
+            Ok(_) => false,
+            Err(e) => !matches!(
+                ProbeError::classify(e),
+                ProbeError::ConnectionRefused
+                    | ProbeError::TimedOut
+                    | ProbeError::ResourceExhausted
+            ),
+        };
+        if let Ok(mut rates) = self.error_rates.lock() {
+            rates.record(ip, failed);
+        }
+    }
+
+    /// Runs scan_range with chunk sizes
+    /// If you want to run RustScan normally, this is the entry point used
+    /// Returns the state of every probed port as a [`ScanResult`], only of
+    /// those probed so far when cancelled, see [`CancellationToken`].
+    pub async fn run(&self) -> ScanResult {
+        let ports = self.ports();
+        if let Ok(mut tracker) = self.responsiveness.lock() {
+            *tracker = ResponsivenessTracker::default();
+        }
+        if let Ok(mut rates) = self.rates.lock() {
+            *rates = RateController::default();
+        }
+        if let Ok(mut sampler) = self.all_open.lock() {
+            sampler.reset();
+        }
+        if let Ok(mut errors) = self.errors.lock() {
+            *errors = ErrorSummary::default();
+        }
+        if let Ok(mut window) = self.window.lock() {
+            *window = Window::default();
+        }
+        let mut scan_result = ScanResult::default();
+        let mut unscanned: BTreeMap<IpAddr, Vec<u16>> = BTreeMap::new();
+        let mut conntrack = ConntrackThrottle::new(self.conntrack_throttle);
+        let stats_start = self.stats.start();
+        self.control.start(self.estimate_ports(ports.len()));
+
+        for (ips, batch_size) in self.policies.groups(&self.ips, self.batch_size) {
+            if self.cancel.is_cancelled() {
+                for ip in &ips {
+                    let ip_ports = self.target_ports.get(ip).unwrap_or(&ports);
+                    unscanned.entry(*ip).or_default().extend(ip_ports);
+                }
+                continue;
+            }
+            // The IPs of a group share their policy, and so their protocols.
+            let protocols = ips
+                .first()
+                .map_or(&self.protocols[..], |ip| self.protocols(ip));
+            let socket_iterator: SocketIterator =
+                SocketIterator::with_target_ports(&ips, &ports, &self.target_ports)
+                    .with_protocols(protocols)
+                    .with_order(self.iteration_order);
+            let mut spread = Spread::new(socket_iterator, self.spread, batch_size);
+            let mut ftrs = FuturesUnordered::new();
+            let mut in_flight = HashSet::new();
+            let mut remaining: HashMap<IpAddr, usize> = ips
+                .iter()
+                .map(|ip| (*ip, self.socket_count(&[*ip], ports.len())))
+                .collect();
+            for (ip, _) in remaining.iter().filter(|(_, count)| **count == 0) {
+                self.hook_host_complete(*ip, &scan_result);
             }
 
-            match result {
-                Ok(socket) => open_sockets.push(socket),
-                Err(e) => {
-                    let error_string = e.to_string();
-                    if errors.len() < self.ips.len() * 1000 {
-                        errors.insert(error_string);
+            for _ in 0..batch_size {
+                if let Some((socket, protocol)) = spread.next() {
+                    self.control.throttle().await;
+                    in_flight.insert((socket, protocol));
+                    ftrs.push(self.scan_socket(socket, protocol));
+                } else {
+                    break;
+                }
+            }
+
+            debug!("Start scanning sockets. \nBatch size {}\nNumber of ip-s {}\nNumber of ports {}\nTargets all together {} ",
+                batch_size,
+                ips.len(),
+                &ports.len(),
+                self.socket_count(&ips, ports.len()));
+
+            self.stats.in_flight(ftrs.len());
+            while let Some((socket, protocol, result)) = ftrs.next().await {
+                spread.done(socket.ip());
+                in_flight.remove(&(socket, protocol));
+                if !self.cancel.is_cancelled() && conntrack.should_refill(ftrs.len(), batch_size) {
+                    // Usually one probe replaces the one done, more when
+                    // probes were held back by the per-host cap.
+                    let window = self.window(conntrack.window(batch_size));
+                    while ftrs.len() < window {
+                        let Some((next, next_protocol)) = spread.next() else {
+                            break;
+                        };
+                        self.control.throttle().await;
+                        in_flight.insert((next, next_protocol));
+                        ftrs.push(self.scan_socket(next, next_protocol));
+                        self.stats.in_flight(ftrs.len());
                     }
                 }
+
+                self.control.record(result.as_ref().ok().copied());
+                if let Some(state) = port_state(protocol, &result) {
+                    scan_result.record(socket, protocol, state);
+                }
+                if let Some(count) = remaining.get_mut(&socket.ip()) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.hook_host_complete(socket.ip(), &scan_result);
+                    }
+                }
+                if self.cancel.is_cancelled() {
+                    debug!("Scan cancelled, dropping {} probes in flight", ftrs.len());
+                    break;
+                }
+            }
+            if self.cancel.is_cancelled() {
+                for (socket, _) in in_flight.into_iter().chain(spread.remaining()) {
+                    unscanned
+                        .entry(socket.ip())
+                        .or_default()
+                        .push(socket.port());
+                }
             }
         }
-        debug!("Typical socket connection errors {errors:?}");
-        debug!("Open Sockets found: {:?}", &open_sockets);
-        open_sockets
+        for ports in unscanned.values_mut() {
+            ports.sort_unstable();
+            ports.dedup();
+        }
+        if let Ok(mut last_unscanned) = self.unscanned.lock() {
+            *last_unscanned = unscanned;
+        }
+        if let Ok(mut peak) = self.conntrack_peak.lock() {
+            *peak = conntrack.peak();
+        }
+        let stats = self.stats.finish(&stats_start);
+        if let Ok(mut last_stats) = self.last_stats.lock() {
+            *last_stats = stats;
+        }
+        // A sweep sums up once its second phase is over.
+        if !self.sweeping {
+            self.summarize(&open_probes(&scan_result), stats.elapsed);
+            self.hook_scan_complete(&scan_result);
+        }
+        debug!("Probe errors: {}", self.error_summary());
+        debug!("Open Sockets found: {:?}", scan_result.open());
+        scan_result
+    }
+
+    /// The ports to scan, in scan order, without the excluded ones.
+    fn ports(&self) -> Vec<u16> {
+        self.port_strategy.order()
+    }
+
+    /// Scans a socket over `protocol`, counting the error in the error
+    /// summary when the port isn't found open. Returns the socket and the
+    /// protocol along with the result, as errors don't carry them.
+    ///
+    /// Probes running out of file descriptors are sent again once the scan
+    /// paused and lowered its window, see [`Exhaustion`].
+    async fn scan_socket(
+        &self,
+        socket: SocketAddr,
+        protocol: Protocol,
+    ) -> (SocketAddr, Protocol, io::Result<SocketAddr>) {
+        self.wait_for_resources().await;
+        let mut result = self.probe_socket(socket, protocol).await;
+        for _ in 0..exhaustion::MAX_RETRIES {
+            match &result {
+                Err(e) if ProbeError::classify(e) == ProbeError::ResourceExhausted => {
+                    self.exhausted();
+                    self.wait_for_resources().await;
+                    result = self.probe_socket(socket, protocol).await;
+                }
+                _ => break,
+            }
+        }
+        if let Err(e) = &result {
+            if is_skipped(e) {
+                return (socket, protocol, result);
+            }
+            if let Ok(mut errors) = self.errors.lock() {
+                errors.record(socket.ip(), e);
+            }
+        }
+        self.record_error_rate(&socket.ip(), &result);
+        (socket, protocol, result)
     }
 
     /// Given a socket, scan it self.tries times.
     /// Turns the address into a SocketAddr
     /// Deals with the `<result>` type
-    /// If it experiences error ErrorKind::Other then too many files are open and it Panics!
-    /// Else any other error, it returns the error in Result as a string
+    /// Errors, running out of open files included, are returned with their
+    /// kind so they can be classified as a [`ProbeError`].
     /// If no errors occur, it returns the port number in Result to signify the port is open.
     /// This function mainly deals with the logic of Results handling.
     /// # Example
     ///
     /// ```compile_fail
-    /// scanner.scan_socket(socket)
+    /// scanner.probe_socket(socket)
     /// ```
     ///
     /// Note: `self` must contain `self.ip`.
-    async fn scan_socket(
-        &self,
-        socket: SocketAddr,
-        udp_map: BTreeMap<Vec<u16>, Vec<u8>>,
-    ) -> io::Result<SocketAddr> {
-        if self.udp {
-            return self.scan_udp_socket(socket, udp_map).await;
+    async fn probe_socket(&self, socket: SocketAddr, protocol: Protocol) -> io::Result<SocketAddr> {
+        let policy = self.policies.get(&socket.ip());
+        let timeout = policy
+            .and_then(|policy| policy.timeout())
+            .unwrap_or(self.timeout);
+        let tries = policy
+            .and_then(|policy| policy.tries)
+            .map_or(self.tries, |tries| {
+                NonZeroU8::new(tries).unwrap_or(NonZeroU8::MIN)
+            })
+            .get();
+        if protocol == Protocol::Udp {
+            return self.scan_udp_socket(socket, timeout, tries).await;
+        }
+
+        let sampled = self
+            .all_open
+            .lock()
+            .is_ok_and(|sampler| sampler.is_sampled(&socket.ip()));
+        if sampled {
+            return Err(io::Error::other(Skipped));
         }
 
-        let tries = self.tries.get();
         for nr_try in 1..=tries {
-            match self.connect(socket).await {
-                Ok(tcp_stream) => {
-                    debug!(
-                        "Connection was successful, shutting down stream {}",
-                        &socket
-                    );
-                    if let Err(e) = tcp_stream.shutdown(Shutdown::Both) {
-                        debug!("Shutdown stream error {}", &e);
-                    }
-                    self.fmt_ports(socket);
+            let delay = self.backoff_delay(&socket.ip()) + self.retry_delay(nr_try);
+            if !delay.is_zero() {
+                async_std::task::sleep(delay).await;
+            }
+
+            let started = Instant::now();
+            self.acquire_rate_slot(socket.ip()).await;
+            self.stats.attempt();
+            let result = self.handshake(socket, timeout).await;
+            self.release_rate_slot(socket.ip(), &result);
+            match result {
+                Ok(()) => {
+                    self.record_latency(&socket.ip(), started.elapsed());
+                    self.record_answer(socket.ip(), true);
+                    self.record_all_open(socket.ip(), true);
+                    self.fmt_ports(socket, Protocol::Tcp);
 
                     debug!("Return Ok after {nr_try} tries");
                     return Ok(socket);
                 }
                 Err(e) => {
-                    let mut error_string = e.to_string();
-
-                    assert!(!error_string.to_lowercase().contains("too many open files"), "Too many open files. Please reduce batch size. The default is 5000. Try -b 2500.");
+                    let probe_error = ProbeError::classify(&e);
+                    let refused = probe_error == ProbeError::ConnectionRefused;
+                    if refused {
+                        self.record_latency(&socket.ip(), started.elapsed());
+                    }
+                    self.record_answer(socket.ip(), refused);
 
-                    if nr_try == tries {
-                        error_string.push(' ');
-                        error_string.push_str(&socket.ip().to_string());
-                        return Err(io::Error::other(error_string));
+                    if nr_try == tries || probe_error.is_definitive() {
+                        self.record_all_open(socket.ip(), false);
+                        if self.sweeping && probe_error == ProbeError::TimedOut {
+                            if let Ok(mut timed_out) = self.timed_out.lock() {
+                                timed_out.push(socket);
+                            }
+                        }
+                        return Err(probe_error::with_target(e, socket.ip()));
                     }
                 }
             };
@@ -172,30 +825,43 @@ impl Scanner {
         unreachable!();
     }
 
+    /// How long try `nr_try` of a probe waits, nothing for the first.
+    fn retry_delay(&self, nr_try: u8) -> Duration {
+        match nr_try {
+            1 => Duration::ZERO,
+            _ => self.retry.delay(nr_try - 1),
+        }
+    }
+
     async fn scan_udp_socket(
         &self,
         socket: SocketAddr,
-        udp_map: BTreeMap<Vec<u16>, Vec<u8>>,
+        timeout: Duration,
+        tries: u8,
     ) -> io::Result<SocketAddr> {
-        let mut payload: Vec<u8> = Vec::new();
-        for (key, value) in udp_map {
-            if key.contains(&socket.port()) {
-                payload = value;
-            }
-        }
+        let payload = self.udp_payloads.get(socket.port());
+        let wait = self.read_timeout.unwrap_or(timeout);
 
-        let tries = self.tries.get();
-        for _ in 1..=tries {
-            match self.udp_scan(socket, &payload, self.timeout).await {
+        for nr_try in 1..=tries {
+            let delay = self.retry_delay(nr_try);
+            if !delay.is_zero() {
+                async_std::task::sleep(delay).await;
+            }
+            match self.udp_scan(socket, payload, timeout, wait).await {
                 Ok(true) => return Ok(socket),
-                Ok(false) => continue,
+                Ok(false) => {
+                    if let Some(e) = self.udp_silence(socket) {
+                        return Err(e);
+                    }
+                }
                 Err(e) => return Err(e),
             }
         }
 
-        Err(io::Error::other(format!(
-            "UDP scan timed-out for all tries on socket {socket}"
-        )))
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("UDP scan timed-out for all tries on socket {socket}"),
+        ))
     }
 
     /// Performs the connection to the socket with timeout
@@ -213,9 +879,31 @@ impl Scanner {
     /// ```
     ///
     async fn connect(&self, socket: SocketAddr) -> io::Result<TcpStream> {
+        self.connect_within(socket, self.timeout).await
+    }
+
+    /// Checks whether `socket` accepts connections: with a SYN when SYN
+    /// scanning an IPv4 target, with a full connect otherwise.
+    async fn handshake(&self, socket: SocketAddr, timeout: Duration) -> io::Result<()> {
+        if let (Some(syn), SocketAddr::V4(target)) = (&self.syn, socket) {
+            return syn.probe(target, timeout).await;
+        }
+
+        let tcp_stream = self.connect_within(socket, timeout).await?;
+        debug!(
+            "Connection was successful, shutting down stream {}",
+            &socket
+        );
+        if let Err(e) = tcp_stream.shutdown(Shutdown::Both) {
+            debug!("Shutdown stream error {}", &e);
+        }
+        Ok(())
+    }
+
+    async fn connect_within(&self, socket: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
         let stream = io::timeout(
-            self.timeout,
-            async move { TcpStream::connect(socket).await },
+            timeout,
+            connector::connect_tcp(self.scoped(socket), &self.socket_options),
         )
         .await?;
         Ok(stream)
@@ -236,15 +924,11 @@ impl Scanner {
     /// ```
     ///
     async fn udp_bind(&self, socket: SocketAddr) -> io::Result<UdpSocket> {
-        let local_addr = match socket {
-            SocketAddr::V4(_) => "0.0.0.0:0".parse::<SocketAddr>().unwrap(),
-            SocketAddr::V6(_) => "[::]:0".parse::<SocketAddr>().unwrap(),
-        };
-
-        UdpSocket::bind(local_addr).await
+        connector::bind_udp(socket, &self.socket_options)
     }
 
-    /// Performs a UDP scan on the specified socket with a payload and wait duration
+    /// Performs a UDP scan on the specified socket with a payload, giving up
+    /// on sending it after `timeout` and on an answer after `wait`
     /// # Example
     ///
     /// ```compile_fail
@@ -255,31 +939,36 @@ impl Scanner {
     /// let ip = IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1));
     /// let socket = SocketAddr::new(ip, port);
     /// let payload = vec![0, 1, 2, 3];
+    /// let timeout = Duration::from_millis(500);
     /// let wait = Duration::from_secs(1);
-    /// let result = scanner.udp_scan(socket, payload, wait).await;
+    /// let result = scanner.udp_scan(socket, payload, timeout, wait).await;
     /// // returns Result which is either Ok(true) if response received, or Ok(false) if timed out.
     /// // Err is returned for other I/O errors.
     async fn udp_scan(
         &self,
         socket: SocketAddr,
         payload: &[u8],
+        timeout: Duration,
         wait: Duration,
     ) -> io::Result<bool> {
         match self.udp_bind(socket).await {
             Ok(udp_socket) => {
                 let mut buf = [0u8; 1024];
 
-                udp_socket.connect(socket).await?;
-                udp_socket.send(payload).await?;
+                udp_socket.connect(self.scoped(socket)).await?;
+                self.stats.attempt();
+                let sent = io::timeout(timeout, udp_socket.send(payload)).await?;
+                self.stats.sent(sent);
 
                 match io::timeout(wait, udp_socket.recv(&mut buf)).await {
                     Ok(size) => {
                         debug!("Received {size} bytes");
-                        self.fmt_ports(socket);
+                        self.stats.received(size);
+                        self.fmt_ports(socket, Protocol::Udp);
                         Ok(true)
                     }
                     Err(e) => {
-                        if e.kind() == io::ErrorKind::TimedOut {
+                        if ProbeError::classify(&e) == ProbeError::TimedOut {
                             Ok(false)
                         } else {
                             Err(e)
@@ -288,24 +977,62 @@ impl Scanner {
                 }
             }
             Err(e) => {
-                println!("Err E binding sock {e:?}");
+                debug!("Error binding UDP socket for {socket}: {e:?}");
+                self.sink.emit(&ScanEvent::LocalError {
+                    socket,
+                    error: ProbeError::classify(&e),
+                });
                 Err(e)
             }
         }
     }
 
-    /// Formats and prints the port status
-    fn fmt_ports(&self, socket: SocketAddr) {
-        if !self.greppable {
-            if self.accessible {
-                println!("Open {socket}");
-            } else {
-                println!("Open {}", socket.to_string().purple());
-            }
+    /// Reports a port open over `protocol` to the sink, the hooks and the
+    /// stream of [`Scanner::run_streaming`]
+    fn fmt_ports(&self, socket: SocketAddr, protocol: Protocol) {
+        if !self.sweeping {
+            self.sink.emit(&ScanEvent::Open(socket, protocol));
+            self.hook_port_open(socket, protocol);
+            self.stream_open(socket);
         }
     }
 }
 
+/// The state a probe over `protocol` ended in, see [`PortState`].
+/// Prints open ports as they are found, unless the output is greppable.
+#[cfg(feature = "tui")]
+fn terminal_sink(greppable: bool, accessible: bool) -> Box<dyn ResultSink> {
+    if greppable {
+        Box::new(NullSink)
+    } else {
+        Box::new(TerminalSink::new(accessible))
+    }
+}
+
+/// Without the `tui` feature open ports are only sent to the sinks given
+/// to [`Scanner::with_sink`].
+#[cfg(not(feature = "tui"))]
+fn terminal_sink(_greppable: bool, _accessible: bool) -> Box<dyn ResultSink> {
+    Box::new(NullSink)
+}
+
+fn port_state(protocol: Protocol, result: &io::Result<SocketAddr>) -> Option<PortState> {
+    match protocol {
+        Protocol::Tcp => PortState::of(result),
+        Protocol::Udp => PortState::of_udp(result),
+    }
+}
+
+/// Every open port of `result`, a socket open over both TCP and UDP coming
+/// twice, as counted by the [`ScanSummary`].
+fn open_probes(result: &ScanResult) -> Vec<SocketAddr> {
+    result
+        .open_tagged()
+        .into_iter()
+        .map(|(socket, _)| socket)
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -455,6 +1182,100 @@ mod tests {
         // if the scan fails, it wouldn't be able to assert_eq! as it panicked!
         assert_eq!(1, 1);
     }
+    #[test]
+    fn auto_batch_fits_the_open_file_limit() {
+        let scanner = |batch_size| {
+            Scanner::new(
+                &["127.0.0.1".parse().unwrap()],
+                batch_size,
+                Duration::from_millis(100),
+                1,
+                true,
+                PortStrategy::Manual(vec![80]),
+                true,
+                vec![],
+                false,
+            )
+            .with_auto_batch()
+        };
+
+        assert_eq!(scanner(10).batch_size(), 10);
+        let huge = usize::MAX / 2;
+        assert_eq!(
+            scanner(huge).batch_size(),
+            resource_limits::safe_batch_size(huge)
+        );
+    }
+
+    #[test]
+    fn udp_waits_for_answers_past_the_timeout() {
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let open = server.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 64];
+            while let Ok((_, peer)) = server.recv_from(&mut buf) {
+                std::thread::sleep(Duration::from_millis(300));
+                let _ = server.send_to(b"late", peer);
+            }
+        });
+        let scanner = |read_timeout| {
+            Scanner::new(
+                &[open.ip()],
+                10,
+                Duration::from_millis(100),
+                1,
+                true,
+                PortStrategy::Manual(vec![open.port()]),
+                true,
+                vec![],
+                true,
+            )
+            .with_read_timeout(read_timeout)
+        };
+
+        assert!(block_on(scanner(None).run()).open().is_empty());
+        assert_eq!(
+            block_on(scanner(Some(Duration::from_secs(2))).run()).open(),
+            vec![open]
+        );
+    }
+
+    #[test]
+    fn scans_tcp_and_udp_in_one_run() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp = listener.local_addr().unwrap();
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let udp = server.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 64];
+            while let Ok((_, peer)) = server.recv_from(&mut buf) {
+                let _ = server.send_to(b"pong", peer);
+            }
+        });
+        let scanner = Scanner::new(
+            &[tcp.ip()],
+            10,
+            Duration::from_millis(500),
+            1,
+            true,
+            PortStrategy::Manual(vec![tcp.port(), udp.port()]),
+            true,
+            vec![],
+            false,
+        )
+        .with_protocols(&[Protocol::Tcp, Protocol::Udp]);
+
+        let result = block_on(scanner.run());
+
+        let mut open = result.open_tagged();
+        open.sort();
+        let mut expected = vec![(tcp, Protocol::Tcp), (udp, Protocol::Udp)];
+        expected.sort();
+        assert_eq!(open, expected);
+        assert_eq!(result.len(), 4);
+        assert_eq!(scanner.summary().open_ports, 2);
+    }
+
     #[test]
     fn udp_ipv6_runs() {
         // Makes sure the program still runs and doesn't panic
@@ -523,4 +1344,73 @@ mod tests {
         block_on(scanner.run());
         assert_eq!(1, 1);
     }
+
+    #[test]
+    fn cancelled_scan_stops_early() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let addrs = vec!["127.0.0.1".parse::<IpAddr>().unwrap()];
+        let strategy = PortStrategy::pick(&None, Some(vec![port]), ScanOrder::Serial);
+        let token = CancellationToken::new();
+        let scanner = Scanner::new(
+            &addrs,
+            10,
+            Duration::from_millis(100),
+            1,
+            true,
+            strategy,
+            true,
+            vec![],
+            false,
+        )
+        .with_cancellation(token.clone());
+
+        assert_eq!(
+            block_on(scanner.run()).open(),
+            vec![listener.local_addr().unwrap()]
+        );
+
+        token.cancel();
+        assert!(scanner.is_cancelled());
+        assert!(block_on(scanner.run()).open().is_empty());
+        assert_eq!(scanner.summary().probes, 0);
+    }
+
+    #[test]
+    fn cancelled_scan_keeps_what_is_left() {
+        #[derive(Debug)]
+        struct CancelAfterHost(CancellationToken);
+
+        impl ResultHook for CancelAfterHost {
+            fn on_host_complete(&self, _ip: IpAddr, _open: &[u16]) {
+                self.0.cancel();
+            }
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        let addrs: Vec<IpAddr> = vec!["127.0.0.1".parse().unwrap(), "127.0.0.2".parse().unwrap()];
+        let strategy = PortStrategy::pick(&None, Some(vec![open.port()]), ScanOrder::Serial);
+        let token = CancellationToken::new();
+        let scanner = Scanner::new(
+            &addrs,
+            1,
+            Duration::from_millis(100),
+            1,
+            true,
+            strategy,
+            true,
+            vec![],
+            false,
+        )
+        .with_cancellation(token.clone())
+        .with_hook(Box::new(CancelAfterHost(token)));
+
+        assert_eq!(block_on(scanner.run()).open(), vec![open]);
+        assert!(scanner.is_cancelled());
+        assert_eq!(
+            scanner.unscanned(),
+            BTreeMap::from([(addrs[1], vec![open.port()])])
+        );
+    }
 }
@@ -0,0 +1,282 @@
+//! The payloads sent to UDP ports to get an answer out of their service.
+//!
+//! The built-in table is generated from nmap's `nmap-payloads` at build
+//! time. Files in the same format can override or extend it, for services
+//! nmap doesn't know about:
+//!
+//! ```text
+//! # A proprietary discovery protocol
+//! udp 9999,10000-10002 "\x01\x00HELLO"
+//!   "\r\n"
+//! ```
+use crate::generated::get_parsed_data;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// One payload per UDP port. Ports without one get an empty datagram.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UdpPayloads {
+    by_port: HashMap<u16, Vec<u8>>,
+}
+
+impl UdpPayloads {
+    /// The payloads of the built-in nmap table.
+    pub fn builtin() -> Self {
+        let mut payloads = Self::default();
+        for (ports, payload) in get_parsed_data() {
+            payloads.insert(ports, payload);
+        }
+        payloads
+    }
+
+    /// Reads a payload file in the `nmap-payloads` format.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        Self::parse(&content)
+    }
+
+    /// Parses payloads in the `nmap-payloads` format: `udp`, the ports and
+    /// one or more quoted strings, which may continue on the next lines.
+    /// Keywords after the strings, like `source`, are ignored along with
+    /// the rest of their line, and so are comments starting with `#`.
+    ///
+    /// nmap sends every payload given for a port, here a later entry for a
+    /// port replaces the earlier one.
+    ///
+    /// ```rust
+    /// # use rustscan::scanner::UdpPayloads;
+    /// let payloads = UdpPayloads::parse("udp 7,9-10 \"\\x0D\\x0A\" \"ok\" source 53").unwrap();
+    /// assert_eq!(payloads.get(9), b"\r\nok");
+    /// assert_eq!(payloads.get(11), b"");
+    /// ```
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut payloads = Self::default();
+        let mut entry: Option<Entry> = None;
+
+        for (index, line) in content.lines().enumerate() {
+            let number = index + 1;
+            let error = |e: String| format!("{number}: {e}");
+            let mut tokens = tokenize(line).map_err(error)?.into_iter().peekable();
+
+            if let Some(Token::Word(word)) = tokens.peek() {
+                if word.as_str() != "udp" {
+                    // A keyword like `source`, which applies to the entry
+                    // above and isn't supported.
+                    continue;
+                }
+                tokens.next();
+                if let Some(entry) = entry.take() {
+                    payloads.add(entry)?;
+                }
+                let ports = match tokens.next() {
+                    Some(Token::Word(ports)) => parse_ports(&ports).map_err(error)?,
+                    _ => return Err(error("expected ports after udp".to_owned())),
+                };
+                entry = Some(Entry {
+                    line: number,
+                    ports,
+                    payload: None,
+                });
+            }
+
+            for token in tokens {
+                match (token, entry.as_mut()) {
+                    (Token::Quoted(bytes), Some(entry)) => {
+                        entry.payload.get_or_insert_with(Vec::new).extend(bytes)
+                    }
+                    (Token::Quoted(_), None) => {
+                        return Err(error("payload outside of a udp entry".to_owned()))
+                    }
+                    (Token::Word(_), _) => break,
+                }
+            }
+        }
+        if let Some(entry) = entry {
+            payloads.add(entry)?;
+        }
+        Ok(payloads)
+    }
+
+    /// Adds the payloads of `other`, replacing the ones of the same ports.
+    pub fn extend(&mut self, other: UdpPayloads) {
+        self.by_port.extend(other.by_port);
+    }
+
+    /// The payload sent to `port`, empty when there is none.
+    pub fn get(&self, port: u16) -> &[u8] {
+        self.by_port
+            .get(&port)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Number of ports with a payload.
+    pub fn len(&self) -> usize {
+        self.by_port.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_port.is_empty()
+    }
+
+    fn add(&mut self, entry: Entry) -> Result<(), String> {
+        let payload = entry
+            .payload
+            .ok_or_else(|| format!("{}: udp entry without a payload", entry.line))?;
+        self.insert(&entry.ports, &payload);
+        Ok(())
+    }
+
+    fn insert(&mut self, ports: &[u16], payload: &[u8]) {
+        for port in ports {
+            self.by_port.insert(*port, payload.to_vec());
+        }
+    }
+}
+
+/// A `udp` entry being read, with the line it starts on.
+struct Entry {
+    line: usize,
+    ports: Vec<u16>,
+    payload: Option<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Quoted(Vec<u8>),
+}
+
+/// Splits a line into words and quoted strings, dropping its comment.
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => break,
+            '"' => tokens.push(Token::Quoted(unescape(&mut chars)?)),
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(next) = chars.next_if(|next| !next.is_whitespace() && *next != '"') {
+                    word.push(next);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Reads the bytes of a quoted string up to its closing quote, decoding the
+/// C escapes nmap supports.
+fn unescape(chars: &mut impl Iterator<Item = char>) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Ok(bytes),
+            '\\' => {
+                let byte = match chars.next() {
+                    Some('x') => {
+                        let hex: String = chars.by_ref().take(2).collect();
+                        u8::from_str_radix(&hex, 16)
+                            .map_err(|_| format!("invalid escape \\x{hex}"))?
+                    }
+                    Some('0') => 0,
+                    Some('a') => 0x07,
+                    Some('b') => 0x08,
+                    Some('f') => 0x0c,
+                    Some('n') => b'\n',
+                    Some('r') => b'\r',
+                    Some('t') => b'\t',
+                    Some('v') => 0x0b,
+                    Some(c @ ('\\' | '"')) => c as u8,
+                    Some(c) => return Err(format!("invalid escape \\{c}")),
+                    None => break,
+                };
+                bytes.push(byte);
+            }
+            c => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+    Err("unterminated payload".to_owned())
+}
+
+/// Parses a comma separated list of ports and inclusive ranges.
+fn parse_ports(spec: &str) -> Result<Vec<u16>, String> {
+    let mut ports = Vec::new();
+    for part in spec.split(',').filter(|part| !part.is_empty()) {
+        let invalid = || format!("invalid port {part}");
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let start: u16 = start.parse().map_err(|_| invalid())?;
+                let end: u16 = end.parse().map_err(|_| invalid())?;
+                if start > end {
+                    return Err(invalid());
+                }
+                ports.extend(start..=end);
+            }
+            None => ports.push(part.parse().map_err(|_| invalid())?),
+        }
+    }
+    Ok(ports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UdpPayloads;
+
+    #[test]
+    fn parses_multiline_entries() {
+        let content = "\
+# Comment
+udp 1234,2000-2002 \"\\x01\\x02\" # trailing comment
+  \"abc\"
+  source 5678
+udp 1234 \"override\"
+";
+        let payloads = UdpPayloads::parse(content).unwrap();
+
+        assert_eq!(payloads.get(2001), b"\x01\x02abc");
+        assert_eq!(payloads.get(2002), b"\x01\x02abc");
+        assert_eq!(payloads.get(1234), b"override");
+        assert_eq!(payloads.len(), 4);
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        assert_eq!(
+            UdpPayloads::parse("udp 53 \"\\xZZ\""),
+            Err("1: invalid escape \\xZZ".to_owned())
+        );
+        assert_eq!(
+            UdpPayloads::parse("\n\"orphan\""),
+            Err("2: payload outside of a udp entry".to_owned())
+        );
+        assert!(UdpPayloads::parse("udp 70000 \"x\"").is_err());
+        assert!(UdpPayloads::parse("udp 53 \"open").is_err());
+        assert_eq!(
+            UdpPayloads::parse("udp 53\nudp 54 \"x\""),
+            Err("1: udp entry without a payload".to_owned())
+        );
+        assert!(UdpPayloads::parse("udp 53\n  \"on the next line\"").is_ok());
+    }
+
+    #[test]
+    fn user_payloads_override_builtin_ones() {
+        let mut payloads = UdpPayloads::builtin();
+        assert!(!payloads.get(53).is_empty());
+
+        payloads.extend(UdpPayloads::parse("udp 53,40000 \"mine\"").unwrap());
+        assert_eq!(payloads.get(53), b"mine");
+        assert_eq!(payloads.get(40000), b"mine");
+        assert_eq!(payloads.get(7), b"\r\n\r\n");
+    }
+}
@@ -0,0 +1,246 @@
+//! Classification of the errors a probe can run into.
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+
+/// Why a probe didn't find a port open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ProbeError {
+    /// The target answered with a reset, the port is closed.
+    ConnectionRefused,
+    /// Nothing came back before the timeout, the port is likely filtered.
+    TimedOut,
+    /// The target, or a router on the way, reported the host unreachable.
+    HostUnreachable,
+    /// No route to the target's network.
+    NetworkUnreachable,
+    /// The local machine refused to send the probe, e.g. a local firewall.
+    PermissionDenied,
+    /// The local machine ran out of sockets, ports, buffers or memory.
+    ResourceExhausted,
+    Other,
+}
+
+impl ProbeError {
+    /// Classifies an error from its OS error code when there is one, also
+    /// when it wraps the error that has it, and from its kind otherwise.
+    ///
+    /// ```rust
+    /// # use rustscan::scanner::ProbeError;
+    /// # use std::io;
+    /// let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+    /// assert_eq!(ProbeError::classify(&refused), ProbeError::ConnectionRefused);
+    /// ```
+    pub fn classify(error: &io::Error) -> Self {
+        if let Some(probe_error) = os_error(error).and_then(from_os_error) {
+            return probe_error;
+        }
+
+        match error.kind() {
+            io::ErrorKind::ConnectionRefused => ProbeError::ConnectionRefused,
+            io::ErrorKind::TimedOut => ProbeError::TimedOut,
+            io::ErrorKind::HostUnreachable => ProbeError::HostUnreachable,
+            io::ErrorKind::NetworkUnreachable => ProbeError::NetworkUnreachable,
+            io::ErrorKind::PermissionDenied => ProbeError::PermissionDenied,
+            io::ErrorKind::AddrNotAvailable | io::ErrorKind::OutOfMemory => {
+                ProbeError::ResourceExhausted
+            }
+            _ if error
+                .to_string()
+                .to_lowercase()
+                .contains("too many open files") =>
+            {
+                ProbeError::ResourceExhausted
+            }
+            _ => ProbeError::Other,
+        }
+    }
+
+    /// Whether the probe got a definitive answer, so trying again would
+    /// get the same one.
+    pub fn is_definitive(self) -> bool {
+        matches!(
+            self,
+            ProbeError::ConnectionRefused
+                | ProbeError::HostUnreachable
+                | ProbeError::NetworkUnreachable
+                | ProbeError::PermissionDenied
+        )
+    }
+}
+
+/// A probe error with the address it was sent to.
+#[derive(Debug)]
+struct TargetedError {
+    source: io::Error,
+    ip: IpAddr,
+}
+
+impl fmt::Display for TargetedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.source, self.ip)
+    }
+}
+
+impl Error for TargetedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Adds the address `error` came from to its message, keeping its kind and
+/// its OS error code for [`ProbeError::classify`].
+pub(super) fn with_target(error: io::Error, ip: IpAddr) -> io::Error {
+    io::Error::new(error.kind(), TargetedError { source: error, ip })
+}
+
+/// The OS error code of `error`, or of the error it wraps.
+fn os_error(error: &io::Error) -> Option<i32> {
+    error.raw_os_error().or_else(|| {
+        let inner = error.get_ref()?;
+        inner
+            .downcast_ref::<io::Error>()
+            .or_else(|| inner.source()?.downcast_ref::<io::Error>())
+            .and_then(os_error)
+    })
+}
+
+impl From<&io::Error> for ProbeError {
+    fn from(error: &io::Error) -> Self {
+        ProbeError::classify(error)
+    }
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            ProbeError::ConnectionRefused => "connection refused",
+            ProbeError::TimedOut => "timed out",
+            ProbeError::HostUnreachable => "host unreachable",
+            ProbeError::NetworkUnreachable => "network unreachable",
+            ProbeError::PermissionDenied => "permission denied",
+            ProbeError::ResourceExhausted => "out of local resources",
+            ProbeError::Other => "other errors",
+        };
+        f.write_str(description)
+    }
+}
+
+#[cfg(unix)]
+fn from_os_error(code: i32) -> Option<ProbeError> {
+    match code {
+        libc::ECONNREFUSED => Some(ProbeError::ConnectionRefused),
+        libc::ETIMEDOUT => Some(ProbeError::TimedOut),
+        libc::EHOSTUNREACH | libc::EHOSTDOWN => Some(ProbeError::HostUnreachable),
+        libc::ENETUNREACH | libc::ENETDOWN => Some(ProbeError::NetworkUnreachable),
+        libc::EACCES | libc::EPERM => Some(ProbeError::PermissionDenied),
+        libc::EMFILE | libc::ENFILE | libc::ENOBUFS | libc::ENOMEM | libc::EADDRNOTAVAIL => {
+            Some(ProbeError::ResourceExhausted)
+        }
+        _ => None,
+    }
+}
+
+/// The Winsock error codes probes run into, which unlike the errno values
+/// of `libc` start at 10000.
+#[cfg(windows)]
+mod wsa {
+    pub const WSAEACCES: i32 = 10013;
+    pub const WSAEMFILE: i32 = 10024;
+    pub const WSAEADDRNOTAVAIL: i32 = 10049;
+    pub const WSAENETDOWN: i32 = 10050;
+    pub const WSAENETUNREACH: i32 = 10051;
+    pub const WSAENOBUFS: i32 = 10055;
+    pub const WSAETIMEDOUT: i32 = 10060;
+    pub const WSAECONNREFUSED: i32 = 10061;
+    pub const WSAEHOSTDOWN: i32 = 10064;
+    pub const WSAEHOSTUNREACH: i32 = 10065;
+}
+
+#[cfg(windows)]
+fn from_os_error(code: i32) -> Option<ProbeError> {
+    use wsa::*;
+
+    match code {
+        WSAECONNREFUSED => Some(ProbeError::ConnectionRefused),
+        WSAETIMEDOUT => Some(ProbeError::TimedOut),
+        WSAEHOSTDOWN | WSAEHOSTUNREACH => Some(ProbeError::HostUnreachable),
+        WSAENETDOWN | WSAENETUNREACH => Some(ProbeError::NetworkUnreachable),
+        WSAEACCES => Some(ProbeError::PermissionDenied),
+        WSAEMFILE | WSAEADDRNOTAVAIL | WSAENOBUFS => Some(ProbeError::ResourceExhausted),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn from_os_error(_code: i32) -> Option<ProbeError> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{with_target, ProbeError};
+    use std::io;
+
+    #[test]
+    fn classifies_resource_exhaustion() {
+        let exhausted =
+            |error: io::Error| ProbeError::classify(&error) == ProbeError::ResourceExhausted;
+
+        #[cfg(unix)]
+        assert!(exhausted(io::Error::from_raw_os_error(libc::EMFILE)));
+        assert!(exhausted(io::Error::other("Too many open files")));
+        assert!(!exhausted(io::Error::from(
+            io::ErrorKind::ConnectionRefused
+        )));
+        assert!(!exhausted(io::Error::from(io::ErrorKind::TimedOut)));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn classifies_os_errors() {
+        let classify = |code| ProbeError::classify(&io::Error::from_raw_os_error(code));
+
+        assert_eq!(classify(libc::ECONNREFUSED), ProbeError::ConnectionRefused);
+        assert_eq!(classify(libc::EHOSTUNREACH), ProbeError::HostUnreachable);
+        assert_eq!(classify(libc::ENETUNREACH), ProbeError::NetworkUnreachable);
+        assert_eq!(classify(libc::EPERM), ProbeError::PermissionDenied);
+        assert_eq!(classify(libc::EINVAL), ProbeError::Other);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn classifies_winsock_errors() {
+        use super::wsa::*;
+
+        let classify = |code| ProbeError::classify(&io::Error::from_raw_os_error(code));
+
+        assert_eq!(classify(WSAECONNREFUSED), ProbeError::ConnectionRefused);
+        assert_eq!(classify(WSAEHOSTUNREACH), ProbeError::HostUnreachable);
+        assert_eq!(classify(WSAENETUNREACH), ProbeError::NetworkUnreachable);
+        assert_eq!(classify(WSAENOBUFS), ProbeError::ResourceExhausted);
+        // ECONNREFUSED on Linux, not a Winsock code.
+        assert_eq!(classify(111), ProbeError::Other);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn targeted_errors_keep_their_os_error() {
+        let error = with_target(
+            io::Error::from_raw_os_error(libc::ENOBUFS),
+            "10.0.0.1".parse().unwrap(),
+        );
+
+        assert_eq!(ProbeError::classify(&error), ProbeError::ResourceExhausted);
+        assert!(error.to_string().ends_with(" 10.0.0.1"));
+    }
+
+    #[test]
+    fn only_answers_are_definitive() {
+        assert!(ProbeError::ConnectionRefused.is_definitive());
+        assert!(ProbeError::HostUnreachable.is_definitive());
+        assert!(!ProbeError::TimedOut.is_definitive());
+        assert!(!ProbeError::ResourceExhausted.is_definitive());
+    }
+}
@@ -0,0 +1,191 @@
+//! Periodic progress updates while a scan runs.
+use super::{ScanControl, ScanEstimate, ScanResult, Scanner};
+
+use futures::{future::FutureExt, pin_mut, select};
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// How often progress is reported.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How far along a scan is, see [`Scanner::run_with_progress`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanProgress {
+    /// Sockets probed so far.
+    pub scanned: u64,
+    /// Sockets the scan will probe.
+    pub total: u64,
+    /// Open ports found so far.
+    pub open: usize,
+    pub elapsed: Duration,
+    /// How long the whole scan takes at most, zero when unknown.
+    pub estimated: Duration,
+}
+
+impl ScanProgress {
+    /// The scanned share of the sockets, from 0 to 1.
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.scanned as f64 / self.total as f64).min(1.0)
+    }
+
+    /// Time left at the rate so far, at most what the [`ScanEstimate`] of the
+    /// scan leaves for the sockets not yet scanned. Before anything was
+    /// scanned only the estimate is known, `None` without one either.
+    pub fn remaining(&self) -> Option<Duration> {
+        let bound = (!self.estimated.is_zero()).then(|| {
+            ScanEstimate {
+                sockets: self.total,
+                duration: self.estimated,
+            }
+            .remaining(self.scanned)
+        });
+        if self.scanned == 0 {
+            return bound;
+        }
+        let left = self.total.saturating_sub(self.scanned) as f64;
+        let pace = self.elapsed.mul_f64(left / self.scanned as f64);
+        Some(bound.map_or(pace, |bound| pace.min(bound)))
+    }
+}
+
+impl fmt::Display for ScanProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} sockets ({:.0}%), {} open, {:.0}s",
+            self.scanned,
+            self.total,
+            self.fraction() * 100.0,
+            self.open,
+            self.elapsed.as_secs_f64()
+        )?;
+        if let Some(remaining) = self.remaining() {
+            write!(f, ", about {:.0}s left", remaining.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `scan`, calling `callback` with the progress read from `control`
+/// every quarter of a second, and once more when the scan is over.
+///
+/// Useful with scans that need the scanner mutably, like
+/// [`Scanner::sweep`]: take [`Scanner::control`] first.
+pub async fn report_progress<T, F>(
+    control: &ScanControl,
+    scan: impl Future<Output = T>,
+    mut callback: F,
+) -> T
+where
+    F: FnMut(ScanProgress),
+{
+    let started = Instant::now();
+    let progress = || {
+        let status = control.status();
+        ScanProgress {
+            scanned: status.probed,
+            total: status.total,
+            open: status.open,
+            elapsed: started.elapsed(),
+            estimated: status.estimated,
+        }
+    };
+
+    let scan = scan.fuse();
+    pin_mut!(scan);
+    loop {
+        let tick = async_std::task::sleep(PROGRESS_INTERVAL).fuse();
+        pin_mut!(tick);
+        select! {
+            result = scan => {
+                callback(progress());
+                return result;
+            }
+            () = tick => callback(progress()),
+        }
+    }
+}
+
+impl Scanner {
+    /// Like [`Scanner::run`], calling `callback` with the progress of the
+    /// scan every quarter of a second, and once more when it is over.
+    ///
+    /// ```rust
+    /// # use async_std::task::block_on;
+    /// # use rustscan::input::{PortRange, ScanOrder};
+    /// # use rustscan::port_strategy::PortStrategy;
+    /// # use rustscan::scanner::Scanner;
+    /// # use std::time::Duration;
+    /// let range = PortRange { start: 1, end: 100 };
+    /// let strategy = PortStrategy::pick(&Some(range), None, ScanOrder::Serial);
+    /// let scanner = Scanner::new(
+    ///     &["127.0.0.1".parse().unwrap()],
+    ///     10,
+    ///     Duration::from_millis(100),
+    ///     1,
+    ///     true,
+    ///     strategy,
+    ///     true,
+    ///     vec![],
+    ///     false,
+    /// );
+    /// let mut last = None;
+    /// block_on(scanner.run_with_progress(|progress| last = Some(progress)));
+    /// assert_eq!(last.map(|progress| progress.scanned), Some(100));
+    /// ```
+    pub async fn run_with_progress<F>(&self, callback: F) -> ScanResult
+    where
+        F: FnMut(ScanProgress),
+    {
+        report_progress(&self.control, self.run(), callback).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScanProgress;
+    use std::time::Duration;
+
+    #[test]
+    fn estimates_time_left() {
+        let progress = ScanProgress {
+            scanned: 250,
+            total: 1_000,
+            open: 2,
+            elapsed: Duration::from_secs(10),
+            estimated: Duration::ZERO,
+        };
+
+        assert_eq!(progress.fraction(), 0.25);
+        assert_eq!(progress.remaining(), Some(Duration::from_secs(30)));
+        assert_eq!(
+            progress.to_string(),
+            "250/1000 sockets (25%), 2 open, 10s, about 30s left"
+        );
+        assert_eq!(ScanProgress::default().remaining(), None);
+    }
+
+    #[test]
+    fn estimate_bounds_time_left() {
+        let mut progress = ScanProgress {
+            scanned: 0,
+            total: 1_000,
+            open: 0,
+            elapsed: Duration::from_secs(1),
+            estimated: Duration::from_secs(20),
+        };
+        assert_eq!(progress.remaining(), Some(Duration::from_secs(20)));
+
+        // The slow start would take 30s more, the estimate leaves 15s.
+        progress.scanned = 250;
+        progress.elapsed = Duration::from_secs(10);
+        assert_eq!(progress.remaining(), Some(Duration::from_secs(15)));
+
+        progress.elapsed = Duration::from_secs(2);
+        assert_eq!(progress.remaining(), Some(Duration::from_secs(6)));
+    }
+}
@@ -0,0 +1,257 @@
+//! Per-host limits on the number of probes in flight.
+//!
+//! Firewalls that rate limit a host drop the probes above their rate, which
+//! then look like filtered ports. The outcomes of the probes to each host
+//! are counted in windows: the first window gives the share of probes the
+//! host normally drops (filtered ports), and a later window dropping many
+//! more than that, with timeouts or resets, halves the number of probes
+//! allowed in flight to that host. Windows back at the usual share let the
+//! limit grow again a few sockets at a time, until the host is no longer
+//! limited.
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+
+use super::ProbeError;
+
+/// Number of probes in a window.
+const WINDOW: usize = 50;
+
+/// A window dropping this much more than the first one lowers the limit.
+const DROP_INCREASE: f64 = 0.25;
+
+/// A window dropping at most this much more than the first one raises the
+/// limit again.
+const RECOVERY_MARGIN: f64 = 0.05;
+
+/// Sockets added to the limit of a host after a window without drops.
+const GROWTH: usize = 4;
+
+/// How a probe to a host ended, as far as its rate limiting goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Outcome {
+    /// The port accepted or refused the connection.
+    Answered,
+    /// Nothing came back, or the connection was reset before it was set up.
+    Dropped,
+}
+
+impl Outcome {
+    /// `None` for errors that say nothing about the host's rate limiting,
+    /// like unreachable networks or local errors.
+    pub(super) fn of(result: &io::Result<()>) -> Option<Self> {
+        let Err(e) = result else {
+            return Some(Outcome::Answered);
+        };
+        if matches!(
+            e.kind(),
+            io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+        ) {
+            return Some(Outcome::Dropped);
+        }
+        match ProbeError::classify(e) {
+            ProbeError::ConnectionRefused => Some(Outcome::Answered),
+            ProbeError::TimedOut => Some(Outcome::Dropped),
+            _ => None,
+        }
+    }
+}
+
+/// A host that was probed with fewer sockets at once because it started
+/// dropping probes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub ip: IpAddr,
+    /// Most probes that were in flight to the host at once before it was
+    /// limited.
+    pub peak: usize,
+    /// Fewest probes the host was limited to.
+    pub lowest: usize,
+    pub baseline_drop_rate: f64,
+    pub worst_drop_rate: f64,
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} dropped {:.0}% of the probes with up to {} in flight, up from {:.0}%, so it was probed with as few as {} sockets at once.",
+            self.ip,
+            self.worst_drop_rate * 100.0,
+            self.peak,
+            self.baseline_drop_rate * 100.0,
+            self.lowest,
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct HostRate {
+    in_flight: usize,
+    peak: usize,
+    /// `None` while the host isn't limited.
+    limit: Option<usize>,
+    probes: usize,
+    dropped: usize,
+    baseline_drop_rate: Option<f64>,
+    limited: Option<RateLimit>,
+}
+
+impl HostRate {
+    fn end_window(&mut self, ip: IpAddr) {
+        let rate = self.dropped as f64 / self.probes as f64;
+        self.probes = 0;
+        self.dropped = 0;
+
+        let Some(baseline) = self.baseline_drop_rate else {
+            self.baseline_drop_rate = Some(rate);
+            return;
+        };
+
+        if rate > baseline + DROP_INCREASE {
+            let limit = (self.limit.unwrap_or(self.peak) / 2).max(1);
+            self.limit = Some(limit);
+            let limited = self.limited.get_or_insert(RateLimit {
+                ip,
+                peak: self.peak,
+                lowest: limit,
+                baseline_drop_rate: baseline,
+                worst_drop_rate: rate,
+            });
+            limited.lowest = limited.lowest.min(limit);
+            limited.worst_drop_rate = limited.worst_drop_rate.max(rate);
+        } else if rate <= baseline + RECOVERY_MARGIN {
+            self.limit = self
+                .limit
+                .map(|limit| limit + GROWTH)
+                .filter(|limit| *limit < self.peak);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct RateController {
+    hosts: HashMap<IpAddr, HostRate>,
+}
+
+impl RateController {
+    /// Counts a probe to `ip` as in flight, unless the host already has as
+    /// many as its limit allows.
+    pub(super) fn try_acquire(&mut self, ip: IpAddr) -> bool {
+        let host = self.hosts.entry(ip).or_default();
+        if host.limit.is_some_and(|limit| host.in_flight >= limit) {
+            return false;
+        }
+        host.in_flight += 1;
+        if host.limit.is_none() {
+            host.peak = host.peak.max(host.in_flight);
+        }
+        true
+    }
+
+    /// Ends a probe to `ip` started with [`RateController::try_acquire`].
+    pub(super) fn release(&mut self, ip: IpAddr, outcome: Option<Outcome>) {
+        let Some(host) = self.hosts.get_mut(&ip) else {
+            return;
+        };
+        host.in_flight = host.in_flight.saturating_sub(1);
+        let Some(outcome) = outcome else {
+            return;
+        };
+
+        host.probes += 1;
+        if outcome == Outcome::Dropped {
+            host.dropped += 1;
+        }
+        if host.probes >= WINDOW {
+            host.end_window(ip);
+        }
+    }
+
+    /// The hosts that were limited, in no particular order.
+    pub(super) fn limited(&self) -> Vec<RateLimit> {
+        self.hosts
+            .values()
+            .filter_map(|host| host.limited)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Outcome, RateController, GROWTH, WINDOW};
+    use std::io;
+    use std::net::IpAddr;
+
+    /// Runs a window of probes to `ip` with `in_flight` probes at once,
+    /// `dropped` of which get no answer.
+    fn run_window(controller: &mut RateController, ip: IpAddr, in_flight: usize, dropped: usize) {
+        for probe in 0..WINDOW {
+            if probe % in_flight == 0 {
+                for _ in 0..in_flight {
+                    assert!(controller.try_acquire(ip));
+                }
+            }
+            let outcome = if probe < dropped {
+                Outcome::Dropped
+            } else {
+                Outcome::Answered
+            };
+            controller.release(ip, Some(outcome));
+        }
+    }
+
+    fn limit(controller: &RateController, ip: IpAddr) -> Option<usize> {
+        controller.hosts[&ip].limit
+    }
+
+    #[test]
+    fn halves_the_limit_when_drops_rise() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let mut controller = RateController::default();
+
+        run_window(&mut controller, ip, 10, 5);
+        run_window(&mut controller, ip, 10, 10);
+        assert_eq!(limit(&controller, ip), None);
+
+        run_window(&mut controller, ip, 10, 40);
+        assert_eq!(limit(&controller, ip), Some(5));
+        run_window(&mut controller, ip, 5, 40);
+        assert_eq!(limit(&controller, ip), Some(2));
+
+        let limited = controller.limited();
+        assert_eq!(limited.len(), 1);
+        assert_eq!(limited[0].peak, 10);
+        assert_eq!(limited[0].lowest, 2);
+
+        run_window(&mut controller, ip, 2, 5);
+        assert_eq!(limit(&controller, ip), Some(2 + GROWTH));
+        run_window(&mut controller, ip, 2, 5);
+        assert_eq!(limit(&controller, ip), None);
+    }
+
+    #[test]
+    fn leaves_filtered_hosts_alone() {
+        let ip: IpAddr = "10.0.0.2".parse().unwrap();
+        let mut controller = RateController::default();
+
+        run_window(&mut controller, ip, 10, WINDOW);
+        run_window(&mut controller, ip, 10, WINDOW);
+
+        assert_eq!(limit(&controller, ip), None);
+        assert!(controller.limited().is_empty());
+    }
+
+    #[test]
+    fn resets_count_as_drops() {
+        let reset = Err(io::Error::from(io::ErrorKind::ConnectionReset));
+        let refused = Err(io::Error::from(io::ErrorKind::ConnectionRefused));
+        let denied = Err(io::Error::from(io::ErrorKind::PermissionDenied));
+
+        assert_eq!(Outcome::of(&reset), Some(Outcome::Dropped));
+        assert_eq!(Outcome::of(&refused), Some(Outcome::Answered));
+        assert_eq!(Outcome::of(&Ok(())), Some(Outcome::Answered));
+        assert_eq!(Outcome::of(&denied), None);
+    }
+}
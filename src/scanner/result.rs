@@ -0,0 +1,242 @@
+//! The state of every port a scan probed.
+use super::sampling::is_skipped;
+use super::ProbeError;
+use crate::output::Protocol;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+
+/// What probing a port found, the way nmap names it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PortState {
+    /// The port accepted the connection, or a UDP probe got an answer.
+    Open,
+    /// The host answered with a reset.
+    Closed,
+    /// Nothing came back, or an error got in the way, like an unreachable
+    /// host or a firewall rejecting the probe.
+    Filtered,
+    /// A UDP probe that got no answer at all: the port is either open with
+    /// a service ignoring the payload, or filtered.
+    OpenFiltered,
+}
+
+impl PortState {
+    /// The state a probe of a port ended in, `None` when the port was
+    /// skipped rather than probed.
+    pub(super) fn of(result: &io::Result<SocketAddr>) -> Option<Self> {
+        match result {
+            Ok(_) => Some(PortState::Open),
+            Err(e) if is_skipped(e) => None,
+            Err(e) if ProbeError::classify(e) == ProbeError::ConnectionRefused => {
+                Some(PortState::Closed)
+            }
+            Err(_) => Some(PortState::Filtered),
+        }
+    }
+
+    /// Like [`PortState::of`] for a UDP probe, which can't tell a silent
+    /// open port from a filtered one.
+    pub(super) fn of_udp(result: &io::Result<SocketAddr>) -> Option<Self> {
+        match result {
+            Err(e) if ProbeError::classify(e) == ProbeError::TimedOut => {
+                Some(PortState::OpenFiltered)
+            }
+            _ => Self::of(result),
+        }
+    }
+}
+
+impl fmt::Display for PortState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PortState::Open => "open",
+            PortState::Closed => "closed",
+            PortState::Filtered => "filtered",
+            PortState::OpenFiltered => "open|filtered",
+        })
+    }
+}
+
+/// The outcome of a scan: the state of every socket probed over each
+/// protocol, returned by [`Scanner::run`](super::Scanner::run).
+///
+/// Every probed socket is kept, closed and filtered ones included, so full
+/// range scans of many hosts take memory accordingly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanResult {
+    states: BTreeMap<(SocketAddr, Protocol), PortState>,
+}
+
+impl ScanResult {
+    /// Sets the state of `socket` over `protocol`, replacing the one of an
+    /// earlier probe.
+    pub fn record(&mut self, socket: SocketAddr, protocol: Protocol, state: PortState) {
+        self.states.insert((socket, protocol), state);
+    }
+
+    pub fn state(&self, socket: &SocketAddr, protocol: Protocol) -> Option<PortState> {
+        self.states.get(&(*socket, protocol)).copied()
+    }
+
+    /// The probed sockets with their protocol and state, sorted by address.
+    pub fn iter(&self) -> impl Iterator<Item = (SocketAddr, Protocol, PortState)> + '_ {
+        self.states
+            .iter()
+            .map(|((socket, protocol), state)| (*socket, *protocol, *state))
+    }
+
+    /// The sockets in `state` over any protocol, sorted by address.
+    pub fn with_state(&self, state: PortState) -> Vec<SocketAddr> {
+        let mut sockets: Vec<SocketAddr> = self
+            .iter()
+            .filter(|(_, _, other)| *other == state)
+            .map(|(socket, _, _)| socket)
+            .collect();
+        sockets.dedup();
+        sockets
+    }
+
+    /// The open ports of `ip` over any protocol, in ascending order.
+    pub fn open_ports(&self, ip: IpAddr) -> Vec<u16> {
+        let mut ports: Vec<u16> = self
+            .states
+            .range(
+                (SocketAddr::new(ip, 0), Protocol::Tcp)
+                    ..=(SocketAddr::new(ip, u16::MAX), Protocol::Udp),
+            )
+            .filter(|(_, state)| **state == PortState::Open)
+            .map(|((socket, _), _)| socket.port())
+            .collect();
+        ports.dedup();
+        ports
+    }
+
+    /// The sockets open over any protocol, see [`ScanResult::open_tagged`]
+    /// to tell TCP and UDP apart.
+    pub fn open(&self) -> Vec<SocketAddr> {
+        self.with_state(PortState::Open)
+    }
+
+    /// The open sockets along with the protocol they are open over, a
+    /// socket open over both TCP and UDP coming once for each.
+    pub fn open_tagged(&self) -> Vec<(SocketAddr, Protocol)> {
+        self.iter()
+            .filter(|(_, _, state)| *state == PortState::Open)
+            .map(|(socket, protocol, _)| (socket, protocol))
+            .collect()
+    }
+
+    pub fn closed(&self) -> Vec<SocketAddr> {
+        self.with_state(PortState::Closed)
+    }
+
+    pub fn filtered(&self) -> Vec<SocketAddr> {
+        self.with_state(PortState::Filtered)
+    }
+
+    /// The UDP sockets that didn't answer at all.
+    pub fn open_filtered(&self) -> Vec<SocketAddr> {
+        self.with_state(PortState::OpenFiltered)
+    }
+
+    /// The hosts none of whose probed ports answered, either down or
+    /// behind a firewall dropping everything.
+    pub fn filtered_hosts(&self) -> Vec<IpAddr> {
+        let answering: BTreeSet<IpAddr> = self
+            .iter()
+            .filter(|(_, _, state)| !matches!(state, PortState::Filtered | PortState::OpenFiltered))
+            .map(|(socket, _, _)| socket.ip())
+            .collect();
+        self.states
+            .keys()
+            .map(|(socket, _)| socket.ip())
+            .filter(|ip| !answering.contains(ip))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Number of probes, a socket probed over both TCP and UDP counting
+    /// twice.
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PortState, ScanResult};
+    use crate::output::Protocol::{Tcp, Udp};
+    use std::io;
+    use std::net::{IpAddr, SocketAddr};
+
+    #[test]
+    fn classifies_probe_results() {
+        let socket: SocketAddr = "10.0.0.1:22".parse().unwrap();
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        let timed_out = io::Error::from(io::ErrorKind::TimedOut);
+
+        assert_eq!(PortState::of(&Ok(socket)), Some(PortState::Open));
+        assert_eq!(PortState::of(&Err(refused)), Some(PortState::Closed));
+        assert_eq!(PortState::of(&Err(timed_out)), Some(PortState::Filtered));
+
+        let silent = io::Error::new(io::ErrorKind::TimedOut, "UDP scan timed-out");
+        let rejected = io::Error::from(io::ErrorKind::HostUnreachable);
+        assert_eq!(
+            PortState::of_udp(&Err(silent)),
+            Some(PortState::OpenFiltered)
+        );
+        assert_eq!(PortState::of_udp(&Err(rejected)), Some(PortState::Filtered));
+        assert_eq!(PortState::OpenFiltered.to_string(), "open|filtered");
+    }
+
+    #[test]
+    fn tells_filtered_hosts_apart() {
+        let mut result = ScanResult::default();
+        result.record("10.0.0.1:22".parse().unwrap(), Tcp, PortState::Open);
+        result.record("10.0.0.1:23".parse().unwrap(), Tcp, PortState::Filtered);
+        result.record("10.0.0.2:22".parse().unwrap(), Tcp, PortState::Filtered);
+        result.record("10.0.0.2:23".parse().unwrap(), Tcp, PortState::Filtered);
+        result.record("10.0.0.3:22".parse().unwrap(), Tcp, PortState::Closed);
+
+        assert_eq!(result.open(), vec!["10.0.0.1:22".parse().unwrap()]);
+        assert_eq!(result.open_ports("10.0.0.1".parse().unwrap()), vec![22]);
+        assert!(result.open_ports("10.0.0.2".parse().unwrap()).is_empty());
+        assert_eq!(result.closed(), vec!["10.0.0.3:22".parse().unwrap()]);
+        assert_eq!(result.filtered().len(), 3);
+        assert_eq!(
+            result.filtered_hosts(),
+            vec!["10.0.0.2".parse::<IpAddr>().unwrap()]
+        );
+
+        result.record("10.0.0.1:22".parse().unwrap(), Tcp, PortState::Closed);
+        assert!(result.open().is_empty());
+        assert_eq!(result.len(), 5);
+    }
+
+    #[test]
+    fn tags_results_with_their_protocol() {
+        let socket: SocketAddr = "10.0.0.1:53".parse().unwrap();
+        let mut result = ScanResult::default();
+        result.record(socket, Tcp, PortState::Open);
+        result.record(socket, Udp, PortState::Open);
+        result.record(
+            "10.0.0.1:123".parse().unwrap(),
+            Udp,
+            PortState::OpenFiltered,
+        );
+
+        assert_eq!(result.open(), vec![socket]);
+        assert_eq!(result.open_tagged(), vec![(socket, Tcp), (socket, Udp)]);
+        assert_eq!(result.open_ports(socket.ip()), vec![53]);
+        assert_eq!(result.state(&socket, Udp), Some(PortState::Open));
+        assert_eq!(result.len(), 3);
+    }
+}
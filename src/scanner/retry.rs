@@ -0,0 +1,165 @@
+//! How long a probe waits before trying a port again.
+//!
+//! Retrying right away against a congested target mostly fails the same
+//! way the first try did, so the tries after the first are spread out.
+//! Every delay is jittered, half of it being random, so the retries of a
+//! batch don't all land at once either.
+use super::Scanner;
+
+use rand::RngExt;
+use serde_derive::Deserialize;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The delay before each retry of a probe, as accepted by `--retry-backoff`:
+/// `none`, `fixed:<ms>`, `linear:<ms>` or `exponential:<ms>:<cap ms>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum RetryPolicy {
+    /// Retry right away.
+    None,
+    /// The same delay before every retry.
+    Fixed(Duration),
+    /// The delay grows by the same step with every retry.
+    Linear(Duration),
+    /// The delay doubles with every retry, up to `cap`.
+    Exponential { base: Duration, cap: Duration },
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::Exponential {
+            base: Duration::from_millis(50),
+            cap: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay before `retry`, the first retry being 1, without jitter.
+    ///
+    /// ```rust
+    /// # use rustscan::scanner::RetryPolicy;
+    /// # use std::time::Duration;
+    /// let policy: RetryPolicy = "exponential:100:300".parse().unwrap();
+    /// assert_eq!(policy.max_delay(1), Duration::from_millis(100));
+    /// assert_eq!(policy.max_delay(2), Duration::from_millis(200));
+    /// assert_eq!(policy.max_delay(3), Duration::from_millis(300));
+    /// ```
+    pub fn max_delay(&self, retry: u8) -> Duration {
+        match *self {
+            RetryPolicy::None => Duration::ZERO,
+            RetryPolicy::Fixed(delay) => delay,
+            RetryPolicy::Linear(step) => step.saturating_mul(u32::from(retry)),
+            RetryPolicy::Exponential { base, cap } => {
+                let factor = 1u32
+                    .checked_shl(u32::from(retry.saturating_sub(1)))
+                    .unwrap_or(u32::MAX);
+                base.checked_mul(factor).unwrap_or(cap).min(cap)
+            }
+        }
+    }
+
+    /// The delay before `retry`, between half of [`RetryPolicy::max_delay`]
+    /// and all of it.
+    pub fn delay(&self, retry: u8) -> Duration {
+        let max = self.max_delay(retry);
+        if max.is_zero() {
+            return max;
+        }
+        max / 2 + max.mul_f64(rand::rng().random_range(0.0..0.5))
+    }
+}
+
+impl FromStr for RetryPolicy {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let millis = |value: &str| {
+            value
+                .parse::<u64>()
+                .map(Duration::from_millis)
+                .map_err(|_| format!("Invalid delay {value} in retry backoff {spec}"))
+        };
+        let parts: Vec<&str> = spec.split(':').collect();
+        match parts.as_slice() {
+            ["none"] => Ok(RetryPolicy::None),
+            ["fixed", delay] => Ok(RetryPolicy::Fixed(millis(delay)?)),
+            ["linear", step] => Ok(RetryPolicy::Linear(millis(step)?)),
+            ["exponential", base, cap] => Ok(RetryPolicy::Exponential {
+                base: millis(base)?,
+                cap: millis(cap)?,
+            }),
+            _ => Err(format!(
+                "Invalid retry backoff {spec}, expected none, fixed:<ms>, linear:<ms> or exponential:<ms>:<cap ms>"
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for RetryPolicy {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        spec.parse()
+    }
+}
+
+impl fmt::Display for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryPolicy::None => f.write_str("none"),
+            RetryPolicy::Fixed(delay) => write!(f, "fixed:{}", delay.as_millis()),
+            RetryPolicy::Linear(step) => write!(f, "linear:{}", step.as_millis()),
+            RetryPolicy::Exponential { base, cap } => {
+                write!(f, "exponential:{}:{}", base.as_millis(), cap.as_millis())
+            }
+        }
+    }
+}
+
+impl Scanner {
+    /// Waits between the tries of a probe following `retry`, by default
+    /// exponentially from 50ms up to a second.
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use std::time::Duration;
+
+    #[test]
+    fn delays_grow_with_retries() {
+        let ms = Duration::from_millis;
+        let fixed: RetryPolicy = "fixed:100".parse().unwrap();
+        let linear: RetryPolicy = "linear:100".parse().unwrap();
+        let exponential = RetryPolicy::default();
+
+        assert_eq!(fixed.max_delay(3), ms(100));
+        assert_eq!(linear.max_delay(3), ms(300));
+        assert_eq!(exponential.max_delay(2), ms(100));
+        assert_eq!(exponential.max_delay(200), ms(1000));
+        assert_eq!(RetryPolicy::None.delay(5), Duration::ZERO);
+
+        for _ in 0..100 {
+            let delay = linear.delay(2);
+            assert!(delay >= ms(100) && delay <= ms(200));
+        }
+    }
+
+    #[test]
+    fn parses_specs() {
+        for spec in ["none", "fixed:10", "linear:20", "exponential:50:1000"] {
+            assert_eq!(spec.parse::<RetryPolicy>().unwrap().to_string(), spec);
+        }
+        assert!("exponential:50".parse::<RetryPolicy>().is_err());
+        assert!("fixed:soon".parse::<RetryPolicy>().is_err());
+    }
+}
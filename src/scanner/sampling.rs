@@ -0,0 +1,130 @@
+//! Sampling of hosts that accept connections on nearly every port.
+//!
+//! CDN edges, some firewalls and tarpits complete the handshake on every
+//! port, so scanning all of them takes long and only yields a misleading
+//! list of open ports. Once such a host has been probed on enough ports to
+//! be recognised, the rest of its ports are skipped.
+use super::tarpit::{all_ports_open, Tarpit};
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+
+/// The error of a port left unprobed because its host answers on every
+/// port. Neither a probe error nor unprobed, see [`is_skipped`].
+#[derive(Debug)]
+pub(super) struct Skipped;
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the host answers on every port, skipping its other ports")
+    }
+}
+
+impl Error for Skipped {}
+
+/// Whether `error` is a port skipped by the [`AllOpenSampler`].
+pub(super) fn is_skipped(error: &io::Error) -> bool {
+    error.get_ref().is_some_and(|inner| inner.is::<Skipped>())
+}
+
+/// Probes per host, for the hosts scanned so far.
+#[derive(Debug, Default)]
+pub(super) struct AllOpenSampler {
+    sample: Option<usize>,
+    hosts: BTreeMap<IpAddr, Probed>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Probed {
+    open: usize,
+    total: usize,
+}
+
+impl AllOpenSampler {
+    /// Hosts recognised as all-open stop being probed after `sample` ports,
+    /// `None` never stops probing them.
+    pub(super) fn new(sample: Option<usize>) -> Self {
+        Self {
+            sample,
+            hosts: BTreeMap::new(),
+        }
+    }
+
+    /// Forgets the hosts of a previous scan.
+    pub(super) fn reset(&mut self) {
+        self.hosts.clear();
+    }
+
+    pub(super) fn record(&mut self, ip: IpAddr, open: bool) {
+        if self.sample.is_none() {
+            return;
+        }
+        let probed = self.hosts.entry(ip).or_default();
+        probed.total += 1;
+        if open {
+            probed.open += 1;
+        }
+    }
+
+    /// Whether the remaining ports of `ip` should be skipped.
+    pub(super) fn is_sampled(&self, ip: &IpAddr) -> bool {
+        let (Some(sample), Some(probed)) = (self.sample, self.hosts.get(ip)) else {
+            return false;
+        };
+        probed.total >= sample && all_ports_open(probed.open, probed.total).is_some()
+    }
+
+    /// The hosts whose ports were only sampled, with what the sample found.
+    pub(super) fn sampled(&self) -> Vec<Tarpit> {
+        self.hosts
+            .iter()
+            .filter(|(ip, _)| self.is_sampled(ip))
+            .filter_map(|(ip, probed)| {
+                Some(Tarpit {
+                    ip: *ip,
+                    reason: all_ports_open(probed.open, probed.total)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AllOpenSampler;
+    use std::net::IpAddr;
+
+    #[test]
+    fn samples_hosts_answering_on_every_port() {
+        let cdn: IpAddr = "192.0.2.1".parse().unwrap();
+        let server: IpAddr = "192.0.2.2".parse().unwrap();
+        let mut sampler = AllOpenSampler::new(Some(200));
+        for port in 0..200 {
+            sampler.record(cdn, port % 20 != 0);
+            sampler.record(server, port == 80);
+        }
+
+        assert!(sampler.is_sampled(&cdn));
+        assert!(!sampler.is_sampled(&server));
+        assert_eq!(sampler.sampled().len(), 1);
+
+        sampler.reset();
+        assert!(!sampler.is_sampled(&cdn));
+    }
+
+    #[test]
+    fn waits_for_the_whole_sample() {
+        let cdn: IpAddr = "192.0.2.1".parse().unwrap();
+        let mut sampler = AllOpenSampler::new(Some(500));
+        for _ in 0..499 {
+            sampler.record(cdn, true);
+        }
+        assert!(!sampler.is_sampled(&cdn));
+
+        sampler.record(cdn, true);
+        assert!(sampler.is_sampled(&cdn));
+    }
+}
@@ -0,0 +1,202 @@
+//! Setting a scanner up following the command line options, the same way
+//! for a single scan and for the scans of the interactive shell.
+use super::{
+    IcmpListener, MultiSink, ResultSink, Scanner, SocketOptions, SpreadStrategy, SynProber,
+    TcpExtensions, UdpPayloads,
+};
+use crate::capabilities::{Capabilities, Capability};
+use crate::input::{Opts, ScanType};
+use crate::output::Protocol;
+use crate::policy::Policies;
+use crate::privsep::{self, RawSockets};
+use crate::sinks;
+use crate::system::cgroup::CgroupLimits;
+use crate::system::resource_limits;
+#[cfg(feature = "tui")]
+use crate::tui::TerminalSink;
+use crate::warning;
+
+use log::debug;
+use std::net::IpAddr;
+use std::time::Duration;
+
+impl Scanner {
+    /// Applies the scan options of `opts`: protocols, socket options,
+    /// retries, rate, spread, timeouts, backoff and throttling, policies,
+    /// UDP payloads, output sinks and the SYN scan, which probes through
+    /// the raw sockets of `raw_sockets`, or new ones when `None`.
+    ///
+    /// The targets' own ports and scopes are set apart, as they come with
+    /// the targets, but lose the excluded ports here. Errors tell which
+    /// option can't be used.
+    pub fn with_opts(
+        mut self,
+        opts: &Opts,
+        mut raw_sockets: Option<&mut RawSockets>,
+    ) -> Result<Self, String> {
+        let exclude_ports = opts
+            .excluded_ports()
+            .map_err(|e| format!("Invalid excluded ports: {e}"))?;
+        for ports in self.target_ports.values_mut() {
+            ports.retain(|port| !exclude_ports.contains(port));
+        }
+
+        let cgroup = CgroupLimits::detect();
+        let cgroup_buffer_size = cgroup.as_ref().and_then(CgroupLimits::socket_buffer_size);
+        let socket_options = SocketOptions {
+            // Connections from a fixed port would otherwise keep it in
+            // TIME_WAIT, failing the retries to the same target port.
+            linger_zero: opts.linger_zero || opts.source_port.is_some(),
+            nodelay: opts.tcp_nodelay,
+            send_buffer_size: opts.send_buffer_size.or(cgroup_buffer_size),
+            recv_buffer_size: opts.recv_buffer_size.or(cgroup_buffer_size),
+            tos: opts.tos,
+            source_ip: opts.source_ip,
+            source_port: opts.source_port,
+            interface: opts.interface.clone(),
+        };
+        let families = [
+            self.ips.iter().find(|ip| ip.is_ipv4()),
+            self.ips.iter().find(|ip| ip.is_ipv6()),
+        ];
+        for ip in families.iter().copied().flatten() {
+            if let Err(e) = socket_options.check(*ip) {
+                let family = if ip.is_ipv4() { "IPv4" } else { "IPv6" };
+                return Err(format!(
+                    "The socket options can't be used with {family} targets: {e}"
+                ));
+            }
+        }
+
+        let protocols = opts.protocols();
+        // UDP sockets don't share their source port, see SocketOptions.
+        let one_at_a_time = opts.source_port.is_some() && protocols.contains(&Protocol::Udp);
+        if one_at_a_time && self.batch_size > 1 {
+            warning!(
+                "UDP probes can't share --source-port, the scan probes one port at a time.",
+                opts.greppable,
+                opts.accessible
+            );
+            self.batch_size = 1;
+        }
+
+        let mut policies = Policies::new(&opts.policies)?;
+        let max_batch_size = cgroup.as_ref().and_then(CgroupLimits::max_batch_size);
+        policies.cap_batch_sizes(|batch_size| {
+            if one_at_a_time {
+                return 1;
+            }
+            let batch_size = resource_limits::safe_batch_size(batch_size);
+            max_batch_size.map_or(batch_size, |max| batch_size.min(max))
+        });
+
+        let mut sinks: Vec<Box<dyn ResultSink>> = Vec::new();
+        #[cfg(feature = "tui")]
+        if !opts.greppable {
+            sinks.push(Box::new(
+                TerminalSink::new(opts.accessible)
+                    .numeric(opts.numeric)
+                    .show_protocol(protocols.len() > 1)
+                    .progress_bar(opts.shows_progress_bar()),
+            ));
+        }
+        for spec in &opts.output {
+            sinks.push(
+                sinks::open(spec).map_err(|e| format!("Could not open output {spec:?}: {e}"))?,
+            );
+        }
+
+        self = self
+            .with_protocols(&protocols)
+            .with_socket_options(socket_options)
+            .with_retry_policy(opts.retry_backoff)
+            .with_rate(opts.rate)
+            .with_iteration_order(opts.iteration_order)
+            .with_spread(
+                opts.max_per_host
+                    .map_or(SpreadStrategy::Batch, SpreadStrategy::PerHost),
+            )
+            .with_read_timeout(
+                opts.read_timeout
+                    .map(|read_timeout| Duration::from_millis(read_timeout.into())),
+            )
+            .with_anomaly_backoff(opts.backoff)
+            .with_adaptive_rate(!opts.no_adaptive_rate)
+            .with_conntrack_throttle(!opts.no_conntrack_throttle)
+            .with_all_open_sample(opts.all_open_sample.filter(|&sample| sample > 0))
+            .with_policies(policies)
+            .with_sink(Box::new(MultiSink::new(sinks)));
+        if let Some(path) = &opts.udp_payloads {
+            let payloads =
+                UdpPayloads::read(path).map_err(|e| format!("Invalid UDP payloads file: {e}"))?;
+            self = self.with_udp_payloads(payloads);
+        }
+
+        if protocols.contains(&Protocol::Udp) {
+            let socket = raw_sockets
+                .as_mut()
+                .and_then(|sockets| sockets.icmp_v4.take())
+                .or_else(privsep::open_icmp_v4);
+            match socket.map(IcmpListener::new) {
+                Some(Ok(listener)) => self = self.with_icmp_listener(listener),
+                Some(Err(e)) => debug!("Could not listen for ICMP unreachables: {e}"),
+                None => debug!("No raw ICMP socket, UDP ports are told apart by the kernel"),
+            }
+        }
+        if opts.decoys.is_some() && opts.scan_type != ScanType::Syn {
+            warning!(
+                "Decoys are only sent by --scan-type syn, the connect scan ignores them.",
+                opts.greppable,
+                opts.accessible
+            );
+        }
+        if (opts.mptcp || opts.ecn) && opts.scan_type != ScanType::Syn {
+            warning!(
+                "Multipath TCP and ECN are only asked for by --scan-type syn, the connect scan ignores --mptcp and --ecn.",
+                opts.greppable,
+                opts.accessible
+            );
+        }
+        if opts.syn_scans() {
+            let decoys = opts.decoys.clone().unwrap_or_default();
+            let extensions = TcpExtensions {
+                mptcp: opts.mptcp,
+                ecn: opts.ecn,
+            };
+            let socket = raw_sockets
+                .as_mut()
+                .and_then(|sockets| sockets.tcp_v4.take())
+                .or_else(privsep::open_tcp_v4);
+            let source_ip = match opts.source_ip {
+                Some(IpAddr::V4(source_ip)) => Some(source_ip),
+                _ => None,
+            };
+            let prober = socket.map(|socket| {
+                SynProber::new(socket, opts.source_port)
+                    .and_then(|prober| prober.with_source_ip(source_ip))
+                    .and_then(|prober| prober.with_decoys(decoys))
+                    .map(|prober| prober.with_extensions(extensions))
+            });
+            match prober {
+                Some(Ok(prober)) => self = self.with_syn_prober(prober),
+                Some(Err(e)) => warning!(
+                    format!("Could not start the SYN scan, falling back to a connect scan: {e}"),
+                    opts.greppable,
+                    opts.accessible
+                ),
+                None => warning!(
+                    format!(
+                        "{}, falling back to a connect scan.",
+                        Capabilities::detect()
+                            .require("A SYN scan", &[Capability::RawSockets])
+                            .err()
+                            .unwrap_or_default()
+                    ),
+                    opts.greppable,
+                    opts.accessible
+                ),
+            }
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,123 @@
+//! Where the scanner sends its results while it runs.
+//!
+//! The scanner never prints anything itself. Open ports and other events
+//! are handed to a [`ResultSink`], so library users decide what happens to
+//! them and the CLI can print them, write them to a file, or both.
+use super::{ProbeError, ScanSummary};
+use crate::output::Protocol;
+
+use std::fmt;
+use std::net::SocketAddr;
+
+/// Something that happened during a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScanEvent {
+    /// A port was found open over a protocol.
+    Open(SocketAddr, Protocol),
+    /// A probe couldn't be sent because of a local error, e.g. a UDP socket
+    /// that couldn't be bound.
+    LocalError {
+        socket: SocketAddr,
+        error: ProbeError,
+    },
+    /// The scan is over.
+    Finished(ScanSummary),
+}
+
+/// Receives the events of a scan as they happen. Probes run concurrently,
+/// so events arrive from several tasks and in no particular order.
+pub trait ResultSink: fmt::Debug + Send + Sync {
+    fn emit(&self, event: &ScanEvent);
+}
+
+/// Drops every event, for callers that only want the results [`run`]
+/// returns.
+///
+/// [`run`]: super::Scanner::run
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullSink;
+
+impl ResultSink for NullSink {
+    fn emit(&self, _event: &ScanEvent) {}
+}
+
+/// Sends every event to each of several sinks, in order.
+#[derive(Debug, Default)]
+pub struct MultiSink(Vec<Box<dyn ResultSink>>);
+
+impl MultiSink {
+    pub fn new(sinks: Vec<Box<dyn ResultSink>>) -> Self {
+        Self(sinks)
+    }
+}
+
+impl ResultSink for MultiSink {
+    fn emit(&self, event: &ScanEvent) {
+        for sink in &self.0 {
+            sink.emit(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MultiSink, ResultSink, ScanEvent};
+    use crate::output::Protocol;
+    use crate::port_strategy::PortStrategy;
+    use crate::scanner::Scanner;
+    use async_std::task::block_on;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Debug, Default)]
+    struct Recorder(Arc<Mutex<Vec<ScanEvent>>>);
+
+    impl ResultSink for Recorder {
+        fn emit(&self, event: &ScanEvent) {
+            self.0.lock().unwrap().push(*event);
+        }
+    }
+
+    #[test]
+    fn open_ports_go_to_the_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        let scanner = Scanner::new(
+            &[open.ip()],
+            10,
+            Duration::from_millis(500),
+            1,
+            false,
+            PortStrategy::Manual(vec![open.port()]),
+            true,
+            vec![],
+            false,
+        )
+        .with_sink(Box::new(Recorder(events.clone())));
+
+        assert_eq!(block_on(scanner.run()).open(), vec![open]);
+        let events = events.lock().unwrap();
+        assert_eq!(events[0], ScanEvent::Open(open, Protocol::Tcp));
+        assert!(matches!(&events[1..], [ScanEvent::Finished(summary)] if summary.open_ports == 1));
+    }
+
+    #[test]
+    fn every_sink_gets_every_event() {
+        let first = Arc::new(Mutex::new(Vec::new()));
+        let second = Arc::new(Mutex::new(Vec::new()));
+        let sink = MultiSink::new(vec![
+            Box::new(Recorder(first.clone())),
+            Box::new(Recorder(second.clone())),
+        ]);
+        let event = ScanEvent::Open("127.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+
+        sink.emit(&event);
+
+        assert_eq!(*first.lock().unwrap(), vec![event]);
+        assert_eq!(*second.lock().unwrap(), vec![event]);
+    }
+}
@@ -1,17 +1,37 @@
-use itertools::{iproduct, Product};
+use crate::input::IterationOrder;
+use crate::output::Protocol;
+
+use gcd::Gcd;
+use rand::RngExt;
+use std::collections::HashMap;
 use std::net::{IpAddr, SocketAddr};
 
 pub struct SocketIterator<'s> {
-    // product_it is a cartesian product iterator over
-    // the slices of ports and IP addresses.
-    //
-    // The IP/port order is intentionally reversed here since we want
-    // the itertools::iproduct! macro below to generate the pairs with
-    // all the IPs for one port before moving on to the next one
-    // ("hold the port, go through all the IPs, then advance the port...").
-    // See also the comments in the iterator implementation for an example.
-    product_it:
-        Product<Box<std::slice::Iter<'s, u16>>, Box<std::slice::Iter<'s, std::net::IpAddr>>>,
+    ips: &'s [IpAddr],
+    ports: &'s [u16],
+    // Ports of the IPs that don't get `ports`, see
+    // `SocketIterator::with_target_ports`.
+    target_ports: Option<&'s HashMap<IpAddr, Vec<u16>>>,
+    // Every socket is given once for each of these, in order.
+    protocols: &'s [Protocol],
+    // The socket being given for each protocol, and the next protocol.
+    current: Option<SocketAddr>,
+    protocol_index: usize,
+    order: IterationOrder,
+    // By default the iterator holds the index of the port, goes through all
+    // the IPs, then advances the index ("hold the port, go through all the
+    // IPs, then advance the port..."). IPs with fewer ports than the others
+    // are skipped once they ran out of them.
+    port_index: usize,
+    ip_index: usize,
+    // Length of the longest port list, past which every IP ran out.
+    longest: usize,
+    // Interleaved, the iterator walks the IP and port index pairs, numbered
+    // port after port, `stride` at a time. The stride is coprime to their
+    // count, so every pair comes up once before `given` reaches it.
+    stride: usize,
+    index: usize,
+    given: usize,
 }
 
 /// An iterator that receives a slice of IPs and ports and returns a Socket
@@ -21,38 +41,170 @@ pub struct SocketIterator<'s> {
 /// generating a vector containing all these combinations.
 impl<'s> SocketIterator<'s> {
     pub fn new(ips: &'s [IpAddr], ports: &'s [u16]) -> Self {
-        let ports_it = Box::new(ports.iter());
-        let ips_it = Box::new(ips.iter());
-        Self {
-            product_it: iproduct!(ports_it, ips_it),
+        Self::build(ips, ports, None)
+    }
+
+    /// Like [`SocketIterator::new`], the IPs of `target_ports` getting
+    /// their own ports instead of `ports`.
+    pub fn with_target_ports(
+        ips: &'s [IpAddr],
+        ports: &'s [u16],
+        target_ports: &'s HashMap<IpAddr, Vec<u16>>,
+    ) -> Self {
+        Self::build(ips, ports, Some(target_ports))
+    }
+
+    fn build(
+        ips: &'s [IpAddr],
+        ports: &'s [u16],
+        target_ports: Option<&'s HashMap<IpAddr, Vec<u16>>>,
+    ) -> Self {
+        let mut iterator = Self {
+            ips,
+            ports,
+            target_ports,
+            protocols: &[Protocol::Tcp],
+            current: None,
+            protocol_index: 0,
+            order: IterationOrder::default(),
+            port_index: 0,
+            ip_index: 0,
+            longest: 0,
+            stride: 1,
+            index: 0,
+            given: 0,
+        };
+        iterator.longest = ips
+            .iter()
+            .map(|ip| iterator.ports_of(ip).len())
+            .max()
+            .unwrap_or(0);
+        iterator
+    }
+
+    /// Gives every socket once for each of `protocols`, instead of only
+    /// for TCP.
+    #[must_use]
+    pub fn with_protocols(mut self, protocols: &'s [Protocol]) -> Self {
+        self.protocols = protocols;
+        self
+    }
+
+    /// Combines the IPs and ports in `order` instead of port after port.
+    #[must_use]
+    pub fn with_order(mut self, order: IterationOrder) -> Self {
+        self.order = order;
+        let count = self.ips.len() * self.longest;
+        if order == IterationOrder::Interleaved && count > 1 {
+            let mut rng = rand::rng();
+            self.stride = loop {
+                let stride = rng.random_range(1..count);
+                if stride.gcd(count) == 1 {
+                    break stride;
+                }
+            };
+            self.index = rng.random_range(0..count);
         }
+        self
+    }
+
+    fn ports_of(&self, ip: &IpAddr) -> &'s [u16] {
+        self.target_ports
+            .and_then(|target_ports| target_ports.get(ip))
+            .map_or(self.ports, Vec::as_slice)
     }
 }
 
 #[allow(clippy::doc_link_with_quotes)]
 impl Iterator for SocketIterator<'_> {
-    type Item = SocketAddr;
+    type Item = (SocketAddr, Protocol);
 
     /// Returns a socket based on the combination of one of the provided
-    /// IPs and ports or None when these combinations are exhausted. Every
-    /// IP will have the same port until a port is incremented.
+    /// IPs and ports, along with the protocol to probe it over, or None
+    /// when these combinations are exhausted. By default every IP will have
+    /// the same port until a port is incremented, see
+    /// [`SocketIterator::with_order`].
     ///
     /// let it = SocketIterator::new(&["127.0.0.1", "192.168.0.1"], &[80, 443]);
-    /// it.next(); // 127.0.0.1:80
-    /// it.next(); // 192.168.0.1:80
-    /// it.next(); // 127.0.0.1:443
-    /// it.next(); // 192.168.0.1:443
+    /// it.next(); // (127.0.0.1:80, Tcp)
+    /// it.next(); // (192.168.0.1:80, Tcp)
+    /// it.next(); // (127.0.0.1:443, Tcp)
+    /// it.next(); // (192.168.0.1:443, Tcp)
     /// it.next(); // None
     fn next(&mut self) -> Option<Self::Item> {
-        self.product_it
-            .next()
-            .map(|(port, ip)| SocketAddr::new(*ip, *port))
+        loop {
+            if let Some(socket) = self.current {
+                if let Some(protocol) = self.protocols.get(self.protocol_index) {
+                    self.protocol_index += 1;
+                    return Some((socket, *protocol));
+                }
+            }
+            self.current = Some(self.next_socket()?);
+            self.protocol_index = 0;
+        }
+    }
+}
+
+impl SocketIterator<'_> {
+    fn next_socket(&mut self) -> Option<SocketAddr> {
+        match self.order {
+            IterationOrder::PortMajor => self.next_port_major(),
+            IterationOrder::HostMajor => self.next_host_major(),
+            IterationOrder::Interleaved => self.next_interleaved(),
+        }
+    }
+
+    fn next_port_major(&mut self) -> Option<SocketAddr> {
+        while self.port_index < self.longest {
+            let Some(ip) = self.ips.get(self.ip_index) else {
+                self.ip_index = 0;
+                self.port_index += 1;
+                continue;
+            };
+            self.ip_index += 1;
+            if let Some(port) = self.ports_of(ip).get(self.port_index) {
+                return Some(SocketAddr::new(*ip, *port));
+            }
+        }
+        None
+    }
+
+    fn next_host_major(&mut self) -> Option<SocketAddr> {
+        while let Some(ip) = self.ips.get(self.ip_index) {
+            if let Some(port) = self.ports_of(ip).get(self.port_index) {
+                self.port_index += 1;
+                return Some(SocketAddr::new(*ip, *port));
+            }
+            self.port_index = 0;
+            self.ip_index += 1;
+        }
+        None
+    }
+
+    fn next_interleaved(&mut self) -> Option<SocketAddr> {
+        let count = self.ips.len() * self.longest;
+        while self.given < count {
+            let ip = &self.ips[self.index % self.ips.len()];
+            let port = self.ports_of(ip).get(self.index / self.ips.len()).copied();
+            self.given += 1;
+            self.index += self.stride;
+            if self.index >= count {
+                self.index -= count;
+            }
+            if let Some(port) = port {
+                return Some(SocketAddr::new(*ip, port));
+            }
+        }
+        None
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::SocketIterator;
+    use crate::input::IterationOrder;
+    use crate::output::Protocol::{Tcp, Udp};
+    use std::collections::{HashMap, HashSet};
     use std::net::{IpAddr, SocketAddr};
 
     #[test]
@@ -64,12 +216,111 @@ mod tests {
         let ports: Vec<u16> = vec![22, 80, 443];
         let mut it = SocketIterator::new(&addrs, &ports);
 
-        assert_eq!(Some(SocketAddr::new(addrs[0], ports[0])), it.next());
-        assert_eq!(Some(SocketAddr::new(addrs[1], ports[0])), it.next());
-        assert_eq!(Some(SocketAddr::new(addrs[0], ports[1])), it.next());
-        assert_eq!(Some(SocketAddr::new(addrs[1], ports[1])), it.next());
-        assert_eq!(Some(SocketAddr::new(addrs[0], ports[2])), it.next());
-        assert_eq!(Some(SocketAddr::new(addrs[1], ports[2])), it.next());
+        assert_eq!(Some((SocketAddr::new(addrs[0], ports[0]), Tcp)), it.next());
+        assert_eq!(Some((SocketAddr::new(addrs[1], ports[0]), Tcp)), it.next());
+        assert_eq!(Some((SocketAddr::new(addrs[0], ports[1]), Tcp)), it.next());
+        assert_eq!(Some((SocketAddr::new(addrs[1], ports[1]), Tcp)), it.next());
+        assert_eq!(Some((SocketAddr::new(addrs[0], ports[2]), Tcp)), it.next());
+        assert_eq!(Some((SocketAddr::new(addrs[1], ports[2]), Tcp)), it.next());
         assert_eq!(None, it.next());
     }
+
+    #[test]
+    fn goes_through_every_port_of_a_host_first() {
+        let addrs = vec![
+            "127.0.0.1".parse::<IpAddr>().unwrap(),
+            "192.168.0.1".parse::<IpAddr>().unwrap(),
+        ];
+        let ports: Vec<u16> = vec![22, 80];
+        let mut target_ports = HashMap::new();
+        target_ports.insert(addrs[1], vec![443]);
+
+        let sockets: Vec<SocketAddr> =
+            SocketIterator::with_target_ports(&addrs, &ports, &target_ports)
+                .with_order(IterationOrder::HostMajor)
+                .map(|(socket, _)| socket)
+                .collect();
+
+        assert_eq!(
+            sockets,
+            vec![
+                SocketAddr::new(addrs[0], 22),
+                SocketAddr::new(addrs[0], 80),
+                SocketAddr::new(addrs[1], 443),
+            ]
+        );
+    }
+
+    #[test]
+    fn interleaves_every_socket_once() {
+        let addrs: Vec<IpAddr> = (1..=7)
+            .map(|host| format!("10.0.0.{host}").parse().unwrap())
+            .collect();
+        let ports: Vec<u16> = (1..=30).collect();
+        let mut target_ports = HashMap::new();
+        target_ports.insert(addrs[3], vec![8080]);
+
+        let sockets: Vec<SocketAddr> =
+            SocketIterator::with_target_ports(&addrs, &ports, &target_ports)
+                .with_order(IterationOrder::Interleaved)
+                .map(|(socket, _)| socket)
+                .collect();
+        let unique: HashSet<SocketAddr> = sockets.iter().copied().collect();
+
+        assert_eq!(sockets.len(), 6 * 30 + 1);
+        assert_eq!(unique.len(), sockets.len());
+        assert!(unique.contains(&SocketAddr::new(addrs[3], 8080)));
+        assert!(!unique.contains(&SocketAddr::new(addrs[3], 1)));
+    }
+
+    #[test]
+    fn gives_targets_their_own_ports() {
+        let addrs = vec![
+            "127.0.0.1".parse::<IpAddr>().unwrap(),
+            "192.168.0.1".parse::<IpAddr>().unwrap(),
+            "192.168.0.2".parse::<IpAddr>().unwrap(),
+        ];
+        let ports: Vec<u16> = vec![22];
+        let mut target_ports = HashMap::new();
+        target_ports.insert(addrs[1], vec![80, 443, 8080]);
+        target_ports.insert(addrs[2], vec![]);
+
+        let sockets: Vec<SocketAddr> =
+            SocketIterator::with_target_ports(&addrs, &ports, &target_ports)
+                .map(|(socket, _)| socket)
+                .collect();
+
+        assert_eq!(
+            sockets,
+            vec![
+                SocketAddr::new(addrs[0], 22),
+                SocketAddr::new(addrs[1], 80),
+                SocketAddr::new(addrs[1], 443),
+                SocketAddr::new(addrs[1], 8080),
+            ]
+        );
+    }
+
+    #[test]
+    fn gives_every_socket_for_each_protocol() {
+        let addrs = vec![
+            "127.0.0.1".parse::<IpAddr>().unwrap(),
+            "192.168.0.1".parse::<IpAddr>().unwrap(),
+        ];
+        let ports: Vec<u16> = vec![53];
+
+        let sockets: Vec<_> = SocketIterator::new(&addrs, &ports)
+            .with_protocols(&[Tcp, Udp])
+            .collect();
+
+        assert_eq!(
+            sockets,
+            vec![
+                (SocketAddr::new(addrs[0], 53), Tcp),
+                (SocketAddr::new(addrs[0], 53), Udp),
+                (SocketAddr::new(addrs[1], 53), Tcp),
+                (SocketAddr::new(addrs[1], 53), Udp),
+            ]
+        );
+    }
 }
@@ -0,0 +1,141 @@
+//! Spreading the probes in flight across the hosts of a scan.
+//!
+//! The socket iterator already goes through every host for a port before
+//! the next port, but with a batch far larger than the number of hosts
+//! each host still gets a large share of the probes in flight, which can
+//! saturate it and trip its IDS while the batch could go to others. With a
+//! per-host cap, the sockets of hosts at their cap are set aside and the
+//! batch is filled with the sockets of other hosts meanwhile.
+use super::socket_iterator::SocketIterator;
+use crate::output::Protocol;
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
+
+/// How the probes in flight are spread across hosts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpreadStrategy {
+    /// Probes go out in the order of the socket iterator, a host getting as
+    /// many of the batch as its sockets come up.
+    #[default]
+    Batch,
+    /// At most this many probes in flight per host.
+    PerHost(NonZeroUsize),
+}
+
+/// The sockets of a scan in the order they can go out under a
+/// [`SpreadStrategy`].
+pub(super) struct Spread<'s> {
+    sockets: SocketIterator<'s>,
+    per_host: Option<usize>,
+    in_flight: HashMap<IpAddr, usize>,
+    /// Sockets of hosts that were at their cap, in iterator order.
+    deferred: VecDeque<(SocketAddr, Protocol)>,
+    /// How many sockets are set aside at most, so a single host doesn't
+    /// get its whole iterator buffered.
+    max_deferred: usize,
+}
+
+impl<'s> Spread<'s> {
+    pub(super) fn new(
+        sockets: SocketIterator<'s>,
+        strategy: SpreadStrategy,
+        batch_size: usize,
+    ) -> Self {
+        Self {
+            sockets,
+            per_host: match strategy {
+                SpreadStrategy::Batch => None,
+                SpreadStrategy::PerHost(per_host) => Some(per_host.get()),
+            },
+            in_flight: HashMap::new(),
+            deferred: VecDeque::new(),
+            max_deferred: batch_size.max(1),
+        }
+    }
+
+    /// The next socket to probe, `None` when every host with sockets left
+    /// is at its cap, or no sockets are left.
+    pub(super) fn next(&mut self) -> Option<(SocketAddr, Protocol)> {
+        let Some(per_host) = self.per_host else {
+            return self.sockets.next();
+        };
+        let ready = self.deferred.iter().position(|(socket, _)| {
+            self.in_flight.get(&socket.ip()).copied().unwrap_or(0) < per_host
+        });
+        let next = match ready {
+            Some(index) => self.deferred.remove(index),
+            None => loop {
+                if self.deferred.len() >= self.max_deferred {
+                    break None;
+                }
+                let (socket, protocol) = self.sockets.next()?;
+                if self.in_flight.get(&socket.ip()).copied().unwrap_or(0) < per_host {
+                    break Some((socket, protocol));
+                }
+                self.deferred.push_back((socket, protocol));
+            },
+        }?;
+        *self.in_flight.entry(next.0.ip()).or_default() += 1;
+        Some(next)
+    }
+
+    /// The sockets [`Spread::next`] didn't give yet, e.g. to resume a
+    /// cancelled scan from.
+    pub(super) fn remaining(self) -> impl Iterator<Item = (SocketAddr, Protocol)> + 's {
+        self.deferred.into_iter().chain(self.sockets)
+    }
+
+    /// Counts a probe of `ip` given by [`Spread::next`] as done.
+    pub(super) fn done(&mut self, ip: IpAddr) {
+        if let Some(in_flight) = self.in_flight.get_mut(&ip) {
+            *in_flight = in_flight.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Spread, SpreadStrategy};
+    use crate::scanner::socket_iterator::SocketIterator;
+    use std::net::IpAddr;
+    use std::num::NonZeroUsize;
+
+    fn next(spread: &mut Spread<'_>) -> Option<String> {
+        spread.next().map(|(socket, _)| socket.to_string())
+    }
+
+    #[test]
+    fn caps_probes_per_host() {
+        let one: IpAddr = "10.0.0.1".parse().unwrap();
+        let two: IpAddr = "10.0.0.2".parse().unwrap();
+        let ips = [one, two];
+        let ports = [1, 2, 3];
+        let strategy = SpreadStrategy::PerHost(NonZeroUsize::new(2).unwrap());
+        let mut spread = Spread::new(SocketIterator::new(&ips, &ports), strategy, 10);
+
+        assert_eq!(next(&mut spread).as_deref(), Some("10.0.0.1:1"));
+        assert_eq!(next(&mut spread).as_deref(), Some("10.0.0.2:1"));
+        assert_eq!(next(&mut spread).as_deref(), Some("10.0.0.1:2"));
+        assert_eq!(next(&mut spread).as_deref(), Some("10.0.0.2:2"));
+        assert_eq!(next(&mut spread), None);
+
+        spread.done(two);
+        assert_eq!(next(&mut spread).as_deref(), Some("10.0.0.2:3"));
+        assert_eq!(next(&mut spread), None);
+        spread.done(one);
+        assert_eq!(next(&mut spread).as_deref(), Some("10.0.0.1:3"));
+        spread.done(one);
+        assert_eq!(next(&mut spread), None);
+    }
+
+    #[test]
+    fn batches_follow_the_iterator() {
+        let ips = ["10.0.0.1".parse().unwrap()];
+        let ports = [1, 2, 3];
+        let mut spread = Spread::new(SocketIterator::new(&ips, &ports), SpreadStrategy::Batch, 10);
+
+        assert_eq!(std::iter::from_fn(|| spread.next()).count(), 3);
+    }
+}
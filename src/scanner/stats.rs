@@ -0,0 +1,199 @@
+//! Resource usage of a scan, reported with `--stats`.
+//!
+//! Counters are kept with atomics as probes run concurrently. CPU time and
+//! TCP retransmissions are read from the system before and after the scan,
+//! retransmissions being system-wide since the kernel doesn't track them
+//! per process.
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// What a scan used, see [`Scanner::stats`](super::Scanner::stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanStats {
+    /// Wall clock time of the scan.
+    pub elapsed: Duration,
+    /// CPU time used by the process during the scan, where measurable.
+    pub cpu_time: Option<Duration>,
+    /// Most probes in flight at the same time. Each holds a socket while it
+    /// waits for an answer, but probes waiting out a retry delay count too.
+    pub peak_in_flight: usize,
+    /// Connects and UDP probes started, retries included.
+    pub connection_attempts: u64,
+    /// Payload bytes sent, only UDP probes send any.
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// TCP segments retransmitted by the whole system during the scan,
+    /// where measurable, the scan's own and those of every other program.
+    pub system_retransmissions: Option<u64>,
+}
+
+impl fmt::Display for ScanStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.elapsed.as_secs_f64();
+        let rate = if secs > 0.0 {
+            self.connection_attempts as f64 / secs
+        } else {
+            0.0
+        };
+        writeln!(f, "Wall time:           {secs:.2}s")?;
+        match self.cpu_time {
+            Some(cpu) => writeln!(f, "CPU time:            {:.2}s", cpu.as_secs_f64())?,
+            None => writeln!(f, "CPU time:            unavailable")?,
+        }
+        writeln!(f, "Peak in flight:      {}", self.peak_in_flight)?;
+        writeln!(
+            f,
+            "Connection attempts: {} ({rate:.0}/s)",
+            self.connection_attempts
+        )?;
+        writeln!(
+            f,
+            "Bytes sent/received: {}/{}",
+            self.bytes_sent, self.bytes_received
+        )?;
+        match self.system_retransmissions {
+            Some(retransmissions) => {
+                write!(f, "Retransmissions:     {retransmissions} (system-wide)")
+            }
+            None => write!(f, "Retransmissions:     unavailable"),
+        }
+    }
+}
+
+/// Counts probes while the scan runs.
+#[derive(Debug, Default)]
+pub(super) struct StatsCollector {
+    attempts: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    peak_in_flight: AtomicUsize,
+}
+
+impl StatsCollector {
+    pub(super) fn attempt(&self) {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(super) fn received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(super) fn in_flight(&self, probes: usize) {
+        self.peak_in_flight.fetch_max(probes, Ordering::Relaxed);
+    }
+
+    /// Starts counting a new scan.
+    pub(super) fn start(&self) -> Snapshot {
+        self.attempts.store(0, Ordering::Relaxed);
+        self.bytes_sent.store(0, Ordering::Relaxed);
+        self.bytes_received.store(0, Ordering::Relaxed);
+        self.peak_in_flight.store(0, Ordering::Relaxed);
+        Snapshot {
+            started: Instant::now(),
+            cpu_time: cpu_time(),
+            retransmissions: retransmitted_segments(),
+        }
+    }
+
+    pub(super) fn finish(&self, start: &Snapshot) -> ScanStats {
+        ScanStats {
+            elapsed: start.started.elapsed(),
+            cpu_time: cpu_time()
+                .zip(start.cpu_time)
+                .map(|(now, then)| now.saturating_sub(then)),
+            peak_in_flight: self.peak_in_flight.load(Ordering::Relaxed),
+            connection_attempts: self.attempts.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            system_retransmissions: retransmitted_segments()
+                .zip(start.retransmissions)
+                .map(|(now, then)| now.saturating_sub(then)),
+        }
+    }
+}
+
+/// System readings taken when the scan starts.
+#[derive(Debug)]
+pub(super) struct Snapshot {
+    started: Instant,
+    cpu_time: Option<Duration>,
+    retransmissions: Option<u64>,
+}
+
+/// User and system CPU time used by the process so far.
+#[cfg(unix)]
+fn cpu_time() -> Option<Duration> {
+    // SAFETY: usage is plain data that getrusage fills in.
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    // SAFETY: usage is a valid rusage for getrusage to write to, and
+    // RUSAGE_SELF a valid target.
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    let to_duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+    Some(to_duration(usage.ru_utime) + to_duration(usage.ru_stime))
+}
+
+#[cfg(not(unix))]
+fn cpu_time() -> Option<Duration> {
+    None
+}
+
+/// TCP segments retransmitted by the system, from /proc/net/snmp on Linux.
+fn retransmitted_segments() -> Option<u64> {
+    parse_retransmitted_segments(&std::fs::read_to_string("/proc/net/snmp").ok()?)
+}
+
+/// /proc/net/snmp has a header line and a value line per protocol.
+fn parse_retransmitted_segments(snmp: &str) -> Option<u64> {
+    let mut tcp = snmp.lines().filter(|line| line.starts_with("Tcp:"));
+    let (header, values) = (tcp.next()?, tcp.next()?);
+    let index = header
+        .split_whitespace()
+        .position(|field| field == "RetransSegs")?;
+    values.split_whitespace().nth(index)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_retransmitted_segments, StatsCollector};
+
+    #[test]
+    fn parses_retransmissions_from_snmp() {
+        let snmp = "Ip: Forwarding DefaultTTL\nIp: 1 64\n\
+                    Tcp: RtoAlgorithm RtoMin ActiveOpens RetransSegs InErrs\n\
+                    Tcp: 1 200 4242 17 0\n";
+
+        assert_eq!(parse_retransmitted_segments(snmp), Some(17));
+        assert_eq!(
+            parse_retransmitted_segments("Ip: Forwarding\nIp: 1\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn counts_a_scan() {
+        let collector = StatsCollector::default();
+        collector.attempt();
+        let start = collector.start();
+        collector.attempt();
+        collector.attempt();
+        collector.sent(10);
+        collector.received(4);
+        collector.in_flight(3);
+        collector.in_flight(2);
+
+        let stats = collector.finish(&start);
+        assert_eq!(stats.connection_attempts, 2);
+        assert_eq!((stats.bytes_sent, stats.bytes_received), (10, 4));
+        assert_eq!(stats.peak_in_flight, 3);
+    }
+}
@@ -0,0 +1,68 @@
+//! Open ports handed out as they are found, rather than once the scan is
+//! over.
+use super::Scanner;
+
+use futures::channel::mpsc::{self, UnboundedSender};
+use futures::stream::{self, Stream, StreamExt};
+use std::net::SocketAddr;
+
+impl Scanner {
+    /// Like [`Scanner::run`], yielding every open port as soon as it is
+    /// found. The scan runs while the stream is polled and the stream ends
+    /// with it, so consumers can start working on a host, e.g. running nmap
+    /// on it, while the others are still being scanned.
+    ///
+    /// ```rust
+    /// # use async_std::task::block_on;
+    /// # use futures::StreamExt;
+    /// # use rustscan::input::ScanOrder;
+    /// # use rustscan::port_strategy::PortStrategy;
+    /// # use rustscan::scanner::Scanner;
+    /// # use std::net::TcpListener;
+    /// # use std::time::Duration;
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let port = listener.local_addr().unwrap().port();
+    /// let strategy = PortStrategy::pick(&None, Some(vec![port]), ScanOrder::Serial);
+    /// let scanner = Scanner::new(
+    ///     &["127.0.0.1".parse().unwrap()],
+    ///     10,
+    ///     Duration::from_millis(500),
+    ///     1,
+    ///     true,
+    ///     strategy,
+    ///     true,
+    ///     vec![],
+    ///     false,
+    /// );
+    /// let open: Vec<_> = block_on(scanner.run_streaming().collect());
+    /// assert_eq!(open, vec![listener.local_addr().unwrap()]);
+    /// ```
+    pub fn run_streaming(&self) -> impl Stream<Item = SocketAddr> + '_ {
+        let (sender, receiver) = mpsc::unbounded();
+        let scan = stream::once(async move {
+            self.set_stream(Some(sender));
+            self.run().await;
+            // Dropping the sender ends the stream.
+            self.set_stream(None);
+            None
+        })
+        .filter_map(|open: Option<SocketAddr>| async move { open });
+        stream::select(receiver, scan)
+    }
+
+    /// Hands `socket` to the stream of [`Scanner::run_streaming`], when one
+    /// is being polled.
+    pub(super) fn stream_open(&self, socket: SocketAddr) {
+        if let Ok(stream) = self.stream.lock() {
+            if let Some(sender) = stream.as_ref() {
+                let _ = sender.unbounded_send(socket);
+            }
+        }
+    }
+
+    fn set_stream(&self, sender: Option<UnboundedSender<SocketAddr>>) {
+        if let Ok(mut stream) = self.stream.lock() {
+            *stream = sender;
+        }
+    }
+}
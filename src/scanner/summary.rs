@@ -0,0 +1,176 @@
+//! What a scan found, in a few numbers.
+use super::{ErrorSummary, ProbeError, Scanner};
+use crate::address::SampleCoverage;
+use crate::benchmark::ScanMetrics;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// The outcome of a scan, see [`Scanner::summary`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanSummary {
+    pub hosts_scanned: usize,
+    pub hosts_with_open_ports: usize,
+    pub open_ports: usize,
+    /// Sockets probed, retries not included.
+    pub probes: u64,
+    /// Probes answered with a reset.
+    pub closed: u64,
+    /// Probes that got no answer before the timeout.
+    pub timed_out: u64,
+    /// Probes that failed in any other way, locally or on the way.
+    pub errors: u64,
+    pub duration: Duration,
+    /// The share of the target space scanned with `--sample`.
+    pub coverage: Option<SampleCoverage>,
+}
+
+impl ScanSummary {
+    /// Sums up a scan of `hosts_scanned` hosts that took `duration`, found
+    /// `open` and ran into `errors`.
+    pub fn new(
+        hosts_scanned: usize,
+        open: &[SocketAddr],
+        errors: &ErrorSummary,
+        duration: Duration,
+    ) -> Self {
+        let closed = errors.count(ProbeError::ConnectionRefused);
+        let timed_out = errors.count(ProbeError::TimedOut);
+        Self {
+            hosts_scanned,
+            hosts_with_open_ports: open
+                .iter()
+                .map(SocketAddr::ip)
+                .collect::<HashSet<_>>()
+                .len(),
+            open_ports: open.len(),
+            probes: open.len() as u64 + errors.total(),
+            closed,
+            timed_out,
+            errors: errors.total() - closed - timed_out,
+            duration,
+            coverage: None,
+        }
+    }
+
+    /// Sockets probed per second.
+    pub fn rate(&self) -> f64 {
+        let secs = self.duration.as_secs_f64();
+        if secs > 0.0 {
+            self.probes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for ScanSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Scanned {} hosts in {:.2}s ({:.0} probes/s): {} open ports on {} hosts, {} closed, {} timed out, {} errors.",
+            self.hosts_scanned,
+            self.duration.as_secs_f64(),
+            self.rate(),
+            self.open_ports,
+            self.hosts_with_open_ports,
+            self.closed,
+            self.timed_out,
+            self.errors
+        )?;
+        if let Some(coverage) = self.coverage {
+            write!(f, " Sampled {coverage}.")?;
+        }
+        Ok(())
+    }
+}
+
+impl Scanner {
+    /// The summary of the last scan, also sent to the result sink once the
+    /// scan is over.
+    pub fn summary(&self) -> ScanSummary {
+        self.last_summary
+            .lock()
+            .map(|summary| *summary)
+            .unwrap_or_default()
+    }
+
+    /// The timing and error counts of the last scan. Add the runtime of
+    /// other phases with [`ScanMetrics::with_phases`].
+    pub fn metrics(&self) -> ScanMetrics {
+        ScanMetrics::new(&self.summary(), &self.error_summary())
+    }
+
+    /// Tells in the summary that the targets are a sample of a larger
+    /// target space, see `--sample`.
+    #[must_use]
+    pub fn with_sample_coverage(mut self, coverage: Option<SampleCoverage>) -> Self {
+        self.sample_coverage = coverage;
+        self
+    }
+
+    /// Sums up a scan that found `open` in `duration` and sends the summary
+    /// to the sink.
+    pub(super) fn summarize(&self, open: &[SocketAddr], duration: Duration) {
+        let summary = ScanSummary {
+            coverage: self.sample_coverage,
+            ..ScanSummary::new(self.ips.len(), open, &self.error_summary(), duration)
+        };
+        if let Ok(mut last) = self.last_summary.lock() {
+            *last = summary;
+        }
+        self.sink.emit(&super::ScanEvent::Finished(summary));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScanSummary;
+    use crate::address::SampleCoverage;
+    use crate::scanner::ErrorSummary;
+    use std::io;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    #[test]
+    fn sums_up_a_scan() {
+        let ip = "10.0.0.1".parse().unwrap();
+        let mut errors = ErrorSummary::default();
+        errors.record(ip, &io::Error::from(io::ErrorKind::ConnectionRefused));
+        errors.record(ip, &io::Error::from(io::ErrorKind::ConnectionRefused));
+        errors.record(ip, &io::Error::from(io::ErrorKind::TimedOut));
+        errors.record(ip, &io::Error::other("boom"));
+        let open: Vec<SocketAddr> = vec![
+            "10.0.0.1:22".parse().unwrap(),
+            "10.0.0.1:80".parse().unwrap(),
+            "10.0.0.2:80".parse().unwrap(),
+            "10.0.0.3:80".parse().unwrap(),
+        ];
+
+        let summary = ScanSummary::new(5, &open, &errors, Duration::from_secs(2));
+        assert_eq!(summary.hosts_with_open_ports, 3);
+        assert_eq!(summary.probes, 8);
+        assert_eq!(
+            (summary.closed, summary.timed_out, summary.errors),
+            (2, 1, 1)
+        );
+        assert_eq!(
+            summary.to_string(),
+            "Scanned 5 hosts in 2.00s (4 probes/s): 4 open ports on 3 hosts, 2 closed, 1 timed out, 1 errors."
+        );
+
+        let sampled = ScanSummary {
+            coverage: Some(SampleCoverage {
+                sampled: 5,
+                population: 1000,
+                seed: 7,
+            }),
+            ..summary
+        };
+        assert!(sampled
+            .to_string()
+            .ends_with(" Sampled 5 of 1000 addresses (0.5000%, --seed 7)."));
+    }
+}
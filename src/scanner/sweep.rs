@@ -0,0 +1,177 @@
+//! Two-phase scanning: a fast sweep with a short timeout and a single try,
+//! then a second look with the configured timeout and tries at everything
+//! that looked open or timed out on a host that otherwise answers.
+//!
+//! Most ports of a typical host are closed and refuse connections well
+//! within the short timeout, so the expensive settings are only paid for
+//! the few ports where they make a difference.
+use super::{estimate, ProbeError, ScanResult, Scanner};
+use crate::output::Protocol;
+
+use futures::future;
+use futures::stream::{self, StreamExt};
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU8;
+use std::time::{Duration, Instant};
+
+/// The sweep uses this fraction of the configured timeout.
+const SWEEP_TIMEOUT_DIVISOR: u32 = 4;
+
+/// The sweep timeout is never lower than this, unless the configured
+/// timeout already is.
+const MIN_SWEEP_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Timed out ports of a host are only looked at again when they are at
+/// most this fraction of its scanned ports. Above it the host is filtered
+/// as a whole and a second look would just repeat the scan.
+const MAX_AMBIGUOUS_RATIO: f64 = 0.5;
+
+impl Scanner {
+    /// Scans in two phases, see the module documentation. Open ports are
+    /// only printed once confirmed by the second phase, which also settles
+    /// the state of the ports it looks at again. Once cancelled, the second
+    /// phase looks at no more ports, the others keeping their first state.
+    ///
+    /// Scans probing UDP go through a single [`run`](Scanner::run), since
+    /// closed UDP ports time out just like filtered ones.
+    pub async fn sweep(&mut self) -> ScanResult {
+        if self.scans(Protocol::Udp) {
+            return self.run().await;
+        }
+
+        let started = Instant::now();
+        let (timeout, tries) = (self.timeout, self.tries);
+        self.timeout = (timeout / SWEEP_TIMEOUT_DIVISOR)
+            .max(MIN_SWEEP_TIMEOUT)
+            .min(timeout);
+        self.tries = NonZeroU8::MIN;
+        self.sweeping = true;
+        let mut swept = self.run().await;
+        self.timeout = timeout;
+        self.tries = tries;
+        self.sweeping = false;
+
+        let timed_out = self
+            .timed_out
+            .lock()
+            .map(|mut timed_out| std::mem::take(&mut *timed_out))
+            .unwrap_or_default();
+        let ports = self.ports().len();
+        let ambiguous = ambiguous_sockets(timed_out, |ip| {
+            self.target_ports.get(&ip).map_or(ports, Vec::len)
+        });
+        let open: HashSet<SocketAddr> = swept.open().into_iter().collect();
+        debug!(
+            "Sweep found {} open and {} ambiguous sockets",
+            open.len(),
+            ambiguous.len()
+        );
+
+        self.control.extend(estimate(
+            1,
+            open.len() + ambiguous.len(),
+            self.batch_size,
+            self.timeout,
+            self.tries.get(),
+            self.retry,
+        ));
+        let looked_at: Vec<(SocketAddr, _, _)> =
+            stream::iter(open.iter().copied().chain(ambiguous))
+                .take_while(|_| future::ready(!self.cancel.is_cancelled()))
+                .map(|socket| self.scan_socket(socket, Protocol::Tcp))
+                .buffer_unordered(self.batch_size.max(1))
+                .inspect(|(socket, _, result)| {
+                    // Sockets open in the first look were already counted as
+                    // such, the others replace their timeout of the first look.
+                    if open.contains(socket) {
+                        self.control.record(None);
+                        return;
+                    }
+                    if let Ok(mut errors) = self.errors.lock() {
+                        errors.forget(socket.ip(), ProbeError::TimedOut);
+                    }
+                    self.control.record(result.as_ref().ok().copied());
+                })
+                .collect()
+                .await;
+        for (socket, protocol, result) in looked_at {
+            if let Some(state) = super::port_state(protocol, &result) {
+                swept.record(socket, protocol, state);
+            }
+        }
+        for ip in &self.ips {
+            self.hook_host_complete(*ip, &swept);
+        }
+        self.summarize(&swept.open(), started.elapsed());
+        self.hook_scan_complete(&swept);
+        swept
+    }
+}
+
+/// The timed out sockets of hosts where timeouts are the exception, hosts
+/// being scanned on `scanned_ports` of their ports.
+fn ambiguous_sockets(
+    timed_out: Vec<SocketAddr>,
+    scanned_ports: impl Fn(IpAddr) -> usize,
+) -> Vec<SocketAddr> {
+    let mut per_host: HashMap<IpAddr, usize> = HashMap::new();
+    for socket in &timed_out {
+        *per_host.entry(socket.ip()).or_default() += 1;
+    }
+
+    timed_out
+        .into_iter()
+        .filter(|socket| {
+            per_host[&socket.ip()] as f64 <= scanned_ports(socket.ip()) as f64 * MAX_AMBIGUOUS_RATIO
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ambiguous_sockets;
+    use crate::input::{PortRange, ScanOrder};
+    use crate::port_strategy::PortStrategy;
+    use crate::scanner::Scanner;
+    use async_std::task::block_on;
+    use std::net::{IpAddr, SocketAddr};
+    use std::time::Duration;
+
+    #[test]
+    fn filtered_hosts_are_not_ambiguous() {
+        let sometimes: IpAddr = "10.0.0.1".parse().unwrap();
+        let filtered: IpAddr = "10.0.0.2".parse().unwrap();
+        let mut timed_out = vec![SocketAddr::new(sometimes, 22)];
+        timed_out.extend((1..=8).map(|port| SocketAddr::new(filtered, port)));
+
+        assert_eq!(
+            ambiguous_sockets(timed_out, |_| 10),
+            vec![SocketAddr::new(sometimes, 22)]
+        );
+    }
+
+    #[test]
+    fn sweep_runs() {
+        let addrs = vec!["127.0.0.1".parse::<IpAddr>().unwrap()];
+        let range = PortRange {
+            start: 1,
+            end: 1_000,
+        };
+        let strategy = PortStrategy::pick(&Some(range), None, ScanOrder::Random);
+        let mut scanner = Scanner::new(
+            &addrs,
+            10,
+            Duration::from_millis(100),
+            1,
+            true,
+            strategy,
+            true,
+            vec![9000],
+            false,
+        );
+        block_on(scanner.sweep());
+        assert!(!scanner.sweeping);
+    }
+}
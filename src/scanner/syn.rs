@@ -0,0 +1,744 @@
+//! Half-open TCP scanning through a raw socket, like `nmap -sS`.
+//!
+//! A SYN is sent to each port and the answer read off the raw socket: a
+//! SYN-ACK means the port is open, a reset that it is closed. The handshake
+//! is never finished, the local kernel resets the connection it knows
+//! nothing about, so no connection reaches the application on the other
+//! side. Only IPv4 is supported, IPv6 targets are connect scanned.
+//!
+//! SYNs ask for TCP timestamps, and the TSvals of the SYN-ACKs of a host
+//! give an estimate of its uptime, see [`uptime`](super::uptime). They can
+//! also ask for TCP extensions, see [`TcpExtensions`], which the SYN-ACKs
+//! of open ports then agree to or not.
+//!
+//! With decoys, every probe goes out along with a SYN from each decoy
+//! address, so the target sees the scan come from all of them at once.
+//! Those packets carry an IP header we build, as the kernel would put our
+//! own address in it.
+use super::uptime::{estimate_uptime, TimestampSample, UptimeEstimate};
+use super::Scanner;
+use crate::input::ScanType;
+
+use futures::channel::oneshot;
+use log::debug;
+use rand::RngExt;
+use socket2::{SockAddr, Socket};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often the receiving thread checks whether the scan is over.
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// TCP flags.
+const SYN: u8 = 0x02;
+const RST: u8 = 0x04;
+const ACK: u8 = 0x10;
+const ECE: u8 = 0x40;
+const CWR: u8 = 0x80;
+
+/// The window advertised in SYNs, small like most scanners use.
+const WINDOW: u16 = 1024;
+
+/// The MSS option sent with SYNs, as hosts answering a SYN without options
+/// stand out.
+const MSS_OPTION: [u8; 4] = [2, 4, 0x05, 0xb4];
+
+/// TTL of the IP header of packets with decoys.
+const TTL: u8 = 64;
+
+/// Kind and length of the TCP timestamps option.
+const TIMESTAMP_KIND: u8 = 8;
+const TIMESTAMP_LEN: u8 = 10;
+
+/// Kind of the Multipath TCP option, and the subtype of MP_CAPABLE.
+const MPTCP_KIND: u8 = 30;
+const MP_CAPABLE: u8 = 0;
+
+/// MP_CAPABLE of a SYN asking for MPTCP version 1 with HMAC-SHA256, as
+/// Linux sends it.
+const MP_CAPABLE_OPTION: [u8; 4] = [MPTCP_KIND, 4, (MP_CAPABLE << 4) | 1, 0x01];
+
+/// Probes waiting for an answer, by target.
+type Pending = Mutex<HashMap<SocketAddrV4, oneshot::Sender<bool>>>;
+
+/// The first and the latest TSval each host answered with.
+type Timestamps = Mutex<HashMap<Ipv4Addr, Vec<TimestampSample>>>;
+
+/// The extensions the SYN-ACK of each open port agreed to.
+type Agreed = Mutex<HashMap<SocketAddrV4, TcpExtensions>>;
+
+/// TCP extensions a SYN asks for, or a SYN-ACK agrees to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TcpExtensions {
+    /// Multipath TCP, asked for with the MP_CAPABLE option.
+    pub mptcp: bool,
+    /// Explicit Congestion Notification, asked for with the ECE and CWR
+    /// flags and agreed to with ECE alone.
+    pub ecn: bool,
+}
+
+/// Sends SYNs through a raw IPv4 TCP socket and matches the answers read
+/// off it by a background thread to the probes waiting for them.
+pub struct SynProber {
+    socket: Socket,
+    source_port: u16,
+    sequence: u32,
+    decoys: Vec<Ipv4Addr>,
+    extensions: TcpExtensions,
+    pending: Arc<Pending>,
+    timestamps: Arc<Timestamps>,
+    agreed: Arc<Agreed>,
+    /// The local address packets to every target leave from, see
+    /// [`SynProber::with_source_ip`].
+    source_ip: Option<Ipv4Addr>,
+    /// The local address packets to each target leave from.
+    sources: Mutex<HashMap<Ipv4Addr, Ipv4Addr>>,
+    stop: Arc<AtomicBool>,
+    receiver: Option<JoinHandle<()>>,
+}
+
+impl SynProber {
+    /// Starts reading answers off `socket`, a raw IPv4 TCP socket as
+    /// [`RawSockets`](crate::privsep::RawSockets) opens. SYNs are sent from
+    /// `source_port`, or a random high port without one.
+    pub fn new(socket: Socket, source_port: Option<u16>) -> io::Result<Self> {
+        let mut rng = rand::rng();
+        let source_port = source_port.unwrap_or_else(|| rng.random_range(40_000..60_000));
+        let sequence: u32 = rng.random();
+        let pending: Arc<Pending> = Arc::default();
+        let timestamps: Arc<Timestamps> = Arc::default();
+        let agreed: Arc<Agreed> = Arc::default();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let incoming = socket.try_clone()?;
+        incoming.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+        let receiver = {
+            let pending = Arc::clone(&pending);
+            let timestamps = Arc::clone(&timestamps);
+            let agreed = Arc::clone(&agreed);
+            let stop = Arc::clone(&stop);
+            thread::Builder::new()
+                .name("syn-receiver".to_owned())
+                .spawn(move || {
+                    receive(
+                        &incoming,
+                        &pending,
+                        &timestamps,
+                        &agreed,
+                        &stop,
+                        source_port,
+                        sequence,
+                    );
+                })?
+        };
+
+        Ok(Self {
+            socket,
+            source_port,
+            sequence,
+            decoys: Vec::new(),
+            extensions: TcpExtensions::default(),
+            pending,
+            timestamps,
+            agreed,
+            source_ip: None,
+            sources: Mutex::default(),
+            stop,
+            receiver: Some(receiver),
+        })
+    }
+
+    /// Sends a SYN from each of `decoys` along with every probe. Answers to
+    /// them go to the decoys, only our own SYNs are waited for.
+    pub fn with_decoys(mut self, decoys: Vec<Ipv4Addr>) -> io::Result<Self> {
+        if !decoys.is_empty() {
+            self.socket.set_header_included_v4(true)?;
+        }
+        self.decoys = decoys;
+        Ok(self)
+    }
+
+    /// Sends SYNs from `source_ip`, like `--source-ip`, instead of the
+    /// address the kernel routes each target from.
+    pub fn with_source_ip(mut self, source_ip: Option<Ipv4Addr>) -> io::Result<Self> {
+        if let Some(source_ip) = source_ip {
+            self.socket
+                .bind(&SockAddr::from(SocketAddr::from((source_ip, 0))))?;
+        }
+        self.source_ip = source_ip;
+        Ok(self)
+    }
+
+    /// Asks every port for `extensions`, see [`SynProber::agreed`].
+    #[must_use]
+    pub fn with_extensions(mut self, extensions: TcpExtensions) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// The extensions SYNs ask for.
+    pub fn extensions(&self) -> TcpExtensions {
+        self.extensions
+    }
+
+    /// The extensions the SYN-ACK of each open port agreed to, out of the
+    /// ones asked for.
+    pub fn agreed(&self) -> BTreeMap<SocketAddr, TcpExtensions> {
+        let Ok(agreed) = self.agreed.lock() else {
+            return BTreeMap::new();
+        };
+        agreed
+            .iter()
+            .map(|(target, agreed)| {
+                let agreed = TcpExtensions {
+                    mptcp: self.extensions.mptcp && agreed.mptcp,
+                    ecn: self.extensions.ecn && agreed.ecn,
+                };
+                (SocketAddr::V4(*target), agreed)
+            })
+            .collect()
+    }
+
+    /// Sends a SYN to `target` and waits up to `timeout` for the answer.
+    /// Answers the way a connect would: `Ok` for a SYN-ACK, a refused
+    /// connection for a reset and a timeout when nothing came back.
+    pub async fn probe(&self, target: SocketAddrV4, timeout: Duration) -> io::Result<()> {
+        let source = self.source_for(*target.ip())?;
+        let segment = syn_packet(
+            source,
+            self.source_port,
+            target,
+            self.sequence,
+            self.extensions,
+        );
+        let packet = if self.decoys.is_empty() {
+            segment
+        } else {
+            ip_packet(source, *target.ip(), &segment)
+        };
+
+        let (sender, answer) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(target, sender);
+        }
+        // Raw sockets take the port from the packet, not the address.
+        let destination = SockAddr::from(SocketAddr::new(IpAddr::V4(*target.ip()), 0));
+        for decoy in &self.decoys {
+            let segment = syn_packet(
+                *decoy,
+                self.source_port,
+                target,
+                self.sequence,
+                self.extensions,
+            );
+            let decoy_packet = ip_packet(*decoy, *target.ip(), &segment);
+            if let Err(e) = self.socket.send_to(&decoy_packet, &destination) {
+                debug!("Could not send a decoy SYN from {decoy} to {target}: {e}");
+            }
+        }
+        let answer = match self.socket.send_to(&packet, &destination) {
+            Ok(_) => async_std::future::timeout(timeout, answer).await,
+            Err(e) => {
+                self.forget(target);
+                return Err(e);
+            }
+        };
+        self.forget(target);
+
+        match answer {
+            Ok(Ok(true)) => Ok(()),
+            Ok(Ok(false)) => Err(io::Error::from(io::ErrorKind::ConnectionRefused)),
+            _ => Err(io::Error::from(io::ErrorKind::TimedOut)),
+        }
+    }
+
+    /// The uptime of the hosts whose SYN-ACKs carried timestamps far enough
+    /// apart to estimate it, see [`estimate_uptime`].
+    pub fn uptimes(&self) -> BTreeMap<IpAddr, UptimeEstimate> {
+        let Ok(timestamps) = self.timestamps.lock() else {
+            return BTreeMap::new();
+        };
+        timestamps
+            .iter()
+            .filter_map(|(ip, samples)| Some((IpAddr::V4(*ip), estimate_uptime(samples)?)))
+            .collect()
+    }
+
+    fn forget(&self, target: SocketAddrV4) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&target);
+        }
+    }
+
+    /// The local address the kernel routes packets to `target` from, which
+    /// the TCP checksum covers. Connecting a UDP socket sends nothing.
+    fn source_for(&self, target: Ipv4Addr) -> io::Result<Ipv4Addr> {
+        if let Some(source) = self.source_ip {
+            return Ok(source);
+        }
+        if let Some(source) = self
+            .sources
+            .lock()
+            .ok()
+            .and_then(|sources| sources.get(&target).copied())
+        {
+            return Ok(source);
+        }
+
+        let route = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        route.connect((target, 9))?;
+        let IpAddr::V4(source) = route.local_addr()?.ip() else {
+            return Err(io::Error::other(format!("no IPv4 route to {target}")));
+        };
+        if let Ok(mut sources) = self.sources.lock() {
+            sources.insert(target, source);
+        }
+        Ok(source)
+    }
+}
+
+impl fmt::Debug for SynProber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SynProber")
+            .field("source_port", &self.source_port)
+            .field("decoys", &self.decoys)
+            .field("extensions", &self.extensions)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for SynProber {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(receiver) = self.receiver.take() {
+            let _ = receiver.join();
+        }
+    }
+}
+
+impl Scanner {
+    /// Probes TCP ports of IPv4 targets with SYNs sent through `prober`
+    /// instead of connecting to them.
+    #[must_use]
+    pub fn with_syn_prober(mut self, prober: SynProber) -> Self {
+        self.syn = Some(prober);
+        self
+    }
+
+    /// How the scan probes TCP ports.
+    pub fn scan_type(&self) -> ScanType {
+        if self.syn.is_some() {
+            ScanType::Syn
+        } else {
+            ScanType::Connect
+        }
+    }
+
+    /// The uptime of the hosts estimated from their TCP timestamps, only
+    /// known for open ports found by a SYN scan, see
+    /// [`SynProber::uptimes`].
+    pub fn uptimes(&self) -> BTreeMap<IpAddr, UptimeEstimate> {
+        self.syn
+            .as_ref()
+            .map(SynProber::uptimes)
+            .unwrap_or_default()
+    }
+
+    /// The TCP extensions the SYN scan asks for, none without one.
+    pub fn tcp_extensions(&self) -> TcpExtensions {
+        self.syn
+            .as_ref()
+            .map(SynProber::extensions)
+            .unwrap_or_default()
+    }
+
+    /// The TCP extensions each open port found by a SYN scan agreed to,
+    /// see [`SynProber::agreed`].
+    pub fn agreed_tcp_extensions(&self) -> BTreeMap<SocketAddr, TcpExtensions> {
+        self.syn.as_ref().map(SynProber::agreed).unwrap_or_default()
+    }
+}
+
+/// What a packet read off the raw socket says about one of our SYNs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Answer {
+    target: SocketAddrV4,
+    open: bool,
+    /// The TSval of the timestamps option, when the target sent one.
+    tsval: Option<u32>,
+    /// The extensions the target agreed to.
+    extensions: TcpExtensions,
+}
+
+/// Reads packets off `socket` until `stop` is set, handing the answers to
+/// our SYNs to the probes waiting for them and keeping the timestamps and
+/// extensions of the SYN-ACKs.
+fn receive(
+    socket: &Socket,
+    pending: &Pending,
+    timestamps: &Timestamps,
+    agreed: &Agreed,
+    stop: &AtomicBool,
+    port: u16,
+    sequence: u32,
+) {
+    let mut buffer = [0u8; 1500];
+    let mut socket = socket;
+    while !stop.load(Ordering::Relaxed) {
+        let size = match socket.read(&mut buffer) {
+            Ok(size) => size,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                        | io::ErrorKind::Interrupted
+                ) =>
+            {
+                continue
+            }
+            Err(e) => {
+                debug!("Stopped reading answers to SYNs: {e}");
+                return;
+            }
+        };
+
+        let Some(answer) = parse_answer(&buffer[..size], port, sequence) else {
+            continue;
+        };
+        if let (true, Some(tsval), Ok(mut timestamps)) =
+            (answer.open, answer.tsval, timestamps.lock())
+        {
+            let sample = TimestampSample {
+                received: Instant::now(),
+                tsval,
+            };
+            let samples = timestamps.entry(*answer.target.ip()).or_default();
+            if samples.len() < 2 {
+                samples.push(sample);
+            } else {
+                samples[1] = sample;
+            }
+        }
+        if let (true, Ok(mut agreed)) = (answer.open, agreed.lock()) {
+            agreed.insert(answer.target, answer.extensions);
+        }
+        let waiting = pending
+            .lock()
+            .ok()
+            .and_then(|mut pending| pending.remove(&answer.target));
+        if let Some(waiting) = waiting {
+            let _ = waiting.send(answer.open);
+        }
+    }
+}
+
+/// A SYN from `source_port` to `target`, with its checksum. It carries the
+/// MSS and timestamps options, the TSval being the sequence number as the
+/// answers only echo it, and the options of the `extensions` asked for.
+fn syn_packet(
+    source: Ipv4Addr,
+    source_port: u16,
+    target: SocketAddrV4,
+    sequence: u32,
+    extensions: TcpExtensions,
+) -> Vec<u8> {
+    let mut packet = vec![0u8; 36];
+    packet[0..2].copy_from_slice(&source_port.to_be_bytes());
+    packet[2..4].copy_from_slice(&target.port().to_be_bytes());
+    packet[4..8].copy_from_slice(&sequence.to_be_bytes());
+    packet[13] = if extensions.ecn { SYN | ECE | CWR } else { SYN };
+    packet[14..16].copy_from_slice(&WINDOW.to_be_bytes());
+    packet[20..24].copy_from_slice(&MSS_OPTION);
+    // Two NOPs align the timestamps on a word, TSecr stays 0 in a SYN.
+    packet[24..28].copy_from_slice(&[1, 1, TIMESTAMP_KIND, TIMESTAMP_LEN]);
+    packet[28..32].copy_from_slice(&sequence.to_be_bytes());
+    if extensions.mptcp {
+        packet.extend_from_slice(&MP_CAPABLE_OPTION);
+    }
+    // The header length in 32 bit words, with the options.
+    packet[12] = ((packet.len() / 4) as u8) << 4;
+    let checksum = checksum(source, *target.ip(), &packet);
+    packet[16..18].copy_from_slice(&checksum.to_be_bytes());
+    packet
+}
+
+/// `segment` from `source` to `destination` behind an IPv4 header, for a
+/// socket that doesn't add one. The kernel fills in the ID.
+fn ip_packet(source: Ipv4Addr, destination: Ipv4Addr, segment: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0u8; 20];
+    packet[0] = 0x45;
+    packet[2..4].copy_from_slice(&((20 + segment.len()) as u16).to_be_bytes());
+    packet[8] = TTL;
+    packet[9] = 6;
+    packet[12..16].copy_from_slice(&source.octets());
+    packet[16..20].copy_from_slice(&destination.octets());
+    let checksum = internet_checksum(&packet);
+    packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+    packet.extend_from_slice(segment);
+    packet
+}
+
+/// The TCP checksum of `segment`, which covers a pseudo header of the
+/// addresses too.
+fn checksum(source: Ipv4Addr, destination: Ipv4Addr, segment: &[u8]) -> u16 {
+    let mut data = Vec::with_capacity(12 + segment.len());
+    data.extend_from_slice(&source.octets());
+    data.extend_from_slice(&destination.octets());
+    data.extend_from_slice(&[0, 6]);
+    data.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+    data.extend_from_slice(segment);
+    internet_checksum(&data)
+}
+
+/// The one's complement of the one's complement sum of `data`, as IP and
+/// TCP headers carry it.
+fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| {
+            u32::from(u16::from_be_bytes([
+                pair[0],
+                pair.get(1).copied().unwrap_or(0),
+            ]))
+        })
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// The target and whether its port is open, when `packet`, an IPv4 packet
+/// as raw sockets read them, answers one of our SYNs.
+fn parse_answer(packet: &[u8], port: u16, sequence: u32) -> Option<Answer> {
+    let header = packet.get(..20)?;
+    if header[0] >> 4 != 4 || header[9] != 6 {
+        return None;
+    }
+    let source = Ipv4Addr::new(header[12], header[13], header[14], header[15]);
+    let segment = packet.get(usize::from(header[0] & 0x0f) * 4..)?;
+    let tcp = segment.get(..20)?;
+
+    let source_port = u16::from_be_bytes([tcp[0], tcp[1]]);
+    let destination_port = u16::from_be_bytes([tcp[2], tcp[3]]);
+    let acknowledged = u32::from_be_bytes([tcp[8], tcp[9], tcp[10], tcp[11]]);
+    if destination_port != port || acknowledged != sequence.wrapping_add(1) {
+        return None;
+    }
+
+    let flags = tcp[13];
+    let open = if flags & (SYN | ACK) == SYN | ACK {
+        true
+    } else if flags & RST != 0 {
+        false
+    } else {
+        return None;
+    };
+    let options = segment
+        .get(20..usize::from(tcp[12] >> 4) * 4)
+        .unwrap_or_default();
+    Some(Answer {
+        target: SocketAddrV4::new(source, source_port),
+        open,
+        tsval: tsval(options),
+        extensions: TcpExtensions {
+            mptcp: open && mp_capable(options),
+            ecn: open && flags & (ECE | CWR) == ECE,
+        },
+    })
+}
+
+/// The option of `kind` among the TCP `options`, its kind and length
+/// included.
+fn find_option(mut options: &[u8], kind: u8) -> Option<&[u8]> {
+    loop {
+        match *options {
+            [] | [0, ..] => return None,
+            [1, ref rest @ ..] => options = rest,
+            [found, len, ..] if len >= 2 => {
+                let len = usize::from(len);
+                if found == kind {
+                    return options.get(..len);
+                }
+                options = options.get(len..)?;
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// The TSval of the timestamps option among the TCP `options`.
+fn tsval(options: &[u8]) -> Option<u32> {
+    match *find_option(options, TIMESTAMP_KIND)? {
+        [_, TIMESTAMP_LEN, a, b, c, d, ..] => Some(u32::from_be_bytes([a, b, c, d])),
+        _ => None,
+    }
+}
+
+/// Whether the TCP `options` hold MP_CAPABLE, which a SYN-ACK only sends
+/// when the target agrees to Multipath TCP.
+fn mp_capable(options: &[u8]) -> bool {
+    matches!(
+        find_option(options, MPTCP_KIND),
+        Some(&[_, _, subtype, ..]) if subtype >> 4 == MP_CAPABLE
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        checksum, internet_checksum, ip_packet, mp_capable, parse_answer, syn_packet, tsval,
+        Answer, TcpExtensions, ACK, CWR, ECE, RST, SYN,
+    };
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    const SOURCE: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 2);
+    const TARGET: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 10);
+
+    /// An IPv4 packet from the target answering a SYN from port 40000.
+    fn answer(flags: u8, acknowledged: u32) -> Vec<u8> {
+        let mut packet = vec![0u8; 40];
+        packet[0] = 0x45;
+        packet[9] = 6;
+        packet[12..16].copy_from_slice(&TARGET.octets());
+        packet[16..20].copy_from_slice(&SOURCE.octets());
+        packet[20..22].copy_from_slice(&443u16.to_be_bytes());
+        packet[22..24].copy_from_slice(&40_000u16.to_be_bytes());
+        packet[28..32].copy_from_slice(&acknowledged.to_be_bytes());
+        packet[33] = flags;
+        packet
+    }
+
+    #[test]
+    fn builds_syns_with_valid_checksums() {
+        let target = SocketAddrV4::new(TARGET, 443);
+        let packet = syn_packet(SOURCE, 40_000, target, 7, TcpExtensions::default());
+
+        assert_eq!(&packet[0..4], &[0x9c, 0x40, 0x01, 0xbb]);
+        assert_eq!(packet[12] >> 4, 9);
+        assert_eq!(packet[13], SYN);
+        assert_eq!(tsval(&packet[20..]), Some(7));
+        assert!(!mp_capable(&packet[20..]));
+        assert_eq!(checksum(SOURCE, TARGET, &packet), 0);
+    }
+
+    #[test]
+    fn asks_for_mptcp() {
+        let target = SocketAddrV4::new(TARGET, 443);
+        let extensions = TcpExtensions {
+            mptcp: true,
+            ..TcpExtensions::default()
+        };
+        let packet = syn_packet(SOURCE, 40_000, target, 7, extensions);
+
+        assert_eq!(packet.len(), 40);
+        assert_eq!(packet[13], SYN);
+        assert_eq!(packet[12] >> 4, 10);
+        assert_eq!(tsval(&packet[20..]), Some(7));
+        assert!(mp_capable(&packet[20..]));
+        assert_eq!(checksum(SOURCE, TARGET, &packet), 0);
+    }
+
+    #[test]
+    fn asks_for_ecn() {
+        let target = SocketAddrV4::new(TARGET, 443);
+        let extensions = TcpExtensions {
+            ecn: true,
+            ..TcpExtensions::default()
+        };
+        let packet = syn_packet(SOURCE, 40_000, target, 7, extensions);
+
+        assert_eq!(packet.len(), 36);
+        assert_eq!(packet[13], SYN | ECE | CWR);
+        assert_eq!(checksum(SOURCE, TARGET, &packet), 0);
+    }
+
+    #[test]
+    fn wraps_decoy_syns_in_ip_headers() {
+        let decoy = Ipv4Addr::new(203, 0, 113, 7);
+        let target = SocketAddrV4::new(TARGET, 443);
+        let segment = syn_packet(decoy, 53, target, 7, TcpExtensions::default());
+        let packet = ip_packet(decoy, TARGET, &segment);
+
+        assert_eq!(packet.len(), 20 + segment.len());
+        assert_eq!(&packet[2..4], &56u16.to_be_bytes());
+        assert_eq!(&packet[12..16], &decoy.octets());
+        assert_eq!(&packet[16..20], &TARGET.octets());
+        assert_eq!(internet_checksum(&packet[..20]), 0);
+        assert_eq!(&packet[20..22], &53u16.to_be_bytes());
+        assert_eq!(checksum(decoy, TARGET, &packet[20..]), 0);
+    }
+
+    #[test]
+    fn matches_answers_to_our_syns() {
+        let target = SocketAddrV4::new(TARGET, 443);
+        assert_eq!(
+            parse_answer(&answer(SYN | ACK, 8), 40_000, 7),
+            Some(Answer {
+                target,
+                open: true,
+                tsval: None,
+                extensions: TcpExtensions::default(),
+            })
+        );
+        assert_eq!(
+            parse_answer(&answer(RST | ACK, 8), 40_000, 7),
+            Some(Answer {
+                target,
+                open: false,
+                tsval: None,
+                extensions: TcpExtensions::default(),
+            })
+        );
+        assert_eq!(parse_answer(&answer(SYN | ACK, 9), 40_000, 7), None);
+        assert_eq!(parse_answer(&answer(SYN | ACK, 8), 40_001, 7), None);
+        assert_eq!(parse_answer(&answer(ACK, 8), 40_000, 7), None);
+        assert_eq!(parse_answer(&[0x45; 10], 40_000, 7), None);
+    }
+
+    #[test]
+    fn reads_timestamps_off_syn_acks() {
+        let mut packet = answer(SYN | ACK, 8);
+        packet[32] = 8 << 4;
+        packet.extend_from_slice(&[2, 4, 0x05, 0xb4, 1, 1, 8, 10, 0, 0, 0x30, 0x39]);
+        packet.extend_from_slice(&[0, 0, 0, 7]);
+
+        let answer = parse_answer(&packet, 40_000, 7).unwrap();
+        assert_eq!(answer.tsval, Some(12_345));
+
+        assert_eq!(tsval(&[1, 1, 3, 3, 7]), None);
+        assert_eq!(tsval(&[3, 3, 7, 8, 10, 0, 0, 0, 1, 0, 0, 0, 0]), Some(1));
+        assert_eq!(tsval(&[3, 0, 8, 10]), None);
+        assert_eq!(tsval(&[0, 8, 10, 0, 0, 0, 1, 0, 0, 0, 0]), None);
+    }
+
+    #[test]
+    fn reads_extensions_off_syn_acks() {
+        let mut packet = answer(SYN | ACK, 8);
+        packet[32] = 8 << 4;
+        // MP_CAPABLE of a SYN-ACK carries the key of the target.
+        packet.extend_from_slice(&[30, 12, 0x01, 0x01, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let answer = parse_answer(&packet, 40_000, 7).unwrap();
+        assert!(answer.extensions.mptcp);
+
+        // ECE and CWR together ask for ECN, only ECE agrees to it.
+        let agreed = parse_answer(&answer(SYN | ACK | ECE, 8), 40_000, 7).unwrap();
+        assert!(agreed.extensions.ecn);
+        let echoed = parse_answer(&answer(SYN | ACK | ECE | CWR, 8), 40_000, 7).unwrap();
+        assert!(!echoed.extensions.ecn);
+        let reset = parse_answer(&answer(RST | ACK | ECE, 8), 40_000, 7).unwrap();
+        assert!(!reset.extensions.ecn);
+
+        // ADD_ADDR is a Multipath TCP option too, but not MP_CAPABLE.
+        assert!(!mp_capable(&[1, 1, 30, 8, 0x30, 0, 10, 0, 0, 1]));
+        assert!(!mp_capable(&[2, 4, 0x05, 0xb4]));
+    }
+}
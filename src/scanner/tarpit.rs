@@ -0,0 +1,277 @@
+//! Detection of tarpits, CDN frontends and other hosts whose open ports
+//! cannot be taken at face value.
+//!
+//! Two heuristics are used:
+//!   - nearly every scanned port is open, which real hosts never do but
+//!     tarpits and some CDN or firewall frontends do.
+//!   - the host has many open ports, and a sample of them spread across the
+//!     range all hold connections open without a word, or all greet with
+//!     the same bytes. Ports whose clients speak first, like HTTP or TLS,
+//!     are silent on any host and are left out of the sample.
+use super::{ProbeError, Scanner};
+
+use async_std::io::{self, ReadExt};
+use futures::stream::{self, StreamExt};
+use log::debug;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+/// Fraction of the scanned ports that has to be open for a host to be
+/// flagged as answering on every port.
+const ALL_OPEN_RATIO: f64 = 0.9;
+
+/// Scans of fewer ports than this are never flagged as all-open, a host
+/// with 3 out of 3 scanned ports open is perfectly normal.
+const ALL_OPEN_MIN_SCANNED: usize = 100;
+
+/// Hosts need at least this many open ports before they are checked for
+/// silence, real hosts with that many services usually have one that
+/// greets its clients.
+const SILENT_MIN_OPEN: usize = 20;
+
+/// How many open ports of a host are checked for silence. Hosts with fewer
+/// open ports left to sample are not flagged.
+const SILENT_SAMPLE: usize = 5;
+
+/// How many bytes of the greeting of a sampled port are compared.
+const GREETING_LEN: usize = 64;
+
+/// Ports of well-known services whose clients send the first bytes, so the
+/// server stays silent after accepting a connection: HTTP, TLS, DNS, SMB,
+/// RDP and most databases.
+const CLIENT_FIRST_PORTS: [u16; 30] = [
+    53, 80, 88, 135, 139, 443, 445, 465, 636, 993, 995, 1433, 1521, 2049, 3000, 3389, 5000, 5432,
+    5985, 5986, 6379, 8000, 8008, 8080, 8081, 8443, 8888, 9000, 9200, 27017,
+];
+
+/// Why a host was flagged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TarpitReason {
+    /// Nearly every scanned port accepted a connection.
+    AllPortsOpen { open: usize, scanned: usize },
+    /// Many ports are open, but sampled connections were held open without
+    /// receiving any data.
+    Silent { open: usize, sampled: usize },
+    /// Many ports are open, and sampled connections all received the same
+    /// greeting.
+    IdenticalGreetings { open: usize, sampled: usize },
+}
+
+/// A host that is likely a tarpit or a frontend answering on every port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tarpit {
+    pub ip: IpAddr,
+    pub reason: TarpitReason,
+}
+
+impl fmt::Display for Tarpit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            TarpitReason::AllPortsOpen { open, scanned } => write!(
+                f,
+                "{} answered on {open} of {scanned} scanned ports, it is likely a tarpit or a CDN/firewall frontend",
+                self.ip
+            ),
+            TarpitReason::Silent { open, sampled } => write!(
+                f,
+                "{} has {open} open ports but {sampled} sampled ports held the connection without sending any data, it is likely a tarpit",
+                self.ip
+            ),
+            TarpitReason::IdenticalGreetings { open, sampled } => write!(
+                f,
+                "{} has {open} open ports and {sampled} sampled ports sent the same greeting, it is likely a tarpit",
+                self.ip
+            ),
+        }
+    }
+}
+
+impl Scanner {
+    /// Looks for tarpits among the hosts with open ports. The all-open check
+    /// only looks at the results, the silence check reconnects to a few open
+    /// ports of hosts with many of them and waits up to the timeout for a
+    /// greeting.
+    pub async fn detect_tarpits(&self, open_sockets: &[SocketAddr]) -> Vec<Tarpit> {
+        let scanned = self.ports().len();
+        let mut per_host: BTreeMap<IpAddr, Vec<SocketAddr>> = BTreeMap::new();
+        for socket in open_sockets {
+            per_host.entry(socket.ip()).or_default().push(*socket);
+        }
+
+        let mut tarpits: Vec<Tarpit> = Vec::new();
+        let mut silent_candidates: Vec<(IpAddr, Vec<SocketAddr>)> = Vec::new();
+        for (ip, sockets) in per_host {
+            if let Some(reason) = all_ports_open(sockets.len(), scanned) {
+                tarpits.push(Tarpit { ip, reason });
+            } else if sockets.len() >= SILENT_MIN_OPEN {
+                silent_candidates.push((ip, sockets));
+            }
+        }
+
+        let silent: Vec<Tarpit> = stream::iter(silent_candidates)
+            .map(|(ip, sockets)| async move {
+                let mut greetings = Vec::new();
+                for socket in spread_sample(&sockets) {
+                    let greeting = self.greeting(socket).await;
+                    if greeting == Greeting::Closed {
+                        return None;
+                    }
+                    greetings.push(greeting);
+                }
+                let reason = verdict(sockets.len(), &greetings)?;
+                Some(Tarpit { ip, reason })
+            })
+            .buffer_unordered((self.batch_size / SILENT_SAMPLE).max(1))
+            .filter_map(futures::future::ready)
+            .collect()
+            .await;
+
+        tarpits.extend(silent);
+        tarpits
+    }
+
+    /// What `socket` does after accepting a connection, waiting up to the
+    /// timeout for its first bytes.
+    async fn greeting(&self, socket: SocketAddr) -> Greeting {
+        let Ok(mut stream) = self.connect(socket).await else {
+            // A port that no longer accepts connections is not behaving
+            // like a tarpit.
+            return Greeting::Closed;
+        };
+
+        let mut buf = [0u8; GREETING_LEN];
+        match io::timeout(self.timeout, stream.read(&mut buf)).await {
+            Ok(0) => Greeting::Closed,
+            Ok(read) => Greeting::Data(buf[..read].to_vec()),
+            Err(e) if ProbeError::classify(&e) == ProbeError::TimedOut => {
+                debug!("No data from {socket}: {e}");
+                Greeting::Held
+            }
+            Err(_) => Greeting::Closed,
+        }
+    }
+}
+
+/// What an open port did after accepting a connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Greeting {
+    /// Held the connection open without sending anything.
+    Held,
+    /// Sent these first bytes.
+    Data(Vec<u8>),
+    /// Refused or closed the connection without sending anything.
+    Closed,
+}
+
+/// Up to [`SILENT_SAMPLE`] open ports of a host, spread evenly across its
+/// open ports rather than the lowest ones, leaving out
+/// [`CLIENT_FIRST_PORTS`].
+fn spread_sample(sockets: &[SocketAddr]) -> Vec<SocketAddr> {
+    let mut candidates: Vec<SocketAddr> = sockets
+        .iter()
+        .copied()
+        .filter(|socket| !CLIENT_FIRST_PORTS.contains(&socket.port()))
+        .collect();
+    candidates.sort_by_key(SocketAddr::port);
+    if candidates.len() <= SILENT_SAMPLE {
+        return candidates;
+    }
+    (0..SILENT_SAMPLE)
+        .map(|index| candidates[index * candidates.len() / SILENT_SAMPLE])
+        .collect()
+}
+
+/// The silence heuristic, on the greetings of the sampled ports of a host
+/// with `open` open ports. A full sample is needed, and every port has to
+/// hold the connection without a word or send the very same greeting.
+fn verdict(open: usize, greetings: &[Greeting]) -> Option<TarpitReason> {
+    let sampled = greetings.len();
+    if sampled < SILENT_SAMPLE {
+        return None;
+    }
+    if greetings.iter().all(|greeting| *greeting == Greeting::Held) {
+        return Some(TarpitReason::Silent { open, sampled });
+    }
+    let Greeting::Data(first) = &greetings[0] else {
+        return None;
+    };
+    greetings
+        .iter()
+        .all(|greeting| matches!(greeting, Greeting::Data(data) if data == first))
+        .then_some(TarpitReason::IdenticalGreetings { open, sampled })
+}
+
+/// The all-open heuristic, on the number of open and scanned ports of a host.
+pub(super) fn all_ports_open(open: usize, scanned: usize) -> Option<TarpitReason> {
+    if scanned < ALL_OPEN_MIN_SCANNED {
+        return None;
+    }
+
+    let ratio = open as f64 / scanned as f64;
+    (ratio >= ALL_OPEN_RATIO).then_some(TarpitReason::AllPortsOpen { open, scanned })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{all_ports_open, spread_sample, verdict, Greeting, TarpitReason};
+    use std::net::SocketAddr;
+
+    #[test]
+    fn flags_hosts_with_nearly_every_port_open() {
+        assert_eq!(
+            all_ports_open(950, 1_000),
+            Some(TarpitReason::AllPortsOpen {
+                open: 950,
+                scanned: 1_000
+            })
+        );
+        assert_eq!(all_ports_open(10, 1_000), None);
+        assert_eq!(all_ports_open(3, 3), None);
+    }
+
+    #[test]
+    fn samples_across_the_range_without_client_first_ports() {
+        let sockets: Vec<SocketAddr> = [80, 443, 8080]
+            .iter()
+            .chain(&[
+                1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 20_000, 30_000, 40_000, 50_000, 60_000,
+            ])
+            .map(|port| SocketAddr::from(([10, 0, 0, 1], *port)))
+            .collect();
+
+        let ports: Vec<u16> = spread_sample(&sockets)
+            .iter()
+            .map(SocketAddr::port)
+            .collect();
+        assert_eq!(ports, vec![1, 4, 7, 10, 40_000]);
+    }
+
+    #[test]
+    fn needs_a_full_sample_of_held_or_identical_greetings() {
+        let held = vec![Greeting::Held; 5];
+        assert_eq!(
+            verdict(30, &held),
+            Some(TarpitReason::Silent {
+                open: 30,
+                sampled: 5
+            })
+        );
+        assert_eq!(verdict(30, &held[..4]), None);
+
+        let same = vec![Greeting::Data(b"220 ready\r\n".to_vec()); 5];
+        assert_eq!(
+            verdict(30, &same),
+            Some(TarpitReason::IdenticalGreetings {
+                open: 30,
+                sampled: 5
+            })
+        );
+
+        let mut mixed = same;
+        mixed[2] = Greeting::Data(b"SSH-2.0-OpenSSH_9.6\r\n".to_vec());
+        assert_eq!(verdict(30, &mixed), None);
+        mixed[2] = Greeting::Held;
+        assert_eq!(verdict(30, &mixed), None);
+    }
+}
@@ -0,0 +1,53 @@
+//! Transport-layer capabilities of open ports, checked after the scan.
+use super::{connector, Scanner};
+
+use async_std::io;
+use futures::stream::{self, StreamExt};
+use log::debug;
+use std::future::Future;
+use std::net::SocketAddr;
+
+impl Scanner {
+    /// Requests a TCP Fast Open cookie from every open socket, on sockets
+    /// of its own rather than the scan's, and returns the ones whose hosts
+    /// handed one out. Only supported on Linux.
+    pub async fn fast_open_support(&self, open_sockets: &[SocketAddr]) -> Vec<SocketAddr> {
+        self.reconnect_filter(open_sockets, "TCP Fast Open", |socket| async move {
+            connector::request_fast_open_cookie(socket, &self.socket_options).await?;
+            connector::has_fast_open_cookie(socket)
+        })
+        .await
+    }
+
+    /// Runs `check` against every socket, `batch_size` at a time, and keeps
+    /// the sockets it returned `true` for.
+    async fn reconnect_filter<F, Fut>(
+        &self,
+        open_sockets: &[SocketAddr],
+        name: &str,
+        check: F,
+    ) -> Vec<SocketAddr>
+    where
+        F: Fn(SocketAddr) -> Fut,
+        Fut: Future<Output = io::Result<bool>>,
+    {
+        stream::iter(open_sockets.iter().copied())
+            .map(|socket| {
+                let check = check(socket);
+                async move {
+                    match io::timeout(self.timeout, check).await {
+                        Ok(true) => Some(socket),
+                        Ok(false) => None,
+                        Err(e) => {
+                            debug!("Could not check {name} support of {socket}: {e}");
+                            None
+                        }
+                    }
+                }
+            })
+            .buffer_unordered(self.batch_size.max(1))
+            .filter_map(futures::future::ready)
+            .collect()
+            .await
+    }
+}
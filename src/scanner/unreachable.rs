@@ -0,0 +1,229 @@
+//! Telling closed UDP ports from filtered ones by the ICMP destination
+//! unreachable messages they draw, like nmap does.
+//!
+//! An open UDP port that doesn't answer the payload and a port behind a
+//! firewall dropping everything look the same: nothing comes back. A
+//! closed port though makes its host send back a port unreachable, and a
+//! firewall rejecting rather than dropping sends another unreachable code.
+//! These are read off a raw ICMP socket by a background thread, so a UDP
+//! probe that timed out can still tell whether its port is closed or
+//! filtered, and is `open|filtered` only when nothing at all came back.
+//! Only IPv4 is supported.
+use super::Scanner;
+
+use log::debug;
+use socket2::Socket;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::io::{self, Read};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the listening thread checks whether the scan is over.
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+
+const DESTINATION_UNREACHABLE: u8 = 3;
+const PORT_UNREACHABLE: u8 = 3;
+/// Codes nmap takes as a firewall in the way: host, protocol, network and
+/// host administratively prohibited and communication prohibited.
+const FILTERED_CODES: [u8; 5] = [1, 2, 9, 10, 13];
+const UDP: u8 = 17;
+
+/// Unreachable messages received for UDP probes, by target.
+type Received = Mutex<HashMap<SocketAddrV4, Unreachable>>;
+
+/// What an ICMP destination unreachable says about a UDP port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unreachable {
+    /// Port unreachable: nothing listens on the port.
+    Closed,
+    /// Another code, sent by a firewall rejecting the probe.
+    Filtered,
+}
+
+impl Unreachable {
+    fn of(code: u8) -> Option<Self> {
+        match code {
+            PORT_UNREACHABLE => Some(Unreachable::Closed),
+            code if FILTERED_CODES.contains(&code) => Some(Unreachable::Filtered),
+            _ => None,
+        }
+    }
+
+    /// The error a probe of a port ends with, the one a connect would have
+    /// failed with.
+    fn into_error(self) -> io::Error {
+        match self {
+            Unreachable::Closed => io::Error::from(io::ErrorKind::ConnectionRefused),
+            Unreachable::Filtered => io::Error::from(io::ErrorKind::HostUnreachable),
+        }
+    }
+}
+
+/// Reads ICMP destination unreachable messages off a raw socket in the
+/// background and keeps the ones about UDP probes.
+pub struct IcmpListener {
+    received: Arc<Received>,
+    stop: Arc<AtomicBool>,
+    receiver: Option<JoinHandle<()>>,
+}
+
+impl IcmpListener {
+    /// Starts reading `socket`, a raw IPv4 ICMP socket as
+    /// [`RawSockets`](crate::privsep::RawSockets) opens.
+    pub fn new(socket: Socket) -> io::Result<Self> {
+        let received: Arc<Received> = Arc::default();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        socket.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+        let receiver = {
+            let received = Arc::clone(&received);
+            let stop = Arc::clone(&stop);
+            thread::Builder::new()
+                .name("icmp-listener".to_owned())
+                .spawn(move || receive(&socket, &received, &stop))?
+        };
+
+        Ok(Self {
+            received,
+            stop,
+            receiver: Some(receiver),
+        })
+    }
+
+    /// The unreachable message received about `target`, if any, forgetting
+    /// it so a later probe of the same port starts afresh.
+    pub fn take(&self, target: SocketAddr) -> Option<Unreachable> {
+        let SocketAddr::V4(target) = target else {
+            return None;
+        };
+        self.received.lock().ok()?.remove(&target)
+    }
+}
+
+impl fmt::Debug for IcmpListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IcmpListener").finish_non_exhaustive()
+    }
+}
+
+impl Drop for IcmpListener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(receiver) = self.receiver.take() {
+            let _ = receiver.join();
+        }
+    }
+}
+
+impl Scanner {
+    /// Tells closed and filtered UDP ports apart by the ICMP unreachable
+    /// messages `listener` reads, instead of reporting every port that
+    /// didn't answer `open|filtered`.
+    #[must_use]
+    pub fn with_icmp_listener(mut self, listener: IcmpListener) -> Self {
+        self.icmp = Some(listener);
+        self
+    }
+
+    /// The error a UDP probe of `socket` that got no answer ends with: the
+    /// one of an unreachable message received about it, or a timeout.
+    pub(super) fn udp_silence(&self, socket: SocketAddr) -> Option<io::Error> {
+        let unreachable = self.icmp.as_ref()?.take(socket)?;
+        debug!("{socket} is unreachable: {unreachable:?}");
+        Some(unreachable.into_error())
+    }
+}
+
+/// Reads packets off `socket` until `stop` is set, keeping the unreachable
+/// messages about UDP datagrams.
+fn receive(socket: &Socket, received: &Received, stop: &AtomicBool) {
+    let mut buffer = [0u8; 1500];
+    let mut socket = socket;
+    while !stop.load(Ordering::Relaxed) {
+        let size = match socket.read(&mut buffer) {
+            Ok(size) => size,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                        | io::ErrorKind::Interrupted
+                ) =>
+            {
+                continue
+            }
+            Err(e) => {
+                debug!("Stopped reading ICMP messages: {e}");
+                return;
+            }
+        };
+
+        if let Some((target, unreachable)) = parse_unreachable(&buffer[..size]) {
+            if let Ok(mut received) = received.lock() {
+                received.insert(target, unreachable);
+            }
+        }
+    }
+}
+
+/// The UDP target and verdict of an IPv4 packet holding an ICMP destination
+/// unreachable, which quotes the header of the datagram that caused it.
+fn parse_unreachable(packet: &[u8]) -> Option<(SocketAddrV4, Unreachable)> {
+    let icmp = packet.get(usize::from(packet.first()? & 0x0f) * 4..)?;
+    if *icmp.first()? != DESTINATION_UNREACHABLE {
+        return None;
+    }
+    let unreachable = Unreachable::of(*icmp.get(1)?)?;
+
+    let quoted = icmp.get(8..)?;
+    if *quoted.get(9)? != UDP {
+        return None;
+    }
+    let destination: [u8; 4] = quoted.get(16..20)?.try_into().ok()?;
+    let udp = quoted.get(usize::from(quoted[0] & 0x0f) * 4..)?;
+    let port = u16::from_be_bytes(udp.get(2..4)?.try_into().ok()?);
+    Some((
+        SocketAddrV4::new(Ipv4Addr::from(destination), port),
+        unreachable,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_unreachable, Unreachable};
+    use std::net::SocketAddrV4;
+
+    /// An IPv4 packet holding an ICMP destination unreachable with `code`
+    /// about a datagram of `protocol` to 10.0.0.2:161.
+    fn unreachable(code: u8, protocol: u8) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 56, 0, 0, 0, 0, 64, 1, 0, 0];
+        packet.extend_from_slice(&[10, 0, 0, 2, 10, 0, 0, 1]);
+        packet.extend_from_slice(&[3, code, 0, 0, 0, 0, 0, 0]);
+        packet.extend_from_slice(&[0x45, 0, 0, 28, 0, 0, 0, 0, 64, protocol, 0, 0]);
+        packet.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        packet.extend_from_slice(&[0xc3, 0x50, 0, 161, 0, 8, 0, 0]);
+        packet
+    }
+
+    #[test]
+    fn reads_the_quoted_datagram() {
+        let target: SocketAddrV4 = "10.0.0.2:161".parse().unwrap();
+
+        assert_eq!(
+            parse_unreachable(&unreachable(3, 17)),
+            Some((target, Unreachable::Closed))
+        );
+        assert_eq!(
+            parse_unreachable(&unreachable(13, 17)),
+            Some((target, Unreachable::Filtered))
+        );
+        assert_eq!(parse_unreachable(&unreachable(4, 17)), None);
+        assert_eq!(parse_unreachable(&unreachable(3, 6)), None);
+        assert_eq!(parse_unreachable(&unreachable(3, 17)[..40]), None);
+    }
+}
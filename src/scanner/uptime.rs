@@ -0,0 +1,132 @@
+//! Host uptime estimation from TCP timestamps (RFC 7323).
+//!
+//! Many TCP stacks start their timestamp clock at boot and tick it at a
+//! fixed rate. Sampling the TSval of a host a few times lets us work out
+//! the tick rate, and dividing the last TSval by that rate gives an uptime
+//! estimate, the same way `nmap -O` does.
+//!
+//! The estimate is informational only: modern Linux adds a random offset per
+//! connection, and the 32 bit counter wraps after a few weeks at 1000 Hz.
+use std::time::{Duration, Instant};
+
+/// Tick rates seen in the wild. Measured rates are snapped to the closest
+/// one since network jitter makes the raw measurement imprecise.
+const KNOWN_FREQUENCIES: [u32; 6] = [1, 2, 10, 100, 250, 1000];
+
+/// Measured rates further than this fraction away from every known rate
+/// are treated as randomized or broken clocks.
+const MAX_FREQUENCY_DEVIATION: f64 = 0.2;
+
+/// A TSval seen in a response, and when we received it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampSample {
+    pub received: Instant,
+    pub tsval: u32,
+}
+
+/// The estimated tick rate of a host's TCP timestamp clock and the uptime
+/// it implies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UptimeEstimate {
+    pub frequency_hz: u32,
+    pub uptime: Duration,
+}
+
+/// Estimates the uptime of a host from at least two timestamp samples taken
+/// some time apart. Returns `None` when the samples are too close together,
+/// the clock did not move, or its rate matches no known TCP stack.
+///
+/// ```rust
+/// # use rustscan::scanner::uptime::{estimate_uptime, TimestampSample};
+/// # use std::time::{Duration, Instant};
+/// let first = Instant::now();
+/// let samples = [
+///     TimestampSample { received: first, tsval: 86_400_000 },
+///     TimestampSample { received: first + Duration::from_millis(500), tsval: 86_400_500 },
+/// ];
+/// let estimate = estimate_uptime(&samples).unwrap();
+/// assert_eq!(estimate.frequency_hz, 1000);
+/// assert_eq!(estimate.uptime.as_secs(), 86_400);
+/// ```
+pub fn estimate_uptime(samples: &[TimestampSample]) -> Option<UptimeEstimate> {
+    let first = samples.iter().min_by_key(|sample| sample.received)?;
+    let last = samples.iter().max_by_key(|sample| sample.received)?;
+
+    let elapsed = last.received.checked_duration_since(first.received)?;
+    if elapsed < Duration::from_millis(100) {
+        return None;
+    }
+
+    // The counter may have wrapped between the samples.
+    let ticks = last.tsval.wrapping_sub(first.tsval);
+    if ticks == 0 || ticks > u32::MAX / 2 {
+        return None;
+    }
+
+    let measured = f64::from(ticks) / elapsed.as_secs_f64();
+    let frequency_hz = KNOWN_FREQUENCIES.iter().copied().min_by(|a, b| {
+        (f64::from(*a) - measured)
+            .abs()
+            .total_cmp(&(f64::from(*b) - measured).abs())
+    })?;
+
+    let deviation = (measured - f64::from(frequency_hz)).abs() / f64::from(frequency_hz);
+    if deviation > MAX_FREQUENCY_DEVIATION {
+        return None;
+    }
+
+    Some(UptimeEstimate {
+        frequency_hz,
+        uptime: Duration::from_secs(u64::from(last.tsval / frequency_hz)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{estimate_uptime, TimestampSample};
+    use std::time::{Duration, Instant};
+
+    fn samples(hz: u32, start_tsval: u32, elapsed: Duration) -> [TimestampSample; 2] {
+        let start = Instant::now();
+        let ticks = (elapsed.as_secs_f64() * f64::from(hz)) as u32;
+        [
+            TimestampSample {
+                received: start,
+                tsval: start_tsval,
+            },
+            TimestampSample {
+                received: start + elapsed,
+                tsval: start_tsval.wrapping_add(ticks),
+            },
+        ]
+    }
+
+    #[test]
+    fn estimates_common_frequencies() {
+        let estimate = estimate_uptime(&samples(100, 360_000, Duration::from_secs(1))).unwrap();
+        assert_eq!(estimate.frequency_hz, 100);
+        assert_eq!(estimate.uptime, Duration::from_secs(3_601));
+
+        let estimate = estimate_uptime(&samples(250, 250_000, Duration::from_secs(2))).unwrap();
+        assert_eq!(estimate.frequency_hz, 250);
+        assert_eq!(estimate.uptime, Duration::from_secs(1_002));
+    }
+
+    #[test]
+    fn handles_wrapped_counter() {
+        let estimate =
+            estimate_uptime(&samples(1000, u32::MAX - 100, Duration::from_secs(1))).unwrap();
+        assert_eq!(estimate.frequency_hz, 1000);
+    }
+
+    #[test]
+    fn rejects_unusable_samples() {
+        // Too close together to measure anything.
+        assert!(estimate_uptime(&samples(1000, 1_000, Duration::from_millis(10))).is_none());
+        // The clock did not move.
+        assert!(estimate_uptime(&samples(0, 1_000, Duration::from_secs(1))).is_none());
+        // A rate nowhere near a known TCP stack.
+        assert!(estimate_uptime(&samples(600, 1_000, Duration::from_secs(1))).is_none());
+        assert!(estimate_uptime(&[]).is_none());
+    }
+}
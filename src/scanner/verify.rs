@@ -0,0 +1,92 @@
+//! Verification of open ports before they are reported.
+//!
+//! High batch sizes on flaky networks can produce phantom open ports, e.g.
+//! when a middlebox completes handshakes it can't keep up with. Every open
+//! port is connected to once more, patiently and with few connections at
+//! a time, and dropped when it doesn't answer.
+use super::connector;
+use super::Scanner;
+use crate::output::Protocol;
+
+use async_std::io;
+use futures::stream::{self, StreamExt};
+use log::debug;
+use std::net::SocketAddr;
+
+/// The verification timeout is the scan timeout times this.
+const VERIFY_TIMEOUT_MULTIPLIER: u32 = 2;
+
+/// At most this many verification connections are open at the same time.
+const VERIFY_CONCURRENCY: usize = 256;
+
+impl Scanner {
+    /// Reconnects once to every socket in `open_sockets` and returns the
+    /// ones that answered. UDP results are returned as they are, there's no
+    /// connection to verify them with, so scans over both TCP and UDP should
+    /// only pass the sockets open over TCP.
+    ///
+    /// Once the scan is cancelled, the sockets not reconnected to yet are
+    /// returned as they are.
+    pub async fn verify(&self, open_sockets: &[SocketAddr]) -> Vec<SocketAddr> {
+        if !self.scans(Protocol::Tcp) {
+            return open_sockets.to_vec();
+        }
+
+        let timeout = self.timeout * VERIFY_TIMEOUT_MULTIPLIER;
+        let concurrency = self.batch_size.clamp(1, VERIFY_CONCURRENCY);
+        stream::iter(open_sockets.iter().copied())
+            .map(|socket| async move {
+                if self.cancel.is_cancelled() {
+                    return Some(socket);
+                }
+                let result = io::timeout(
+                    timeout,
+                    connector::connect_tcp(self.scoped(socket), &self.socket_options),
+                )
+                .await;
+                if let Err(e) = &result {
+                    debug!("Dropping {socket}, it failed verification: {e}");
+                }
+                result.ok().map(|_| socket)
+            })
+            .buffer_unordered(concurrency)
+            .filter_map(|socket| async move { socket })
+            .collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::{PortRange, ScanOrder};
+    use crate::port_strategy::PortStrategy;
+    use crate::scanner::Scanner;
+    use async_std::task::block_on;
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::Duration;
+
+    #[test]
+    fn drops_ports_that_no_longer_answer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        let closed: SocketAddr = {
+            let gone = TcpListener::bind("127.0.0.1:0").unwrap();
+            gone.local_addr().unwrap()
+        };
+
+        let range = PortRange { start: 1, end: 1 };
+        let scanner = Scanner::new(
+            &[open.ip()],
+            10,
+            Duration::from_millis(500),
+            1,
+            true,
+            PortStrategy::pick(&Some(range), None, ScanOrder::Serial),
+            true,
+            vec![],
+            false,
+        );
+
+        assert_eq!(block_on(scanner.verify(&[open, closed])), vec![open]);
+    }
+}
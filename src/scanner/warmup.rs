@@ -0,0 +1,90 @@
+//! Warm-up phase that tunes the batch size before a large scan.
+//!
+//! A sample of the sockets that are about to be scanned is probed at
+//! increasing concurrency, binary searching for the highest batch size that
+//! neither runs out of local resources nor loses open ports compared to a
+//! conservative baseline.
+use super::{ProbeError, Scanner, SocketIterator};
+use crate::output::Protocol;
+
+use async_std::io;
+use futures::stream::{self, StreamExt};
+use log::debug;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+
+/// Concurrency of the baseline round, and the smallest batch size the
+/// warm-up will ever pick.
+const MIN_BATCH_SIZE: usize = 100;
+
+impl Scanner {
+    /// Finds the highest error-free batch size for this machine and network
+    /// pair, between a small baseline and the configured batch size, and
+    /// uses it for the following [`Scanner::run`].
+    ///
+    /// Scans that have fewer sockets than one batch are left untouched.
+    /// TCP only, scans probing UDP keep the configured batch size.
+    pub async fn warm_up(&mut self) -> usize {
+        if self.scans(Protocol::Udp) || self.batch_size <= MIN_BATCH_SIZE {
+            return self.batch_size;
+        }
+
+        let ports = self.ports();
+        let sample: Vec<SocketAddr> = SocketIterator::new(&self.ips, &ports)
+            .map(|(socket, _)| socket)
+            .take(self.batch_size)
+            .collect();
+        if sample.len() < self.batch_size {
+            debug!("Skipping warm-up, the scan fits in a single batch");
+            return self.batch_size;
+        }
+
+        let mut low = MIN_BATCH_SIZE;
+        let mut high = self.batch_size;
+        let Some(baseline) = self.probe_sample(&sample[..low]).await else {
+            debug!("Warm-up baseline ran out of resources, using batch size {low}");
+            self.batch_size = low;
+            return low;
+        };
+
+        while low < high {
+            let candidate = low + (high - low).div_ceil(2);
+            match self.probe_sample(&sample[..candidate]).await {
+                Some(open) if baseline.is_subset(&open) => {
+                    debug!("Warm-up batch size {candidate} is error free");
+                    low = candidate;
+                }
+                _ => {
+                    debug!("Warm-up batch size {candidate} produced errors");
+                    high = candidate - 1;
+                }
+            }
+        }
+
+        self.batch_size = low;
+        low
+    }
+
+    /// Connects to every socket of the sample at once. Returns the open
+    /// sockets, or `None` when a probe failed because we ran out of local
+    /// resources such as file descriptors or ephemeral ports.
+    async fn probe_sample(&self, sample: &[SocketAddr]) -> Option<HashSet<SocketAddr>> {
+        let results: Vec<(SocketAddr, io::Result<()>)> = stream::iter(sample.iter().copied())
+            .map(|socket| async move { (socket, self.connect(socket).await.map(drop)) })
+            .buffer_unordered(sample.len())
+            .collect()
+            .await;
+
+        let mut open = HashSet::new();
+        for (socket, result) in results {
+            match result {
+                Ok(()) => {
+                    open.insert(socket);
+                }
+                Err(e) if ProbeError::classify(&e) == ProbeError::ResourceExhausted => return None,
+                Err(_) => {}
+            }
+        }
+        Some(open)
+    }
+}
@@ -61,6 +61,9 @@
 //! - The `{{script}}` part will be replaced with the scriptfile full path
 //!   gathered while parsing available scripts.
 //! - The `{{ip}}` part will be replaced with the ip we got from the scan.
+//! - The `{{host}}` part will be replaced with the hostname the ip was
+//!   resolved from, or the ip when it was given as is. The default script
+//!   runs nmap on it, as virtual hosts may answer differently to their name.
 //! - The `{{port}}` part will be reaplced with the ports separated with the
 //!   `ports_separator` found in the script file
 //!
@@ -72,10 +75,44 @@
 //!
 //! If the format is different, the script will be silently discarded and will
 //! not run. With the `Debug` option it's possible to see where it goes wrong.
+//!
+//! ## `scripts_user`
+//!
+//! Setting `scripts_user = "nobody"` in the script config file runs every
+//! script, the default one included, as that user. The scan may need root
+//! for raw sockets, the scripts after it don't. Switching users needs the
+//! privileges `--drop-privileges` gives up, so the two can't be combined.
+//!
+//! ## `trigger_service`
+//!
+//! A script file setting `trigger_service = ["http", "ssl"]` only runs on
+//! the hosts with an open port running one of those services, and only gets
+//! those ports. Services are the ones `--probe-services` found, which holds
+//! for services on non-standard ports, or else the ones the services table
+//! names after the port.
+//!
+//! ## `input`
+//!
+//! A script file setting `input = "json"` in its headers also gets the
+//! results of its host on stdin, as a single JSON object: the host with its
+//! hostnames, tags and open ports, with the services and certificates found
+//! on them, along with when the scan started and its summary. See
+//! [`ScriptContext`]. The call format is filled in as usual.
+//!
+//! ```json
+//! {"host":{"ip":"10.0.0.1","hostnames":[],"tags":[],"ports":[{"port":22,"protocol":"tcp","state":"open","service":"ssh","product":"OpenSSH","version":"9.6"}]},"started_at":1700000000,"summary":{"hosts_scanned":1,"open_ports":1,"duration_ms":1520,...}}
+//! ```
+//!
+//! ## `--sandbox-scripts`
+//!
+//! Runs every script in a restricted environment, see [`Sandbox`]. Script
+//! files can set their own limits with the `cpu_limit` (seconds),
+//! `memory_limit` (MiB) and `file_limit` headers.
 
 #![allow(clippy::module_name_repetitions)]
 
 use crate::input::ScriptsRequired;
+use crate::output::{HostReport, ScriptReport, SummaryReport};
 use anyhow::{anyhow, Result};
 use log::debug;
 use serde_derive::{Deserialize, Serialize};
@@ -83,17 +120,20 @@ use std::fs::{self, File};
 use std::io::{self, prelude::*};
 use std::net::IpAddr;
 use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::process::{Command, Output, Stdio};
 use std::string::ToString;
 use text_placeholder::Template;
 
 #[cfg(unix)]
 use std::os::unix::process::ExitStatusExt;
 
+mod sandbox;
+pub use sandbox::Sandbox;
+
 static DEFAULT: &str = r#"tags = ["core_approved", "RustScan", "default"]
 developer = [ "RustScan", "https://github.com/RustScan" ]
 ports_separator = ","
-call_format = "nmap -vvv -p {{port}} -{{ipversion}} {{ip}}"
+call_format = "nmap -vvv -p {{port}} -{{ipversion}} {{host}}"
 "#;
 
 #[cfg(not(tarpaulin_include))]
@@ -103,8 +143,9 @@ pub fn init_scripts(scripts: &ScriptsRequired) -> Result<Vec<ScriptFile>> {
     match scripts {
         ScriptsRequired::None => {}
         ScriptsRequired::Default => {
-            let default_script =
+            let mut default_script =
                 toml::from_str::<ScriptFile>(DEFAULT).expect("Failed to parse Script file.");
+            default_script.user = scripts_user();
             scripts_to_run.push(default_script);
         }
         ScriptsRequired::Custom => {
@@ -131,7 +172,10 @@ pub fn init_scripts(scripts: &ScriptsRequired) -> Result<Vec<ScriptFile>> {
                             .iter()
                             .all(|tag| config_hashset.contains(tag))
                         {
-                            scripts_to_run.push(script);
+                            scripts_to_run.push(ScriptFile {
+                                user: script_config.scripts_user.clone(),
+                                ..script
+                            });
                         } else {
                             debug!(
                                 "\nScript tags does not match config tags {:?} {}",
@@ -169,6 +213,9 @@ pub struct Script {
     // Ip got from scanner.
     ip: IpAddr,
 
+    // Hostname the ip was resolved from, if any.
+    host: Option<String>,
+
     // Ports found with portscan.
     open_ports: Vec<u16>,
 
@@ -183,12 +230,31 @@ pub struct Script {
 
     // The format how we want the script to run.
     call_format: Option<String>,
+
+    // Restrictions the script runs under, if any.
+    sandbox: Option<Sandbox>,
+
+    // User to run the script as, from `scripts_user`.
+    user: Option<String>,
+
+    // Written to the script's stdin, for `input = "json"`.
+    stdin: Option<String>,
+}
+
+/// What a script reading `input = "json"` gets on stdin.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptContext<'a> {
+    pub host: &'a HostReport,
+    /// When the scan started, in seconds since the Unix epoch.
+    pub started_at: u64,
+    pub summary: &'a SummaryReport,
 }
 
 #[derive(Serialize)]
 struct ExecPartsScript {
     script: String,
     ip: String,
+    host: String,
     port: String,
     ipversion: String,
 }
@@ -196,6 +262,7 @@ struct ExecPartsScript {
 #[derive(Serialize)]
 struct ExecParts {
     ip: String,
+    host: String,
     port: String,
     ipversion: String,
 }
@@ -213,17 +280,61 @@ impl Script {
         Self {
             path,
             ip,
+            host: None,
             open_ports,
             trigger_port,
             ports_separator,
             tags,
             call_format,
+            sandbox: None,
+            user: None,
+            stdin: None,
+        }
+    }
+
+    /// Fills `{{host}}` with `host` instead of the ip.
+    #[must_use]
+    pub fn with_host(mut self, host: String) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    /// Runs the script as another user, so the script phase drops the
+    /// privileges the scan may have needed.
+    #[must_use]
+    pub fn with_user(mut self, user: String) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// Writes `context` as JSON to the script's stdin.
+    #[must_use]
+    pub fn with_json_input(mut self, context: &ScriptContext<'_>) -> Self {
+        self.stdin = serde_json::to_string(context).ok();
+        self
+    }
+
+    /// Runs the script inside a sandbox instead of with the scanner's own
+    /// privileges.
+    #[must_use]
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Runs the script, failing when it exits with anything but 0.
+    pub fn run(self) -> Result<String> {
+        let report = self.execute()?;
+        if report.exit_code != 0 {
+            return Err(anyhow!("Exit code = {}", report.exit_code));
         }
+        Ok(report.output)
     }
 
+    /// Runs the script, reporting how it exited along with its output.
     // Some variables get changed before read, and compiler throws warning on warn(unused_assignments)
     #[allow(unused_assignments)]
-    pub fn run(self) -> Result<String> {
+    pub fn execute(self) -> Result<ScriptReport> {
         debug!("run self {:?}", &self);
 
         let separator = self.ports_separator.unwrap_or_else(|| ",".into());
@@ -244,6 +355,7 @@ impl Script {
         } else {
             return Err(anyhow!("Failed to parse execution format."));
         }
+        let host = self.host.unwrap_or_else(|| self.ip.to_string());
         let default_template: Template = Template::new(&final_call_format);
         let mut to_run = String::new();
 
@@ -251,6 +363,7 @@ impl Script {
             let exec_parts_script: ExecPartsScript = ExecPartsScript {
                 script: self.path.unwrap().to_str().unwrap().to_string(),
                 ip: self.ip.to_string(),
+                host,
                 port: ports_str,
                 ipversion: match &self.ip {
                     IpAddr::V4(_) => String::from("4"),
@@ -261,6 +374,7 @@ impl Script {
         } else {
             let exec_parts: ExecParts = ExecParts {
                 ip: self.ip.to_string(),
+                host,
                 port: ports_str,
                 ipversion: match &self.ip {
                     IpAddr::V4(_) => String::from("4"),
@@ -270,12 +384,22 @@ impl Script {
             to_run = default_template.fill_with_struct(&exec_parts)?;
         }
         debug!("\nScript format to run {to_run}");
-        execute_script(&to_run)
+        execute_script(
+            &to_run,
+            self.sandbox.as_ref(),
+            self.user.as_deref(),
+            self.stdin.as_deref(),
+        )
     }
 }
 
 #[cfg(not(tarpaulin_include))]
-fn execute_script(script: &str) -> Result<String> {
+fn execute_script(
+    script: &str,
+    sandbox: Option<&Sandbox>,
+    user: Option<&str>,
+    stdin: Option<&str>,
+) -> Result<ScriptReport> {
     debug!("\nScript arguments {script}");
 
     let (cmd, arg) = if cfg!(unix) {
@@ -284,12 +408,19 @@ fn execute_script(script: &str) -> Result<String> {
         ("cmd.exe", "/c")
     };
 
-    match Command::new(cmd)
+    let mut command = Command::new(cmd);
+    command
         .args([arg, script])
         .stdin(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-    {
+        .stderr(Stdio::piped());
+    if let Some(sandbox) = sandbox {
+        sandbox.apply(&mut command);
+    }
+    if let Some(user) = user {
+        run_as(&mut command, user)?;
+    }
+
+    match output(&mut command, stdin) {
         Ok(output) => {
             let status = output.status;
 
@@ -308,10 +439,12 @@ fn execute_script(script: &str) -> Result<String> {
                 }
             };
 
-            if es != 0 {
-                return Err(anyhow!("Exit code = {}", es));
-            }
-            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+            Ok(ScriptReport {
+                command: script.to_owned(),
+                exit_code: es,
+                output: String::from_utf8_lossy(&output.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            })
         }
         Err(error) => {
             debug!("Command error {error}",);
@@ -320,6 +453,61 @@ fn execute_script(script: &str) -> Result<String> {
     }
 }
 
+/// Runs `command` to completion like [`Command::output`], writing `stdin`
+/// to it first.
+fn output(command: &mut Command, stdin: Option<&str>) -> io::Result<Output> {
+    let Some(input) = stdin else {
+        return command.output();
+    };
+    let mut child = command.stdout(Stdio::piped()).spawn()?;
+    if let Some(mut pipe) = child.stdin.take() {
+        let input = input.to_owned();
+        // Written from another thread, so a script printing a lot before
+        // reading all of its input doesn't block on a full stdout pipe.
+        std::thread::spawn(move || {
+            if let Err(e) = pipe.write_all(input.as_bytes()) {
+                debug!("Could not write the script input: {e}");
+            }
+        });
+    }
+    child.wait_with_output()
+}
+
+#[cfg(unix)]
+fn run_as(command: &mut Command, user: &str) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let (uid, gid) =
+        sandbox::lookup_user(user).map_err(|e| anyhow!("Can't run scripts as {user}: {e}"))?;
+    command.uid(uid).gid(gid);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn run_as(_command: &mut Command, user: &str) -> Result<()> {
+    Err(anyhow!(
+        "Can't run scripts as {user}: scripts_user is only supported on unix"
+    ))
+}
+
+/// Checks that `scripts` can switch to their `scripts_user`, which needs
+/// the privileges `--drop-privileges` gives up before the scan.
+pub fn check_scripts_user(scripts: &[ScriptFile], drop_privileges: bool) -> Result<()> {
+    match scripts.iter().find_map(|script| script.user.as_deref()) {
+        Some(user) if drop_privileges => Err(anyhow!(
+            "scripts_user = \"{user}\" can't be combined with --drop-privileges, which gives up the privileges needed to run scripts as {user}. Remove one of them."
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The user scripts run as, from `scripts_user` in the script config. Read
+/// for every kind of script, since the default nmap script is the one most
+/// likely to run after a privileged scan.
+fn scripts_user() -> Option<String> {
+    ScriptConfig::read_config().ok()?.scripts_user
+}
+
 pub fn find_scripts(path: PathBuf) -> Result<Vec<PathBuf>> {
     if path.is_dir() {
         debug!("Scripts folder found {}", &path.display());
@@ -340,8 +528,32 @@ pub struct ScriptFile {
     pub tags: Option<Vec<String>>,
     pub developer: Option<Vec<String>>,
     pub port: Option<String>,
+    /// Only runs the script on the ports running one of these services,
+    /// e.g. `["http", "ssl"]`, as named by `--probe-services` or else the
+    /// services table.
+    pub trigger_service: Option<Vec<String>>,
     pub ports_separator: Option<String>,
     pub call_format: Option<String>,
+    /// CPU time the script may use when sandboxed, in seconds.
+    pub cpu_limit: Option<u64>,
+    /// Memory the script may use when sandboxed, in MiB.
+    pub memory_limit: Option<u64>,
+    /// Files the script may have open when sandboxed.
+    pub file_limit: Option<u64>,
+    /// What the script gets on stdin besides its arguments, nothing when
+    /// not set.
+    pub input: Option<ScriptInput>,
+    /// User to run the script as, set from the script config.
+    #[serde(skip)]
+    pub user: Option<String>,
+}
+
+/// Represents what a script reads on stdin, `input` in its headers.
+///   - json is a [`ScriptContext`] of the host the script runs on.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptInput {
+    Json,
 }
 
 impl ScriptFile {
@@ -378,6 +590,37 @@ impl ScriptFile {
             }
         }
     }
+
+    /// The open `ports` of `host` the script runs on: all of them, or the
+    /// ones running a service of `trigger_service`. Empty when the script
+    /// shouldn't run on the host.
+    pub fn ports_for(&self, ports: &[u16], host: Option<&HostReport>) -> Vec<u16> {
+        let Some(services) = &self.trigger_service else {
+            return ports.to_vec();
+        };
+        let mut triggered: Vec<u16> = host
+            .into_iter()
+            .flat_map(|host| &host.ports)
+            .filter(|port| {
+                port.service
+                    .as_ref()
+                    .is_some_and(|service| services.contains(service))
+            })
+            .map(|port| port.port)
+            .filter(|port| ports.contains(port))
+            .collect();
+        triggered.dedup();
+        triggered
+    }
+
+    /// The sandbox this script runs in, with the limits from its headers.
+    pub fn sandbox(&self) -> Sandbox {
+        Sandbox {
+            cpu_seconds: self.cpu_limit,
+            memory_bytes: self.memory_limit.map(|mib| mib.saturating_mul(1024 * 1024)),
+            max_files: self.file_limit,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -386,6 +629,8 @@ pub struct ScriptConfig {
     pub ports: Option<Vec<String>>,
     pub developer: Option<Vec<String>>,
     pub directory: Option<String>,
+    /// Runs every script as this user, e.g. "nobody".
+    pub scripts_user: Option<String>,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -520,6 +765,111 @@ mod tests {
         assert_eq!(output.trim(), "Total args passed to fixtures/.rustscan_scripts/test_script.pl : 2\nArg # 1 : 127.0.0.1\nArg # 2 : 80,8080");
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn fills_the_hostname() {
+        let call_format = Some("echo {{host}} {{ip}}".to_owned());
+        let script = Script::build(
+            None,
+            "127.0.0.1".parse().unwrap(),
+            vec![80],
+            None,
+            None,
+            None,
+            call_format,
+        );
+
+        assert_eq!(script.clone().run().unwrap().trim(), "127.0.0.1 127.0.0.1");
+        let script = script.with_host("localhost".to_owned());
+        assert_eq!(script.run().unwrap().trim(), "localhost 127.0.0.1");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn reports_exit_codes_and_stderr() {
+        let script = Script::build(
+            None,
+            "127.0.0.1".parse().unwrap(),
+            vec![80],
+            None,
+            None,
+            None,
+            Some("echo {{ip}}; echo failed >&2; exit 3".to_owned()),
+        );
+
+        let report = script.clone().execute().unwrap();
+        assert_eq!(report.command, "echo 127.0.0.1; echo failed >&2; exit 3");
+        assert_eq!(report.exit_code, 3);
+        assert_eq!(report.output, "127.0.0.1\n");
+        assert_eq!(report.stderr, "failed\n");
+        assert!(script.run().is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn writes_json_input_to_stdin() {
+        use crate::output::{Protocol, ScanReport};
+        use crate::scanner::ScanSummary;
+        use std::time::UNIX_EPOCH;
+
+        let script_f: ScriptFile = toml::from_str(
+            r#"
+            call_format = "cat"
+            input = "json"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(script_f.input, Some(ScriptInput::Json));
+
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        report.add("127.0.0.1:22".parse().unwrap(), Protocol::Tcp);
+        let context = ScriptContext {
+            host: &report.hosts[0],
+            started_at: report.started_at,
+            summary: &report.summary,
+        };
+        let script = into_script(script_f).with_json_input(&context);
+
+        let output: serde_json::Value = serde_json::from_str(&script.run().unwrap()).unwrap();
+        assert_eq!(output["host"]["ip"], "127.0.0.1");
+        assert_eq!(output["host"]["ports"][0]["port"], 22);
+        assert_eq!(output["summary"]["open_ports"], 0);
+    }
+
+    #[test]
+    fn triggers_on_services() {
+        use crate::output::{Protocol, ScanReport};
+        use crate::probes::ServiceInfo;
+        use crate::scanner::ScanSummary;
+        use std::collections::BTreeMap;
+        use std::time::UNIX_EPOCH;
+
+        let mut report = ScanReport::new(UNIX_EPOCH, ScanSummary::default());
+        for port in [22, 443, 8081] {
+            report.add(format!("10.0.0.1:{port}").parse().unwrap(), Protocol::Tcp);
+        }
+        let mut services = BTreeMap::new();
+        services.insert(
+            "10.0.0.1:8081".parse().unwrap(),
+            ServiceInfo {
+                name: "http".to_owned(),
+                product: None,
+                version: None,
+            },
+        );
+        report.add_services(&services);
+        let host = report.hosts.first();
+        let ports = [22, 443, 8081];
+
+        let script_f: ScriptFile =
+            toml::from_str(r#"trigger_service = ["http", "https"]"#).unwrap();
+        assert_eq!(script_f.ports_for(&ports, host), vec![443, 8081]);
+        let script_f: ScriptFile = toml::from_str(r#"trigger_service = ["smtp"]"#).unwrap();
+        assert!(script_f.ports_for(&ports, host).is_empty());
+        let script_f: ScriptFile = toml::from_str("").unwrap();
+        assert_eq!(script_f.ports_for(&ports, host), ports);
+    }
+
     #[test]
     fn test_custom_directory_config() {
         // Create test config
@@ -564,4 +914,15 @@ mod tests {
 
         assert_eq!(script_dir_base, dirs::home_dir().unwrap());
     }
+
+    #[test]
+    fn scripts_user_conflicts_with_dropped_privileges() {
+        let mut script_f: ScriptFile = toml::from_str("").unwrap();
+        assert!(check_scripts_user(&[script_f.clone()], true).is_ok());
+
+        script_f.user = Some("nobody".to_owned());
+        assert!(check_scripts_user(&[script_f.clone()], false).is_ok());
+        let error = check_scripts_user(&[script_f], true).unwrap_err();
+        assert!(error.to_string().contains("--drop-privileges"));
+    }
 }
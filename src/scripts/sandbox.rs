@@ -0,0 +1,363 @@
+//! Restricted environment for running scripts.
+//!
+//! Scripts are arbitrary commands, and without a sandbox they run with every
+//! privilege the scanner has. A sandboxed script:
+//!
+//! - runs in its own process group, so it can be told apart and signalled
+//!   without touching the scanner,
+//! - doesn't inherit any fd other than stdin, stdout and stderr, raw sockets
+//!   included,
+//! - can't gain privileges through setuid binaries,
+//! - gets the CPU, memory and open file limits from its script file,
+//! - on Linux (x86_64 and aarch64), runs under a seccomp profile denying the
+//!   syscalls that only serve to escape or tamper with the system, such as
+//!   `ptrace`, `mount`, creating namespaces or loading kernel modules.
+use std::process::Command;
+
+/// Limits and restrictions applied to a script process.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sandbox {
+    /// CPU time, in seconds.
+    pub cpu_seconds: Option<u64>,
+    /// Address space, in bytes.
+    pub memory_bytes: Option<u64>,
+    /// Open files.
+    pub max_files: Option<u64>,
+}
+
+impl Sandbox {
+    /// Sets the command up to enter the sandbox between fork and exec.
+    #[cfg(unix)]
+    pub(super) fn apply(&self, command: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        command.process_group(0);
+
+        // Everything is prepared before forking, the child may only make
+        // async-signal-safe calls.
+        let limits = self.rlimits();
+        #[cfg(target_os = "linux")]
+        let filter = seccomp::filter();
+
+        let enter = move || {
+            unix::close_inherited_fds();
+            for &(resource, limit) in &limits {
+                unix::set_rlimit(resource, limit)?;
+            }
+            unix::no_new_privs()?;
+            #[cfg(target_os = "linux")]
+            seccomp::install(&filter)?;
+            Ok(())
+        };
+        // SAFETY: the closure only makes raw syscalls and doesn't allocate.
+        unsafe {
+            command.pre_exec(enter);
+        }
+    }
+
+    /// Windows has no equivalent the standard library can set up, scripts
+    /// run as they are.
+    #[cfg(not(unix))]
+    pub(super) fn apply(&self, _command: &mut Command) {}
+
+    #[cfg(unix)]
+    fn rlimits(&self) -> Vec<(unix::Resource, u64)> {
+        [
+            (libc::RLIMIT_CPU, self.cpu_seconds),
+            (libc::RLIMIT_AS, self.memory_bytes),
+            (libc::RLIMIT_NOFILE, self.max_files),
+        ]
+        .iter()
+        .copied()
+        .filter_map(|(resource, limit)| Some((resource, limit?)))
+        .collect()
+    }
+}
+
+/// Looks up the uid and gid of a user by name.
+#[cfg(unix)]
+pub(super) fn lookup_user(name: &str) -> std::io::Result<(u32, u32)> {
+    use std::ffi::CString;
+    use std::io;
+
+    let c_name = CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: passwd is a plain C struct of integers and pointers, for which
+    // all zeroes is a valid value. getpwnam_r fills it in before it is read.
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; 16 * 1024];
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+
+    // SAFETY: every pointer is valid for the duration of the call and the
+    // buffer length matches the buffer.
+    let ret = unsafe {
+        libc::getpwnam_r(
+            c_name.as_ptr(),
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::from_raw_os_error(ret));
+    }
+    if result.is_null() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no user named {name}"),
+        ));
+    }
+    Ok((passwd.pw_uid, passwd.pw_gid))
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io;
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    pub(super) type Resource = libc::__rlimit_resource_t;
+    #[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+    pub(super) type Resource = libc::c_int;
+
+    pub(super) fn set_rlimit(resource: Resource, limit: u64) -> io::Result<()> {
+        let limit = libc::rlimit {
+            rlim_cur: limit as libc::rlim_t,
+            rlim_max: limit as libc::rlim_t,
+        };
+        // SAFETY: limit outlives the call.
+        if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Marks every fd above stderr close-on-exec. Marking instead of closing
+    /// keeps the pipe the standard library uses to report exec errors.
+    #[cfg(target_os = "linux")]
+    pub(super) fn close_inherited_fds() {
+        const CLOSE_RANGE_CLOEXEC: libc::c_uint = 1 << 2;
+        // SAFETY: close_range only changes fd flags, failing on old kernels
+        // leaves the fds as they were, which std opens close-on-exec anyway.
+        unsafe {
+            libc::syscall(
+                libc::SYS_close_range,
+                3 as libc::c_uint,
+                libc::c_uint::MAX,
+                CLOSE_RANGE_CLOEXEC,
+            );
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(super) fn close_inherited_fds() {
+        // SAFETY: getdtablesize and fcntl only read and change fd flags.
+        unsafe {
+            for fd in 3..libc::getdtablesize() {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(super) fn no_new_privs() -> io::Result<()> {
+        // SAFETY: PR_SET_NO_NEW_PRIVS takes plain integer arguments.
+        let ret = unsafe {
+            libc::prctl(
+                libc::PR_SET_NO_NEW_PRIVS,
+                1 as libc::c_ulong,
+                0 as libc::c_ulong,
+                0 as libc::c_ulong,
+                0 as libc::c_ulong,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(super) fn no_new_privs() -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A deny-list seccomp profile.
+#[cfg(target_os = "linux")]
+mod seccomp {
+    use std::io;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    /// Syscalls a script has no business making.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const DENIED: &[libc::c_long] = &[
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_reboot,
+        libc::SYS_kexec_load,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_bpf,
+        libc::SYS_setns,
+        libc::SYS_unshare,
+        libc::SYS_keyctl,
+        libc::SYS_userfaultfd,
+        libc::SYS_perf_event_open,
+        libc::SYS_open_by_handle_at,
+    ];
+
+    /// Flags of clone that create new namespaces, denied like unshare.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const CLONE_NAMESPACES: libc::c_int = libc::CLONE_NEWNS
+        | libc::CLONE_NEWCGROUP
+        | libc::CLONE_NEWUTS
+        | libc::CLONE_NEWIPC
+        | libc::CLONE_NEWUSER
+        | libc::CLONE_NEWPID
+        | libc::CLONE_NEWNET;
+
+    /// Syscall numbers from this bit on belong to the x32 ABI, which would
+    /// get around the numbers in `DENIED`.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    // seccomp_data field offsets.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const NR_OFFSET: u32 = 0;
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const ARCH_OFFSET: u32 = 4;
+    /// The lower half of the first argument, on little endian targets.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const ARG0_OFFSET: u32 = 16;
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    fn statement(code: u32, k: u32) -> libc::sock_filter {
+        jump(code, k, 0, 0)
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    fn jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+        libc::sock_filter {
+            code: code as u16,
+            jt,
+            jf,
+            k,
+        }
+    }
+
+    /// Kills processes using another syscall ABI, denies the syscalls in
+    /// `DENIED` and clones into new namespaces with EPERM, and clone3, whose
+    /// flags can't be checked, with ENOSYS so libc falls back to clone.
+    /// Allows everything else.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    pub(super) fn filter() -> Vec<libc::sock_filter> {
+        use libc::{
+            BPF_ABS, BPF_JEQ, BPF_JGE, BPF_JMP, BPF_JSET, BPF_K, BPF_LD, BPF_RET, BPF_W, ENOSYS,
+            EPERM, SECCOMP_RET_ALLOW, SECCOMP_RET_ERRNO, SECCOMP_RET_KILL_PROCESS,
+        };
+
+        let mut filter = vec![
+            statement(BPF_LD | BPF_W | BPF_ABS, ARCH_OFFSET),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+            statement(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+            statement(BPF_LD | BPF_W | BPF_ABS, NR_OFFSET),
+            jump(BPF_JMP | BPF_JGE | BPF_K, X32_SYSCALL_BIT, 0, 1),
+            statement(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_clone3 as u32, 0, 1),
+            statement(BPF_RET | BPF_K, SECCOMP_RET_ERRNO | ENOSYS as u32),
+            // clone: check the flags, then load the number back.
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_clone as u32, 0, 4),
+            statement(BPF_LD | BPF_W | BPF_ABS, ARG0_OFFSET),
+            jump(BPF_JMP | BPF_JSET | BPF_K, CLONE_NAMESPACES as u32, 0, 1),
+            statement(BPF_RET | BPF_K, SECCOMP_RET_ERRNO | EPERM as u32),
+            statement(BPF_LD | BPF_W | BPF_ABS, NR_OFFSET),
+        ];
+        for &nr in DENIED {
+            filter.push(jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 1));
+            filter.push(statement(BPF_RET | BPF_K, SECCOMP_RET_ERRNO | EPERM as u32));
+        }
+        filter.push(statement(BPF_RET | BPF_K, SECCOMP_RET_ALLOW));
+        filter
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    pub(super) fn filter() -> Vec<libc::sock_filter> {
+        Vec::new()
+    }
+
+    pub(super) fn install(filter: &[libc::sock_filter]) -> io::Result<()> {
+        if filter.is_empty() {
+            return Ok(());
+        }
+
+        let program = libc::sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_ptr().cast_mut(),
+        };
+        // SAFETY: program points to filter, which outlives the call, and
+        // no_new_privs was set before so no privilege is needed.
+        let ret = unsafe {
+            libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER as libc::c_ulong,
+                &program as *const libc::sock_fprog,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sandbox;
+    use std::process::Command;
+
+    #[test]
+    #[cfg(unix)]
+    fn looks_up_users() {
+        assert_eq!(super::lookup_user("root").unwrap(), (0, 0));
+        assert!(super::lookup_user("no-such-rustscan-user").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn sandboxed_command_runs() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo sandboxed"]);
+        Sandbox {
+            cpu_seconds: Some(10),
+            memory_bytes: None,
+            max_files: Some(64),
+        }
+        .apply(&mut command);
+
+        let output = command.output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "sandboxed\n");
+    }
+
+    #[test]
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    fn filter_denies_every_listed_syscall() {
+        // load arch, check it, kill, load nr, check x32, kill, deny clone3,
+        // check the flags of clone, two per denied syscall, allow
+        assert_eq!(super::seccomp::filter().len(), 4 + 2 + 2 + 5 + 2 * 20 + 1);
+    }
+}
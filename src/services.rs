@@ -0,0 +1,241 @@
+//! Well-known service names and the ports they run on.
+//!
+//! Lets ports be specified by name, e.g. `--exclude-ports ssh,rdp`, and
+//! results be shown along with the service usually behind a port. Besides
+//! the built-in table, the well-known services of IANA's registry are
+//! embedded at build time from `service-names`, and the TCP entries of the
+//! system services database (`/etc/services`) are used, so less common
+//! services can be named too.
+use crate::generated::get_service_names;
+use crate::output::Protocol;
+
+use std::fs;
+use std::sync::OnceLock;
+
+/// Location of the system services database.
+#[cfg(not(windows))]
+const SYSTEM_SERVICES: &str = "/etc/services";
+#[cfg(windows)]
+const SYSTEM_SERVICES: &str = r"C:\Windows\System32\drivers\etc\services";
+
+/// Common TCP services, by their IANA name where there is one and by the
+/// name people use for them otherwise.
+static SERVICES: &[(&str, u16)] = &[
+    ("ftp-data", 20),
+    ("ftp", 21),
+    ("ssh", 22),
+    ("telnet", 23),
+    ("smtp", 25),
+    ("domain", 53),
+    ("dns", 53),
+    ("http", 80),
+    ("kerberos", 88),
+    ("pop3", 110),
+    ("rpcbind", 111),
+    ("ntp", 123),
+    ("msrpc", 135),
+    ("netbios-ssn", 139),
+    ("imap", 143),
+    ("snmp", 161),
+    ("ldap", 389),
+    ("https", 443),
+    ("microsoft-ds", 445),
+    ("smb", 445),
+    ("smtps", 465),
+    ("submission", 587),
+    ("ldaps", 636),
+    ("rsync", 873),
+    ("imaps", 993),
+    ("pop3s", 995),
+    ("mssql", 1433),
+    ("oracle", 1521),
+    ("mqtt", 1883),
+    ("nfs", 2049),
+    ("docker", 2375),
+    ("mysql", 3306),
+    ("rdp", 3389),
+    ("ms-wbt-server", 3389),
+    ("sip", 5060),
+    ("postgresql", 5432),
+    ("amqp", 5672),
+    ("vnc", 5900),
+    ("winrm", 5985),
+    ("x11", 6000),
+    ("redis", 6379),
+    ("kubernetes", 6443),
+    ("irc", 6667),
+    ("http-alt", 8080),
+    ("https-alt", 8443),
+    ("elasticsearch", 9200),
+    ("memcached", 11211),
+    ("mongodb", 27017),
+];
+
+/// Looks up the port of a service by name, ignoring case.
+///
+/// ```rust
+/// # use rustscan::services::port_by_name;
+/// assert_eq!(port_by_name("SSH"), Some(22));
+/// assert_eq!(port_by_name("not-a-service"), None);
+/// ```
+pub fn port_by_name(name: &str) -> Option<u16> {
+    SERVICES
+        .iter()
+        .find(|(service, _)| service.eq_ignore_ascii_case(name))
+        .map(|(_, port)| *port)
+        .or_else(|| {
+            get_service_names()
+                .iter()
+                .find(|(_, protocol, service)| {
+                    *protocol == "tcp" && service.eq_ignore_ascii_case(name)
+                })
+                .map(|(port, _, _)| *port)
+        })
+        .or_else(|| {
+            system_services()
+                .iter()
+                .find(|(service, _)| service.eq_ignore_ascii_case(name))
+                .map(|(_, port)| *port)
+        })
+}
+
+/// Ports of the common services, in ascending order.
+///
+/// ```rust
+/// # use rustscan::services::common_ports;
+/// let ports = common_ports();
+/// assert!(ports.contains(&22) && ports.contains(&443));
+/// assert!(ports.windows(2).all(|pair| pair[0] < pair[1]));
+/// ```
+pub fn common_ports() -> Vec<u16> {
+    let mut ports: Vec<u16> = SERVICES.iter().map(|(_, port)| *port).collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+/// Looks up the name of the service usually running on a port, preferring
+/// its IANA name.
+///
+/// ```rust
+/// # use rustscan::services::name_by_port;
+/// assert_eq!(name_by_port(22), Some("ssh"));
+/// assert_eq!(name_by_port(53), Some("domain"));
+/// assert_eq!(name_by_port(3306), Some("mysql"));
+/// assert_eq!(name_by_port(4), None);
+/// ```
+pub fn name_by_port(port: u16) -> Option<&'static str> {
+    SERVICES
+        .iter()
+        .find(|(_, service_port)| *service_port == port)
+        .map(|(service, _)| *service)
+        .or_else(|| iana_name(port, Protocol::Tcp))
+        .or_else(|| {
+            system_services()
+                .iter()
+                .find(|(_, service_port)| *service_port == port)
+                .map(|(service, _)| service.as_str())
+        })
+}
+
+/// Looks up the name of the service usually running on a port over
+/// `protocol`. UDP services are only known from IANA's registry.
+///
+/// ```rust
+/// # use rustscan::output::Protocol;
+/// # use rustscan::services::service_name;
+/// assert_eq!(service_name(3306, Protocol::Tcp), Some("mysql"));
+/// assert_eq!(service_name(123, Protocol::Udp), Some("ntp"));
+/// assert_eq!(service_name(22, Protocol::Udp), None);
+/// ```
+pub fn service_name(port: u16, protocol: Protocol) -> Option<&'static str> {
+    match protocol {
+        Protocol::Tcp => name_by_port(port),
+        Protocol::Udp => iana_name(port, protocol),
+    }
+}
+
+/// The name IANA's registry gives the service of a port and protocol.
+fn iana_name(port: u16, protocol: Protocol) -> Option<&'static str> {
+    let protocol = match protocol {
+        Protocol::Tcp => "tcp",
+        Protocol::Udp => "udp",
+    };
+    let services = get_service_names();
+    services
+        .binary_search_by(|(service_port, service_protocol, _)| {
+            (*service_port, *service_protocol).cmp(&(port, protocol))
+        })
+        .ok()
+        .map(|index| services[index].2)
+}
+
+/// The TCP services of the system services database, read once. A missing
+/// database is treated as empty.
+fn system_services() -> &'static [(String, u16)] {
+    static SYSTEM: OnceLock<Vec<(String, u16)>> = OnceLock::new();
+    SYSTEM.get_or_init(|| {
+        fs::read_to_string(SYSTEM_SERVICES)
+            .map(|content| parse_services(&content))
+            .unwrap_or_default()
+    })
+}
+
+/// Parses a services database in the `/etc/services` format, e.g.
+/// `ssh 22/tcp # SSH Remote Login Protocol`, into the names and aliases of
+/// its TCP services.
+fn parse_services(content: &str) -> Vec<(String, u16)> {
+    let mut services = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(port)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some(Ok(port)) = port.strip_suffix("/tcp").map(str::parse::<u16>) else {
+            continue;
+        };
+        services.push((name.to_owned(), port));
+        services.extend(fields.map(|alias| (alias.to_owned(), port)));
+    }
+    services
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_services, service_name};
+    use crate::generated::get_service_names;
+    use crate::output::Protocol;
+
+    #[test]
+    fn embedded_services_are_sorted_by_port_and_protocol() {
+        let services = get_service_names();
+        assert!(services
+            .windows(2)
+            .all(|pair| (pair[0].0, pair[0].1) < (pair[1].0, pair[1].1)));
+        assert_eq!(service_name(5432, Protocol::Tcp), Some("postgresql"));
+        assert_eq!(service_name(161, Protocol::Udp), Some("snmp"));
+        assert_eq!(service_name(3306, Protocol::Udp), None);
+    }
+
+    #[test]
+    fn parses_tcp_services_and_aliases() {
+        let content = "\
+# Network services, Internet style
+tcpmux          1/tcp                           # TCP port service multiplexer
+domain          53/udp
+gopher          70/tcp
+www             80/tcp          http            # WorldWideWeb HTTP
+broken          notaport/tcp
+";
+        assert_eq!(
+            parse_services(content),
+            vec![
+                ("tcpmux".to_owned(), 1),
+                ("gopher".to_owned(), 70),
+                ("www".to_owned(), 80),
+                ("http".to_owned(), 80),
+            ]
+        );
+    }
+}
@@ -0,0 +1,470 @@
+//! Interactive shell, `rustscan shell`.
+//!
+//! A session keeps its targets, ports and results between commands, so
+//! hosts can be scanned, inspected and handed to scripts one step at a
+//! time. Resolved hostnames and the latencies and open ports learned from
+//! previous scans are kept for the whole session, so scanning the same
+//! networks again is quicker than starting RustScan over.
+use crate::address::{parse_targets, Targets};
+use crate::input::{Opts, PortRange, PortSpec, ScanOrder};
+use crate::learning::LearningStore;
+use crate::port_strategy::PortStrategy;
+use crate::scanner::Scanner;
+use crate::scripts::{init_scripts, Script};
+use crate::services;
+use crate::{detail, output, warning};
+
+use futures::executor::block_on;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::net::IpAddr;
+use std::time::Duration;
+
+const PROMPT: &str = "rustscan> ";
+
+const HELP: &str = "\
+Commands:
+  targets <address>...    set the hosts, IPs, CIDRs or files to scan
+  ports <spec>,...        set the ports, e.g. 22,80,8000-8100,ssh
+  set <option> <value>    set timeout (ms), batch-size or tries
+  scan                    scan the targets
+  results [ip]...         show the open ports of the last scan
+  scripts [ip]...         run the scripts against the given or all hosts
+  show                    show the targets, ports and settings
+  help                    show this help
+  exit                    leave the shell";
+
+/// A command typed at the prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Targets(Vec<String>),
+    Ports(Vec<u16>),
+    Set(Setting),
+    Scan,
+    Results(Vec<IpAddr>),
+    Scripts(Vec<IpAddr>),
+    Show,
+    Help,
+    Exit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Setting {
+    Timeout(u32),
+    BatchSize(usize),
+    Tries(u8),
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+
+        match (command, args.as_slice()) {
+            ("targets", []) => Err("targets needs at least one address".to_owned()),
+            ("targets", addresses) => Ok(Command::Targets(
+                addresses
+                    .iter()
+                    .flat_map(|address| address.split(','))
+                    .filter(|address| !address.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect(),
+            )),
+            ("ports", [specs]) => {
+                let mut ports = Vec::new();
+                for spec in specs.split(',').filter(|spec| !spec.is_empty()) {
+                    ports.extend(PortSpec::Named(spec.to_owned()).ports()?);
+                }
+                ports.dedup();
+                Ok(Command::Ports(ports))
+            }
+            ("ports", _) => Err("ports takes a comma separated list, e.g. 22,80,1-1000".to_owned()),
+            ("set", [option, value]) => {
+                let invalid = |e| format!("invalid value {value} for {option}: {e}");
+                let setting = match *option {
+                    "timeout" => Setting::Timeout(value.parse().map_err(invalid)?),
+                    "batch-size" => Setting::BatchSize(value.parse().map_err(invalid)?),
+                    "tries" => Setting::Tries(value.parse().map_err(invalid)?),
+                    _ => return Err(format!("unknown option {option}")),
+                };
+                Ok(Command::Set(setting))
+            }
+            ("set", _) => Err("set takes an option and a value, e.g. set timeout 500".to_owned()),
+            ("results", ips) => Ok(Command::Results(parse_ips(ips)?)),
+            ("scripts", ips) => Ok(Command::Scripts(parse_ips(ips)?)),
+            ("scan", []) => Ok(Command::Scan),
+            ("show", []) => Ok(Command::Show),
+            ("help" | "?", []) => Ok(Command::Help),
+            ("exit" | "quit", []) => Ok(Command::Exit),
+            ("scan" | "show" | "help" | "?" | "exit" | "quit", _) => {
+                Err(format!("{command} takes no arguments"))
+            }
+            _ => Err(format!("unknown command {command}, try help")),
+        }
+    }
+}
+
+fn parse_ips(ips: &[&str]) -> Result<Vec<IpAddr>, String> {
+    ips.iter()
+        .map(|ip| ip.parse().map_err(|_| format!("{ip} is not an IP address")))
+        .collect()
+}
+
+/// The state of a shell session.
+struct Session {
+    opts: Opts,
+    targets: Targets,
+    /// Targets of every address resolved during the session.
+    resolved: BTreeMap<String, Targets>,
+    learning: LearningStore,
+    results: BTreeMap<IpAddr, Vec<u16>>,
+}
+
+impl Session {
+    fn new(mut opts: Opts) -> Self {
+        // Progress is printed as it happens, the shell is never greppable.
+        opts.greppable = false;
+        let learning = LearningStore::default_path()
+            .filter(|_| opts.learns())
+            .map(|path| LearningStore::load(&path))
+            .unwrap_or_default();
+        let mut session = Self {
+            opts,
+            targets: Targets::default(),
+            resolved: BTreeMap::new(),
+            learning,
+            results: BTreeMap::new(),
+        };
+        if !session.opts.addresses.is_empty() {
+            session.set_targets(session.opts.addresses.clone());
+        }
+        session
+    }
+
+    /// Runs a command, returns false when the session is over.
+    fn execute(&mut self, command: Command) -> bool {
+        match command {
+            Command::Targets(addresses) => self.set_targets(addresses),
+            Command::Ports(ports) => {
+                self.opts.ports = Some(ports);
+                self.opts.range = None;
+            }
+            Command::Set(Setting::Timeout(timeout)) => self.opts.timeout = timeout,
+            Command::Set(Setting::BatchSize(batch_size)) => self.opts.batch_size = batch_size,
+            Command::Set(Setting::Tries(tries)) => self.opts.tries = tries,
+            Command::Scan => self.scan(),
+            Command::Results(ips) => self.print_results(&ips),
+            Command::Scripts(ips) => self.run_scripts(&ips),
+            Command::Show => self.show(),
+            Command::Help => println!("{HELP}"),
+            Command::Exit => return false,
+        }
+        true
+    }
+
+    /// Resolves the addresses, reusing the answers of earlier lookups.
+    fn set_targets(&mut self, addresses: Vec<String>) {
+        let mut targets = Targets::default();
+        let base = &self.opts;
+        for address in &addresses {
+            let resolved = self.resolved.entry(address.clone()).or_insert_with(|| {
+                let mut opts = base.clone();
+                opts.addresses = vec![address.clone()];
+                parse_targets(&opts)
+            });
+            for ip in &resolved.ips {
+                if !targets.ips.contains(ip) {
+                    targets.ips.push(*ip);
+                }
+            }
+            for (host, ips) in &resolved.hosts {
+                targets.hosts.insert(host.clone(), ips.clone());
+            }
+            for (ip, tags) in &resolved.tags {
+                targets.tags.insert(*ip, tags.clone());
+            }
+            for (ip, scope) in &resolved.scopes {
+                targets.scopes.insert(*ip, *scope);
+            }
+            for (ip, ports) in &resolved.ports {
+                targets.ports.insert(*ip, ports.clone());
+            }
+        }
+        detail!(
+            format!("{} addresses to scan.", targets.ips.len()),
+            self.opts.greppable,
+            self.opts.accessible
+        );
+        self.opts.addresses = addresses;
+        self.targets = targets;
+    }
+
+    fn scan(&mut self) {
+        if self.targets.ips.is_empty() {
+            warning!(
+                "No targets to scan, set them with targets.",
+                self.opts.greppable,
+                self.opts.accessible
+            );
+            return;
+        }
+        let exclude_ports = match self.opts.excluded_ports() {
+            Ok(ports) => ports,
+            Err(e) => {
+                warning!(
+                    format!("Invalid excluded ports: {e}"),
+                    self.opts.greppable,
+                    self.opts.accessible
+                );
+                return;
+            }
+        };
+
+        let ips = &self.targets.ips;
+        let mut timeout = Duration::from_millis(self.opts.timeout.into());
+        if self.opts.adaptive_timeout {
+            if let Some(learned) = self.learning.latency.suggested_timeout(ips) {
+                timeout = learned;
+            }
+        }
+        let mut batch_size = self.opts.batch_size;
+        if self.opts.adaptive_batch {
+            if let Some(learned) = self.learning.errors.suggested_batch_size(ips, batch_size) {
+                batch_size = learned;
+            }
+        }
+        let range = match (&self.opts.ports, &self.opts.range) {
+            (None, None) => Some(PortRange {
+                start: 1,
+                end: 65_535,
+            }),
+            (_, range) => range.clone(),
+        };
+        let mut port_strategy =
+            PortStrategy::pick(&range, self.opts.ports.clone(), self.opts.scan_order);
+        if self.opts.scan_order == ScanOrder::Adaptive {
+            port_strategy = port_strategy.learned(&self.learning.ports, ips);
+        }
+        if self.opts.top_first {
+            port_strategy = port_strategy.tiered(
+                self.opts
+                    .first_ports
+                    .clone()
+                    .unwrap_or_else(services::common_ports),
+            );
+        }
+
+        // Built like the one of a single scan, for the same results.
+        let scanner = Scanner::new(
+            ips,
+            batch_size,
+            timeout,
+            self.opts.tries,
+            self.opts.greppable,
+            port_strategy,
+            self.opts.accessible,
+            exclude_ports,
+            self.opts.udp,
+        )
+        .with_scopes(&self.targets.scopes)
+        .with_target_ports(&self.targets.ports)
+        .with_opts(&self.opts, None);
+        let scanner = match scanner {
+            Ok(scanner) => scanner,
+            Err(e) => {
+                warning!(e, self.opts.greppable, self.opts.accessible);
+                return;
+            }
+        };
+        let open_sockets = block_on(scanner.run()).open();
+
+        self.learning.latency.merge(scanner.latency_profiles());
+        self.learning.errors.merge(scanner.error_rates());
+        self.results.clear();
+        for socket in &open_sockets {
+            self.learning.ports.record(&socket.ip(), socket.port());
+            self.results
+                .entry(socket.ip())
+                .or_default()
+                .push(socket.port());
+        }
+        output!(
+            format!(
+                "Scan finished: {} open ports on {} of {} hosts.",
+                open_sockets.len(),
+                self.results.len(),
+                ips.len()
+            ),
+            self.opts.greppable,
+            self.opts.accessible
+        );
+    }
+
+    /// The hosts of the last scan among `ips`, or all of them.
+    fn selected(&self, ips: &[IpAddr]) -> Vec<(IpAddr, Vec<u16>)> {
+        self.results
+            .iter()
+            .filter(|(ip, _)| ips.is_empty() || ips.contains(ip))
+            .map(|(ip, ports)| (*ip, ports.clone()))
+            .collect()
+    }
+
+    fn print_results(&self, ips: &[IpAddr]) {
+        let selected = self.selected(ips);
+        if selected.is_empty() {
+            detail!("No open ports.", self.opts.greppable, self.opts.accessible);
+        }
+        for (ip, ports) in selected {
+            let ports: Vec<String> = ports.iter().map(ToString::to_string).collect();
+            println!("{ip} -> [{}]", ports.join(","));
+        }
+    }
+
+    fn run_scripts(&self, ips: &[IpAddr]) {
+        let scripts = match init_scripts(&self.opts.scripts) {
+            Ok(scripts) => scripts,
+            Err(e) => {
+                warning!(
+                    format!("Initiating scripts failed!\n{e}"),
+                    self.opts.greppable,
+                    self.opts.accessible
+                );
+                return;
+            }
+        };
+
+        for (ip, ports) in self.selected(ips) {
+            let command = self
+                .opts
+                .command_for(self.targets.tags.get(&ip).map_or(&[][..], Vec::as_slice));
+            for script_f in scripts.clone() {
+                let call_format = match (script_f.call_format, command.is_empty()) {
+                    (Some(call_format), false) => {
+                        Some(format!("{call_format} {}", command.join(" ")))
+                    }
+                    (call_format, _) => call_format,
+                };
+                let mut script = Script::build(
+                    script_f.path,
+                    ip,
+                    ports.clone(),
+                    script_f.port,
+                    script_f.ports_separator,
+                    script_f.tags,
+                    call_format,
+                );
+                if self.opts.sandbox_scripts {
+                    script = script.with_sandbox(script_f.sandbox());
+                }
+                if let Some(user) = script_f.user {
+                    script = script.with_user(user);
+                }
+                match script.run() {
+                    Ok(result) => detail!(result, self.opts.greppable, self.opts.accessible),
+                    Err(e) => warning!(
+                        format!("Error {e}"),
+                        self.opts.greppable,
+                        self.opts.accessible
+                    ),
+                }
+            }
+        }
+    }
+
+    fn show(&self) {
+        let ports = match (&self.opts.ports, &self.opts.range) {
+            (Some(ports), _) => {
+                let ports: Vec<String> = ports.iter().map(ToString::to_string).collect();
+                ports.join(",")
+            }
+            (None, Some(range)) => format!("{}-{}", range.start, range.end),
+            (None, None) => "1-65535".to_owned(),
+        };
+        println!("targets:    {}", self.opts.addresses.join(" "));
+        println!("addresses:  {}", self.targets.ips.len());
+        println!("ports:      {ports}");
+        println!("timeout:    {}ms", self.opts.timeout);
+        println!("batch-size: {}", self.opts.batch_size);
+        println!("tries:      {}", self.opts.tries);
+    }
+}
+
+/// Runs the shell on stdin until `exit` or the end of input. `opts` holds
+/// the settings the session starts with.
+pub fn run(opts: Opts) {
+    let mut session = Session::new(opts);
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        print!("{PROMPT}");
+        let _ = io::stdout().flush();
+        let Some(Ok(line)) = lines.next() else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Command::parse(&line) {
+            Ok(command) => {
+                if !session.execute(command) {
+                    break;
+                }
+            }
+            Err(e) => warning!(e, session.opts.greppable, session.opts.accessible),
+        }
+    }
+
+    if let Some(path) = LearningStore::default_path().filter(|_| session.opts.learns()) {
+        if let Err(e) = session.learning.save(&path) {
+            warning!(
+                format!("Could not save what was learned to {path:?}: {e}"),
+                session.opts.greppable,
+                session.opts.accessible
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Setting};
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            Command::parse("targets 10.0.0.1,example.com 10.0.1.0/24"),
+            Ok(Command::Targets(vec![
+                "10.0.0.1".to_owned(),
+                "example.com".to_owned(),
+                "10.0.1.0/24".to_owned()
+            ]))
+        );
+        assert_eq!(
+            Command::parse("ports 22,80-82"),
+            Ok(Command::Ports(vec![22, 80, 81, 82]))
+        );
+        assert_eq!(
+            Command::parse("set timeout 500"),
+            Ok(Command::Set(Setting::Timeout(500)))
+        );
+        assert_eq!(
+            Command::parse("scripts 10.0.0.1"),
+            Ok(Command::Scripts(vec!["10.0.0.1".parse().unwrap()]))
+        );
+        assert_eq!(Command::parse("results"), Ok(Command::Results(vec![])));
+        assert_eq!(Command::parse("quit"), Ok(Command::Exit));
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        assert!(Command::parse("targets").is_err());
+        assert!(Command::parse("ports nope").is_err());
+        assert!(Command::parse("set timeout soon").is_err());
+        assert!(Command::parse("set colour red").is_err());
+        assert!(Command::parse("results not-an-ip").is_err());
+        assert!(Command::parse("scan now").is_err());
+        assert!(Command::parse("launch").is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use super::event_json;
+use crate::scanner::{ResultSink, ScanEvent};
+
+use log::debug;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Writes events to a file as JSON lines, flushing each one so the file
+/// can be followed while the scan runs.
+#[derive(Debug)]
+pub struct JsonSink {
+    file: Mutex<File>,
+}
+
+impl JsonSink {
+    /// Creates the file, replacing it if it already exists.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            file: Mutex::new(File::create(path)?),
+        })
+    }
+}
+
+impl ResultSink for JsonSink {
+    fn emit(&self, event: &ScanEvent) {
+        let line = format!("{}\n", event_json(event));
+        if let Ok(mut file) = self.file.lock() {
+            if let Err(e) = file.write_all(line.as_bytes()).and_then(|()| file.flush()) {
+                debug!("Could not write {line:?} to the JSON output: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonSink;
+    use crate::output::Protocol;
+    use crate::scanner::{ResultSink, ScanEvent};
+    use std::fs;
+
+    #[test]
+    fn writes_json_lines() {
+        let path = std::env::temp_dir().join(format!("rustscan-json-sink-{}", std::process::id()));
+        let sink = JsonSink::create(&path).unwrap();
+
+        sink.emit(&ScanEvent::Open(
+            "127.0.0.1:22".parse().unwrap(),
+            Protocol::Tcp,
+        ));
+        sink.emit(&ScanEvent::Open(
+            "127.0.0.1:80".parse().unwrap(),
+            Protocol::Tcp,
+        ));
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.ends_with("\"port\":80}\n"));
+    }
+}
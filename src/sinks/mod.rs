@@ -0,0 +1,85 @@
+//! Result sinks that send scan events somewhere else than the terminal,
+//! selected with `--output`.
+//!
+//! Each event is written as a single line of JSON:
+//!
+//! ```json
+//! {"event":"open","ip":"127.0.0.1","port":22,"protocol":"tcp"}
+//! ```
+//!
+//! and the scan ends with a `summary` event holding the [`ScanSummary`].
+//!
+//! [`ScanSummary`]: crate::scanner::ScanSummary
+use crate::input::OutputSpec;
+use crate::scanner::{NullSink, ResultSink, ScanEvent};
+
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use std::io;
+
+mod json;
+mod webhook;
+
+pub use json::JsonSink;
+pub use webhook::WebhookSink;
+
+/// Opens the sink an `--output` flag asks for.
+pub fn open(spec: &OutputSpec) -> io::Result<Box<dyn ResultSink>> {
+    Ok(match spec {
+        OutputSpec::Json(path) => Box::new(JsonSink::create(path)?),
+        OutputSpec::Webhook(url) => Box::new(WebhookSink::new(url)?),
+        // Reports are written once the scan is over, see crate::output.
+        OutputSpec::Report(_) | OutputSpec::NmapXml(_) | OutputSpec::Csv(_) => Box::new(NullSink),
+    })
+}
+
+/// The JSON form of an event.
+pub fn event_json(event: &ScanEvent) -> Value {
+    match event {
+        ScanEvent::Open(socket, protocol) => json!({
+            "event": "open",
+            "ip": socket.ip().to_string(),
+            "port": socket.port(),
+            "protocol": protocol,
+        }),
+        ScanEvent::LocalError { socket, error } => json!({
+            "event": "local_error",
+            "ip": socket.ip().to_string(),
+            "port": socket.port(),
+            "error": error.to_string(),
+        }),
+        ScanEvent::Finished(summary) => json!({
+            "event": "summary",
+            "hosts_scanned": summary.hosts_scanned,
+            "hosts_with_open_ports": summary.hosts_with_open_ports,
+            "open_ports": summary.open_ports,
+            "probes": summary.probes,
+            "closed": summary.closed,
+            "timed_out": summary.timed_out,
+            "errors": summary.errors,
+            "duration_ms": u64::try_from(summary.duration.as_millis()).unwrap_or(u64::MAX),
+            "rate": summary.rate(),
+            "coverage": summary.coverage.map(|coverage| json!({
+                "sampled": coverage.sampled,
+                "population": u64::try_from(coverage.population).unwrap_or(u64::MAX),
+                "seed": coverage.seed,
+            })),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::event_json;
+    use crate::output::Protocol;
+    use crate::scanner::ScanEvent;
+
+    #[test]
+    fn open_ports_as_json() {
+        let event = ScanEvent::Open("192.168.0.1:443".parse().unwrap(), Protocol::Tcp);
+        assert_eq!(
+            event_json(&event).to_string(),
+            r#"{"event":"open","ip":"192.168.0.1","port":443,"protocol":"tcp"}"#
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use super::event_json;
+use crate::scanner::{ResultSink, ScanEvent};
+
+use log::debug;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How long a webhook request may take.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Events waiting to be sent, the ones found while the queue is full are
+/// dropped.
+const QUEUE_SIZE: usize = 10_000;
+
+/// The most events sent in a single request.
+const MAX_BATCH: usize = 1_000;
+
+/// How long dropping the sink waits for the queued events to be sent.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often dropping the sink checks whether the queue was sent.
+const DRAIN_POLL: Duration = Duration::from_millis(10);
+
+/// POSTs the events as a JSON array to an `http://` URL.
+///
+/// Requests are sent one at a time from a background thread so a slow
+/// endpoint never holds up the scan, each with the events queued since the
+/// previous one. Once the endpoint can't be reached the remaining events
+/// are dropped. Dropping the sink waits for the events still queued to be
+/// sent, for [`DRAIN_TIMEOUT`] at most.
+#[derive(Debug)]
+pub struct WebhookSink {
+    sender: Mutex<Option<SyncSender<String>>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// Where a webhook URL points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str) -> io::Result<Self> {
+        let endpoint = Endpoint::parse(url)?;
+        let (sender, receiver) = mpsc::sync_channel::<String>(QUEUE_SIZE);
+        let worker = thread::spawn(move || {
+            while let Ok(event) = receiver.recv() {
+                let mut events = vec![event];
+                events.extend(receiver.try_iter().take(MAX_BATCH - 1));
+                let stream = match endpoint.connect() {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        debug!(
+                            "Webhook {}:{} is unreachable, dropping its events: {e}",
+                            endpoint.host, endpoint.port
+                        );
+                        return;
+                    }
+                };
+                if let Err(e) = endpoint.post(stream, &format!("[{}]", events.join(","))) {
+                    debug!(
+                        "Webhook request to {}:{} failed: {e}",
+                        endpoint.host, endpoint.port
+                    );
+                }
+            }
+        });
+        Ok(Self {
+            sender: Mutex::new(Some(sender)),
+            worker: Some(worker),
+        })
+    }
+}
+
+impl ResultSink for WebhookSink {
+    fn emit(&self, event: &ScanEvent) {
+        if let Ok(sender) = self.sender.lock() {
+            if let Some(sender) = sender.as_ref() {
+                if let Err(mpsc::TrySendError::Full(_)) =
+                    sender.try_send(event_json(event).to_string())
+                {
+                    debug!("Webhook queue is full, dropping an event");
+                }
+            }
+        }
+    }
+}
+
+impl Drop for WebhookSink {
+    fn drop(&mut self) {
+        if let Ok(mut sender) = self.sender.lock() {
+            sender.take();
+        }
+        if let Some(worker) = self.worker.take() {
+            let deadline = Instant::now() + DRAIN_TIMEOUT;
+            while !worker.is_finished() && Instant::now() < deadline {
+                thread::sleep(DRAIN_POLL);
+            }
+            if worker.is_finished() {
+                let _ = worker.join();
+            } else {
+                debug!("Gave up sending the queued webhook events");
+            }
+        }
+    }
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> io::Result<Self> {
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid webhook URL {url}: {reason}"),
+            )
+        };
+
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| invalid("only http:// URLs are supported"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+        // IPv6 hosts are bracketed, [::1]:8080, so look for the port after
+        // the closing bracket.
+        let port_start = authority.rfind(']').unwrap_or(0);
+        let (host, port) = match authority[port_start..].rfind(':') {
+            Some(index) => {
+                let (host, port) = authority.split_at(port_start + index);
+                let port = port[1..]
+                    .parse()
+                    .map_err(|_| invalid("the port is not a number"))?;
+                (host, port)
+            }
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid("the host is missing"));
+        }
+
+        Ok(Self {
+            host: host.to_owned(),
+            port,
+            path: path.to_owned(),
+        })
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let host = self.host.trim_start_matches('[').trim_end_matches(']');
+        let address = (host, self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the host did not resolve"))?;
+        let stream = TcpStream::connect_timeout(&address, WEBHOOK_TIMEOUT)?;
+        stream.set_read_timeout(Some(WEBHOOK_TIMEOUT))?;
+        stream.set_write_timeout(Some(WEBHOOK_TIMEOUT))?;
+        Ok(stream)
+    }
+
+    /// The `Host` header, with the port unless it is the default one.
+    fn host_header(&self) -> String {
+        if self.port == 80 {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    fn post(&self, mut stream: TcpStream, body: &str) -> io::Result<()> {
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            self.path,
+            self.host_header(),
+            body.len()
+        )?;
+
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status)?;
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "unexpected response {:?}",
+                status.trim()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Endpoint, WebhookSink};
+    use crate::output::Protocol;
+    use crate::scanner::{ResultSink, ScanEvent};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn parses_http_urls() {
+        assert_eq!(
+            Endpoint::parse("http://hooks.local:8080/rustscan").unwrap(),
+            Endpoint {
+                host: "hooks.local".to_owned(),
+                port: 8080,
+                path: "/rustscan".to_owned()
+            }
+        );
+        assert_eq!(Endpoint::parse("http://hooks.local").unwrap().port, 80);
+        assert_eq!(Endpoint::parse("http://[::1]:9000/").unwrap().host, "[::1]");
+        assert_eq!(Endpoint::parse("http://[::1]/").unwrap().port, 80);
+        assert!(Endpoint::parse("https://hooks.local").is_err());
+        assert!(Endpoint::parse("http://hooks.local:http/").is_err());
+
+        assert_eq!(
+            Endpoint::parse("http://hooks.local/")
+                .unwrap()
+                .host_header(),
+            "hooks.local"
+        );
+        assert_eq!(
+            Endpoint::parse("http://[::1]:9000/").unwrap().host_header(),
+            "[::1]:9000"
+        );
+    }
+
+    #[test]
+    fn posts_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let url = format!("http://{host}/hook");
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+                if let Some(value) = line.strip_prefix("Host: ") {
+                    assert_eq!(value.trim(), host);
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .unwrap();
+            String::from_utf8(body).unwrap()
+        });
+
+        let sink = WebhookSink::new(&url).unwrap();
+        sink.emit(&ScanEvent::Open(
+            "127.0.0.1:22".parse().unwrap(),
+            Protocol::Tcp,
+        ));
+        drop(sink);
+
+        assert_eq!(
+            server.join().unwrap(),
+            r#"[{"event":"open","ip":"127.0.0.1","port":22,"protocol":"tcp"}]"#
+        );
+    }
+
+    #[test]
+    fn unreachable_endpoints_do_not_hold_up_the_exit() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+
+        let sink = WebhookSink::new(&url).unwrap();
+        for port in 1..=1000 {
+            sink.emit(&ScanEvent::Open(
+                ([127, 0, 0, 1], port).into(),
+                Protocol::Tcp,
+            ));
+        }
+        let start = Instant::now();
+        drop(sink);
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
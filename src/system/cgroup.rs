@@ -0,0 +1,127 @@
+//! Resource limits of the cgroup (v2) RustScan runs in, usually a container.
+//!
+//! `docker run rustscan` gets a generous file limit from the runtime but
+//! often a tight memory limit, and every socket of a batch takes kernel
+//! memory that is charged to the cgroup. Going over it gets the scan killed
+//! instead of slowed down, so batches and socket buffers are sized to fit.
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Memory charged per socket of a batch, a rough upper bound of the kernel
+/// socket structures plus small buffers.
+const MEMORY_PER_SOCKET: u64 = 16 * 1024;
+
+/// Share of the available memory the sockets of a batch may take.
+const MEMORY_SHARE: u64 = 4;
+
+/// Below this much available memory, socket buffers are shrunk.
+const TIGHT_MEMORY: u64 = 512 * 1024 * 1024;
+
+/// Socket buffer size used when memory is tight. A connect scan sends and
+/// receives next to nothing.
+const TIGHT_BUFFER_SIZE: usize = 4096;
+
+/// Limits of the current cgroup. Fields are `None` when unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CgroupLimits {
+    /// `memory.max`, in bytes.
+    pub memory_max: Option<u64>,
+    /// `memory.current`, in bytes.
+    pub memory_current: Option<u64>,
+    /// `pids.max`, threads count against it too.
+    pub pids_max: Option<u64>,
+}
+
+impl CgroupLimits {
+    /// Reads the limits of the cgroup this process is in. `None` when not
+    /// running under cgroup v2, or when nothing is limited.
+    pub fn detect() -> Option<Self> {
+        let membership = fs::read_to_string("/proc/self/cgroup").ok()?;
+        let dir = Path::new(CGROUP_ROOT).join(cgroup_path(&membership)?);
+        let limits = Self::read(&dir);
+
+        (limits.memory_max.is_some() || limits.pids_max.is_some()).then_some(limits)
+    }
+
+    fn read(dir: &Path) -> Self {
+        let read =
+            |file: &str| -> Option<u64> { parse_limit(&fs::read_to_string(dir.join(file)).ok()?) };
+        Self {
+            memory_max: read("memory.max"),
+            memory_current: read("memory.current"),
+            pids_max: read("pids.max"),
+        }
+    }
+
+    /// Memory left before the cgroup limit is hit.
+    pub fn available_memory(&self) -> Option<u64> {
+        let max = self.memory_max?;
+        Some(max.saturating_sub(self.memory_current.unwrap_or_default()))
+    }
+
+    /// The largest batch whose sockets fit in a share of the memory left.
+    pub fn max_batch_size(&self) -> Option<usize> {
+        let sockets = self.available_memory()? / MEMORY_SHARE / MEMORY_PER_SOCKET;
+        Some(usize::try_from(sockets).unwrap_or(usize::MAX).max(1))
+    }
+
+    /// A smaller socket buffer size to use when memory is tight.
+    pub fn socket_buffer_size(&self) -> Option<usize> {
+        (self.available_memory()? < TIGHT_MEMORY).then_some(TIGHT_BUFFER_SIZE)
+    }
+}
+
+/// Finds the cgroup v2 path in `/proc/self/cgroup`, the `0::/path` line.
+fn cgroup_path(membership: &str) -> Option<PathBuf> {
+    membership
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| PathBuf::from(path.trim().trim_start_matches('/')))
+}
+
+/// Parses a cgroup limit file, where "max" means unlimited.
+fn parse_limit(content: &str) -> Option<u64> {
+    match content.trim() {
+        "max" => None,
+        limit => limit.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cgroup_path, parse_limit, CgroupLimits, TIGHT_BUFFER_SIZE};
+    use std::path::PathBuf;
+
+    #[test]
+    fn finds_the_unified_hierarchy() {
+        let membership = "12:pids:/docker/abc\n0::/system.slice/docker-abc.scope\n";
+        assert_eq!(
+            cgroup_path(membership),
+            Some(PathBuf::from("system.slice/docker-abc.scope"))
+        );
+        assert_eq!(cgroup_path("0::/\n"), Some(PathBuf::from("")));
+        assert_eq!(cgroup_path("4:memory:/user.slice\n"), None);
+    }
+
+    #[test]
+    fn max_means_unlimited() {
+        assert_eq!(parse_limit("max\n"), None);
+        assert_eq!(parse_limit("536870912\n"), Some(536_870_912));
+    }
+
+    #[test]
+    fn sizes_batches_to_available_memory() {
+        let limits = CgroupLimits {
+            memory_max: Some(256 * 1024 * 1024),
+            memory_current: Some(64 * 1024 * 1024),
+            pids_max: None,
+        };
+
+        assert_eq!(limits.max_batch_size(), Some(3_072));
+        assert_eq!(limits.socket_buffer_size(), Some(TIGHT_BUFFER_SIZE));
+        assert_eq!(CgroupLimits::default().max_batch_size(), None);
+    }
+}
@@ -0,0 +1,56 @@
+//! Netfilter connection tracking on Linux.
+//!
+//! Every connect of a scan takes an entry in the conntrack table when the
+//! machine does NAT or runs a stateful firewall. Once the table is full, new
+//! SYNs are silently dropped and the ports behind them look filtered.
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+const COUNT_PATH: &str = "/proc/sys/net/netfilter/nf_conntrack_count";
+const MAX_PATH: &str = "/proc/sys/net/netfilter/nf_conntrack_max";
+
+/// A snapshot of the conntrack table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conntrack {
+    /// Connections tracked right now.
+    pub count: usize,
+    /// Connections the table can hold.
+    pub max: usize,
+}
+
+impl Conntrack {
+    /// Reads the table from procfs. `None` when conntrack isn't loaded or
+    /// this isn't Linux.
+    pub fn read() -> Option<Self> {
+        let read = |path: &str| -> Option<usize> {
+            fs::read_to_string(Path::new(path))
+                .ok()?
+                .trim()
+                .parse()
+                .ok()
+        };
+        let max = read(MAX_PATH)?;
+        (max > 0).then_some(Self {
+            count: read(COUNT_PATH)?,
+            max,
+        })
+    }
+
+    /// How full the table is, from 0 to 1.
+    pub fn usage(&self) -> f64 {
+        self.count as f64 / self.max as f64
+    }
+}
+
+impl fmt::Display for Conntrack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} entries ({:.0}%)",
+            self.count,
+            self.max,
+            self.usage() * 100.0
+        )
+    }
+}
@@ -0,0 +1,137 @@
+//! File limits on macOS.
+//!
+//! macOS starts processes with a soft limit of 256 open files and reports an
+//! unlimited hard limit, but refuses any limit above `kern.maxfilesperproc`.
+//! Asking for more than that fails, so the limit has to be read before it
+//! can be raised.
+use std::process::Command;
+
+/// The kernel and launchd limits on open files, as far as they could be read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileLimits {
+    /// Open files allowed across the whole system, `kern.maxfiles`.
+    pub maxfiles: Option<usize>,
+    /// Open files allowed per process, `kern.maxfilesperproc`.
+    pub maxfilesperproc: Option<usize>,
+    /// The soft limit launchd gives the processes it starts.
+    pub launchd_soft: Option<usize>,
+}
+
+impl FileLimits {
+    /// Reads the limits through sysctl and `launchctl limit maxfiles`.
+    pub fn detect() -> Self {
+        Self {
+            maxfiles: sysctl("kern.maxfiles"),
+            maxfilesperproc: sysctl("kern.maxfilesperproc"),
+            launchd_soft: launchd_soft_limit(),
+        }
+    }
+
+    /// The highest open file limit the kernel accepts for this process.
+    pub fn max_nofile(&self) -> Option<usize> {
+        match (self.maxfiles, self.maxfilesperproc) {
+            (Some(system), Some(process)) => Some(system.min(process)),
+            (system, process) => system.or(process),
+        }
+    }
+
+    /// How to raise the limits for good, with the values read from this
+    /// system.
+    pub fn guidance(&self) -> String {
+        let current = |limit: Option<usize>| {
+            limit.map_or_else(|| "unknown".to_owned(), |limit| limit.to_string())
+        };
+        format!(
+            "macOS limits open files with kern.maxfiles ({}), kern.maxfilesperproc ({}) and launchd ({}). \
+             Raise them with 'sudo sysctl -w kern.maxfiles=65536 kern.maxfilesperproc=65536' and \
+             'sudo launchctl limit maxfiles 65536 65536', then open a new terminal.",
+            current(self.maxfiles),
+            current(self.maxfilesperproc),
+            current(self.launchd_soft)
+        )
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn sysctl(name: &str) -> Option<usize> {
+    use std::ffi::CString;
+    use std::os::raw::c_int;
+
+    let name = CString::new(name).ok()?;
+    let mut value: c_int = 0;
+    let mut size = std::mem::size_of::<c_int>();
+    // SAFETY: name is NUL terminated, value and size outlive the call and
+    // size matches the buffer behind value.
+    let ret = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr(),
+            (&mut value as *mut c_int).cast(),
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return None;
+    }
+    usize::try_from(value).ok()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn sysctl(_name: &str) -> Option<usize> {
+    None
+}
+
+fn launchd_soft_limit() -> Option<usize> {
+    if !cfg!(target_os = "macos") {
+        return None;
+    }
+
+    let output = Command::new("launchctl")
+        .args(["limit", "maxfiles"])
+        .output()
+        .ok()?;
+    parse_launchctl_limit(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parses the soft limit out of `launchctl limit maxfiles`, which prints
+/// `maxfiles    256            unlimited`.
+fn parse_launchctl_limit(output: &str) -> Option<usize> {
+    let mut fields = output.split_whitespace();
+    if fields.next()? != "maxfiles" {
+        return None;
+    }
+    fields.next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_launchctl_limit, FileLimits};
+
+    #[test]
+    fn parses_launchctl_output() {
+        assert_eq!(
+            parse_launchctl_limit("\tmaxfiles    256            unlimited      \n"),
+            Some(256)
+        );
+        assert_eq!(parse_launchctl_limit("maxproc 2784 4176"), None);
+        assert_eq!(parse_launchctl_limit(""), None);
+    }
+
+    #[test]
+    fn max_nofile_is_the_tightest_limit() {
+        let limits = FileLimits {
+            maxfiles: Some(122_880),
+            maxfilesperproc: Some(61_440),
+            launchd_soft: Some(256),
+        };
+        assert_eq!(limits.max_nofile(), Some(61_440));
+
+        let limits = FileLimits {
+            maxfilesperproc: None,
+            ..limits
+        };
+        assert_eq!(limits.max_nofile(), Some(122_880));
+        assert_eq!(FileLimits::default().max_nofile(), None);
+    }
+}
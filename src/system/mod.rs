@@ -0,0 +1,7 @@
+//! Detects the limits the host system puts on scanning, such as how many
+//! sockets can be open at once, so batch sizes can be picked to fit them.
+pub mod cgroup;
+pub mod conntrack;
+pub mod macos;
+pub mod resource_limits;
+pub mod windows;
@@ -0,0 +1,108 @@
+//! How many sockets this process can have open at once, and batch sizes
+//! that fit.
+//!
+//! Every probe in flight holds a socket, so a batch size above the open
+//! file limit (`RLIMIT_NOFILE`) makes probes fail with "Too many open
+//! files". Windows has no such limit, the dynamic port range caps the batch
+//! size there instead, see [`SocketLimits`](super::windows::SocketLimits).
+#[cfg(unix)]
+use std::convert::TryFrom;
+#[cfg(unix)]
+use std::io;
+
+/// The open file limit of most Linux distributions.
+pub const DEFAULT_FILE_DESCRIPTORS_LIMIT: usize = 8000;
+
+/// The safest batch size, based on experimentation.
+pub const AVERAGE_BATCH_SIZE: usize = 3000;
+
+/// The soft and hard open file limits of this process.
+#[cfg(unix)]
+pub fn open_file_limits() -> io::Result<(u64, u64)> {
+    rlimit::Resource::NOFILE.get()
+}
+
+/// Sets both open file limits to `limit`, returning the limit actually
+/// set: macOS refuses anything above `kern.maxfilesperproc`, so the limit
+/// is lowered to that there.
+#[cfg(unix)]
+pub fn set_open_file_limit(limit: u64) -> io::Result<u64> {
+    #[cfg(target_os = "macos")]
+    let limit = match super::macos::FileLimits::detect().max_nofile() {
+        Some(max) => limit.min(max as u64),
+        None => limit,
+    };
+    rlimit::Resource::NOFILE.set(limit, limit)?;
+    Ok(limit)
+}
+
+/// Raises the soft open file limit as far as the hard limit and the kernel
+/// allow, returning the soft limit before and after. macOS starts
+/// processes with only 256 open files even though it allows far more.
+#[cfg(unix)]
+pub fn raise_soft_limit() -> io::Result<(u64, u64)> {
+    let (soft, hard) = open_file_limits()?;
+    #[cfg(target_os = "macos")]
+    let target = match super::macos::FileLimits::detect().max_nofile() {
+        Some(max) => hard.min(max as u64),
+        None => hard,
+    };
+    #[cfg(not(target_os = "macos"))]
+    let target = hard;
+    if target <= soft {
+        return Ok((soft, soft));
+    }
+    rlimit::Resource::NOFILE.set(target, hard)?;
+    Ok((soft, target))
+}
+
+/// The batch size to use instead of `requested` when only `limit` files
+/// can be open at once: `requested` when it fits, something leaving room
+/// for the rest of the process otherwise.
+///
+/// ```rust
+/// # use rustscan::system::resource_limits::fit_batch_size;
+/// assert_eq!(fit_batch_size(4_500, 1_048_576), 4_500);
+/// assert_eq!(fit_batch_size(4_500, 1_024), 512);
+/// ```
+pub fn fit_batch_size(requested: usize, limit: usize) -> usize {
+    if limit >= requested {
+        requested
+    } else if limit < AVERAGE_BATCH_SIZE {
+        (limit / 2).max(1)
+    } else if limit > DEFAULT_FILE_DESCRIPTORS_LIMIT {
+        AVERAGE_BATCH_SIZE
+    } else {
+        limit - 100
+    }
+}
+
+/// A batch size of at most `requested` that this system can have open at
+/// once: under the soft open file limit on Unix and the dynamic port range
+/// on Windows.
+pub fn safe_batch_size(requested: usize) -> usize {
+    #[cfg(unix)]
+    let safe = match open_file_limits() {
+        Ok((soft, _)) => fit_batch_size(requested, usize::try_from(soft).unwrap_or(usize::MAX)),
+        Err(_) => requested.min(AVERAGE_BATCH_SIZE),
+    };
+    #[cfg(windows)]
+    let safe = requested.min(super::windows::SocketLimits::detect().safe_batch_size());
+    #[cfg(not(any(unix, windows)))]
+    let safe = requested.min(AVERAGE_BATCH_SIZE);
+    safe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fit_batch_size;
+
+    #[test]
+    fn batch_sizes_fit_the_limit() {
+        assert_eq!(fit_batch_size(50_000, 120), 60);
+        assert_eq!(fit_batch_size(50_000, 1), 1);
+        assert_eq!(fit_batch_size(50_000, 9_000), 3_000);
+        assert_eq!(fit_batch_size(50_000, 5_000), 4_900);
+        assert_eq!(fit_batch_size(10, 1_000_000), 10);
+    }
+}
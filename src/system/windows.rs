@@ -0,0 +1,128 @@
+//! Socket limits on Windows.
+//!
+//! Windows has no file descriptor limit to raise with `--ulimit`. What caps
+//! how many connects can be in flight is the dynamic port range, which every
+//! program on the machine shares and which TIME_WAIT holds on to, and to a
+//! lesser extent how many handles the process already has open.
+use std::process::Command;
+
+/// Size of the default dynamic port range (49152-65535) since Windows Vista.
+pub const DEFAULT_DYNAMIC_PORTS: usize = 16_384;
+
+/// Handles a process can have open before the system starts struggling,
+/// far below the 2^24 hard limit.
+const HANDLE_PRESSURE: u32 = 100_000;
+
+/// What the system allows, as far as it could be detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketLimits {
+    /// Ports available for outgoing connections.
+    pub dynamic_ports: usize,
+    /// Handles this process has open.
+    pub open_handles: Option<u32>,
+}
+
+impl SocketLimits {
+    /// Queries the dynamic port range and the process handle count, falling
+    /// back to Windows defaults for whatever can't be read.
+    pub fn detect() -> Self {
+        Self {
+            dynamic_ports: dynamic_ports().unwrap_or(DEFAULT_DYNAMIC_PORTS),
+            open_handles: open_handles(),
+        }
+    }
+
+    /// A batch size leaving half of the dynamic ports to TIME_WAIT and the
+    /// other programs on the machine.
+    pub fn safe_batch_size(&self) -> usize {
+        (self.dynamic_ports / 2).max(1)
+    }
+
+    /// Whether the process already has enough handles open to slow the
+    /// system down if more sockets pile on.
+    pub fn handle_pressure(&self) -> bool {
+        self.open_handles
+            .is_some_and(|handles| handles >= HANDLE_PRESSURE)
+    }
+}
+
+/// Reads the number of TCP dynamic ports from `netsh`.
+fn dynamic_ports() -> Option<usize> {
+    if !cfg!(windows) {
+        return None;
+    }
+
+    let output = Command::new("netsh")
+        .args(["int", "ipv4", "show", "dynamicport", "tcp"])
+        .output()
+        .ok()?;
+    parse_dynamic_port_count(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parses the output of `netsh int ipv4 show dynamicport tcp`:
+///
+/// ```text
+/// Protocol tcp Dynamic Port Range
+/// ---------------------------------
+/// Start Port      : 49152
+/// Number of Ports : 16384
+/// ```
+fn parse_dynamic_port_count(netsh: &str) -> Option<usize> {
+    netsh
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("Number of Ports"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+}
+
+#[cfg(windows)]
+fn open_handles() -> Option<u32> {
+    use std::ffi::c_void;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+        fn GetProcessHandleCount(process: *mut c_void, count: *mut u32) -> i32;
+    }
+
+    let mut count = 0;
+    // SAFETY: GetCurrentProcess returns a pseudo handle that needs no
+    // closing, and count outlives the call.
+    let ok = unsafe { GetProcessHandleCount(GetCurrentProcess(), &mut count) };
+    (ok != 0).then_some(count)
+}
+
+#[cfg(not(windows))]
+fn open_handles() -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_dynamic_port_count, SocketLimits, DEFAULT_DYNAMIC_PORTS};
+
+    #[test]
+    fn parses_netsh_output() {
+        let netsh = "\r\nProtocol tcp Dynamic Port Range\r\n\
+                     ---------------------------------\r\n\
+                     Start Port      : 10000\r\n\
+                     Number of Ports : 55535\r\n";
+
+        assert_eq!(parse_dynamic_port_count(netsh), Some(55_535));
+        assert_eq!(
+            parse_dynamic_port_count("The requested operation requires elevation."),
+            None
+        );
+    }
+
+    #[test]
+    fn safe_batch_size_leaves_room_for_time_wait() {
+        let limits = SocketLimits {
+            dynamic_ports: DEFAULT_DYNAMIC_PORTS,
+            open_handles: Some(200),
+        };
+
+        assert_eq!(limits.safe_batch_size(), 8_192);
+        assert!(!limits.handle_pressure());
+    }
+}
@@ -1,4 +1,12 @@
 //! Utilities for terminal output during scanning.
+//!
+//! The `warning!`, `detail!` and `output!` macros are always available, the
+//! open ports and progress bar printed while scanning need the `tui`
+//! feature.
+#[cfg(feature = "tui")]
+mod terminal;
+#[cfg(feature = "tui")]
+pub use terminal::{ProgressBar, TerminalSink};
 
 /// Terminal User Interface Module for RustScan
 /// Defines macros to use
@@ -64,6 +72,7 @@ macro_rules! output {
     };
 }
 
+#[cfg(feature = "banner")]
 #[macro_export]
 macro_rules! funny_opening {
     // prints a funny quote / opening
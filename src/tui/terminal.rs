@@ -0,0 +1,137 @@
+//! Open ports and the progress of a scan, printed as the scan goes.
+use crate::scanner::{ResultSink, ScanEvent, ScanProgress};
+use crate::services;
+use colored::Colorize;
+use std::io::Write;
+
+/// Moves the cursor to the start of the line and clears it.
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// Prints open ports to the terminal as they are found, the way RustScan
+/// always has.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TerminalSink {
+    accessible: bool,
+    numeric: bool,
+    progress_bar: bool,
+    show_protocol: bool,
+}
+
+impl TerminalSink {
+    /// `accessible` prints without colors.
+    pub fn new(accessible: bool) -> Self {
+        Self {
+            accessible,
+            numeric: false,
+            progress_bar: false,
+            show_protocol: false,
+        }
+    }
+
+    /// Leaves out the name of the service usually running on open ports.
+    #[must_use]
+    pub fn numeric(mut self, numeric: bool) -> Self {
+        self.numeric = numeric;
+        self
+    }
+
+    /// Follows open ports with their protocol, like `10.0.0.1:53/udp`, for
+    /// scans over both TCP and UDP.
+    #[must_use]
+    pub fn show_protocol(mut self, show_protocol: bool) -> Self {
+        self.show_protocol = show_protocol;
+        self
+    }
+
+    /// Clears the line of a [`ProgressBar`] before printing an open port,
+    /// so the bar is redrawn under it.
+    #[must_use]
+    pub fn progress_bar(mut self, progress_bar: bool) -> Self {
+        self.progress_bar = progress_bar;
+        self
+    }
+}
+
+impl ResultSink for TerminalSink {
+    fn emit(&self, event: &ScanEvent) {
+        if let ScanEvent::Open(socket, protocol) = event {
+            let service = match services::service_name(socket.port(), *protocol) {
+                Some(name) if !self.numeric => format!(" ({name})"),
+                _ => String::new(),
+            };
+            let socket = if self.show_protocol {
+                format!("{socket}/{protocol}")
+            } else {
+                socket.to_string()
+            };
+            if self.progress_bar {
+                print!("{CLEAR_LINE}");
+            }
+            if self.accessible {
+                println!("Open {socket}{service}");
+            } else {
+                println!("Open {}{service}", socket.purple());
+            }
+        }
+    }
+}
+
+/// Draws the progress of a scan on the last line of the terminal, redrawn
+/// in place on every update.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgressBar {
+    width: usize,
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        Self { width: 30 }
+    }
+}
+
+impl ProgressBar {
+    /// The bar and the numbers behind it, without moving the cursor.
+    pub fn render(&self, progress: &ScanProgress) -> String {
+        let filled = (progress.fraction() * self.width as f64) as usize;
+        format!(
+            "[{}{}] {progress}",
+            "=".repeat(filled),
+            " ".repeat(self.width - filled)
+        )
+    }
+
+    /// Draws the bar to stderr over the previous one.
+    pub fn draw(&self, progress: &ScanProgress) {
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "{CLEAR_LINE}{}", self.render(progress));
+        let _ = stderr.flush();
+    }
+
+    /// Removes the bar once the scan is over.
+    pub fn clear(&self) {
+        eprint!("{CLEAR_LINE}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProgressBar;
+    use crate::scanner::ScanProgress;
+    use std::time::Duration;
+
+    #[test]
+    fn bar_fills_with_progress() {
+        let progress = ScanProgress {
+            scanned: 50,
+            total: 100,
+            open: 1,
+            elapsed: Duration::from_secs(2),
+            estimated: Duration::ZERO,
+        };
+
+        assert_eq!(
+            ProgressBar::default().render(&progress),
+            "[===============               ] 50/100 sockets (50%), 1 open, 2s, about 2s left"
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Guided setup, `rustscan wizard`.
+//!
+//! Asks a few questions about what is being scanned and turns the answers
+//! into a RustScan command line. Every flag of the command is explained, so
+//! the wizard doubles as an introduction to the options that matter most.
+use std::io::{self, BufRead, Write};
+
+/// Whether the targets are on a network close by or across the internet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Internal,
+    External,
+}
+
+/// What matters most for the scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    Stealth,
+    Balanced,
+    Speed,
+}
+
+/// Which ports to scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ports {
+    Top,
+    All,
+    Custom(String),
+}
+
+/// The answers given to the wizard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Answers {
+    pub targets: Vec<String>,
+    pub network: Network,
+    pub pace: Pace,
+    pub ports: Ports,
+    pub udp: bool,
+    pub nmap: bool,
+}
+
+/// A flag of the command line along with why the wizard picked it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flag {
+    pub args: Vec<String>,
+    pub reason: &'static str,
+}
+
+fn flag(args: &[&str], reason: &'static str) -> Flag {
+    Flag {
+        args: args.iter().map(|arg| (*arg).to_owned()).collect(),
+        reason,
+    }
+}
+
+impl Answers {
+    /// The flags matching the answers, in the order they are printed.
+    pub fn flags(&self) -> Vec<Flag> {
+        let mut flags = vec![flag(
+            &["-a", self.targets.join(",").as_str()],
+            "the addresses, CIDRs or hostnames to scan",
+        )];
+
+        match &self.ports {
+            Ports::Top => flags.push(flag(
+                &["--top-ports", "1000"],
+                "scan the 1000 most common ports",
+            )),
+            Ports::All => {}
+            Ports::Custom(ports) => {
+                flags.push(flag(&["-p", ports.as_str()], "scan only these ports"))
+            }
+        }
+
+        let timeout = match (self.network, self.pace) {
+            (Network::Internal, Pace::Speed) => Some("500"),
+            (Network::Internal, Pace::Balanced) => Some("1000"),
+            (Network::External, Pace::Speed | Pace::Balanced) => None,
+            (_, Pace::Stealth) => Some("3000"),
+        };
+        if let Some(timeout) = timeout {
+            flags.push(flag(
+                &["-t", timeout],
+                "milliseconds to wait for an answer before a port counts as closed",
+            ));
+        }
+
+        match self.pace {
+            Pace::Stealth => {
+                flags.push(flag(
+                    &["-b", "500"],
+                    "probe fewer ports at once, which is less noisy",
+                ));
+                flags.push(flag(
+                    &["--scan-order", "random"],
+                    "probe ports in a random order instead of counting up",
+                ));
+            }
+            Pace::Balanced => {}
+            Pace::Speed => flags.push(flag(
+                &["-b", "10000"],
+                "probe many ports at once, may need a higher --ulimit",
+            )),
+        }
+        if self.network == Network::External {
+            flags.push(flag(
+                &["--tries", "2"],
+                "retry ports that did not answer, packets get lost on the internet",
+            ));
+        }
+
+        if self.udp {
+            flags.push(flag(
+                &["--udp"],
+                "scan UDP instead of TCP, only ports that answer are found",
+            ));
+        }
+        if !self.nmap {
+            flags.push(flag(
+                &["--scripts", "none"],
+                "only list the open ports, without running nmap on them",
+            ));
+        }
+        flags
+    }
+
+    /// The full command line, starting with `rustscan`.
+    pub fn command_line(&self) -> Vec<String> {
+        let mut args = vec!["rustscan".to_owned()];
+        for flag in self.flags() {
+            args.extend(flag.args);
+        }
+        args
+    }
+}
+
+/// Asks a question until the answer is one of `choices`, an empty answer
+/// picks the first choice.
+fn choose<R: BufRead>(input: &mut R, question: &str, choices: &[&str]) -> io::Result<usize> {
+    loop {
+        print!("{question} [{}] ", choices.join("/"));
+        io::stdout().flush()?;
+        let answer = read_line(input)?.to_lowercase();
+        if answer.is_empty() {
+            return Ok(0);
+        }
+        if let Some(index) = choices
+            .iter()
+            .position(|choice| choice.starts_with(&answer))
+        {
+            return Ok(index);
+        }
+        println!("Please answer one of {}.", choices.join(", "));
+    }
+}
+
+/// Asks a yes or no question, an empty answer being no.
+pub fn confirm<R: BufRead>(input: &mut R, question: &str) -> io::Result<bool> {
+    Ok(choose(input, question, &["no", "yes"])? == 1)
+}
+
+fn read_line<R: BufRead>(input: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "no answer given",
+        ));
+    }
+    Ok(line.trim().to_owned())
+}
+
+/// Asks the scope questions.
+pub fn ask<R: BufRead>(input: &mut R) -> io::Result<Answers> {
+    let targets = loop {
+        print!("What do you want to scan? (IPs, CIDRs or hostnames, comma separated) ");
+        io::stdout().flush()?;
+        let targets: Vec<String> = read_line(input)?
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|target| !target.is_empty())
+            .map(ToOwned::to_owned)
+            .collect();
+        if !targets.is_empty() {
+            break targets;
+        }
+    };
+
+    let network = match choose(
+        input,
+        "Are the targets on your local network or on the internet?",
+        &["internal", "external"],
+    )? {
+        0 => Network::Internal,
+        _ => Network::External,
+    };
+    let pace = match choose(
+        input,
+        "What matters most?",
+        &["balanced", "stealth", "speed"],
+    )? {
+        0 => Pace::Balanced,
+        1 => Pace::Stealth,
+        _ => Pace::Speed,
+    };
+    let ports = match choose(input, "Which ports?", &["top", "all", "custom"])? {
+        0 => Ports::Top,
+        1 => Ports::All,
+        _ => {
+            print!("Which ports? (e.g. 22,80,443,8000-8100) ");
+            io::stdout().flush()?;
+            Ports::Custom(read_line(input)?)
+        }
+    };
+    let udp = choose(input, "Scan UDP instead of TCP?", &["no", "yes"])? == 1;
+    let nmap = choose(input, "Run nmap on the open ports found?", &["yes", "no"])? == 0;
+
+    Ok(Answers {
+        targets,
+        network,
+        pace,
+        ports,
+        udp,
+        nmap,
+    })
+}
+
+/// Runs the wizard on stdin and prints the resulting command. Returns the
+/// command line when it should be run right away.
+pub fn run() -> io::Result<Option<Vec<String>>> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let answers = ask(&mut input)?;
+
+    println!("\nThe command for this scan is:\n");
+    println!("    {}\n", answers.command_line().join(" "));
+    for flag in answers.flags() {
+        println!("  {: <24} {}", flag.args.join(" "), flag.reason);
+    }
+    println!();
+
+    let run = choose(&mut input, "Run it now?", &["no", "yes"])? == 1;
+    Ok(run.then(|| answers.command_line()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ask, confirm, Answers, Network, Pace, Ports};
+    use std::io::Cursor;
+
+    #[test]
+    fn asks_the_scope() {
+        let mut input =
+            Cursor::new("10.0.0.0/24, example.com\next\nstealth\ncustom\n22,80\ny\nn\n");
+        assert_eq!(
+            ask(&mut input).unwrap(),
+            Answers {
+                targets: vec!["10.0.0.0/24".to_owned(), "example.com".to_owned()],
+                network: Network::External,
+                pace: Pace::Stealth,
+                ports: Ports::Custom("22,80".to_owned()),
+                udp: true,
+                nmap: false,
+            }
+        );
+    }
+
+    #[test]
+    fn defaults_on_empty_answers() {
+        let mut input = Cursor::new("127.0.0.1\n\n\n\n\n\n");
+        let answers = ask(&mut input).unwrap();
+        assert_eq!(
+            answers.command_line(),
+            [
+                "rustscan",
+                "-a",
+                "127.0.0.1",
+                "--top-ports",
+                "1000",
+                "-t",
+                "1000"
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_end_of_input() {
+        let mut input = Cursor::new("127.0.0.1\n");
+        assert!(ask(&mut input).is_err());
+    }
+
+    #[test]
+    fn confirms_only_on_yes() {
+        assert!(confirm(&mut Cursor::new("y\n"), "Start?").unwrap());
+        assert!(!confirm(&mut Cursor::new("\n"), "Start?").unwrap());
+        assert!(!confirm(&mut Cursor::new("n\n"), "Start?").unwrap());
+    }
+}